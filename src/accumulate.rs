@@ -0,0 +1,219 @@
+use crate::args::RequestArguments;
+use crate::color::ColorKind;
+use crate::data::{AvailableData, Frame};
+use crate::error;
+use crate::geo::{LatLonBounds, TileCoord};
+use crate::mosaic::{crop_to_bounds, stitch};
+use crate::precip::RateConverter;
+use crate::requester::WeatherRequester;
+use crate::timestamp::{self, TimeDelta, Timestamp};
+
+/// Where precipitation is accumulated by [`accumulate`]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AccumulationArea {
+    /// A single WGS84 location, read at [`POINT_ZOOM`](crate::POINT_ZOOM)
+    Point { lat: f64, lon: f64 },
+    /// Every pixel of the tiles at `zoom` intersecting `bounds`, cropped to `bounds`
+    Region { bounds: LatLonBounds, zoom: u32 },
+}
+
+/// Precipitation estimated to have fallen over a period, see [`accumulate`]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Accumulation {
+    /// The mean depth over the area in mm, dry pixels included. Snow is counted as its liquid
+    /// water equivalent
+    pub mean_mm: f64,
+    /// The largest depth of any pixel of the area in mm, which equals [`Self::mean_mm`] for a
+    /// point
+    pub max_mm: f64,
+    /// How much of the period the frames span. This is shorter than the period if the first
+    /// frame is later than its start, and the depths only cover this part of it
+    pub covered: TimeDelta,
+    /// The number of frames read
+    pub frames: usize,
+}
+
+impl Default for Accumulation {
+    fn default() -> Self {
+        Self {
+            mean_mm: 0.0,
+            max_mm: 0.0,
+            covered: timestamp::delta_from_millis(0),
+            frames: 0,
+        }
+    }
+}
+
+/// Estimates how much precipitation fell over `area` during the `period` ending at the latest
+/// of `frames`, such as the past radar frames of [`AvailableData`]
+///
+/// Each frame is read in black and white and converted to a rate with the default
+/// [`RateConverter`]. Rates are interpolated linearly between consecutive frames and integrated
+/// over time, so only the frames overlapping the period are downloaded, one after another.
+///
+/// This is a rough approximation, useful to tell a drizzle from a downpour but not a
+/// substitute for rain gauges:
+///
+/// - Radar frames are snapshots about 10 minutes apart. Fast moving storms jump between
+///   snapshots, so accumulation is streaky along their path, and showers shorter than the gap
+///   between frames may be missed entirely. Gaps of missing frames are bridged linearly
+/// - Z-R relationships convert reflectivity to rates within a factor of about two, depending
+///   on the type of precipitation, and hail greatly inflates estimates
+/// - Radar measures precipitation aloft, which may evaporate, drift or melt before reaching
+///   the ground, and its beam overshoots low precipitation far from the radar
+/// - Tiles quantize reflectivity to whole dBZ and smooth or upscale the underlying data
+///
+/// ```no_run
+/// use rain_viewer::{accumulate, AccumulationArea, WeatherRequester};
+///
+/// # async fn run() -> Result<(), rain_viewer::Error> {
+/// let req = WeatherRequester::new();
+/// let maps = req.available().await?;
+/// let area = AccumulationArea::Point {
+///     lat: 51.5074,
+///     lon: -0.1278,
+/// };
+/// let period = chrono::Duration::hours(1);
+/// let total = accumulate(&req, &maps, &maps.past_radar, area, period).await?;
+/// println!("{:.1} mm in the last {} minutes", total.mean_mm, total.covered.num_minutes());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Enabled with the `image` feature. Returns Err(...) if the location or zoom of `area` is
+/// invalid, a frame is not a radar frame, or a download fails
+pub async fn accumulate(
+    requester: &WeatherRequester,
+    maps: &AvailableData,
+    frames: &[Frame],
+    area: AccumulationArea,
+    period: TimeDelta,
+) -> Result<Accumulation, error::Error> {
+    let mut frames: Vec<&Frame> = frames.iter().collect();
+    frames.sort_by_key(|frame| frame.time);
+    let Some(end) = frames.last().map(|frame| frame.time) else {
+        return Ok(Accumulation::default());
+    };
+    let period = timestamp::delta_millis(period).max(0);
+    let start = timestamp::from_unix_millis(timestamp::unix_millis(end).saturating_sub(period))
+        .unwrap_or(frames[0].time);
+    // The last frame at or before the start of the period bounds its first interval
+    let first = frames
+        .iter()
+        .rposition(|frame| frame.time <= start)
+        .unwrap_or(0);
+
+    let mut samples = Vec::new();
+    for frame in &frames[first..] {
+        samples.push((frame.time, rates(requester, maps, frame, area).await?));
+    }
+    Ok(integrate(&samples, start, end))
+}
+
+/// The rate in mm/h of each pixel of `area` in `frame`
+async fn rates(
+    requester: &WeatherRequester,
+    maps: &AvailableData,
+    frame: &Frame,
+    area: AccumulationArea,
+) -> Result<Vec<f32>, error::Error> {
+    match area {
+        AccumulationArea::Point { lat, lon } => {
+            let estimate = requester.precip_at(maps, frame, lat, lon).await?;
+            Ok(vec![estimate.intensity().map_or(0.0, |i| i.rate)])
+        }
+        AccumulationArea::Region { bounds, zoom } => {
+            let mut args = RequestArguments::new_tile(TileCoord::new(0, 0, 0))?;
+            args.set_color(ColorKind::BlackAndWhite)
+                .set_smooth(false)
+                .set_snow(true);
+            let tiles = requester
+                .get_region(maps, frame, bounds, zoom, args)
+                .await?;
+            let image = crop_to_bounds(&stitch(&tiles)?, &tiles, &bounds)?.into_rgba8();
+            let converter = RateConverter::default();
+            Ok(image
+                .pixels()
+                .map(|pixel| converter.rate_for_pixel(pixel.0).unwrap_or(0.0))
+                .collect())
+        }
+    }
+}
+
+/// Integrates the rates of each pixel, linearly interpolated between samples sorted by time,
+/// over the part of `start` to `end` the samples span
+fn integrate(samples: &[(Timestamp, Vec<f32>)], start: Timestamp, end: Timestamp) -> Accumulation {
+    let pixels = samples.first().map_or(0, |(_, rates)| rates.len());
+    let mut depths = vec![0.0f64; pixels];
+    let mut covered = 0;
+    for pair in samples.windows(2) {
+        let ((t0, r0), (t1, r1)) = (&pair[0], &pair[1]);
+        let (from, to) = ((*t0).max(start), (*t1).min(end));
+        if to <= from || t1 <= t0 {
+            continue;
+        }
+        covered += timestamp::unix_millis(to) - timestamp::unix_millis(from);
+        let span = timestamp::hours_between(*t0, *t1);
+        let at = |t: Timestamp| timestamp::hours_between(*t0, t) / span;
+        let (a, b) = (at(from), at(to));
+        let hours = timestamp::hours_between(from, to);
+        for ((depth, &r0), &r1) in depths.iter_mut().zip(r0).zip(r1) {
+            // The mean of the interpolated rate over the clipped interval
+            let rate = r0 as f64 + (r1 as f64 - r0 as f64) * (a + b) / 2.0;
+            *depth += rate * hours;
+        }
+    }
+    Accumulation {
+        mean_mm: if pixels == 0 {
+            0.0
+        } else {
+            depths.iter().sum::<f64>() / pixels as f64
+        },
+        max_mm: depths.iter().copied().fold(0.0, f64::max),
+        covered: timestamp::delta_from_millis(covered),
+        frames: samples.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(minutes: i64) -> Timestamp {
+        timestamp::from_unix_seconds(1697000400 + minutes * 60).unwrap()
+    }
+
+    #[test]
+    fn integrates_rates() {
+        // A steady 6 mm/h over one pixel and a shower ramping up over another
+        let samples = [
+            (at(0), vec![6.0, 0.0]),
+            (at(10), vec![6.0, 12.0]),
+            (at(20), vec![6.0, 12.0]),
+        ];
+        let total = integrate(&samples, at(0), at(20));
+        assert_eq!(total.covered, timestamp::delta_from_millis(20 * 60_000));
+        assert_eq!(total.frames, 3);
+        assert!((total.max_mm - 3.0).abs() < 1e-9);
+        // The shower gives 1 mm while ramping up and 2 mm after
+        assert!((total.mean_mm - (2.0 + 3.0) / 2.0).abs() < 1e-9);
+
+        // Clipping the period to its last 15 minutes keeps half of the ramp
+        let total = integrate(&samples, at(5), at(20));
+        assert_eq!(total.covered, timestamp::delta_from_millis(15 * 60_000));
+        let ramp = (6.0 + 12.0) / 2.0 * (5.0 / 60.0);
+        assert!((total.mean_mm - (1.5 + ramp + 2.0) / 2.0).abs() < 1e-9);
+
+        // A period starting before the first frame only covers what the frames span
+        let total = integrate(&samples, at(-30), at(20));
+        assert_eq!(total.covered, timestamp::delta_from_millis(20 * 60_000));
+
+        assert_eq!(
+            integrate(&samples[..1], at(-10), at(0)),
+            Accumulation {
+                frames: 1,
+                ..Accumulation::default()
+            }
+        );
+    }
+}
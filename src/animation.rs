@@ -0,0 +1,391 @@
+//! Animated loops of radar frames
+//!
+//! Animations download the radar tiles of a sequence of past or nowcast frames over an
+//! [`AnimationArea`] and encode them into a single animated image:
+//!
+//! - [`gif`]: a looping GIF, enabled with the `gif` feature
+//! - [`apng`]: an animated PNG, which keeps every color of smooth schemes such as
+//!   [`ColorKind::Titan`](crate::ColorKind::Titan) that GIF's 256 color limit bands, enabled
+//!   with the `apng` feature
+//! - [`video`]: an MP4 or WebM video encoded by FFmpeg, enabled with the `ffmpeg` feature
+//!
+//! Frames are 10 minutes apart, so loops look choppy when played slowly. [`interpolate_frames`]
+//! synthesizes frames between them, by cross-fading or by following the motion of storms.
+//!
+//! Enabled with the `image` feature
+
+use std::time::Duration;
+
+use image::RgbaImage;
+
+use crate::args::RequestArguments;
+use crate::data::{AvailableData, Frame};
+use crate::error;
+use crate::geo::{LatLonBounds, TileCoord};
+use crate::mosaic::{crop_to_bounds, stitch};
+use crate::requester::WeatherRequester;
+use crate::tile::decode_png;
+
+mod interpolate;
+#[cfg(all(feature = "ffmpeg", not(target_arch = "wasm32")))]
+pub mod video;
+
+pub use interpolate::*;
+
+/// The part of the map shown by an animation
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AnimationArea {
+    /// A single tile
+    Tile(TileCoord),
+    /// The tiles at `zoom` intersecting `bounds`, stitched together and cropped to `bounds`
+    Region { bounds: LatLonBounds, zoom: u32 },
+}
+
+/// Timing of an animation
+///
+/// Defaults to showing each frame for half a second, holding the last frame for two seconds
+/// so the loop's end stands out, and looping forever
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnimationOptions {
+    delay: Duration,
+    last_frame_delay: Duration,
+    looping: bool,
+}
+
+impl Default for AnimationOptions {
+    fn default() -> Self {
+        Self {
+            delay: Duration::from_millis(500),
+            last_frame_delay: Duration::from_secs(2),
+            looping: true,
+        }
+    }
+}
+
+impl AnimationOptions {
+    /// Options with the default timing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how long each frame is shown
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Sets how long the last frame is shown before the animation starts over
+    pub fn with_last_frame_delay(mut self, delay: Duration) -> Self {
+        self.last_frame_delay = delay;
+        self
+    }
+
+    /// Sets whether the animation starts over after the last frame, or plays once
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// How long the frame at `index` of an animation of `count` frames is shown
+    pub fn frame_delay(&self, index: usize, count: usize) -> Duration {
+        if index + 1 == count {
+            self.last_frame_delay
+        } else {
+            self.delay
+        }
+    }
+}
+
+/// Downloads `area` for each of `frames`, in order, and decodes it into an image
+///
+/// The size, color and options of `args` apply to every tile, while its location and zoom are
+/// ignored. Returns Err(...) if a frame is not a radar frame, or a download fails
+pub async fn render_frames<'a>(
+    requester: &WeatherRequester,
+    maps: &AvailableData,
+    frames: impl IntoIterator<Item = &'a Frame>,
+    area: &AnimationArea,
+    args: RequestArguments,
+) -> Result<Vec<RgbaImage>, error::Error> {
+    let mut images = Vec::new();
+    for frame in frames {
+        let image = match *area {
+            AnimationArea::Tile(coord) => {
+                let png = requester
+                    .get_tile(maps, frame, args.for_tile(coord)?)
+                    .await?;
+                decode_png(&png)?
+            }
+            AnimationArea::Region { bounds, zoom } => {
+                let tiles = requester
+                    .get_region(maps, frame, bounds, zoom, args)
+                    .await?;
+                crop_to_bounds(&stitch(&tiles)?, &tiles, &bounds)?
+            }
+        };
+        images.push(image.into_rgba8());
+    }
+    Ok(images)
+}
+
+/// Downloads `area` for each of `frames` and encodes it into a GIF, see [`render_frames`]
+///
+/// GIF frames hold at most 256 colors, so smooth color schemes lose some shades.
+///
+/// ```no_run
+/// use rain_viewer::animation::{self, AnimationArea, AnimationOptions};
+/// use rain_viewer::{LatLonBounds, RequestArguments, TileCoord, WeatherRequester};
+///
+/// # async fn run() -> Result<(), rain_viewer::Error> {
+/// let req = WeatherRequester::new();
+/// let maps = req.available().await?;
+/// let area = AnimationArea::Region {
+///     bounds: LatLonBounds::new(2.5, 49.5, 7.2, 53.5)?,
+///     zoom: 6,
+/// };
+/// let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0))?;
+/// let options = AnimationOptions::new().with_delay(std::time::Duration::from_millis(300));
+/// let gif = animation::gif(&req, &maps, maps.all_radar(), &area, args, &options).await?;
+/// std::fs::write("radar.gif", gif)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Enabled with the `gif` feature. Returns Err(...) if `frames` is empty, a frame is not a
+/// radar frame, or a download fails
+#[cfg(feature = "gif")]
+pub async fn gif<'a>(
+    requester: &WeatherRequester,
+    maps: &AvailableData,
+    frames: impl IntoIterator<Item = &'a Frame>,
+    area: &AnimationArea,
+    args: RequestArguments,
+    options: &AnimationOptions,
+) -> Result<Vec<u8>, error::Error> {
+    let images = render_frames(requester, maps, frames, area, args).await?;
+    encode_gif(images, options)
+}
+
+/// Encodes already rendered frames into a GIF, see [`gif`]
+///
+/// Enabled with the `gif` feature. Returns Err(...) if `images` is empty or the images differ
+/// in size
+#[cfg(feature = "gif")]
+pub fn encode_gif(
+    images: Vec<RgbaImage>,
+    options: &AnimationOptions,
+) -> Result<Vec<u8>, error::Error> {
+    use image::codecs::gif::{GifEncoder, Repeat};
+
+    expect_frames(&images)?;
+    let count = images.len();
+    let mut gif = Vec::new();
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut gif, 10);
+        // GIFs without a repeat count play once
+        if options.looping {
+            encoder.set_repeat(Repeat::Infinite)?;
+        }
+        encoder.encode_frames(images.into_iter().enumerate().map(|(i, image)| {
+            let delay = image::Delay::from_saturating_duration(options.frame_delay(i, count));
+            image::Frame::from_parts(image, 0, 0, delay)
+        }))?;
+    }
+    Ok(gif)
+}
+
+/// Downloads `area` for each of `frames` and encodes it into an animated PNG, see
+/// [`render_frames`]
+///
+/// Unlike [`gif`], every color of the tiles is kept. Viewers without APNG support show the
+/// first frame as a still image.
+///
+/// Enabled with the `apng` feature. Returns Err(...) if `frames` is empty, a frame is not a
+/// radar frame, or a download fails
+#[cfg(feature = "apng")]
+pub async fn apng<'a>(
+    requester: &WeatherRequester,
+    maps: &AvailableData,
+    frames: impl IntoIterator<Item = &'a Frame>,
+    area: &AnimationArea,
+    args: RequestArguments,
+    options: &AnimationOptions,
+) -> Result<Vec<u8>, error::Error> {
+    let images = render_frames(requester, maps, frames, area, args).await?;
+    encode_apng(&images, options)
+}
+
+/// Encodes already rendered frames into an animated PNG, see [`apng`]
+///
+/// Enabled with the `apng` feature. Returns Err(...) if `images` is empty or the images differ
+/// in size
+#[cfg(feature = "apng")]
+pub fn encode_apng(
+    images: &[RgbaImage],
+    options: &AnimationOptions,
+) -> Result<Vec<u8>, error::Error> {
+    let (width, height) = expect_frames(images)?;
+
+    let mut apng = Vec::new();
+    let mut encoder = png::Encoder::new(&mut apng, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    // A play count of 0 loops forever
+    let plays = if options.looping { 0 } else { 1 };
+    encoder
+        .set_animated(images.len() as u32, plays)
+        .map_err(png_error)?;
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    for (i, image) in images.iter().enumerate() {
+        let (numerator, denominator) = delay_fraction(options.frame_delay(i, images.len()));
+        writer
+            .set_frame_delay(numerator, denominator)
+            .map_err(png_error)?;
+        writer.write_image_data(image.as_raw()).map_err(png_error)?;
+    }
+    writer.finish().map_err(png_error)?;
+    Ok(apng)
+}
+
+/// A frame delay as the seconds fraction of an APNG frame control chunk, in milliseconds when
+/// they fit
+#[cfg(feature = "apng")]
+fn delay_fraction(delay: Duration) -> (u16, u16) {
+    match u16::try_from(delay.as_millis()) {
+        Ok(millis) => (millis, 1000),
+        Err(_) => (delay.as_secs().try_into().unwrap_or(u16::MAX), 1),
+    }
+}
+
+#[cfg(feature = "apng")]
+fn png_error(err: png::EncodingError) -> error::Error {
+    image::ImageError::Encoding(image::error::EncodingError::new(
+        image::ImageFormat::Png.into(),
+        err,
+    ))
+    .into()
+}
+
+/// Checks that an animation has frames to show, all of the same size, and returns their
+/// `(width, height)`
+#[cfg(any(
+    feature = "gif",
+    feature = "apng",
+    all(feature = "ffmpeg", not(target_arch = "wasm32"))
+))]
+fn expect_frames(images: &[RgbaImage]) -> Result<(u32, u32), error::ParameterError> {
+    let (width, height) = images
+        .first()
+        .ok_or_else(|| {
+            error::ParameterError::InvalidSize(
+                0,
+                "An animation needs at least one frame".to_owned(),
+            )
+        })?
+        .dimensions();
+    match images
+        .iter()
+        .find(|image| image.dimensions() != (width, height))
+    {
+        Some(image) => Err(error::ParameterError::InvalidSize(
+            image.width(),
+            format!("Every frame must be {width}x{height} pixels like the first"),
+        )),
+        None => Ok((width, height)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays() {
+        let options = AnimationOptions::new().with_delay(Duration::from_millis(100));
+        assert_eq!(options.frame_delay(0, 3), Duration::from_millis(100));
+        assert_eq!(options.frame_delay(2, 3), Duration::from_secs(2));
+    }
+
+    #[cfg(feature = "apng")]
+    #[test]
+    fn encodes_apng() {
+        use image::codecs::png::PngDecoder;
+        use image::AnimationDecoder;
+
+        // A gradient with more than 256 colors, which a GIF could not hold
+        let gradient = RgbaImage::from_fn(32, 16, |x, y| {
+            image::Rgba([x as u8 * 8, y as u8 * 16, 7, 255])
+        });
+        let images = vec![gradient.clone(), RgbaImage::new(32, 16)];
+        let options = AnimationOptions::new()
+            .with_delay(Duration::from_millis(250))
+            .with_last_frame_delay(Duration::from_secs(100));
+        let apng = encode_apng(&images, &options).unwrap();
+
+        let decoder = PngDecoder::new(std::io::Cursor::new(&apng)).unwrap();
+        let frames = decoder
+            .apng()
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].buffer(), &gradient);
+        assert_eq!(frames[1].buffer().get_pixel(5, 5).0, [0; 4]);
+        assert_eq!(
+            Duration::from(frames[0].delay()),
+            Duration::from_millis(250)
+        );
+        assert_eq!(Duration::from(frames[1].delay()), Duration::from_secs(100));
+
+        let mismatched = vec![gradient, RgbaImage::new(4, 4)];
+        assert!(encode_apng(&mismatched, &options).is_err());
+        assert!(encode_apng(&[], &options).is_err());
+    }
+
+    #[cfg(feature = "apng")]
+    #[test]
+    fn delay_fractions() {
+        assert_eq!(delay_fraction(Duration::from_millis(500)), (500, 1000));
+        assert_eq!(delay_fraction(Duration::from_secs(70)), (70, 1));
+    }
+
+    #[cfg(feature = "gif")]
+    #[test]
+    fn encodes_gif() {
+        use image::codecs::gif::GifDecoder;
+        use image::AnimationDecoder;
+
+        let images = [[200, 0, 0, 255], [0, 0, 200, 255], [0, 0, 0, 0]]
+            .map(|color| RgbaImage::from_pixel(4, 3, image::Rgba(color)))
+            .to_vec();
+        let options = AnimationOptions::new()
+            .with_delay(Duration::from_millis(100))
+            .with_last_frame_delay(Duration::from_millis(400));
+        let gif = encode_gif(images, &options).unwrap();
+
+        let decoder = GifDecoder::new(std::io::Cursor::new(&gif)).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].buffer().dimensions(), (4, 3));
+        assert_eq!(frames[1].buffer().get_pixel(0, 0).0, [0, 0, 200, 255]);
+        assert_eq!(frames[2].buffer().get_pixel(3, 2).0[3], 0);
+        let delays: Vec<_> = frames
+            .iter()
+            .map(|frame| Duration::from(frame.delay()))
+            .collect();
+        assert_eq!(delays[0], Duration::from_millis(100));
+        assert_eq!(delays[2], Duration::from_millis(400));
+        // Looping GIFs carry the NETSCAPE application extension
+        assert!(gif.windows(11).any(|window| window == b"NETSCAPE2.0"));
+
+        let once = encode_gif(
+            vec![RgbaImage::new(1, 1)],
+            &AnimationOptions::new().with_looping(false),
+        )
+        .unwrap();
+        assert!(!once.windows(11).any(|window| window == b"NETSCAPE2.0"));
+
+        assert!(encode_gif(Vec::new(), &options).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use image::RgbaImage;
+
+use crate::error;
+use crate::motion::{expect_same_size, mix, premultiply, sample, unpremultiply, MotionField};
+
+/// How intermediate frames are synthesized between two rendered frames
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Interpolation {
+    /// Fades from one frame into the next. Cheap, but moving storms appear in both places at
+    /// once halfway through
+    CrossFade,
+    /// Estimates how each block of precipitation moved between the frames, by searching up to
+    /// `max_shift` pixels in every direction, and moves it part of the way before fading.
+    /// Larger shifts follow faster storms at higher zoom levels, at a quadratic cost in time
+    MotionCompensated { max_shift: u32 },
+}
+
+/// Synthesizes the frame a fraction `t` of the way from `from` to `to`, where `0.0` gives
+/// `from` and `1.0` gives `to`
+///
+/// `t` is clamped to that range. Returns Err(...) if the images differ in size
+pub fn interpolate(
+    from: &RgbaImage,
+    to: &RgbaImage,
+    t: f32,
+    interpolation: Interpolation,
+) -> Result<RgbaImage, error::Error> {
+    expect_same_size(from, to)?;
+    let t = t.clamp(0.0, 1.0);
+    Ok(match interpolation {
+        Interpolation::CrossFade => RgbaImage::from_fn(from.width(), from.height(), |x, y| {
+            let a = premultiply(from.get_pixel(x, y));
+            let b = premultiply(to.get_pixel(x, y));
+            unpremultiply(mix(a, b, t))
+        }),
+        Interpolation::MotionCompensated { max_shift } => {
+            let field = MotionField::estimate_unchecked(from, to, max_shift);
+            RgbaImage::from_fn(from.width(), from.height(), |x, y| {
+                let (dx, dy) = field.at(x as f32 + 0.5, y as f32 + 0.5);
+                let (x, y) = (x as f32, y as f32);
+                let a = sample(from, x - t * dx, y - t * dy);
+                let b = sample(to, x + (1.0 - t) * dx, y + (1.0 - t) * dy);
+                unpremultiply(mix(a, b, t))
+            })
+        }
+    })
+}
+
+/// Inserts `steps` synthesized frames between each pair of consecutive `images`, such as to
+/// smooth an animation of frames 10 minutes apart
+///
+/// Divide the frame delay of the animation by `steps + 1` to keep its pace. Returns Err(...)
+/// if the images differ in size
+pub fn interpolate_frames(
+    images: &[RgbaImage],
+    steps: usize,
+    interpolation: Interpolation,
+) -> Result<Vec<RgbaImage>, error::Error> {
+    let mut frames = Vec::with_capacity(images.len() + images.len().saturating_sub(1) * steps);
+    for pair in images.windows(2) {
+        frames.push(pair[0].clone());
+        for step in 1..=steps {
+            let t = step as f32 / (steps + 1) as f32;
+            frames.push(interpolate(&pair[0], &pair[1], t, interpolation)?);
+        }
+    }
+    frames.extend(images.last().cloned());
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    /// A transparent image with an opaque square of `size` pixels at `(x, y)`
+    fn blob(x: u32, y: u32, size: u32) -> RgbaImage {
+        RgbaImage::from_fn(32, 32, |px, py| {
+            if (x..x + size).contains(&px) && (y..y + size).contains(&py) {
+                Rgba([0, 200, 0, 255])
+            } else {
+                Rgba([0; 4])
+            }
+        })
+    }
+
+    #[test]
+    fn cross_fade() {
+        let from = RgbaImage::from_pixel(2, 2, Rgba([200, 0, 0, 255]));
+        let to = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 200, 255]));
+        let half = interpolate(&from, &to, 0.5, Interpolation::CrossFade).unwrap();
+        assert_eq!(half.get_pixel(1, 1).0, [100, 0, 100, 255]);
+        assert_eq!(
+            interpolate(&from, &to, 0.0, Interpolation::CrossFade).unwrap(),
+            from
+        );
+
+        // Fading into nothing keeps the color and lowers the alpha
+        let clear = RgbaImage::new(2, 2);
+        let faded = interpolate(&from, &clear, 0.25, Interpolation::CrossFade).unwrap();
+        assert_eq!(faded.get_pixel(0, 0).0, [200, 0, 0, 191]);
+
+        assert!(interpolate(&from, &RgbaImage::new(3, 2), 0.5, Interpolation::CrossFade).is_err());
+    }
+
+    #[test]
+    fn motion_compensated() {
+        let from = blob(4, 10, 4);
+        let to = blob(12, 10, 4);
+        let motion = Interpolation::MotionCompensated { max_shift: 10 };
+        let half = interpolate(&from, &to, 0.5, motion).unwrap();
+        // The square is halfway along its path instead of fading in both places
+        for x in 8..12 {
+            assert_eq!(half.get_pixel(x, 11).0, [0, 200, 0, 255]);
+        }
+        assert_eq!(half.get_pixel(5, 11).0[3], 0);
+        assert_eq!(half.get_pixel(13, 11).0[3], 0);
+
+        // Out of reach of the search, so it fades
+        let slow = Interpolation::MotionCompensated { max_shift: 2 };
+        let faded = interpolate(&from, &to, 0.5, slow).unwrap();
+        assert_eq!(faded.get_pixel(5, 11).0[3], 128);
+    }
+
+    #[test]
+    fn frame_sequences() {
+        let images = [0, 4, 8].map(|x| blob(x, 0, 4));
+        let frames = interpolate_frames(&images, 3, Interpolation::CrossFade).unwrap();
+        assert_eq!(frames.len(), 3 + 2 * 3);
+        assert_eq!(frames[0], images[0]);
+        assert_eq!(frames[4], images[1]);
+        assert_eq!(frames[8], images[2]);
+
+        assert!(interpolate_frames(&[], 3, Interpolation::CrossFade)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,277 @@
+//! MP4 and WebM video output through [FFmpeg](https://ffmpeg.org)
+//!
+//! Enabled with the `ffmpeg` feature. Videos are much smaller than GIFs or animated PNGs of the
+//! same frames, so they suit web pages and chat apps. Frames are piped into an `ffmpeg`
+//! process, which must be installed separately.
+//!
+//! ```no_run
+//! use rain_viewer::animation::video::{self, VideoEncoder, VideoFormat};
+//! use rain_viewer::animation::{AnimationArea, AnimationOptions};
+//! use rain_viewer::{RequestArguments, TileCoord, WeatherRequester};
+//!
+//! # async fn run() -> Result<(), rain_viewer::Error> {
+//! let req = WeatherRequester::new();
+//! let maps = req.available().await?;
+//! let area = AnimationArea::Tile(TileCoord::new(4, 7, 6));
+//! let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0))?;
+//! let encoder = VideoEncoder::new(VideoFormat::WebM);
+//! let frames = maps.all_radar();
+//! let options = AnimationOptions::new();
+//! video::export(&req, &maps, frames, &area, args, &options, &encoder, "radar.webm").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use image::RgbaImage;
+
+use crate::animation::{expect_frames, render_frames, AnimationArea, AnimationOptions};
+use crate::args::RequestArguments;
+use crate::data::{AvailableData, Frame};
+use crate::error;
+use crate::requester::WeatherRequester;
+
+/// The container and codec of a video
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum VideoFormat {
+    /// H.264 in an MP4 container, which plays nearly everywhere. Transparent pixels turn black,
+    /// so draw frames over a basemap first to keep them readable
+    Mp4,
+    /// VP9 in a WebM container, which keeps transparency in browsers that support it
+    WebM,
+}
+
+impl VideoFormat {
+    /// The FFmpeg arguments selecting the codec of this format
+    fn codec_args(self) -> &'static [&'static str] {
+        match self {
+            // H.264 with 4:2:0 chroma needs even dimensions
+            VideoFormat::Mp4 => &[
+                "-c:v",
+                "libx264",
+                "-pix_fmt",
+                "yuv420p",
+                "-vf",
+                "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+                "-movflags",
+                "+faststart",
+            ],
+            VideoFormat::WebM => &[
+                "-c:v",
+                "libvpx-vp9",
+                "-pix_fmt",
+                "yuva420p",
+                "-b:v",
+                "0",
+                "-crf",
+                "32",
+            ],
+        }
+    }
+}
+
+/// Encodes frames into videos with an `ffmpeg` executable
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VideoEncoder {
+    program: PathBuf,
+    format: VideoFormat,
+}
+
+impl VideoEncoder {
+    /// An encoder writing `format` with the `ffmpeg` found on the `PATH`
+    pub fn new(format: VideoFormat) -> Self {
+        Self {
+            program: PathBuf::from("ffmpeg"),
+            format,
+        }
+    }
+
+    /// Sets the `ffmpeg` executable to run
+    pub fn with_program(mut self, program: impl Into<PathBuf>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    /// Encodes `images` into a video file at `path`, replacing any existing file
+    ///
+    /// Videos play at a constant frame rate of one frame per delay of `options`, so the last
+    /// frame is repeated to hold it for about its longer delay. Videos do not loop by
+    /// themselves, so the looping option is left to the player. This blocks until `ffmpeg`
+    /// exits.
+    ///
+    /// Returns Err(...) if `images` is empty or the images differ in size, `ffmpeg` cannot be
+    /// run, or it fails
+    pub fn encode(
+        &self,
+        images: &[RgbaImage],
+        options: &AnimationOptions,
+        path: impl AsRef<Path>,
+    ) -> Result<(), error::Error> {
+        let (width, height) = expect_frames(images)?;
+
+        let mut child = Command::new(&self.program)
+            .args(self.args(width, height, options, path.as_ref()))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let written =
+            repeated_frames(images, options).try_for_each(|image| stdin.write_all(image.as_raw()));
+        // Closing stdin ends the input, letting ffmpeg finish the file
+        drop(stdin);
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(std::io::Error::other(format!(
+                "{} exited with {}: {}",
+                self.program.display(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+            .into());
+        }
+        written?;
+        Ok(())
+    }
+
+    /// The command line arguments reading raw RGBA frames from stdin into `path`
+    fn args(
+        &self,
+        width: u32,
+        height: u32,
+        options: &AnimationOptions,
+        path: &Path,
+    ) -> Vec<OsString> {
+        let millis = options.delay.as_millis().max(1);
+        let mut args: Vec<OsString> = [
+            "-y",
+            "-loglevel",
+            "error",
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgba",
+            "-s",
+            &format!("{width}x{height}"),
+            "-framerate",
+            &format!("1000/{millis}"),
+            "-i",
+            "-",
+        ]
+        .into_iter()
+        .chain(self.format.codec_args().iter().copied())
+        .map(OsString::from)
+        .collect();
+        args.push(path.into());
+        args
+    }
+}
+
+/// `images` with the last image repeated to last for about the last frame delay of `options`
+fn repeated_frames<'a>(
+    images: &'a [RgbaImage],
+    options: &AnimationOptions,
+) -> impl Iterator<Item = &'a RgbaImage> {
+    let delay = options.delay.as_millis().max(1);
+    let holds = (options.last_frame_delay.as_millis() as f64 / delay as f64).round() as usize;
+    let last = images.last().into_iter();
+    images[..images.len().saturating_sub(1)]
+        .iter()
+        .chain(last.cycle().take(holds.max(1)))
+}
+
+/// Downloads `area` for each of `frames` and encodes it into a video file at `path` with
+/// `encoder`, see [`render_frames`] and [`VideoEncoder::encode`]
+///
+/// Returns Err(...) if `frames` is empty, a frame is not a radar frame, a download fails, or
+/// encoding fails
+#[allow(clippy::too_many_arguments)]
+pub async fn export<'a>(
+    requester: &WeatherRequester,
+    maps: &AvailableData,
+    frames: impl IntoIterator<Item = &'a Frame>,
+    area: &AnimationArea,
+    args: RequestArguments,
+    options: &AnimationOptions,
+    encoder: &VideoEncoder,
+    path: impl AsRef<Path>,
+) -> Result<(), error::Error> {
+    let images = render_frames(requester, maps, frames, area, args).await?;
+    encoder.encode(&images, options, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn arguments() {
+        let encoder = VideoEncoder::new(VideoFormat::Mp4);
+        let options = AnimationOptions::new().with_delay(Duration::from_millis(250));
+        let args = encoder.args(256, 128, &options, Path::new("radar.mp4"));
+        let args: Vec<_> = args.iter().map(|arg| arg.to_str().unwrap()).collect();
+        assert!(args.windows(2).any(|pair| pair == ["-s", "256x128"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["-framerate", "1000/250"]));
+        assert!(args.windows(2).any(|pair| pair == ["-c:v", "libx264"]));
+        assert_eq!(args.last(), Some(&"radar.mp4"));
+    }
+
+    #[test]
+    fn holds_last_frame() {
+        let images = [1, 2, 3].map(|value| RgbaImage::from_pixel(1, 1, image::Rgba([value; 4])));
+        let options = AnimationOptions::new()
+            .with_delay(Duration::from_millis(500))
+            .with_last_frame_delay(Duration::from_secs(2));
+        let values: Vec<_> = repeated_frames(&images, &options)
+            .map(|image| image.get_pixel(0, 0).0[0])
+            .collect();
+        assert_eq!(values, [1, 2, 3, 3, 3, 3]);
+
+        let short = options.with_last_frame_delay(Duration::ZERO);
+        assert_eq!(repeated_frames(&images, &short).count(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipes_frames() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Stands in for ffmpeg by copying stdin to the output path, its last argument
+        let dir = tempfile::tempdir().unwrap();
+        let program = dir.path().join("ffmpeg");
+        std::fs::write(
+            &program,
+            "#!/bin/sh\nfor last; do :; done\ncat > \"$last\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let encoder = VideoEncoder::new(VideoFormat::WebM).with_program(&program);
+        let images = vec![RgbaImage::new(4, 2); 2];
+        let options = AnimationOptions::new().with_last_frame_delay(Duration::from_secs(1));
+        let path = dir.path().join("radar.webm");
+        encoder.encode(&images, &options, &path).unwrap();
+        // The last frame is held for two frame delays
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 3 * 4 * 2 * 4);
+
+        let failing = dir.path().join("failing");
+        std::fs::write(&failing, "#!/bin/sh\necho broken >&2\nexit 1\n").unwrap();
+        std::fs::set_permissions(&failing, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let err = VideoEncoder::new(VideoFormat::Mp4)
+            .with_program(&failing)
+            .encode(&images, &options, &path)
+            .unwrap_err();
+        assert!(err.to_string().contains("broken"));
+
+        let missing = VideoEncoder::new(VideoFormat::Mp4).with_program(dir.path().join("none"));
+        assert!(missing.encode(&images, &options, &path).is_err());
+    }
+}
@@ -0,0 +1,475 @@
+use crate::color::ColorKind;
+use crate::data::{AvailableData, Frame};
+use crate::error::{self, ParameterError};
+use crate::geo::TileCoord;
+
+/// The largest latitude representable in the web mercator projection used by tiles
+pub(crate) const MAX_LATITUDE: f64 = 85.051_128_78;
+
+/// The highest zoom level Rain Viewer serves radar tiles for.
+/// See <https://www.rainviewer.com/api/weather-maps-api.html>
+pub const MAX_RADAR_ZOOM: u32 = 12;
+
+/// The highest zoom level Rain Viewer serves infrared satellite tiles for.
+/// See <https://www.rainviewer.com/api/weather-maps-api.html>
+pub const MAX_SATELLITE_ZOOM: u32 = 9;
+
+/// The highest zoom level Rain Viewer serves radar coverage tiles for.
+/// The coverage layer follows the radar product
+pub const MAX_COVERAGE_ZOOM: u32 = MAX_RADAR_ZOOM;
+
+/// The zoom level of the tiles point queries such as
+/// [`WeatherRequester::is_raining_at`](crate::WeatherRequester::is_raining_at) read
+///
+/// Rain Viewer's radar composites resolve about a kilometer, which 256 pixel tiles reach at
+/// this zoom, so deeper tiles only upscale the same data
+pub const POINT_ZOOM: u32 = 7;
+
+/// Checks that `zoom` does not exceed the maximum zoom of the product being requested
+pub(crate) fn validate_zoom(zoom: u32, max_zoom: u32) -> Result<(), ParameterError> {
+    if zoom > max_zoom {
+        Err(ParameterError::InvalidZoom(
+            zoom,
+            format!("The max zoom for this product is {}", max_zoom),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Where on the map a tile is requested from
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum TileLocation {
+    /// Slippy map tile coordinates
+    Coordinates { x: u32, y: u32 },
+    /// The tile covering a latitude/longitude, resolved by Rain Viewer
+    Position { lat: f64, lon: f64 },
+}
+
+impl TileLocation {
+    /// Validates slippy map tile coordinates
+    ///
+    /// `coord.z` must be at most `max_zoom` and `coord.x` and `coord.y` must be less than
+    /// `2^z`, or Err(...) is returned
+    pub(crate) fn coordinates(coord: TileCoord, max_zoom: u32) -> Result<Self, ParameterError> {
+        validate_zoom(coord.z, max_zoom)?;
+        coord.validate()?;
+        Ok(Self::Coordinates {
+            x: coord.x,
+            y: coord.y,
+        })
+    }
+
+    /// Validates a GPS location
+    ///
+    /// `zoom` must be at most `max_zoom`, `lat` must be within the web mercator limits of
+    /// +/-85.0511 degrees and `lon` must be within +/-180 degrees, or Err(...) is returned
+    pub(crate) fn position(
+        lat: f64,
+        lon: f64,
+        zoom: u32,
+        max_zoom: u32,
+    ) -> Result<Self, ParameterError> {
+        validate_zoom(zoom, max_zoom)?;
+        if !(-MAX_LATITUDE..=MAX_LATITUDE).contains(&lat) {
+            Err(ParameterError::LatitudeOutOfRange(
+                lat,
+                format!(
+                    "Latitude must be between -{} and {}",
+                    MAX_LATITUDE, MAX_LATITUDE
+                ),
+            ))
+        } else if !(-180.0..=180.0).contains(&lon) {
+            Err(ParameterError::LongitudeOutOfRange(
+                lon,
+                "Longitude must be between -180 and 180".to_owned(),
+            ))
+        } else {
+            Ok(Self::Position { lat, lon })
+        }
+    }
+}
+
+/// The image sizes served by Rain Viewer
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TileSize {
+    /// 256x256 pixel tiles, the standard slippy map size
+    #[default]
+    Px256,
+    /// 512x512 pixel tiles, suitable for high DPI displays
+    Px512,
+}
+
+impl From<TileSize> for u32 {
+    fn from(size: TileSize) -> Self {
+        match size {
+            TileSize::Px256 => 256,
+            TileSize::Px512 => 512,
+        }
+    }
+}
+
+impl TryFrom<u32> for TileSize {
+    type Error = ParameterError;
+
+    /// Converts a size in pixels into a [`TileSize`]
+    ///
+    /// `size` must be 256 or 512 else Err(...) is returned
+    fn try_from(size: u32) -> Result<Self, Self::Error> {
+        match size {
+            256 => Ok(TileSize::Px256),
+            512 => Ok(TileSize::Px512),
+            _ => Err(ParameterError::InvalidSize(
+                size,
+                "Image size must be either 256 or 512".to_owned(),
+            )),
+        }
+    }
+}
+
+/// Builds a tile url following the `{host}{path}/{size}/{z}/{x}/{y}/{color}/{options}.png` format
+fn tile_url(
+    host: &str,
+    path: &str,
+    size: TileSize,
+    zoom: u32,
+    location: TileLocation,
+    color: u32,
+    options: &str,
+) -> String {
+    let location = match location {
+        TileLocation::Coordinates { x, y } => format!("{}/{}", x, y),
+        TileLocation::Position { lat, lon } => format!("{}/{}", lat, lon),
+    };
+    format!(
+        "{}{}/{}/{}/{}/{}/{}.png",
+        host,
+        path,
+        u32::from(size),
+        zoom,
+        location,
+        color,
+        options,
+    )
+}
+
+/// The url of `frame`'s tiles with the size, color and options of `args`, with `{z}`, `{x}`
+/// and `{y}` placeholders for the tile
+///
+/// This is for map libraries such as Leaflet and OpenLayers that download tiles themselves,
+/// while this crate discovers the frames. The location of `args` is ignored. Satellite frames
+/// are served without color or options, so only the size of `args` applies to them.
+///
+/// ```
+/// use rain_viewer::{tile_url_template, RequestArguments, TileCoord};
+///
+/// # fn run(maps: &rain_viewer::AvailableData) -> Result<(), rain_viewer::Error> {
+/// let frame = maps.latest_past().unwrap();
+/// let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0))?;
+/// // Pass to Leaflet's L.tileLayer or OpenLayers' XYZ source
+/// let template = tile_url_template(maps, frame, &args);
+/// assert!(template.ends_with("/256/{z}/{x}/{y}/2/1_1.png"));
+/// # Ok(())
+/// # }
+/// ```
+pub fn tile_url_template(maps: &AvailableData, frame: &Frame, args: &RequestArguments) -> String {
+    let RequestArgumentsInner::Tile(tile) = &args.inner;
+    let (color, options) = if frame.kind.is_radar() {
+        let options = format!("{}_{}", tile.smooth as u8, tile.snow as u8);
+        (u32::from(tile.color), options)
+    } else {
+        (0, "0_0".to_owned())
+    };
+    format!(
+        "{}{}/{}/{{z}}/{{x}}/{{y}}/{}/{}.png",
+        maps.host,
+        frame.path,
+        u32::from(tile.size),
+        color,
+        options,
+    )
+}
+
+/// The path coverage tiles are served under, in place of a frame path
+pub(crate) const COVERAGE_PATH: &str = "/v2/coverage/0";
+
+/// Builds the url of the radar coverage tile at the given slippy map coordinates
+///
+/// Coverage tiles are not tied to a frame, so they are always served from `host` under
+/// [`COVERAGE_PATH`]
+pub(crate) fn coverage_url(
+    host: &str,
+    x: u32,
+    y: u32,
+    zoom: u32,
+) -> Result<String, ParameterError> {
+    let location = TileLocation::coordinates(TileCoord::new(x, y, zoom), MAX_COVERAGE_ZOOM)?;
+    Ok(tile_url(
+        host,
+        COVERAGE_PATH,
+        TileSize::Px256,
+        zoom,
+        location,
+        0,
+        "0_0",
+    ))
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct TileArguments {
+    pub(crate) size: TileSize,
+    pub(crate) location: TileLocation,
+    pub(crate) zoom: u32,
+    pub(crate) color: ColorKind,
+    pub(crate) smooth: bool,
+    pub(crate) snow: bool,
+}
+
+impl TileArguments {
+    /// Builds the url for this tile given the host and frame path returned by the API
+    pub(crate) fn url(&self, host: &str, path: &str) -> String {
+        let options = format!("{}_{}", self.smooth as u8, self.snow as u8);
+        tile_url(
+            host,
+            path,
+            self.size,
+            self.zoom,
+            self.location,
+            self.color.into(),
+            &options,
+        )
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum RequestArgumentsInner {
+    Tile(TileArguments),
+}
+
+/// Arguments needed to pull a rain tile from rainviewer
+#[derive(Copy, Clone)]
+pub struct RequestArguments {
+    pub(crate) inner: RequestArgumentsInner,
+}
+
+impl RequestArguments {
+    /// Creates arguments struct suitable for making a radar image request for a single tile
+    ///
+    /// `coord.z` must be at most [`MAX_RADAR_ZOOM`] and `coord.x` and `coord.y` must be less
+    /// than `2^z`, or Err(...) is returned
+    pub fn new_tile(coord: TileCoord) -> Result<Self, error::ParameterError> {
+        Ok(Self::with_location(
+            TileLocation::coordinates(coord, MAX_RADAR_ZOOM)?,
+            coord.z,
+        ))
+    }
+
+    /// Creates arguments struct suitable for making a radar image request for the tile covering
+    /// the given GPS location
+    ///
+    /// `zoom` must be at most [`MAX_RADAR_ZOOM`], `lat` must be within the web mercator limits
+    /// of +/-85.0511 degrees and `lon` must be within +/-180 degrees, or Err(...) is returned
+    pub fn new_position(lat: f64, lon: f64, zoom: u32) -> Result<Self, error::ParameterError> {
+        Ok(Self::with_location(
+            TileLocation::position(lat, lon, zoom, MAX_RADAR_ZOOM)?,
+            zoom,
+        ))
+    }
+
+    /// Copies the size, color and options of these arguments to the radar tile at `coord`
+    ///
+    /// Returns Err(...) if `coord` is not a valid radar tile
+    pub(crate) fn for_tile(&self, coord: TileCoord) -> Result<Self, error::ParameterError> {
+        let location = TileLocation::coordinates(coord, MAX_RADAR_ZOOM)?;
+        let mut args = *self;
+        match &mut args.inner {
+            RequestArgumentsInner::Tile(tile) => {
+                tile.location = location;
+                tile.zoom = coord.z;
+            }
+        };
+        Ok(args)
+    }
+
+    fn with_location(location: TileLocation, zoom: u32) -> Self {
+        Self {
+            inner: RequestArgumentsInner::Tile(TileArguments {
+                size: TileSize::Px256,
+                location,
+                zoom,
+                color: ColorKind::UniversalBlue,
+                smooth: true,
+                snow: true,
+            }),
+        }
+    }
+
+    /// Sets the size of the resulting image when the API call is made.
+    pub fn set_size(&mut self, size: TileSize) -> &mut Self {
+        match &mut self.inner {
+            RequestArgumentsInner::Tile(tile) => {
+                tile.size = size;
+            }
+        };
+        self
+    }
+
+    /// Sets the size of the resulting tile image when the API call is made
+    pub fn set_smooth(&mut self, smooth: bool) -> &mut Self {
+        match &mut self.inner {
+            RequestArgumentsInner::Tile(tile) => {
+                tile.smooth = smooth;
+            }
+        };
+        self
+    }
+
+    /// Sets weather or not the resulting tile should show snow
+    pub fn set_snow(&mut self, snow: bool) -> &mut Self {
+        match &mut self.inner {
+            RequestArgumentsInner::Tile(tile) => {
+                tile.snow = snow;
+            }
+        };
+        self
+    }
+
+    /// Sets the color scheme for the tile
+    pub fn set_color(&mut self, color: ColorKind) -> &mut Self {
+        match &mut self.inner {
+            RequestArgumentsInner::Tile(tile) => {
+                tile.color = color;
+            }
+        };
+        self
+    }
+}
+
+/// Arguments needed to pull an infrared satellite tile from rainviewer
+///
+/// Satellite imagery is only served in a single color scheme and without the smooth or snow
+/// options, so those radar-only settings cannot be set here
+#[derive(Copy, Clone, Debug)]
+pub struct SatelliteArguments {
+    pub(crate) size: TileSize,
+    pub(crate) location: TileLocation,
+    pub(crate) zoom: u32,
+}
+
+impl SatelliteArguments {
+    /// Creates arguments struct suitable for making a satellite image request for a single tile
+    ///
+    /// `coord.z` must be at most [`MAX_SATELLITE_ZOOM`] and `coord.x` and `coord.y` must be
+    /// less than `2^z`, or Err(...) is returned
+    pub fn new_tile(coord: TileCoord) -> Result<Self, error::ParameterError> {
+        Ok(Self {
+            size: TileSize::Px256,
+            location: TileLocation::coordinates(coord, MAX_SATELLITE_ZOOM)?,
+            zoom: coord.z,
+        })
+    }
+
+    /// Creates arguments struct suitable for making a satellite image request for the tile
+    /// covering the given GPS location
+    ///
+    /// `zoom` must be at most [`MAX_SATELLITE_ZOOM`], `lat` must be within the web mercator
+    /// limits of +/-85.0511 degrees and `lon` must be within +/-180 degrees, or Err(...) is
+    /// returned
+    pub fn new_position(lat: f64, lon: f64, zoom: u32) -> Result<Self, error::ParameterError> {
+        Ok(Self {
+            size: TileSize::Px256,
+            location: TileLocation::position(lat, lon, zoom, MAX_SATELLITE_ZOOM)?,
+            zoom,
+        })
+    }
+
+    /// Sets the size of the resulting image when the API call is made.
+    pub fn set_size(&mut self, size: TileSize) -> &mut Self {
+        self.size = size;
+        self
+    }
+
+    /// The satellite tile with the same size and location as the radar tile of `args`
+    ///
+    /// Returns Err(...) if the zoom of `args` exceeds [`MAX_SATELLITE_ZOOM`]
+    #[cfg(feature = "image")]
+    pub(crate) fn matching(args: &RequestArguments) -> Result<Self, ParameterError> {
+        let RequestArgumentsInner::Tile(tile) = args.inner;
+        validate_zoom(tile.zoom, MAX_SATELLITE_ZOOM)?;
+        Ok(Self {
+            size: tile.size,
+            location: tile.location,
+            zoom: tile.zoom,
+        })
+    }
+
+    /// Builds the url for this tile given the host and frame path returned by the API
+    ///
+    /// Satellite tiles always use color scheme `0` and options `0_0`
+    pub(crate) fn url(&self, host: &str, path: &str) -> String {
+        tile_url(host, path, self.size, self.zoom, self.location, 0, "0_0")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position_url() {
+        let args = RequestArguments::new_position(40.7128, -74.006, 5).unwrap();
+        let RequestArgumentsInner::Tile(tile) = args.inner;
+        assert_eq!(
+            tile.url("https://tilecache.rainviewer.com", "/v2/radar/1234"),
+            "https://tilecache.rainviewer.com/v2/radar/1234/256/5/40.7128/-74.006/2/1_1.png"
+        );
+    }
+
+    #[test]
+    fn satellite_url() {
+        let mut args = SatelliteArguments::new_tile(TileCoord::new(3, 5, 4)).unwrap();
+        args.set_size(TileSize::Px512);
+        assert_eq!(
+            args.url("https://tilecache.rainviewer.com", "/v2/satellite/abcd"),
+            "https://tilecache.rainviewer.com/v2/satellite/abcd/512/4/3/5/0/0_0.png"
+        );
+    }
+
+    #[test]
+    fn zoom_limits() {
+        assert!(RequestArguments::new_tile(TileCoord::new(0, 0, MAX_RADAR_ZOOM)).is_ok());
+        assert!(matches!(
+            RequestArguments::new_tile(TileCoord::new(0, 0, 32)),
+            Err(ParameterError::InvalidZoom(32, _))
+        ));
+        assert!(matches!(
+            SatelliteArguments::new_position(0.0, 0.0, MAX_SATELLITE_ZOOM + 1),
+            Err(ParameterError::InvalidZoom(_, _))
+        ));
+    }
+
+    #[test]
+    fn coverage() {
+        assert_eq!(
+            coverage_url("https://tilecache.rainviewer.com", 1, 2, 3).unwrap(),
+            "https://tilecache.rainviewer.com/v2/coverage/0/256/3/1/2/0/0_0.png"
+        );
+        assert!(coverage_url("https://tilecache.rainviewer.com", 8, 2, 3).is_err());
+    }
+
+    #[test]
+    fn url_template() {
+        let maps = AvailableData::fixture();
+        let mut args = RequestArguments::new_tile(TileCoord::new(1, 2, 3)).unwrap();
+        args.set_size(TileSize::Px512)
+            .set_color(ColorKind::Titan)
+            .set_smooth(false);
+        assert_eq!(
+            tile_url_template(&maps, &maps.past_radar[0], &args),
+            "https://tilecache.rainviewer.com/v2/radar/1696998600/512/{z}/{x}/{y}/3/0_1.png"
+        );
+        // Satellite tiles take only the size
+        let satellite = tile_url_template(&maps, &maps.infrared_satellite[0], &args);
+        assert!(satellite.ends_with("/512/{z}/{x}/{y}/0/0_0.png"));
+    }
+}
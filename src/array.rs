@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+
+use image::RgbaImage;
+use ndarray::Array2;
+
+use crate::error;
+use crate::geo::{GeoTransform, TileCoord};
+use crate::mosaic::{stitch, Grid};
+use crate::precip::decode_black_and_white;
+use crate::tile::Tile;
+
+/// A grid of reflectivity values in dBZ, together with where it lies on the map
+///
+/// `values` is indexed by `[row, column]`, with rows from north to south. Pixels without
+/// precipitation are `NaN`.
+///
+/// ```no_run
+/// # async fn run(req: &rain_viewer::WeatherRequester) -> Result<(), rain_viewer::Error> {
+/// use rain_viewer::{ColorKind, DbzArray, RequestArguments, Tile, TileCoord};
+///
+/// let maps = req.available().await?;
+/// let frame = maps.latest_past().unwrap();
+/// let coord = TileCoord::new(4, 7, 6);
+/// let mut args = RequestArguments::new_tile(coord)?;
+/// args.set_color(ColorKind::BlackAndWhite);
+///
+/// let tile = Tile::new(req.get_tile(&maps, frame, args).await?, ColorKind::BlackAndWhite);
+/// let dbz = DbzArray::from_tile(&tile, coord)?;
+/// let max = dbz.values.iter().copied().fold(f32::NAN, f32::max);
+/// println!("Strongest echo: {max} dBZ");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Enabled with the `ndarray` feature
+#[derive(Clone, Debug, PartialEq)]
+pub struct DbzArray {
+    /// Reflectivity in dBZ, indexed by `[row, column]`
+    pub values: Array2<f32>,
+    /// Maps `[row, column]` indices of `values` to web mercator coordinates
+    pub transform: GeoTransform,
+}
+
+impl DbzArray {
+    /// The reflectivity of `tile`, which lies at `coord`
+    ///
+    /// Returns Err(...) if the tile was not requested with
+    /// [`ColorKind::BlackAndWhite`](crate::ColorKind::BlackAndWhite), or is not a valid PNG
+    /// image
+    pub fn from_tile(tile: &Tile, coord: TileCoord) -> Result<Self, error::Error> {
+        let rgba = tile_rgba(tile)?;
+        Ok(Self {
+            transform: GeoTransform::new(coord, rgba.width()),
+            values: to_dbz(&rgba),
+        })
+    }
+
+    /// The reflectivity of a region of tiles of one zoom level, such as those returned by
+    /// [`WeatherRequester::get_region`](crate::WeatherRequester::get_region)
+    ///
+    /// The tiles are assembled as by [`stitch`], so tiles missing from the region are `NaN`.
+    ///
+    /// Returns Err(...) if `tiles` is empty, the tiles do not share a zoom level, or a tile was
+    /// not requested with [`ColorKind::BlackAndWhite`](crate::ColorKind::BlackAndWhite) or is
+    /// not a valid PNG image
+    pub fn from_region(tiles: &BTreeMap<TileCoord, Tile>) -> Result<Self, error::Error> {
+        for tile in tiles.values() {
+            tile.expect_black_and_white()?;
+        }
+        let grid = Grid::new(tiles.keys())?;
+        let mosaic = stitch(tiles)?.into_rgba8();
+        let tile_size = mosaic.width() / grid.columns.len() as u32;
+        let north_west = TileCoord::new(grid.columns[0], grid.top, grid.zoom);
+        Ok(Self {
+            transform: GeoTransform::new(north_west, tile_size),
+            values: to_dbz(&mosaic),
+        })
+    }
+}
+
+impl Tile {
+    /// The reflectivity in dBZ of each pixel of this tile, indexed by `[row, column]`
+    ///
+    /// Pixels without precipitation are `NaN`. See [`DbzArray::from_tile`] to also get where
+    /// the pixels lie on the map.
+    ///
+    /// Enabled with the `ndarray` feature. Returns Err(...) if the tile was not requested with
+    /// [`ColorKind::BlackAndWhite`](crate::ColorKind::BlackAndWhite), or is not a valid PNG
+    /// image
+    pub fn to_dbz_array(&self) -> Result<Array2<f32>, error::Error> {
+        Ok(to_dbz(&tile_rgba(self)?))
+    }
+}
+
+fn tile_rgba(tile: &Tile) -> Result<RgbaImage, error::Error> {
+    tile.expect_black_and_white()?;
+    tile.to_rgba8()
+}
+
+/// Converts the pixels of a black and white image to dBZ
+fn to_dbz(image: &RgbaImage) -> Array2<f32> {
+    let shape = (image.height() as usize, image.width() as usize);
+    Array2::from_shape_fn(shape, |(row, column)| {
+        let [value, _, _, alpha] = image.get_pixel(column as u32, row as u32).0;
+        if alpha == 0 {
+            f32::NAN
+        } else {
+            decode_black_and_white(value).0
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, GrayAlphaImage, LumaA};
+
+    use super::*;
+    use crate::color::ColorKind;
+
+    fn tile(pixels: [(u8, u8); 4]) -> Tile {
+        let image = GrayAlphaImage::from_fn(2, 2, |x, y| {
+            let (value, alpha) = pixels[(y * 2 + x) as usize];
+            LumaA([value, alpha])
+        });
+        let mut png = Vec::new();
+        DynamicImage::ImageLumaA8(image)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        Tile::new(png, ColorKind::BlackAndWhite)
+    }
+
+    #[test]
+    fn tile_values() {
+        let tile = tile([(52, 255), (0, 0), (32, 255), (170, 255)]);
+        let values = tile.to_dbz_array().unwrap();
+        assert_eq!(values.dim(), (2, 2));
+        assert_eq!(values[[0, 0]], 20.0);
+        assert!(values[[0, 1]].is_nan());
+        assert_eq!(values[[1, 0]], 0.0);
+        // Snow is offset by 128
+        assert_eq!(values[[1, 1]], 10.0);
+
+        let coord = TileCoord::new(1, 0, 1);
+        let array = DbzArray::from_tile(&tile, coord).unwrap();
+        assert_eq!(array.transform, GeoTransform::new(coord, 2));
+
+        let colorized = Tile::new(tile.png().clone(), ColorKind::Titan);
+        assert!(colorized.to_dbz_array().is_err());
+    }
+
+    #[test]
+    fn region_values() {
+        let mut tiles = BTreeMap::new();
+        tiles.insert(TileCoord::new(3, 1, 2), tile([(60, 255); 4]));
+        tiles.insert(TileCoord::new(0, 2, 2), tile([(40, 255); 4]));
+
+        let array = DbzArray::from_region(&tiles).unwrap();
+        // The region wraps around the antimeridian, with a missing tile in each row
+        assert_eq!(array.values.dim(), (4, 4));
+        assert_eq!(array.values[[0, 0]], 28.0);
+        assert!(array.values[[0, 3]].is_nan());
+        assert_eq!(array.values[[3, 3]], 8.0);
+        assert_eq!(
+            array.transform,
+            GeoTransform::new(TileCoord::new(3, 1, 2), 2)
+        );
+    }
+}
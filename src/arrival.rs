@@ -0,0 +1,270 @@
+use image::RgbaImage;
+
+use crate::args::RequestArguments;
+use crate::color::ColorKind;
+use crate::data::{AvailableData, Frame, FrameKind};
+use crate::error;
+use crate::geo::{wrap_lon, LatLonBounds, TileCoord};
+use crate::mosaic::stitch_georeferenced;
+use crate::motion::MotionField;
+use crate::precip::{PrecipEstimate, RateConverter};
+use crate::requester::WeatherRequester;
+use crate::timestamp::{self, Timestamp};
+
+/// The zoom level of the area whose motion is extrapolated
+const MOTION_ZOOM: u32 = 6;
+
+/// The distance around the location whose motion is extrapolated, which covers two hours of
+/// storms moving at 100 km/h
+const MOTION_RADIUS_KM: f64 = 250.0;
+
+/// The largest motion searched for between frames, in pixels at [`MOTION_ZOOM`]. This follows
+/// storms of about 150 km/h at mid latitudes for frames 10 minutes apart
+const MAX_SHIFT: u32 = 16;
+
+/// How far ahead of the latest past frame motion is extrapolated
+const HORIZON_MINUTES: i64 = 120;
+
+/// When precipitation is expected to reach a location, see
+/// [`WeatherRequester::rain_arrival`]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RainArrival {
+    /// The time of the first frame expected to show precipitation, or `None` if none is
+    /// expected within about two hours. This is the time of the latest past frame if it is
+    /// already falling
+    pub eta: Option<Timestamp>,
+    /// How much to trust [`Self::eta`], from 0 to 1, whether or not precipitation is expected
+    pub confidence: f32,
+    /// The first nowcast frame showing precipitation, or `None` if none does or it is already
+    /// falling
+    pub nowcast: Option<Timestamp>,
+    /// When extrapolating the motion of the latest frame brings precipitation, or `None` if it
+    /// does not within two hours or it is already falling
+    pub extrapolated: Option<Timestamp>,
+}
+
+impl RainArrival {
+    /// Combines the arrival times of the nowcast and of motion extrapolation, taking the
+    /// earlier as the estimate
+    fn combine(nowcast: Option<Timestamp>, extrapolated: Option<Timestamp>) -> Self {
+        let (eta, confidence) = match (nowcast, extrapolated) {
+            (Some(a), Some(b)) if timestamp::hours_between(a, b).abs() <= 1.0 / 3.0 => {
+                (Some(a.min(b)), 0.8)
+            }
+            (Some(a), Some(b)) => (Some(a.min(b)), 0.5),
+            (Some(time), None) | (None, Some(time)) => (Some(time), 0.4),
+            (None, None) => (None, 0.7),
+        };
+        Self {
+            eta,
+            confidence,
+            nowcast,
+            extrapolated,
+        }
+    }
+}
+
+impl WeatherRequester {
+    /// Estimates when rain or snow will first reach the given WGS84 location, such as for
+    /// "rain in 20 minutes" notifications
+    ///
+    /// Two forecasts are combined:
+    ///
+    /// - The nowcast frames are read at the location like [`Self::nowcast_at`]
+    /// - The motion between the two latest past frames is estimated with [`MotionField`] over
+    ///   about 250 km around the location, and the latest frame is moved along it in steps of
+    ///   the frame interval for up to two hours, which reaches beyond the nowcast
+    ///
+    /// The earlier of the two is the estimate. [`RainArrival::confidence`] is a rough guide
+    /// rather than a probability:
+    ///
+    /// - 1 if precipitation is already falling in the latest past frame
+    /// - 0.8 if both forecasts expect it within 20 minutes of each other, and 0.5 if further
+    ///   apart
+    /// - 0.4 if only one forecast expects it
+    /// - 0.7 if neither does, as precipitation may still develop in place or move in from
+    ///   further away
+    ///
+    /// ```no_run
+    /// use rain_viewer::WeatherRequester;
+    ///
+    /// # async fn run() -> Result<(), rain_viewer::Error> {
+    /// let req = WeatherRequester::new();
+    /// let arrival = req.rain_arrival(51.5074, -0.1278).await?;
+    /// match arrival.eta {
+    ///     Some(eta) if arrival.confidence >= 0.5 => {
+    ///         let minutes = (eta - chrono::Utc::now()).num_minutes().max(0);
+    ///         println!("Rain in about {minutes} minutes");
+    ///     }
+    ///     Some(_) => println!("Rain is possible soon"),
+    ///     None => println!("No rain expected"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Enabled with the `image` feature. Returns Err(...) if `lat` is beyond the web mercator
+    /// limits of +/-85.0511 degrees, `lon` is beyond +/-180 degrees, no past radar frames are
+    /// available, or a download fails
+    pub async fn rain_arrival(&self, lat: f64, lon: f64) -> Result<RainArrival, error::Error> {
+        RequestArguments::new_position(lat, lon, crate::POINT_ZOOM)?;
+        let maps = self.available().await?;
+        let mut past: Vec<&Frame> = maps.past_radar.iter().collect();
+        past.sort_by_key(|frame| frame.time);
+        let Some(&latest) = past.last() else {
+            return Err(error::Error::NoFrames(FrameKind::PastRadar));
+        };
+        if self
+            .precip_at(&maps, latest, lat, lon)
+            .await?
+            .is_precipitating()
+        {
+            return Ok(RainArrival {
+                eta: Some(latest.time),
+                confidence: 1.0,
+                nowcast: None,
+                extrapolated: None,
+            });
+        }
+
+        let mut nowcast: Vec<&Frame> = maps
+            .nowcast_radar
+            .iter()
+            .filter(|frame| frame.time > latest.time)
+            .collect();
+        nowcast.sort_by_key(|frame| frame.time);
+        let estimates = futures_util::future::try_join_all(
+            nowcast
+                .iter()
+                .map(|frame| self.precip_at(&maps, frame, lat, lon)),
+        )
+        .await?;
+        let nowcast = nowcast
+            .iter()
+            .zip(estimates)
+            .find(|(_, estimate)| estimate.is_precipitating())
+            .map(|(frame, _)| frame.time);
+
+        let extrapolated = match past.len().checked_sub(2).map(|i| past[i]) {
+            Some(previous) => {
+                self.extrapolate_arrival(&maps, previous, latest, lat, lon)
+                    .await?
+            }
+            None => None,
+        };
+        Ok(RainArrival::combine(nowcast, extrapolated))
+    }
+
+    /// When moving the precipitation of `latest` along its motion since `previous` brings it
+    /// to the given location
+    async fn extrapolate_arrival(
+        &self,
+        maps: &AvailableData,
+        previous: &Frame,
+        latest: &Frame,
+        lat: f64,
+        lon: f64,
+    ) -> Result<Option<Timestamp>, error::Error> {
+        let interval = timestamp::unix_millis(latest.time) - timestamp::unix_millis(previous.time);
+        if interval <= 0 {
+            return Ok(None);
+        }
+        let steps = (HORIZON_MINUTES * 60_000 / interval.max(1000)) as u32;
+
+        let lat_radius = MOTION_RADIUS_KM / 111.32;
+        let lon_radius = (lat_radius / lat.to_radians().cos()).min(179.0);
+        let bounds = LatLonBounds::new(
+            wrap_lon(lon - lon_radius),
+            (lat - lat_radius).max(-90.0),
+            wrap_lon(lon + lon_radius),
+            (lat + lat_radius).min(90.0),
+        )?;
+        let mut args = RequestArguments::new_tile(TileCoord::new(0, 0, 0))?;
+        args.set_color(ColorKind::BlackAndWhite)
+            .set_smooth(false)
+            .set_snow(true);
+        let (from, to) = futures_util::future::try_join(
+            self.get_region(maps, previous, bounds, MOTION_ZOOM, args),
+            self.get_region(maps, latest, bounds, MOTION_ZOOM, args),
+        )
+        .await?;
+        let (from, _) = stitch_georeferenced(&from)?;
+        let (to, transform) = stitch_georeferenced(&to)?;
+
+        let field = MotionField::estimate(&from, &to, MAX_SHIFT)?;
+        let (x, y) = transform.lat_lon_to_pixel(lat, lon);
+        Ok(
+            arrival_step(&field, &to, x as f32, y as f32, steps).and_then(|step| {
+                timestamp::from_unix_millis(
+                    timestamp::unix_millis(latest.time) + interval * step as i64,
+                )
+            }),
+        )
+    }
+}
+
+/// The first of up to `steps` frame intervals after which `field` brings the precipitation of
+/// `image` to the pixel position `(x, y)`
+///
+/// The position is traced back along the motion one interval at a time, to the pixel of
+/// `image` whose precipitation would arrive there. Tracing stops once it leaves the image,
+/// since what lies beyond is unknown.
+fn arrival_step(field: &MotionField, image: &RgbaImage, x: f32, y: f32, steps: u32) -> Option<u32> {
+    let converter = RateConverter::default();
+    let (mut x, mut y) = (x, y);
+    for step in 1..=steps {
+        let (dx, dy) = field.at(x, y);
+        (x, y) = (x - dx, y - dy);
+        if x < 0.0 || y < 0.0 || x >= image.width() as f32 || y >= image.height() as f32 {
+            return None;
+        }
+        let pixel = image.get_pixel(x as u32, y as u32).0;
+        if PrecipEstimate::from_pixel(pixel, &converter).is_precipitating() {
+            return Some(step);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    /// A transparent image with a square of 40 dBZ of rain 8 pixels wide at `(x, y)`
+    fn shower(x: u32, y: u32) -> RgbaImage {
+        RgbaImage::from_fn(64, 64, |px, py| {
+            if (x..x + 8).contains(&px) && (y..y + 8).contains(&py) {
+                Rgba([72, 72, 72, 255])
+            } else {
+                Rgba([0; 4])
+            }
+        })
+    }
+
+    #[test]
+    fn traces_arrival() {
+        // A shower moving 4 pixels east per frame
+        let field = MotionField::estimate(&shower(4, 28), &shower(8, 28), 8).unwrap();
+        let latest = shower(8, 28);
+        // Its leading edge is about 15 pixels west of the location
+        assert_eq!(arrival_step(&field, &latest, 30.5, 31.5, 10), Some(4));
+        assert_eq!(arrival_step(&field, &latest, 30.5, 31.5, 3), None);
+        // Locations west of it, or off its track, stay dry
+        assert_eq!(arrival_step(&field, &latest, 2.5, 31.5, 10), None);
+        assert_eq!(arrival_step(&field, &latest, 30.5, 4.5, 10), None);
+    }
+
+    #[test]
+    fn combines_forecasts() {
+        let at = |minutes: i64| timestamp::from_unix_seconds(1697000400 + minutes * 60).unwrap();
+        let arrival = RainArrival::combine(Some(at(20)), Some(at(30)));
+        assert_eq!((arrival.eta, arrival.confidence), (Some(at(20)), 0.8));
+        let arrival = RainArrival::combine(Some(at(60)), Some(at(20)));
+        assert_eq!((arrival.eta, arrival.confidence), (Some(at(20)), 0.5));
+        let arrival = RainArrival::combine(None, Some(at(40)));
+        assert_eq!((arrival.eta, arrival.confidence), (Some(at(40)), 0.4));
+        assert_eq!(RainArrival::combine(None, None).eta, None);
+    }
+}
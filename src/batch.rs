@@ -0,0 +1,453 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures_util::StreamExt;
+
+use crate::args::{validate_zoom, RequestArguments, MAX_RADAR_ZOOM};
+use crate::data::{AvailableData, Frame};
+use crate::error::{self, Error};
+use crate::geo::{tiles_in_bbox, LatLonBounds, TileCoord};
+use crate::requester::WeatherRequester;
+
+/// The number of tiles a [`BatchDownloader`] downloads at once by default
+const DEFAULT_CONCURRENCY: usize = 8;
+
+/// The delay before the first retry of a failed tile, doubled for each further retry
+#[cfg(not(target_arch = "wasm32"))]
+const RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// The longest delay between two retries of a tile
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// The delay before retrying a tile for the `attempt + 1`th time
+#[cfg(not(target_arch = "wasm32"))]
+fn retry_delay(attempt: u32) -> std::time::Duration {
+    2u32.checked_pow(attempt)
+        .and_then(|factor| RETRY_DELAY.checked_mul(factor))
+        .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY))
+}
+
+/// The delay before retrying a tile that failed with `err` for the `attempt + 1`th time. Honours
+/// the server's `Retry-After` when rate limited, up to [`MAX_RETRY_DELAY`]
+#[cfg(not(target_arch = "wasm32"))]
+fn retry_delay_after(err: &Error, attempt: u32) -> std::time::Duration {
+    match err {
+        Error::RateLimited {
+            retry_after: Some(retry_after),
+        } => (*retry_after).min(MAX_RETRY_DELAY),
+        _ => retry_delay(attempt),
+    }
+}
+
+/// How far a [`BatchDownloader`] has come, passed to its progress callback after each tile
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of tiles finished so far, whether they succeeded or failed
+    pub done: usize,
+    /// The number of tiles in the batch
+    pub total: usize,
+    /// The number of bytes downloaded so far
+    pub bytes: u64,
+}
+
+/// The outcome of a batch download. Tiles that failed are missing from `tiles` and listed in
+/// `errors` instead
+#[derive(Debug, Default)]
+pub struct BatchResult {
+    pub tiles: BTreeMap<TileCoord, Bytes>,
+    pub errors: BTreeMap<TileCoord, Error>,
+    /// Tiles that were not downloaded because the manifest passed to
+    /// [`BatchDownloader::resume`] lists them as completed
+    pub skipped: BTreeSet<TileCoord>,
+}
+
+impl BatchResult {
+    /// Whether every tile was downloaded
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+type ProgressCallback = Arc<dyn Fn(Progress) + Send + Sync>;
+type TileCallback = Arc<dyn Fn(TileCoord, &Bytes) -> Result<(), Error> + Send + Sync>;
+
+/// Downloads many tiles of a frame concurrently, retrying tiles that fail
+///
+/// Unlike [`WeatherRequester::get_region`], a failed tile does not abort the batch. Tiles that
+/// still fail after their retries are reported in [`BatchResult::errors`] alongside the tiles
+/// that were downloaded.
+///
+/// ```no_run
+/// use rain_viewer::{BatchDownloader, LatLonBounds, RequestArguments, TileCoord, WeatherRequester};
+///
+/// # async fn run() -> Result<(), rain_viewer::Error> {
+/// let req = WeatherRequester::new();
+/// let maps = req.available().await?;
+/// let frame = maps.latest_past().unwrap();
+/// let bounds = LatLonBounds::new(-125.0, 24.0, -66.0, 50.0)?;
+///
+/// let batch = BatchDownloader::new(req)
+///     .with_concurrency(16)
+///     .with_progress(|p| println!("{}/{} tiles, {} bytes", p.done, p.total, p.bytes));
+/// let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0))?;
+/// let result = batch.download_region(&maps, frame, bounds, 6, args).await?;
+/// println!("{} tiles failed", result.errors.len());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct BatchDownloader {
+    requester: WeatherRequester,
+    concurrency: usize,
+    retries: u32,
+    progress: Option<ProgressCallback>,
+    on_tile: Option<TileCallback>,
+    #[cfg(not(target_arch = "wasm32"))]
+    manifest: Option<Arc<manifest::Manifest>>,
+}
+
+impl BatchDownloader {
+    /// Creates a downloader issuing requests through `requester`, downloading 8 tiles at once
+    /// and retrying each failed tile twice
+    pub fn new(requester: WeatherRequester) -> Self {
+        Self {
+            requester,
+            concurrency: DEFAULT_CONCURRENCY,
+            retries: 2,
+            progress: None,
+            on_tile: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            manifest: None,
+        }
+    }
+
+    /// Sets how many tiles are downloaded at once
+    ///
+    /// # Panics
+    ///
+    /// Panics if `concurrency` is zero
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        assert!(concurrency > 0, "batch concurrency must not be zero");
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Sets how many times a tile is retried after a transient failure, such as a timeout or a
+    /// server error
+    ///
+    /// Retries of the same tile wait 250ms, doubling after each attempt up to 30s, or as long as
+    /// a rate limited response's `Retry-After` asks, also up to 30s. Invalid tile coordinates and
+    /// responses that are not images are not retried
+    ///
+    /// Tiles are never retried on wasm32, which has no timer to wait between retries
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Calls `progress` each time a tile finishes downloading or fails
+    pub fn with_progress(mut self, progress: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    /// Calls `on_tile` with each tile as soon as it is downloaded, such as to write it to disk
+    ///
+    /// If `on_tile` fails, the tile is reported in [`BatchResult::errors`] instead and is not
+    /// recorded as completed in the manifest set by [`Self::resume`]
+    pub fn with_on_tile(
+        mut self,
+        on_tile: impl Fn(TileCoord, &Bytes) -> Result<(), Error> + Send + Sync + 'static,
+    ) -> Self {
+        self.on_tile = Some(Arc::new(on_tile));
+        self
+    }
+
+    /// Records completed tiles in the manifest file at `path`, and skips tiles it already lists
+    ///
+    /// Use the same manifest when running a batch job again after it was interrupted, and
+    /// persist tiles from [`Self::with_on_tile`] since tiles completed by the previous run are
+    /// listed in [`BatchResult::skipped`] rather than downloaded again. Tiles are identified by
+    /// their url, so changing the frame or the tile options downloads every tile again. The
+    /// manifest is created if it does not exist.
+    ///
+    /// Not available on wasm32
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn resume(mut self, path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        self.manifest = Some(Arc::new(manifest::Manifest::open(path.as_ref())?));
+        Ok(self)
+    }
+
+    /// Downloads the radar tiles of `frame` at `coords`
+    ///
+    /// The size, color and options of `args` apply to every tile, while its location is
+    /// ignored. Returns Err(...) only if `frame` is not a radar frame. Failures of single
+    /// tiles, including invalid coordinates, are reported in the result
+    pub async fn download(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        args: RequestArguments,
+        coords: impl IntoIterator<Item = TileCoord>,
+    ) -> Result<BatchResult, error::Error> {
+        frame.expect_radar()?;
+        let mut result = BatchResult::default();
+        let coords: Vec<_> = coords
+            .into_iter()
+            .filter(|&coord| {
+                let completed = self.is_completed(maps, frame, args, coord);
+                if completed {
+                    result.skipped.insert(coord);
+                }
+                !completed
+            })
+            .collect();
+        let mut progress = Progress {
+            done: 0,
+            total: coords.len(),
+            bytes: 0,
+        };
+
+        let mut downloads = futures_util::stream::iter(coords)
+            .map(|coord| async move { (coord, self.download_tile(maps, frame, args, coord).await) })
+            .buffer_unordered(self.concurrency);
+        while let Some((coord, tile)) = downloads.next().await {
+            progress.done += 1;
+            match tile.and_then(|tile| self.complete(maps, frame, args, coord, tile)) {
+                Ok(tile) => {
+                    progress.bytes += tile.len() as u64;
+                    result.tiles.insert(coord, tile);
+                }
+                Err(err) => {
+                    result.errors.insert(coord, err);
+                }
+            }
+            if let Some(callback) = &self.progress {
+                callback(progress);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Downloads every radar tile of `frame` at `zoom` intersecting `bounds`
+    ///
+    /// See [`Self::download`]. Also returns Err(...) if `zoom` is greater than
+    /// [`MAX_RADAR_ZOOM`](crate::MAX_RADAR_ZOOM)
+    pub async fn download_region(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        bounds: LatLonBounds,
+        zoom: u32,
+        args: RequestArguments,
+    ) -> Result<BatchResult, error::Error> {
+        validate_zoom(zoom, MAX_RADAR_ZOOM)?;
+        let coords = tiles_in_bbox(&bounds, zoom);
+        self.download(maps, frame, args, coords).await
+    }
+
+    /// Whether the manifest lists `coord` as completed
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    fn is_completed(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        args: RequestArguments,
+        coord: TileCoord,
+    ) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(manifest) = &self.manifest {
+            return match args.for_tile(coord) {
+                Ok(args) => crate::requester::radar_tile_key(maps, frame, &args)
+                    .is_ok_and(|key| manifest.contains(&key.url)),
+                Err(_) => false,
+            };
+        }
+        false
+    }
+
+    /// Passes a downloaded tile to the `on_tile` callback, then records it in the manifest
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    fn complete(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        args: RequestArguments,
+        coord: TileCoord,
+        tile: Bytes,
+    ) -> Result<Bytes, Error> {
+        if let Some(on_tile) = &self.on_tile {
+            on_tile(coord, &tile)?;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(manifest) = &self.manifest {
+            let key = crate::requester::radar_tile_key(maps, frame, &args.for_tile(coord)?)?;
+            manifest.record(coord, &key.url)?;
+        }
+        Ok(tile)
+    }
+
+    async fn download_tile(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        args: RequestArguments,
+        coord: TileCoord,
+    ) -> Result<Bytes, error::Error> {
+        let args = args.for_tile(coord)?;
+        let retries = if cfg!(target_arch = "wasm32") {
+            0
+        } else {
+            self.retries
+        };
+        let mut attempt = 0;
+        loop {
+            match self.requester.get_tile(maps, frame, args).await {
+                Err(err) if attempt < retries && is_transient(&err) => {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    futures_timer::Delay::new(retry_delay_after(&err, attempt)).await;
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod manifest {
+    use std::collections::HashSet;
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::path::Path;
+    use std::sync::Mutex;
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::geo::TileCoord;
+
+    /// The tiles completed by a batch job, stored as one JSON record per line
+    #[derive(Debug)]
+    pub(super) struct Manifest {
+        completed: Mutex<(HashSet<String>, File)>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Record {
+        z: u32,
+        x: u32,
+        y: u32,
+        url: String,
+    }
+
+    impl Manifest {
+        pub(super) fn open(path: &Path) -> io::Result<Self> {
+            let mut completed = HashSet::new();
+            if path.exists() {
+                for line in BufReader::new(File::open(path)?).lines() {
+                    // A record torn by a crash is downloaded again
+                    if let Ok(record) = serde_json::from_str::<Record>(&line?) {
+                        completed.insert(record.url);
+                    }
+                }
+            }
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            Ok(Self {
+                completed: Mutex::new((completed, file)),
+            })
+        }
+
+        pub(super) fn contains(&self, url: &str) -> bool {
+            self.completed.lock().unwrap().0.contains(url)
+        }
+
+        pub(super) fn record(&self, coord: TileCoord, url: &str) -> io::Result<()> {
+            let record = Record {
+                z: coord.z,
+                x: coord.x,
+                y: coord.y,
+                url: url.to_owned(),
+            };
+            let mut line = serde_json::to_string(&record).expect("records serialize");
+            line.push('\n');
+
+            let mut completed = self.completed.lock().unwrap();
+            completed.1.write_all(line.as_bytes())?;
+            completed.0.insert(record.url);
+            Ok(())
+        }
+    }
+}
+
+/// Whether a request failing with `err` may succeed when retried
+fn is_transient(err: &Error) -> bool {
+    match err {
+        Error::Reqwest(_) | Error::Transport(_) | Error::Timeout(_) => true,
+        Error::RateLimited { .. } => true,
+        Error::Http(err) => err.status.is_server_error(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transient_errors() {
+        let server_error = Error::from_response(
+            "https://tilecache.rainviewer.com",
+            http::StatusCode::BAD_GATEWAY,
+            &http::HeaderMap::new(),
+            b"",
+        );
+        let not_found = Error::from_response(
+            "https://tilecache.rainviewer.com",
+            http::StatusCode::NOT_FOUND,
+            &http::HeaderMap::new(),
+            b"",
+        );
+        assert!(is_transient(&server_error));
+        assert!(!is_transient(&not_found));
+        assert!(is_transient(&Error::Timeout(
+            std::time::Duration::from_secs(1)
+        )));
+        assert!(!is_transient(&Error::Cancelled));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn retry_delays() {
+        use std::time::Duration;
+
+        assert_eq!(retry_delay(0), Duration::from_millis(250));
+        assert_eq!(retry_delay(2), Duration::from_secs(1));
+        assert_eq!(retry_delay(7), MAX_RETRY_DELAY);
+        // Large retry counts neither overflow nor wait longer
+        assert_eq!(retry_delay(31), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(u32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn rate_limited_retry_delays() {
+        use std::time::Duration;
+
+        let rate_limited = |retry_after| Error::RateLimited { retry_after };
+        assert_eq!(
+            retry_delay_after(&rate_limited(Some(Duration::from_secs(5))), 0),
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            retry_delay_after(&rate_limited(Some(Duration::from_secs(3600))), 0),
+            MAX_RETRY_DELAY
+        );
+        assert_eq!(retry_delay_after(&rate_limited(None), 2), retry_delay(2));
+        assert_eq!(
+            retry_delay_after(&Error::Timeout(Duration::from_secs(1)), 1),
+            retry_delay(1)
+        );
+    }
+}
@@ -0,0 +1,48 @@
+//! Prints the precipitation at one or more locations over the past and nowcast radar frames
+//! as CSV, for spreadsheets and scripts in other languages
+//!
+//! ```text
+//! rain_viewer_csv 51.5074,-0.1278 48.8566,2.3522 > london_paris.csv
+//! ```
+//!
+//! The columns are described by `rain_viewer::write_series_csv`.
+
+use std::process::ExitCode;
+
+use rain_viewer::blocking::WeatherRequester;
+use rain_viewer::{write_series_csv, PointSeries};
+
+const USAGE: &str = "Usage: rain_viewer_csv LAT,LON [LAT,LON ...]";
+
+fn main() -> ExitCode {
+    let locations: Option<Vec<(f64, f64)>> = std::env::args()
+        .skip(1)
+        .map(|arg| {
+            let (lat, lon) = arg.split_once(',')?;
+            Some((lat.trim().parse().ok()?, lon.trim().parse().ok()?))
+        })
+        .collect();
+    let locations = match locations {
+        Some(locations) if !locations.is_empty() => locations,
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let req = WeatherRequester::new();
+    let series: Result<Vec<PointSeries>, rain_viewer::Error> = locations
+        .into_iter()
+        .map(|(lat, lon)| {
+            let samples = req.nowcast_at(lat, lon)?;
+            Ok(PointSeries { lat, lon, samples })
+        })
+        .collect();
+    match series.and_then(|series| write_series_csv(std::io::stdout().lock(), &series)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("rain_viewer_csv: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
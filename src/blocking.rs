@@ -0,0 +1,189 @@
+//! A blocking requester for use outside of an async runtime
+//!
+//! Enabled with the `blocking` feature. This mirrors [`crate::WeatherRequester`] using
+//! [`reqwest::blocking`], for CLIs and scripts that do not otherwise need tokio.
+//!
+//! ```no_run
+//! let req = rain_viewer::blocking::WeatherRequester::new();
+//! let maps = req.available().unwrap();
+//! let frame = maps.latest_past().unwrap();
+//! let tile = rain_viewer::TileCoord::new(4, 7, 6);
+//! let args = rain_viewer::RequestArguments::new_tile(tile).unwrap();
+//! let png = req.get_tile(&maps, frame, args).unwrap();
+//! ```
+//!
+//! Like [`reqwest::blocking`], this requester must not be used from within an async runtime.
+
+use bytes::Bytes;
+
+use crate::args::{RequestArguments, SatelliteArguments};
+use crate::data::{AvailableData, Frame, RawAvailableData};
+use crate::error::{self, Error};
+use crate::requester::{
+    check_png, coverage_tile_key, radar_tile_key, satellite_tile_key, WEATHER_MAPS_URL,
+};
+#[cfg(feature = "image")]
+use crate::requester::{point_args, point_pixel};
+
+/// Issues blocking requests to the Rain Viewer API
+#[derive(Clone, Debug, Default)]
+pub struct WeatherRequester {
+    client: reqwest::blocking::Client,
+}
+
+impl WeatherRequester {
+    /// Creates a requester with the default connection options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a requester that issues requests through an existing blocking `client`
+    pub fn with_client(client: reqwest::blocking::Client) -> Self {
+        Self { client }
+    }
+
+    /// Queries the Rain Viewer API for what current and historical data is available.
+    ///
+    /// See [`crate::WeatherRequester::available`]
+    pub fn available(&self) -> Result<AvailableData, error::Error> {
+        let raw: RawAvailableData = serde_json::from_slice(&self.get(WEATHER_MAPS_URL)?)?;
+        Ok(raw.into())
+    }
+
+    /// Obtains a single radar tile
+    ///
+    /// See [`crate::WeatherRequester::get_tile`]
+    pub fn get_tile(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        args: RequestArguments,
+    ) -> Result<Bytes, error::Error> {
+        self.get_png(&radar_tile_key(maps, frame, &args)?.url)
+    }
+
+    /// Obtains a single tile of rain decoded into an image
+    ///
+    /// See [`crate::WeatherRequester::get_tile_image`]
+    #[cfg(feature = "image")]
+    pub fn get_tile_image(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        args: RequestArguments,
+    ) -> Result<image::DynamicImage, error::Error> {
+        crate::tile::decode_png(&self.get_tile(maps, frame, args)?)
+    }
+
+    /// Reads the precipitation at the given WGS84 location in every past and nowcast radar
+    /// frame, in chronological order
+    ///
+    /// See [`crate::WeatherRequester::nowcast_at`]. Frames are downloaded one after another
+    #[cfg(feature = "image")]
+    pub fn nowcast_at(
+        &self,
+        lat: f64,
+        lon: f64,
+    ) -> Result<Vec<(crate::Timestamp, crate::precip::PrecipEstimate)>, error::Error> {
+        let (tile, args) = point_args(lat, lon)?;
+        let maps = self.available()?;
+        let converter = crate::precip::RateConverter::default();
+        maps.radar_timeline()
+            .map(|entry| {
+                let frame = entry.frame();
+                let image = self.get_tile_image(&maps, frame, args)?.into_rgba8();
+                let (x, y) = point_pixel(&image, tile, lat, lon);
+                let estimate =
+                    crate::precip::PrecipEstimate::from_pixel(image.get_pixel(x, y).0, &converter);
+                Ok((frame.time, estimate))
+            })
+            .collect()
+    }
+
+    /// Obtains a radar tile drawn over the infrared satellite tile nearest in time
+    ///
+    /// See [`crate::WeatherRequester::get_blended_tile`]
+    #[cfg(feature = "image")]
+    pub fn get_blended_tile(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        args: RequestArguments,
+        opacity: f32,
+    ) -> Result<image::DynamicImage, error::Error> {
+        frame.expect_radar()?;
+        let satellite_frame = maps
+            .nearest_satellite_frame(frame.time)
+            .ok_or(error::Error::NoFrames(crate::data::FrameKind::Satellite))?;
+        let satellite_args = SatelliteArguments::matching(&args)?;
+        let satellite = self.get_satellite_tile(maps, satellite_frame, satellite_args)?;
+        let radar = self.get_tile(maps, frame, args)?;
+        crate::compose::compose_over(&satellite, &radar, opacity)
+    }
+
+    /// Obtains a single tile of infrared satellite imagery
+    ///
+    /// See [`crate::WeatherRequester::get_satellite_tile`]
+    pub fn get_satellite_tile(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        args: SatelliteArguments,
+    ) -> Result<Bytes, error::Error> {
+        self.get_png(&satellite_tile_key(maps, frame, &args)?.url)
+    }
+
+    /// Obtains a single tile of the radar coverage layer
+    ///
+    /// See [`crate::WeatherRequester::get_coverage_tile`]
+    pub fn get_coverage_tile(&self, x: u32, y: u32, zoom: u32) -> Result<Bytes, error::Error> {
+        self.get_png(&coverage_tile_key(x, y, zoom)?.url)
+    }
+
+    fn get_png(&self, url: &str) -> Result<Bytes, error::Error> {
+        let res = self.send(url)?;
+        check_png(url, &res)?;
+        Ok(res.into_body())
+    }
+
+    fn get(&self, url: &str) -> Result<Bytes, error::Error> {
+        Ok(self.send(url)?.into_body())
+    }
+
+    fn send(&self, url: &str) -> Result<http::Response<Bytes>, error::Error> {
+        let res = self.client.get(url).send()?;
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = res.bytes()?;
+        match status {
+            http::StatusCode::OK => {
+                let mut res = http::Response::new(body);
+                *res.headers_mut() = headers;
+                Ok(res)
+            }
+            status => Err(Error::from_response(url, status, &headers, &body)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geo::TileCoord;
+
+    #[test]
+    fn validates_before_requesting() {
+        let req = WeatherRequester::new();
+        let maps = AvailableData::fixture();
+        let args = RequestArguments::new_tile(TileCoord::new(0, 0, 1)).unwrap();
+
+        assert!(matches!(
+            req.get_tile(&maps, &maps.infrared_satellite[0], args),
+            Err(Error::Parameter(_))
+        ));
+        assert!(matches!(
+            req.get_coverage_tile(2, 0, 1),
+            Err(Error::Parameter(_))
+        ));
+    }
+}
@@ -0,0 +1,255 @@
+//! Typestate builder for tile request arguments
+//!
+//! [`TileRequestBuilder`] tracks which product is being requested and whether a location has
+//! been chosen in its type, so that a request without a location or a satellite request with
+//! radar-only options fails to compile rather than at runtime.
+//!
+//! ```
+//! use rain_viewer::{ColorKind, TileRequestBuilder, TileSize};
+//!
+//! # fn main() -> Result<(), rain_viewer::ParameterError> {
+//! let radar = TileRequestBuilder::radar()
+//!     .tile(4, 7, 6)?
+//!     .color(ColorKind::Titan)
+//!     .smooth(false)
+//!     .build();
+//!
+//! let satellite = TileRequestBuilder::satellite()
+//!     .position(40.7128, -74.006, 5)?
+//!     .size(TileSize::Px512)
+//!     .build();
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::args::{
+    RequestArguments, RequestArgumentsInner, SatelliteArguments, TileArguments, TileLocation,
+    TileSize, MAX_RADAR_ZOOM, MAX_SATELLITE_ZOOM,
+};
+use crate::color::ColorKind;
+use crate::error::ParameterError;
+use crate::geo::TileCoord;
+
+/// Marker for builders producing radar [`RequestArguments`]
+#[derive(Copy, Clone, Debug)]
+pub struct Radar {
+    color: ColorKind,
+    smooth: bool,
+    snow: bool,
+}
+
+/// Marker for builders producing [`SatelliteArguments`]
+#[derive(Copy, Clone, Debug)]
+pub struct Satellite;
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for super::Radar {}
+    impl Sealed for super::Satellite {}
+}
+
+/// The imagery products a [`TileRequestBuilder`] can produce arguments for
+pub trait Product: private::Sealed {
+    /// The highest zoom level Rain Viewer serves this product at
+    const MAX_ZOOM: u32;
+}
+
+impl Product for Radar {
+    const MAX_ZOOM: u32 = MAX_RADAR_ZOOM;
+}
+
+impl Product for Satellite {
+    const MAX_ZOOM: u32 = MAX_SATELLITE_ZOOM;
+}
+
+/// Marker for builders that have not been given a location yet
+#[derive(Copy, Clone, Debug)]
+pub struct NoLocation;
+
+/// Marker for builders that have a validated location and can be built
+#[derive(Copy, Clone, Debug)]
+pub struct Located {
+    location: TileLocation,
+    zoom: u32,
+}
+
+/// Builder for [`RequestArguments`] and [`SatelliteArguments`]
+///
+/// Defaults match [`RequestArguments::new_tile`]: 256 pixel tiles, [`ColorKind::UniversalBlue`],
+/// smoothing and snow enabled
+#[derive(Copy, Clone, Debug)]
+pub struct TileRequestBuilder<P, L> {
+    product: P,
+    location: L,
+    size: TileSize,
+}
+
+impl TileRequestBuilder<Radar, NoLocation> {
+    /// Starts building arguments for a radar tile request
+    pub const fn radar() -> Self {
+        Self {
+            product: Radar {
+                color: ColorKind::UniversalBlue,
+                smooth: true,
+                snow: true,
+            },
+            location: NoLocation,
+            size: TileSize::Px256,
+        }
+    }
+}
+
+impl TileRequestBuilder<Satellite, NoLocation> {
+    /// Starts building arguments for an infrared satellite tile request
+    pub const fn satellite() -> Self {
+        Self {
+            product: Satellite,
+            location: NoLocation,
+            size: TileSize::Px256,
+        }
+    }
+}
+
+impl<P: Product> TileRequestBuilder<P, NoLocation> {
+    /// Requests the tile at the given slippy map coordinates
+    ///
+    /// `zoom` must be at most [`Product::MAX_ZOOM`] and `x` and `y` must be less than `2^zoom`,
+    /// or Err(...) is returned
+    pub fn tile(
+        self,
+        x: u32,
+        y: u32,
+        zoom: u32,
+    ) -> Result<TileRequestBuilder<P, Located>, ParameterError> {
+        Ok(self.locate(
+            TileLocation::coordinates(TileCoord::new(x, y, zoom), P::MAX_ZOOM)?,
+            zoom,
+        ))
+    }
+
+    /// Requests the tile covering the given GPS location
+    ///
+    /// `zoom` must be at most [`Product::MAX_ZOOM`], `lat` must be within the web mercator
+    /// limits of +/-85.0511 degrees and `lon` must be within +/-180 degrees, or Err(...) is
+    /// returned
+    pub fn position(
+        self,
+        lat: f64,
+        lon: f64,
+        zoom: u32,
+    ) -> Result<TileRequestBuilder<P, Located>, ParameterError> {
+        Ok(self.locate(TileLocation::position(lat, lon, zoom, P::MAX_ZOOM)?, zoom))
+    }
+
+    fn locate(self, location: TileLocation, zoom: u32) -> TileRequestBuilder<P, Located> {
+        TileRequestBuilder {
+            product: self.product,
+            location: Located { location, zoom },
+            size: self.size,
+        }
+    }
+}
+
+impl<P, L> TileRequestBuilder<P, L> {
+    /// Sets the size of the resulting image
+    pub fn size(mut self, size: TileSize) -> Self {
+        self.size = size;
+        self
+    }
+}
+
+impl<L> TileRequestBuilder<Radar, L> {
+    /// Sets the color scheme for the tile
+    pub fn color(mut self, color: ColorKind) -> Self {
+        self.product.color = color;
+        self
+    }
+
+    /// Sets whether or not the tile image is smoothed
+    pub fn smooth(mut self, smooth: bool) -> Self {
+        self.product.smooth = smooth;
+        self
+    }
+
+    /// Sets whether or not the resulting tile should show snow
+    pub fn snow(mut self, snow: bool) -> Self {
+        self.product.snow = snow;
+        self
+    }
+}
+
+impl TileRequestBuilder<Radar, Located> {
+    /// Finishes building the radar request arguments
+    pub fn build(self) -> RequestArguments {
+        RequestArguments {
+            inner: RequestArgumentsInner::Tile(TileArguments {
+                size: self.size,
+                location: self.location.location,
+                zoom: self.location.zoom,
+                color: self.product.color,
+                smooth: self.product.smooth,
+                snow: self.product.snow,
+            }),
+        }
+    }
+}
+
+impl TileRequestBuilder<Satellite, Located> {
+    /// Finishes building the satellite request arguments
+    pub fn build(self) -> SatelliteArguments {
+        SatelliteArguments {
+            size: self.size,
+            location: self.location.location,
+            zoom: self.location.zoom,
+        }
+    }
+}
+
+impl RequestArguments {
+    /// Starts a [`TileRequestBuilder`] for radar tiles
+    pub const fn builder() -> TileRequestBuilder<Radar, NoLocation> {
+        TileRequestBuilder::radar()
+    }
+}
+
+impl SatelliteArguments {
+    /// Starts a [`TileRequestBuilder`] for satellite tiles
+    pub const fn builder() -> TileRequestBuilder<Satellite, NoLocation> {
+        TileRequestBuilder::satellite()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_matches_setters() {
+        let built = TileRequestBuilder::radar()
+            .tile(4, 7, 6)
+            .unwrap()
+            .color(ColorKind::Titan)
+            .smooth(false)
+            .size(TileSize::Px512)
+            .build();
+
+        let mut set = RequestArguments::new_tile(TileCoord::new(4, 7, 6)).unwrap();
+        set.set_color(ColorKind::Titan)
+            .set_smooth(false)
+            .set_size(TileSize::Px512);
+
+        let RequestArgumentsInner::Tile(built) = built.inner;
+        let RequestArgumentsInner::Tile(set) = set.inner;
+        assert_eq!(built.url("h", "/p"), set.url("h", "/p"));
+    }
+
+    #[test]
+    fn builder_rejects_invalid_location() {
+        assert!(TileRequestBuilder::satellite().tile(0, 2, 1).is_err());
+        assert!(TileRequestBuilder::radar().position(0.0, 200.0, 1).is_err());
+        assert!(TileRequestBuilder::satellite()
+            .tile(0, 0, MAX_SATELLITE_ZOOM + 1)
+            .is_err());
+    }
+}
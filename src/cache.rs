@@ -0,0 +1,321 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+
+use crate::data::FrameKind;
+use crate::transport::BoxFuture;
+
+/// Identifies a cached tile
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TileKey {
+    /// The path of the frame the tile belongs to, such as `/v2/radar/1697000400`
+    pub path: String,
+    /// The kind of frame the tile belongs to, or `None` for radar coverage tiles
+    pub kind: Option<FrameKind>,
+    /// The full url of the tile, which encodes the frame path and all tile arguments
+    pub url: String,
+}
+
+impl TileKey {
+    pub(crate) fn new(path: &str, kind: Option<FrameKind>, url: String) -> Self {
+        Self {
+            path: path.to_owned(),
+            kind,
+            url,
+        }
+    }
+}
+
+/// A store [`WeatherRequester`](crate::WeatherRequester) consults before downloading a tile,
+/// attached with [`WeatherRequester::with_cache`](crate::WeatherRequester::with_cache)
+///
+/// Implement this trait to back the cache with redis, sled or another store. [`MemoryCache`]
+/// is the reference implementation. Caching is best effort, so implementations should swallow
+/// their own errors rather than fail the request
+pub trait TileCache: Send + Sync {
+    /// Returns the cached tile for `key`, if any
+    fn get<'a>(&'a self, key: &'a TileKey) -> BoxFuture<'a, Option<Bytes>>;
+
+    /// Stores `tile` under `key`
+    fn put<'a>(&'a self, key: &'a TileKey, tile: Bytes) -> BoxFuture<'a, ()>;
+
+    /// Removes the tile cached under `key`
+    fn invalidate<'a>(&'a self, key: &'a TileKey) -> BoxFuture<'a, ()>;
+
+    /// Removes the tiles of frames whose path is not in `frames`, since frames that rolled off the
+    /// API will never be requested again. Tiles not tied to a frame, such as radar coverage
+    /// tiles, are kept
+    ///
+    /// Called with the paths of all available frames whenever the requester fetches the frame
+    /// list. The default implementation does nothing, leaving expiry to the cache itself
+    fn retain_frames<'a>(&'a self, frames: &'a HashSet<String>) -> BoxFuture<'a, ()> {
+        let _ = frames;
+        Box::pin(std::future::ready(()))
+    }
+}
+
+impl<C: TileCache + ?Sized> TileCache for Arc<C> {
+    fn get<'a>(&'a self, key: &'a TileKey) -> BoxFuture<'a, Option<Bytes>> {
+        (**self).get(key)
+    }
+
+    fn put<'a>(&'a self, key: &'a TileKey, tile: Bytes) -> BoxFuture<'a, ()> {
+        (**self).put(key, tile)
+    }
+
+    fn invalidate<'a>(&'a self, key: &'a TileKey) -> BoxFuture<'a, ()> {
+        (**self).invalidate(key)
+    }
+
+    fn retain_frames<'a>(&'a self, frames: &'a HashSet<String>) -> BoxFuture<'a, ()> {
+        (**self).retain_frames(frames)
+    }
+}
+
+impl TileKey {
+    /// Returns true if this tile belongs to a frame that is not in `frames`
+    pub(crate) fn is_expired_frame(&self, frames: &HashSet<String>) -> bool {
+        self.kind.is_some() && !frames.contains(&self.path)
+    }
+}
+
+/// Creates a [`moka`] cache for [`WeatherRequester::with_cache`], holding up to `max_bytes`
+/// bytes of tiles
+///
+/// Each entry is weighed by the size of its tile. Tiles of 4 GiB or more are given the maximum
+/// weight. Invalidation closures are enabled, so that tiles of expired frames can be removed by
+/// [`TileCache::retain_frames`]
+///
+/// Enabled with the `moka` feature
+///
+/// [`WeatherRequester::with_cache`]: crate::WeatherRequester::with_cache
+#[cfg(feature = "moka")]
+pub fn moka_tile_cache(max_bytes: u64) -> moka::future::Cache<TileKey, Bytes> {
+    moka::future::Cache::builder()
+        .weigher(|_key, tile: &Bytes| u32::try_from(tile.len()).unwrap_or(u32::MAX))
+        .max_capacity(max_bytes)
+        .support_invalidation_closures()
+        .build()
+}
+
+/// Caches tiles in a [`moka::future::Cache`]
+///
+/// Enabled with the `moka` feature
+#[cfg(feature = "moka")]
+impl TileCache for moka::future::Cache<TileKey, Bytes> {
+    fn get<'a>(&'a self, key: &'a TileKey) -> BoxFuture<'a, Option<Bytes>> {
+        Box::pin(moka::future::Cache::get(self, key))
+    }
+
+    fn put<'a>(&'a self, key: &'a TileKey, tile: Bytes) -> BoxFuture<'a, ()> {
+        Box::pin(self.insert(key.clone(), tile))
+    }
+
+    fn invalidate<'a>(&'a self, key: &'a TileKey) -> BoxFuture<'a, ()> {
+        Box::pin(moka::future::Cache::invalidate(self, key))
+    }
+
+    /// Requires invalidation closures to be enabled on the cache, as done by
+    /// [`moka_tile_cache`]. Does nothing otherwise
+    fn retain_frames<'a>(&'a self, frames: &'a HashSet<String>) -> BoxFuture<'a, ()> {
+        let frames = frames.clone();
+        let _ = self.invalidate_entries_if(move |key, _| key.is_expired_frame(&frames));
+        Box::pin(std::future::ready(()))
+    }
+}
+
+/// An in-memory least recently used tile cache
+///
+/// Once either the entry count or the byte budget is exceeded, the least recently used tiles are
+/// evicted. Attach it to a requester with
+/// [`WeatherRequester::with_cache`](crate::WeatherRequester::with_cache), wrapped in an [`Arc`]
+/// to keep a handle for inspecting it
+#[derive(Debug)]
+pub struct MemoryCache {
+    max_entries: usize,
+    max_bytes: usize,
+    state: Mutex<LruState>,
+}
+
+#[derive(Debug, Default)]
+struct LruState {
+    entries: HashMap<TileKey, (Bytes, u64)>,
+    /// Keys by the tick they were last used at, oldest first
+    order: BTreeMap<u64, TileKey>,
+    tick: u64,
+    bytes: usize,
+}
+
+impl LruState {
+    fn touch(&mut self, key: &TileKey) -> Option<Bytes> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (tile, used) = self.entries.get_mut(key)?;
+        self.order.remove(used);
+        *used = tick;
+        self.order.insert(tick, key.clone());
+        Some(tile.clone())
+    }
+
+    fn remove(&mut self, key: &TileKey) {
+        if let Some((tile, used)) = self.entries.remove(key) {
+            self.order.remove(&used);
+            self.bytes -= tile.len();
+        }
+    }
+}
+
+impl MemoryCache {
+    /// Creates a cache holding at most `max_entries` tiles and `max_bytes` bytes of tile data
+    pub fn new(max_entries: usize, max_bytes: usize) -> Self {
+        Self {
+            max_entries,
+            max_bytes,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Returns the cached tile for `key`, marking it as recently used
+    pub fn get(&self, key: &TileKey) -> Option<Bytes> {
+        self.state.lock().unwrap().touch(key)
+    }
+
+    /// Caches `tile` under `key`, evicting the least recently used tiles to stay within budget.
+    /// Tiles larger than the whole byte budget are not cached
+    pub fn put(&self, key: TileKey, tile: Bytes) {
+        if tile.len() > self.max_bytes || self.max_entries == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.remove(&key);
+        state.tick += 1;
+        let tick = state.tick;
+        state.bytes += tile.len();
+        state.order.insert(tick, key.clone());
+        state.entries.insert(key, (tile, tick));
+
+        while state.entries.len() > self.max_entries || state.bytes > self.max_bytes {
+            let Some((_, oldest)) = state.order.pop_first() else {
+                break;
+            };
+            if let Some((tile, _)) = state.entries.remove(&oldest) {
+                state.bytes -= tile.len();
+            }
+        }
+    }
+
+    /// Removes the tile cached under `key`
+    pub fn invalidate(&self, key: &TileKey) {
+        self.state.lock().unwrap().remove(key);
+    }
+
+    /// The number of cached tiles
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    /// Returns true if no tiles are cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The total size of the cached tiles in bytes
+    pub fn size_bytes(&self) -> usize {
+        self.state.lock().unwrap().bytes
+    }
+}
+
+impl TileCache for MemoryCache {
+    fn get<'a>(&'a self, key: &'a TileKey) -> BoxFuture<'a, Option<Bytes>> {
+        Box::pin(std::future::ready(MemoryCache::get(self, key)))
+    }
+
+    fn put<'a>(&'a self, key: &'a TileKey, tile: Bytes) -> BoxFuture<'a, ()> {
+        MemoryCache::put(self, key.clone(), tile);
+        Box::pin(std::future::ready(()))
+    }
+
+    fn invalidate<'a>(&'a self, key: &'a TileKey) -> BoxFuture<'a, ()> {
+        MemoryCache::invalidate(self, key);
+        Box::pin(std::future::ready(()))
+    }
+
+    fn retain_frames<'a>(&'a self, frames: &'a HashSet<String>) -> BoxFuture<'a, ()> {
+        let mut state = self.state.lock().unwrap();
+        let expired: Vec<TileKey> = state
+            .entries
+            .keys()
+            .filter(|key| key.is_expired_frame(frames))
+            .cloned()
+            .collect();
+        for key in &expired {
+            state.remove(key);
+        }
+        Box::pin(std::future::ready(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(n: u32) -> TileKey {
+        TileKey::new(
+            "/v2/radar/1",
+            Some(FrameKind::PastRadar),
+            format!("https://host/v2/radar/1/256/1/{n}/0/2/1_1.png"),
+        )
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = MemoryCache::new(2, 100);
+        cache.put(key(0), Bytes::from_static(b"a"));
+        cache.put(key(1), Bytes::from_static(b"b"));
+        assert!(cache.get(&key(0)).is_some());
+
+        cache.put(key(2), Bytes::from_static(b"c"));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&key(1)).is_none());
+        assert_eq!(cache.get(&key(0)).unwrap(), "a");
+
+        cache.invalidate(&key(0));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.size_bytes(), 1);
+    }
+
+    #[test]
+    fn byte_budget() {
+        let cache = MemoryCache::new(10, 10);
+        cache.put(key(0), Bytes::from_static(b"123456"));
+        cache.put(key(1), Bytes::from_static(b"12345"));
+        assert!(cache.get(&key(0)).is_none());
+        assert_eq!(cache.size_bytes(), 5);
+
+        // Replacing an entry does not count it twice
+        cache.put(key(1), Bytes::from_static(b"1234"));
+        assert_eq!(cache.size_bytes(), 4);
+
+        cache.put(key(2), Bytes::from_static(b"12345678901"));
+        assert!(cache.get(&key(2)).is_none());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn retain_frames() {
+        let cache = MemoryCache::new(10, 100);
+        let coverage = TileKey::new("/v2/coverage/0", None, "coverage".to_owned());
+        cache.put(key(0), Bytes::from_static(b"a"));
+        cache.put(coverage.clone(), Bytes::from_static(b"b"));
+
+        let mut frames = HashSet::from(["/v2/radar/1".to_owned()]);
+        TileCache::retain_frames(&cache, &frames).await;
+        assert_eq!(cache.len(), 2);
+
+        frames.clear();
+        TileCache::retain_frames(&cache, &frames).await;
+        assert!(cache.get(&key(0)).is_none());
+        assert!(cache.get(&coverage).is_some());
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+
+use bytes::Bytes;
+
+use crate::error::Error;
+
+/// Deduplicates concurrent requests for the same url, so that only one of them reaches the
+/// transport and the others share its response
+#[derive(Debug, Default)]
+pub(crate) struct Coalescer {
+    in_flight: Mutex<HashMap<String, Arc<Slot>>>,
+}
+
+/// The eventual response of an in flight request
+#[derive(Debug, Default)]
+struct Slot {
+    state: Mutex<SlotState>,
+}
+
+#[derive(Debug, Default)]
+struct SlotState {
+    /// `Some(None)` once the request failed or was cancelled
+    result: Option<Option<Bytes>>,
+    wakers: Vec<Waker>,
+}
+
+impl Slot {
+    fn complete(&self, result: Option<Bytes>) {
+        let mut state = self.state.lock().unwrap();
+        state.result = Some(result);
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+
+    async fn wait(&self) -> Option<Bytes> {
+        std::future::poll_fn(|cx| {
+            let mut state = self.state.lock().unwrap();
+            match &state.result {
+                Some(result) => Poll::Ready(result.clone()),
+                None => {
+                    if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                        state.wakers.push(cx.waker().clone());
+                    }
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+}
+
+/// Completes the slot of the leading request when it finishes or is dropped
+struct Leader<'a> {
+    coalescer: &'a Coalescer,
+    key: &'a str,
+    slot: Arc<Slot>,
+    result: Option<Bytes>,
+}
+
+impl Drop for Leader<'_> {
+    fn drop(&mut self) {
+        self.coalescer.in_flight.lock().unwrap().remove(self.key);
+        self.slot.complete(self.result.take());
+    }
+}
+
+impl Coalescer {
+    /// Runs `fetch` unless a request for `key` is already in flight, in which case its response
+    /// is shared
+    ///
+    /// Errors are not shared, since [`Error`] cannot be cloned. If the request in flight fails or
+    /// is cancelled, one of the waiting callers runs `fetch` in its place and the others wait
+    /// for it in turn, so a failure does not turn into a burst of duplicate requests
+    pub(crate) async fn run<F, Fut>(&self, key: &str, fetch: F) -> Result<Bytes, Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Bytes, Error>>,
+    {
+        loop {
+            let existing = {
+                let mut in_flight = self.in_flight.lock().unwrap();
+                match in_flight.get(key) {
+                    Some(slot) => Err(slot.clone()),
+                    None => {
+                        let slot = Arc::new(Slot::default());
+                        in_flight.insert(key.to_owned(), slot.clone());
+                        Ok(slot)
+                    }
+                }
+            };
+
+            match existing {
+                Ok(slot) => {
+                    let mut leader = Leader {
+                        coalescer: self,
+                        key,
+                        slot,
+                        result: None,
+                    };
+                    let res = fetch().await;
+                    leader.result = res.as_ref().ok().cloned();
+                    return res;
+                }
+                Err(slot) => {
+                    if let Some(body) = slot.wait().await {
+                        return Ok(body);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn coalesces_concurrent_requests() {
+        let coalescer = Coalescer::default();
+        let fetches = AtomicUsize::new(0);
+        let fetch = || async {
+            fetches.fetch_add(1, Ordering::SeqCst);
+            tokio::task::yield_now().await;
+            Ok(Bytes::from_static(b"tile"))
+        };
+
+        let (a, b, c) = tokio::join!(
+            coalescer.run("a", fetch),
+            coalescer.run("a", fetch),
+            coalescer.run("b", fetch),
+        );
+        assert_eq!(a.unwrap(), "tile");
+        assert_eq!(b.unwrap(), "tile");
+        assert_eq!(c.unwrap(), "tile");
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+        assert!(coalescer.in_flight.lock().unwrap().is_empty());
+
+        // Later requests are not served from the finished one
+        coalescer.run("a", fetch).await.unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn errors_are_not_shared() {
+        let coalescer = Coalescer::default();
+        let fetches = AtomicUsize::new(0);
+        let fetch = || async {
+            let n = fetches.fetch_add(1, Ordering::SeqCst);
+            tokio::task::yield_now().await;
+            match n {
+                0 => Err(Error::Timeout(std::time::Duration::ZERO)),
+                _ => Ok(Bytes::from_static(b"tile")),
+            }
+        };
+
+        let (a, b) = tokio::join!(coalescer.run("a", fetch), coalescer.run("a", fetch));
+        assert!(a.is_err());
+        assert_eq!(b.unwrap(), "tile");
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn one_waiter_retries_after_a_failure() {
+        let coalescer = Coalescer::default();
+        let fetches = AtomicUsize::new(0);
+        let fetch = || async {
+            let n = fetches.fetch_add(1, Ordering::SeqCst);
+            tokio::task::yield_now().await;
+            match n {
+                0 => Err(Error::RateLimited { retry_after: None }),
+                _ => Ok(Bytes::from_static(b"tile")),
+            }
+        };
+
+        let (a, b, c, d) = tokio::join!(
+            coalescer.run("a", fetch),
+            coalescer.run("a", fetch),
+            coalescer.run("a", fetch),
+            coalescer.run("a", fetch),
+        );
+        assert!(a.is_err());
+        for res in [b, c, d] {
+            assert_eq!(res.unwrap(), "tile");
+        }
+        // The leader's failure is retried by a single waiter, which the others wait for
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+        assert!(coalescer.in_flight.lock().unwrap().is_empty());
+    }
+}
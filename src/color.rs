@@ -0,0 +1,288 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::ParameterError;
+
+/// The kinds of colors supported by rainviewer
+/// All have different visual attributes. See <https://www.rainviewer.com/api/color-schemes.html>
+/// for more information
+///
+/// Rain Viewer occasionally adds new schemes. Use [`ColorKind::Custom`] to request a scheme by
+/// its number before this crate knows about it
+///
+/// Schemes compare and hash by their scheme number, so `ColorKind::Custom(3)` equals
+/// [`ColorKind::Titan`]
+#[derive(Copy, Clone, Debug)]
+#[non_exhaustive]
+pub enum ColorKind {
+    BlackAndWhite,
+    Original,
+    UniversalBlue,
+    Titan,
+    TheWeatherChannel,
+    Meteored,
+    NexradLevelIII,
+    RainbowSelexIS,
+    DarkSky,
+    /// A color scheme identified by its raw Rain Viewer scheme number
+    Custom(u32),
+}
+
+impl From<ColorKind> for u32 {
+    fn from(color: ColorKind) -> Self {
+        // Values obtained from: https://www.rainviewer.com/api/color-schemes.html
+        match color {
+            ColorKind::BlackAndWhite => 0,
+            ColorKind::Original => 1,
+            ColorKind::UniversalBlue => 2,
+            ColorKind::Titan => 3,
+            ColorKind::TheWeatherChannel => 4,
+            ColorKind::Meteored => 5,
+            ColorKind::NexradLevelIII => 6,
+            ColorKind::RainbowSelexIS => 7,
+            ColorKind::DarkSky => 8,
+            ColorKind::Custom(value) => value,
+        }
+    }
+}
+
+impl PartialEq for ColorKind {
+    fn eq(&self, other: &Self) -> bool {
+        u32::from(*self) == u32::from(*other)
+    }
+}
+
+impl Eq for ColorKind {}
+
+impl std::hash::Hash for ColorKind {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        u32::from(*self).hash(state);
+    }
+}
+
+impl TryFrom<u32> for ColorKind {
+    type Error = ParameterError;
+
+    /// Converts a Rain Viewer scheme number into a [`ColorKind`]
+    ///
+    /// Known numbers map to their named variant, and other numbers up to 255 map to
+    /// [`ColorKind::Custom`]. Scheme numbers are a single byte, so larger values return Err(...)
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ColorKind::BlackAndWhite),
+            1 => Ok(ColorKind::Original),
+            2 => Ok(ColorKind::UniversalBlue),
+            3 => Ok(ColorKind::Titan),
+            4 => Ok(ColorKind::TheWeatherChannel),
+            5 => Ok(ColorKind::Meteored),
+            6 => Ok(ColorKind::NexradLevelIII),
+            7 => Ok(ColorKind::RainbowSelexIS),
+            8 => Ok(ColorKind::DarkSky),
+            9..=255 => Ok(ColorKind::Custom(value)),
+            _ => Err(ParameterError::InvalidColor(
+                value,
+                "Color scheme numbers must be between 0 and 255".to_owned(),
+            )),
+        }
+    }
+}
+
+/// Human readable information about a color scheme, for building scheme pickers
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ColorMetadata {
+    /// The name Rain Viewer uses for the scheme
+    pub name: &'static str,
+
+    /// A short description of how the scheme looks
+    pub description: &'static str,
+
+    /// True if pixel values in this scheme increase linearly with reflectivity (dBZ), which
+    /// allows recovering dBZ values directly from the image
+    pub linear_dbz: bool,
+}
+
+impl ColorKind {
+    /// Every named color scheme known to this crate, in Rain Viewer's numbering order
+    pub const ALL: [ColorKind; 9] = [
+        ColorKind::BlackAndWhite,
+        ColorKind::Original,
+        ColorKind::UniversalBlue,
+        ColorKind::Titan,
+        ColorKind::TheWeatherChannel,
+        ColorKind::Meteored,
+        ColorKind::NexradLevelIII,
+        ColorKind::RainbowSelexIS,
+        ColorKind::DarkSky,
+    ];
+
+    /// Returns display information about this scheme
+    ///
+    /// [`ColorKind::Custom`] schemes with a number this crate does not know return `None`
+    pub fn metadata(self) -> Option<ColorMetadata> {
+        let (name, description, linear_dbz) = match ColorKind::try_from(u32::from(self)).ok()? {
+            ColorKind::BlackAndWhite => (
+                "Black and White",
+                "Grayscale where brightness increases linearly with dBZ",
+                true,
+            ),
+            ColorKind::Original => (
+                "Original",
+                "Rain Viewer's original green to red palette",
+                false,
+            ),
+            ColorKind::UniversalBlue => (
+                "Universal Blue",
+                "Rain Viewer's default palette built around shades of blue",
+                false,
+            ),
+            ColorKind::Titan => (
+                "TITAN",
+                "High contrast palette from the TITAN storm tracking software",
+                false,
+            ),
+            ColorKind::TheWeatherChannel => (
+                "The Weather Channel",
+                "Palette used by The Weather Channel's radar maps",
+                false,
+            ),
+            ColorKind::Meteored => (
+                "Meteored",
+                "Palette used by the Meteored weather service",
+                false,
+            ),
+            ColorKind::NexradLevelIII => (
+                "NEXRAD Level III",
+                "The US National Weather Service NEXRAD reflectivity palette",
+                false,
+            ),
+            ColorKind::RainbowSelexIS => (
+                "Rainbow @ SELEX-IS",
+                "Rainbow palette from the SELEX-IS radar software",
+                false,
+            ),
+            ColorKind::DarkSky => (
+                "Dark Sky",
+                "Soft palette from the Dark Sky weather app",
+                false,
+            ),
+            ColorKind::Custom(_) => return None,
+        };
+        Some(ColorMetadata {
+            name,
+            description,
+            linear_dbz,
+        })
+    }
+}
+
+/// The names used to print and parse the named color schemes
+const NAMES: [(ColorKind, &str); 9] = [
+    (ColorKind::BlackAndWhite, "black-and-white"),
+    (ColorKind::Original, "original"),
+    (ColorKind::UniversalBlue, "universal-blue"),
+    (ColorKind::Titan, "titan"),
+    (ColorKind::TheWeatherChannel, "the-weather-channel"),
+    (ColorKind::Meteored, "meteored"),
+    (ColorKind::NexradLevelIII, "nexrad-level-iii"),
+    (ColorKind::RainbowSelexIS, "rainbow-selex-is"),
+    (ColorKind::DarkSky, "dark-sky"),
+];
+
+impl fmt::Display for ColorKind {
+    /// Writes the kebab case name of the scheme, such as `universal-blue`.
+    /// [`ColorKind::Custom`] schemes are written as their scheme number
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = u32::from(*self);
+        match NAMES.iter().find(|(color, _)| u32::from(*color) == value) {
+            Some((_, name)) => f.write_str(name),
+            None => write!(f, "{}", value),
+        }
+    }
+}
+
+impl FromStr for ColorKind {
+    type Err = ParameterError;
+
+    /// Parses a scheme name such as `titan` or `Universal_Blue`, or a scheme number
+    ///
+    /// Names are case insensitive and treat `-`, `_` and spaces the same
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(value) = s.parse::<u32>() {
+            return ColorKind::try_from(value);
+        }
+        let normalized = s.to_lowercase().replace(['_', ' '], "-");
+        NAMES
+            .iter()
+            .find(|(_, name)| *name == normalized)
+            .map(|(color, _)| *color)
+            .ok_or_else(|| {
+                let names: Vec<_> = NAMES.iter().map(|(_, name)| *name).collect();
+                ParameterError::InvalidColorName(
+                    s.to_owned(),
+                    format!("Expected a scheme number or one of: {}", names.join(", ")),
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_round_trip() {
+        for value in 0..=255 {
+            let color = ColorKind::try_from(value).unwrap();
+            assert_eq!(u32::from(color), value);
+        }
+        assert!(matches!(ColorKind::try_from(3), Ok(ColorKind::Titan)));
+        assert!(matches!(
+            ColorKind::try_from(255),
+            Ok(ColorKind::Custom(255))
+        ));
+        assert!(ColorKind::try_from(256).is_err());
+    }
+
+    #[test]
+    fn custom_numbers_equal_named_schemes() {
+        assert_eq!(ColorKind::Custom(3), ColorKind::Titan);
+        assert_ne!(ColorKind::Custom(9), ColorKind::Titan);
+        let schemes: std::collections::HashSet<_> =
+            [ColorKind::Titan, ColorKind::Custom(3), ColorKind::Custom(9)].into();
+        assert_eq!(schemes.len(), 2);
+        assert_eq!(ColorKind::try_from(3).unwrap(), ColorKind::Titan);
+    }
+
+    #[test]
+    fn all_have_metadata() {
+        for (i, color) in ColorKind::ALL.iter().enumerate() {
+            assert_eq!(u32::from(*color), i as u32);
+            assert!(color.metadata().is_some());
+        }
+        assert_eq!(ColorKind::Custom(3).metadata(), ColorKind::Titan.metadata());
+        assert_eq!(ColorKind::Custom(200).metadata(), None);
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for (color, name) in NAMES {
+            assert_eq!(color.to_string(), name);
+            assert_eq!(
+                u32::from(name.parse::<ColorKind>().unwrap()),
+                u32::from(color)
+            );
+        }
+        assert!(matches!(
+            "Universal_Blue".parse(),
+            Ok(ColorKind::UniversalBlue)
+        ));
+        assert!(matches!(" TITAN ".parse(), Ok(ColorKind::Titan)));
+        assert!(matches!("17".parse(), Ok(ColorKind::Custom(17))));
+        assert_eq!(ColorKind::Custom(17).to_string(), "17");
+        assert!(matches!(
+            "titanic".parse::<ColorKind>(),
+            Err(ParameterError::InvalidColorName(_, _))
+        ));
+    }
+}
@@ -0,0 +1,134 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, RgbaImage};
+
+use crate::error;
+use crate::tile::decode_png;
+
+/// Draws a radar tile over a basemap tile of the same coordinate, such as an OpenStreetMap or
+/// satellite tile, for displays without a map widget to layer them
+///
+/// `opacity` scales the radar's own transparency, from `0.0` for only the basemap to `1.0` for
+/// the radar as served, and is clamped to that range. The radar tile is scaled to the size of
+/// the basemap tile if they differ.
+///
+/// Enabled with the `image` feature. Returns Err(...) if either tile is not a valid PNG image
+pub fn compose_over(
+    base_png: &[u8],
+    radar_png: &[u8],
+    opacity: f32,
+) -> Result<DynamicImage, error::Error> {
+    let mut base = decode_png(base_png)?.into_rgba8();
+    let radar = decode_png(radar_png)?;
+    blend_over(&mut base, radar, opacity);
+    Ok(DynamicImage::ImageRgba8(base))
+}
+
+/// Scales the transparency of a decoded tile by `opacity`, from `0.0` for fully transparent to
+/// `1.0` for unchanged, clamped to that range
+///
+/// This lets a display fade a layer in and out without downloading it again. The scale is
+/// relative to the image's current alpha, so apply it to a copy of the decoded tile rather than
+/// repeatedly to the same image:
+///
+/// ```
+/// use image::{Rgba, RgbaImage};
+/// use rain_viewer::set_opacity;
+///
+/// let tile = RgbaImage::from_pixel(256, 256, Rgba([0, 200, 0, 255]));
+/// for step in 0..=10 {
+///     let mut faded = tile.clone();
+///     set_opacity(&mut faded, step as f32 / 10.0);
+/// }
+/// ```
+///
+/// Enabled with the `image` feature
+pub fn set_opacity(image: &mut RgbaImage, opacity: f32) {
+    let opacity = opacity.clamp(0.0, 1.0);
+    for pixel in image.pixels_mut() {
+        pixel.0[3] = (pixel.0[3] as f32 * opacity).round() as u8;
+    }
+}
+
+/// Alpha blends `overlay`, scaled to the size of `base` and with its alpha scaled by
+/// `opacity`, over `base`
+pub(crate) fn blend_over(base: &mut RgbaImage, overlay: DynamicImage, opacity: f32) {
+    let (width, height) = base.dimensions();
+    let overlay = if overlay.width() == width && overlay.height() == height {
+        overlay.into_rgba8()
+    } else {
+        overlay
+            .resize_exact(width, height, FilterType::Triangle)
+            .into_rgba8()
+    };
+    let opacity = opacity.clamp(0.0, 1.0);
+    for (below, above) in base.pixels_mut().zip(overlay.pixels()) {
+        let source_alpha = above.0[3] as f32 / 255.0 * opacity;
+        let dest_alpha = below.0[3] as f32 / 255.0;
+        let alpha = source_alpha + dest_alpha * (1.0 - source_alpha);
+        if alpha == 0.0 {
+            below.0 = [0; 4];
+            continue;
+        }
+        for channel in 0..3 {
+            let source = above.0[channel] as f32 * source_alpha;
+            let dest = below.0[channel] as f32 * dest_alpha * (1.0 - source_alpha);
+            below.0[channel] = ((source + dest) / alpha).round() as u8;
+        }
+        below.0[3] = (alpha * 255.0).round() as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(size: u32, color: [u8; 4]) -> Vec<u8> {
+        let image = RgbaImage::from_pixel(size, size, image::Rgba(color));
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(image)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn composes() {
+        let base = png(4, [0, 0, 200, 255]);
+        let radar = png(2, [200, 0, 0, 255]);
+
+        let opaque = compose_over(&base, &radar, 1.0).unwrap().into_rgba8();
+        assert_eq!(opaque.dimensions(), (4, 4));
+        assert_eq!(opaque.get_pixel(3, 3).0, [200, 0, 0, 255]);
+
+        let half = compose_over(&base, &radar, 0.5).unwrap().into_rgba8();
+        assert_eq!(half.get_pixel(0, 0).0, [100, 0, 100, 255]);
+
+        // Transparent radar pixels leave the basemap visible
+        let clear = compose_over(&base, &png(4, [200, 0, 0, 0]), 1.0).unwrap();
+        assert_eq!(clear.into_rgba8().get_pixel(1, 1).0, [0, 0, 200, 255]);
+
+        assert!(compose_over(b"not a png", &radar, 1.0).is_err());
+    }
+
+    #[test]
+    fn opacity() {
+        let mut image = RgbaImage::from_pixel(2, 1, image::Rgba([10, 20, 30, 200]));
+        image.put_pixel(1, 0, image::Rgba([10, 20, 30, 0]));
+        set_opacity(&mut image, 0.5);
+        assert_eq!(image.get_pixel(0, 0).0, [10, 20, 30, 100]);
+        assert_eq!(image.get_pixel(1, 0).0, [10, 20, 30, 0]);
+
+        set_opacity(&mut image, 2.0);
+        assert_eq!(image.get_pixel(0, 0).0[3], 100);
+        set_opacity(&mut image, -1.0);
+        assert_eq!(image.get_pixel(0, 0).0[3], 0);
+    }
+
+    #[test]
+    fn blends_translucent_layers() {
+        let mut base = RgbaImage::from_pixel(1, 1, image::Rgba([0, 0, 0, 0]));
+        let overlay = RgbaImage::from_pixel(1, 1, image::Rgba([10, 20, 30, 128]));
+        blend_over(&mut base, DynamicImage::ImageRgba8(overlay), 1.0);
+        assert_eq!(base.get_pixel(0, 0).0, [10, 20, 30, 128]);
+    }
+}
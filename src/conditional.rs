@@ -0,0 +1,86 @@
+/// Cache validators identifying the version of a response, sent back to Rain Viewer so that
+/// unchanged payloads are not downloaded again
+///
+/// Obtained from [`Conditional::Modified`]. The default value has no validators, which makes
+/// the first conditional request unconditional
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Validators {
+    /// The `ETag` header of the response, sent as `If-None-Match`
+    pub etag: Option<String>,
+    /// The `Last-Modified` header of the response, sent as `If-Modified-Since`
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub(crate) fn from_headers(headers: &http::HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        Self {
+            etag: header(http::header::ETAG),
+            last_modified: header(http::header::LAST_MODIFIED),
+        }
+    }
+
+    /// The conditional request headers for these validators
+    pub(crate) fn to_headers(&self) -> http::HeaderMap {
+        let mut headers = http::HeaderMap::new();
+        let mut insert = |name, value: &Option<String>| {
+            if let Some(value) = value.as_deref().and_then(|v| v.parse().ok()) {
+                headers.insert(name, value);
+            }
+        };
+        insert(http::header::IF_NONE_MATCH, &self.etag);
+        insert(http::header::IF_MODIFIED_SINCE, &self.last_modified);
+        headers
+    }
+}
+
+/// The result of a conditional request
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Conditional<T> {
+    /// The payload changed since the validators were obtained. Pass the new validators to the
+    /// next request
+    Modified(T, Validators),
+    /// The payload is unchanged, so the previously downloaded value can be reused
+    NotModified,
+}
+
+impl<T> Conditional<T> {
+    /// Returns the new value, or `None` if it was not modified
+    pub fn modified(self) -> Option<T> {
+        match self {
+            Conditional::Modified(value, _) => Some(value),
+            Conditional::NotModified => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_round_trip() {
+        let mut response = http::HeaderMap::new();
+        response.insert(http::header::ETAG, "\"abc\"".parse().unwrap());
+        response.insert(
+            http::header::LAST_MODIFIED,
+            "Sun, 06 Nov 1994 08:49:37 GMT".parse().unwrap(),
+        );
+
+        let validators = Validators::from_headers(&response);
+        assert_eq!(validators.etag.as_deref(), Some("\"abc\""));
+
+        let request = validators.to_headers();
+        assert_eq!(request[http::header::IF_NONE_MATCH], "\"abc\"");
+        assert_eq!(
+            request[http::header::IF_MODIFIED_SINCE],
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+        assert!(Validators::default().to_headers().is_empty());
+    }
+}
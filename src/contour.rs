@@ -0,0 +1,475 @@
+use std::collections::{BTreeMap, HashMap};
+
+use image::RgbaImage;
+use serde_json::{json, Value};
+
+use crate::color::ColorKind;
+use crate::error::{self, ParameterError};
+use crate::geo::{GeoTransform, TileCoord};
+use crate::mosaic::stitch_georeferenced;
+use crate::tile::Tile;
+
+/// A polygon of a [`Contour`], as closed rings of `(lat, lon)` points whose first point is
+/// repeated last
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContourPolygon {
+    /// The outer boundary, counterclockwise on the map
+    pub exterior: Vec<(f64, f64)>,
+    /// The boundaries of the areas below the threshold inside the polygon, clockwise on the map
+    pub holes: Vec<Vec<(f64, f64)>>,
+}
+
+/// The areas of an image at or above a reflectivity, see [`ContourExtractor`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contour {
+    /// The weakest reflectivity in dBZ inside the polygons
+    pub threshold_dbz: f32,
+    pub polygons: Vec<ContourPolygon>,
+}
+
+impl Contour {
+    /// This contour as a GeoJSON `Feature` with a `MultiPolygon` geometry and the threshold
+    /// in a `dbz` property
+    pub fn to_geojson(&self) -> Value {
+        let point = |&(lat, lon): &(f64, f64)| json!([round(lon), round(lat)]);
+        let ring = |ring: &Vec<(f64, f64)>| ring.iter().map(point).collect::<Vec<_>>();
+        let polygons: Vec<Vec<Vec<Value>>> = self
+            .polygons
+            .iter()
+            .map(|polygon| {
+                std::iter::once(&polygon.exterior)
+                    .chain(&polygon.holes)
+                    .map(ring)
+                    .collect()
+            })
+            .collect();
+        json!({
+            "type": "Feature",
+            "properties": { "dbz": self.threshold_dbz },
+            "geometry": { "type": "MultiPolygon", "coordinates": polygons },
+        })
+    }
+}
+
+/// `contours` as a GeoJSON `FeatureCollection`, with one feature per contour as by
+/// [`Contour::to_geojson`]
+///
+/// Contours of higher thresholds lie inside those of lower ones, so vector map styles should
+/// draw the features in order, or filter them by their `dbz` property.
+pub fn contours_to_geojson(contours: &[Contour]) -> Value {
+    json!({
+        "type": "FeatureCollection",
+        "features": contours.iter().map(Contour::to_geojson).collect::<Vec<_>>(),
+    })
+}
+
+/// Traces the outlines of precipitation in decoded tiles with marching squares, so radar can
+/// be drawn by vector map styles instead of raster tiles
+///
+/// Outlines pass between pixel centers, interpolated linearly by reflectivity between
+/// neighbouring pixels with precipitation, and halfway to dry pixels. Diagonal neighbours
+/// are joined into one polygon, like cells of [`CellDetector`](crate::storm::CellDetector).
+/// Polygons follow the orientation of RFC 7946 and are cut off at the edges of the image.
+///
+/// ```no_run
+/// # async fn run(req: &rain_viewer::WeatherRequester) -> Result<(), rain_viewer::Error> {
+/// use rain_viewer::{contours_to_geojson, ColorKind, ContourExtractor};
+/// use rain_viewer::{LatLonBounds, RequestArguments, Tile, TileCoord};
+///
+/// let maps = req.available().await?;
+/// let frame = maps.latest_past().unwrap();
+/// let bounds = LatLonBounds::new(-100.0, 30.0, -90.0, 40.0)?;
+/// let mut args = RequestArguments::new_tile(TileCoord::new(0, 0, 0))?;
+/// args.set_color(ColorKind::BlackAndWhite).set_smooth(false);
+///
+/// let tiles = req.get_region(&maps, frame, bounds, 6, args).await?;
+/// let tiles = tiles
+///     .into_iter()
+///     .map(|(coord, png)| (coord, Tile::new(png, ColorKind::BlackAndWhite)))
+///     .collect();
+/// let contours = ContourExtractor::new().extract_region(&tiles)?;
+/// std::fs::write("radar.geojson", contours_to_geojson(&contours).to_string())?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Enabled with the `image` feature
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContourExtractor {
+    thresholds: Vec<f32>,
+}
+
+impl Default for ContourExtractor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContourExtractor {
+    /// An extractor of the light, moderate and heavy precipitation contours of 20, 35 and
+    /// 50 dBZ
+    pub fn new() -> Self {
+        Self {
+            thresholds: vec![20.0, 35.0, 50.0],
+        }
+    }
+
+    /// Sets the reflectivities in dBZ to trace, which are sorted from the weakest
+    pub fn with_thresholds(mut self, thresholds: impl Into<Vec<f32>>) -> Self {
+        self.thresholds = thresholds.into();
+        self.thresholds.sort_by(f32::total_cmp);
+        self
+    }
+
+    /// Traces a contour for each threshold, from the weakest, in a decoded tile or mosaic
+    /// rendered with `color`, whose pixels are placed on the map by `transform`
+    ///
+    /// Pixels are decoded with [`ColorKind::dbz_for_pixel`]. Returns Err(...) if no palette
+    /// is bundled for `color`
+    pub fn extract(
+        &self,
+        image: &RgbaImage,
+        color: ColorKind,
+        transform: &GeoTransform,
+    ) -> Result<Vec<Contour>, error::Error> {
+        if color.palette().is_none() {
+            return Err(ParameterError::InvalidColor(
+                color.into(),
+                "No palette is bundled for this color scheme".to_owned(),
+            )
+            .into());
+        }
+        let grid = Samples {
+            width: image.width(),
+            height: image.height(),
+            dbz: image
+                .pixels()
+                .map(|pixel| color.dbz_for_pixel(pixel.0))
+                .collect(),
+        };
+        Ok(self
+            .thresholds
+            .iter()
+            .map(|&threshold| Contour {
+                threshold_dbz: threshold,
+                polygons: grid.polygons(threshold, transform),
+            })
+            .collect())
+    }
+
+    /// Traces the contours of `tile`, which lies at `coord`, see [`ContourExtractor::extract`]
+    ///
+    /// Returns Err(...) if no palette is bundled for the color scheme of the tile, or it is
+    /// not a valid PNG image
+    pub fn extract_tile(
+        &self,
+        tile: &Tile,
+        coord: TileCoord,
+    ) -> Result<Vec<Contour>, error::Error> {
+        let image = tile.to_rgba8()?;
+        let transform = GeoTransform::new(coord, image.width());
+        self.extract(&image, tile.color(), &transform)
+    }
+
+    /// Traces the contours of a region of tiles of one zoom level and color scheme, such as
+    /// those returned by [`WeatherRequester::get_region`](crate::WeatherRequester::get_region)
+    ///
+    /// The tiles are assembled as by [`stitch`](crate::stitch), so polygons spanning several
+    /// tiles are traced whole. Longitudes east of the antimeridian continue past 180 for
+    /// regions crossing it. Returns Err(...) if `tiles` is empty, the tiles do not share a
+    /// zoom level, no palette is bundled for their color scheme, or a tile is not a valid PNG
+    /// image
+    pub fn extract_region(
+        &self,
+        tiles: &BTreeMap<TileCoord, Tile>,
+    ) -> Result<Vec<Contour>, error::Error> {
+        let color = tiles
+            .values()
+            .next()
+            .map_or(ColorKind::BlackAndWhite, Tile::color);
+        let (mosaic, transform) = stitch_georeferenced(tiles)?;
+        self.extract(&mosaic, color, &transform)
+    }
+}
+
+/// A crossing of a contour with the line between two neighbouring samples, as the column and
+/// row of the first sample and whether the line is vertical
+type Crossing = (u32, u32, bool);
+
+/// The reflectivity of each pixel of an image, sampled at pixel centers. Samples are indexed
+/// from a ring of dry samples around the image, so that every contour is closed
+struct Samples {
+    width: u32,
+    height: u32,
+    dbz: Vec<Option<f32>>,
+}
+
+impl Samples {
+    fn get(&self, column: u32, row: u32) -> Option<f32> {
+        if column == 0 || row == 0 || column > self.width || row > self.height {
+            return None;
+        }
+        self.dbz[((row - 1) * self.width + column - 1) as usize]
+    }
+
+    fn inside(&self, column: u32, row: u32, threshold: f32) -> bool {
+        self.get(column, row).is_some_and(|dbz| dbz >= threshold)
+    }
+
+    /// The pixel position of `crossing`
+    fn position(&self, (column, row, vertical): Crossing, threshold: f32) -> (f64, f64) {
+        let (next_column, next_row) = if vertical {
+            (column, row + 1)
+        } else {
+            (column + 1, row)
+        };
+        let t = match (self.get(column, row), self.get(next_column, next_row)) {
+            (Some(a), Some(b)) if a != b => ((threshold - a) / (b - a)).clamp(0.0, 1.0) as f64,
+            _ => 0.5,
+        };
+        let (dx, dy) = if vertical { (0.0, t) } else { (t, 0.0) };
+        // Sample 1 is the center of the first pixel
+        (column as f64 - 0.5 + dx, row as f64 - 0.5 + dy)
+    }
+
+    /// The segments of the contour of `threshold`, each leaving the area inside it on its
+    /// right in image coordinates, as a map from their start to their end, along with the
+    /// starts in scan order
+    fn segments(&self, threshold: f32) -> (Vec<Crossing>, HashMap<Crossing, Crossing>) {
+        let mut starts = Vec::new();
+        let mut segments = HashMap::new();
+        for row in 0..=self.height {
+            for column in 0..=self.width {
+                // The corners of the square clockwise from its top left, and the crossings of
+                // the edges leaving each of them
+                let corners = [
+                    self.inside(column, row, threshold),
+                    self.inside(column + 1, row, threshold),
+                    self.inside(column + 1, row + 1, threshold),
+                    self.inside(column, row + 1, threshold),
+                ];
+                let edges = [
+                    (column, row, false),
+                    (column + 1, row, true),
+                    (column, row + 1, false),
+                    (column, row, true),
+                ];
+                let crossings: Vec<(Crossing, bool)> = (0..4)
+                    .filter(|&i| corners[i] != corners[(i + 1) % 4])
+                    .map(|i| (edges[i], corners[i]))
+                    .collect();
+                // Each crossing leaving the inside is joined to the next crossing clockwise,
+                // which joins diagonal neighbours when only they are inside
+                for (i, &(start, leaving)) in crossings.iter().enumerate() {
+                    if leaving {
+                        let end = crossings[(i + 1) % crossings.len()].0;
+                        starts.push(start);
+                        segments.insert(start, end);
+                    }
+                }
+            }
+        }
+        (starts, segments)
+    }
+
+    /// The polygons of the contour of `threshold`, placed on the map by `transform`
+    fn polygons(&self, threshold: f32, transform: &GeoTransform) -> Vec<ContourPolygon> {
+        let (starts, mut segments) = self.segments(threshold);
+        let mut exteriors = Vec::new();
+        let mut holes = Vec::new();
+        for start in starts {
+            let Some(mut next) = segments.remove(&start) else {
+                continue;
+            };
+            let mut ring = vec![self.position(start, threshold)];
+            while next != start {
+                let point = self.position(next, threshold);
+                if ring.last() != Some(&point) {
+                    ring.push(point);
+                }
+                next = segments
+                    .remove(&next)
+                    .expect("every crossing starts a segment");
+            }
+            // Clockwise in image coordinates, with rows increasing downwards, is an exterior
+            match signed_area(&ring) {
+                area if area > 0.0 => exteriors.push((area, ring, Vec::new())),
+                area if area < 0.0 => holes.push(ring),
+                _ => {}
+            }
+        }
+        // Each hole belongs to the smallest exterior containing it
+        for hole in holes {
+            let owner = exteriors
+                .iter_mut()
+                .filter(|(_, exterior, _)| contains(exterior, hole[0]))
+                .min_by(|(a, _, _), (b, _, _)| a.total_cmp(b));
+            if let Some((_, _, owned)) = owner {
+                owned.push(hole);
+            }
+        }
+        // Images are drawn north up, so exteriors are clockwise on the map too, and RFC 7946
+        // wants them counterclockwise
+        let georeference = |ring: Vec<(f64, f64)>| {
+            let mut ring: Vec<_> = ring
+                .into_iter()
+                .rev()
+                .map(|(column, row)| transform.pixel_to_lat_lon(column, row))
+                .collect();
+            ring.push(ring[0]);
+            ring
+        };
+        exteriors
+            .into_iter()
+            .map(|(_, exterior, holes)| ContourPolygon {
+                exterior: georeference(exterior),
+                holes: holes.into_iter().map(georeference).collect(),
+            })
+            .collect()
+    }
+}
+
+/// Twice the area of `ring`, positive if it is clockwise as drawn in image coordinates
+fn signed_area(ring: &[(f64, f64)]) -> f64 {
+    let next = ring.iter().cycle().skip(1);
+    ring.iter()
+        .zip(next)
+        .map(|(&(x0, y0), &(x1, y1))| x0 * y1 - x1 * y0)
+        .sum()
+}
+
+/// Whether `point` is inside `ring`, by counting the edges crossed by a ray towards the right
+fn contains(ring: &[(f64, f64)], (x, y): (f64, f64)) -> bool {
+    let next = ring.iter().cycle().skip(1);
+    ring.iter()
+        .zip(next)
+        .filter(|&(&(x0, y0), &(x1, y1))| {
+            (y0 > y) != (y1 > y) && x < x0 + (y - y0) / (y1 - y0) * (x1 - x0)
+        })
+        .count()
+        % 2
+        == 1
+}
+
+/// Rounds a coordinate to 6 decimals, about 10 cm, which keeps GeoJSON compact
+fn round(degrees: f64) -> f64 {
+    (degrees * 1e6).round() / 1e6
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    /// A black and white image of `rows`, where each value is a reflectivity in dBZ with
+    /// `None` as transparent
+    fn image(rows: &[&[Option<i32>]]) -> RgbaImage {
+        RgbaImage::from_fn(rows[0].len() as u32, rows.len() as u32, |x, y| {
+            match rows[y as usize][x as usize] {
+                Some(dbz) => {
+                    let value = (dbz + 32) as u8;
+                    Rgba([value, value, value, 255])
+                }
+                None => Rgba([0; 4]),
+            }
+        })
+    }
+
+    /// Maps pixels to degrees with the top left corner of the image at the origin, one degree
+    /// per pixel near the equator
+    fn transform() -> GeoTransform {
+        let degree = 6_378_137.0f64.to_radians();
+        GeoTransform {
+            origin_x: 0.0,
+            origin_y: 0.0,
+            pixel_width: degree,
+            pixel_height: degree,
+        }
+    }
+
+    #[test]
+    fn traces_rings() {
+        let (d, r, s) = (None, Some(25), Some(55));
+        let ring = image(&[
+            &[d, d, d, d, d, d, d, d],
+            &[d, r, r, r, r, r, r, d],
+            &[d, r, d, d, d, d, r, d],
+            &[d, r, d, s, d, d, r, d],
+            &[d, r, d, r, d, d, r, d],
+            &[d, r, d, d, d, d, r, d],
+            &[d, r, r, r, r, r, r, d],
+            &[d, d, d, d, d, d, d, d],
+        ]);
+        let extractor = ContourExtractor::new().with_thresholds([50.0, 20.0]);
+        let contours = extractor
+            .extract(&ring, ColorKind::BlackAndWhite, &transform())
+            .unwrap();
+        assert_eq!(
+            contours.iter().map(|c| c.threshold_dbz).collect::<Vec<_>>(),
+            [20.0, 50.0]
+        );
+
+        // The ring of light rain has a hole, holding an island of heavy rain
+        let light = &contours[0].polygons;
+        assert_eq!(light.len(), 2);
+        assert_eq!(light[0].holes.len(), 1);
+        assert!(light[1].holes.is_empty());
+        let heavy = &contours[1].polygons;
+        assert_eq!(heavy.len(), 1);
+
+        // The island's outline runs halfway to its dry neighbours, and is interpolated towards
+        // the light rain below it
+        let island = &heavy[0].exterior;
+        assert_eq!(island.len(), 5);
+        assert_eq!(island.first(), island.last());
+        let step = (50.0 - 55.0) / (25.0 - 55.0);
+        for (column, row) in [(3.5, 3.0), (3.0, 3.5), (4.0, 3.5), (3.5, 3.5 + step)] {
+            let (lat, lon) = transform().pixel_to_lat_lon(column, row);
+            assert!(island
+                .iter()
+                .any(|point| (point.0 - lat).abs() < 1e-6 && (point.1 - lon).abs() < 1e-6));
+        }
+
+        // Exteriors are counterclockwise on the map, and holes clockwise
+        let area = |ring: &[(f64, f64)]| {
+            let ring: Vec<_> = ring.iter().map(|&(lat, lon)| (lon, lat)).collect();
+            signed_area(&ring)
+        };
+        assert!(area(&light[0].exterior) > 0.0);
+        assert!(area(&light[0].holes[0]) < 0.0);
+        assert!(area(island) > 0.0);
+
+        // Diagonal neighbours are one polygon
+        let diagonal = image(&[&[s, d], &[d, s]]);
+        let contours = ContourExtractor::new()
+            .with_thresholds([50.0])
+            .extract(&diagonal, ColorKind::BlackAndWhite, &transform())
+            .unwrap();
+        assert_eq!(contours[0].polygons.len(), 1);
+
+        assert!(extractor
+            .extract(&diagonal, ColorKind::Titan, &transform())
+            .is_err());
+    }
+
+    #[test]
+    fn writes_geojson() {
+        let image = image(&[&[Some(40)]]);
+        let contours = ContourExtractor::new()
+            .with_thresholds([20.0, 50.0])
+            .extract(&image, ColorKind::BlackAndWhite, &transform())
+            .unwrap();
+        let geojson = contours_to_geojson(&contours);
+        assert_eq!(geojson["type"], "FeatureCollection");
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[0]["properties"]["dbz"], 20.0);
+        assert_eq!(features[0]["geometry"]["type"], "MultiPolygon");
+        // The pixel's diamond, as [lon, lat] pairs
+        let ring = &features[0]["geometry"]["coordinates"][0][0];
+        assert_eq!(ring.as_array().unwrap().len(), 5);
+        assert!(ring.as_array().unwrap().contains(&json!([0.5, 0.0])));
+        assert_eq!(features[1]["geometry"]["coordinates"], json!([]));
+    }
+}
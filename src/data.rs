@@ -0,0 +1,460 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::ParameterError;
+use crate::timestamp::{self, TimeDelta, Timestamp};
+
+/// The product and time period a [`Frame`] belongs to
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FrameKind {
+    /// Radar observations from the past
+    PastRadar,
+    /// Radar forecast (nowcast) for the near future
+    NowcastRadar,
+    /// Infrared satellite imagery
+    Satellite,
+}
+
+impl FrameKind {
+    /// Returns true if frames of this kind can be requested with radar options
+    pub fn is_radar(self) -> bool {
+        matches!(self, FrameKind::PastRadar | FrameKind::NowcastRadar)
+    }
+}
+
+/// Indicates that radar or satellite data is available for the time given at path [`path`]
+///
+/// Frames are ordered by time, then path, so they can be sorted chronologically and used as map
+/// keys
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Frame {
+    /// The timestamp when this data was generated
+    pub time: Timestamp,
+
+    /// The path where this data can be accessed
+    pub path: String,
+
+    /// Which product this frame belongs to
+    pub kind: FrameKind,
+}
+
+impl PartialOrd for Frame {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Frame {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.time, &self.path, self.kind).cmp(&(other.time, &other.path, other.kind))
+    }
+}
+
+impl Frame {
+    /// Returns [`time`](Self::time) as the number of seconds since the Unix epoch
+    pub fn unix_time(&self) -> i64 {
+        timestamp::unix_seconds(self.time)
+    }
+
+    /// Returns [`time`](Self::time) as a [`time::OffsetDateTime`] in UTC, or `None` if it is
+    /// out of the range of the `time` crate
+    #[cfg(feature = "time")]
+    pub fn offset_date_time(&self) -> Option<time::OffsetDateTime> {
+        timestamp::to_offset_date_time(self.time)
+    }
+
+    /// Returns [`time`](Self::time) as a [`jiff::Timestamp`], or `None` if it is out of the
+    /// range of `jiff`
+    #[cfg(feature = "jiff")]
+    pub fn jiff_timestamp(&self) -> Option<jiff::Timestamp> {
+        timestamp::to_jiff_timestamp(self.time)
+    }
+
+    /// Returns how long ago this frame was generated relative to `now`
+    ///
+    /// Nowcast frames lie in the future, so their age is negative until `now` passes them
+    pub fn age(&self, now: Timestamp) -> TimeDelta {
+        timestamp::between(self.time, now)
+    }
+
+    /// Returns true if this frame is a forecast relative to `maps`, meaning it lies after the
+    /// latest past radar observation in `maps`
+    ///
+    /// Unlike checking [`kind`](Self::kind), this stays correct for frames kept from an older
+    /// [`AvailableData`] whose forecasts have since been observed
+    pub fn is_forecast(&self, maps: &AvailableData) -> bool {
+        maps.latest_past()
+            .is_some_and(|latest| self.time > latest.time)
+    }
+
+    /// Returns Err(...) unless this frame holds radar imagery
+    pub(crate) fn expect_radar(&self) -> Result<(), ParameterError> {
+        if self.kind.is_radar() {
+            Ok(())
+        } else {
+            Err(ParameterError::InvalidFrame(
+                self.kind,
+                "Radar tiles can only be requested for past or nowcast radar frames".to_owned(),
+            ))
+        }
+    }
+
+    /// Returns Err(...) unless this frame holds satellite imagery
+    pub(crate) fn expect_satellite(&self) -> Result<(), ParameterError> {
+        if self.kind == FrameKind::Satellite {
+            Ok(())
+        } else {
+            Err(ParameterError::InvalidFrame(
+                self.kind,
+                "Satellite tiles can only be requested for infrared satellite frames".to_owned(),
+            ))
+        }
+    }
+}
+
+/// A radar frame yielded by [`AvailableData::radar_timeline`], tagged with whether it was
+/// observed or forecast
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TimelineEntry<'a> {
+    /// A past radar frame containing observed precipitation
+    Observation(&'a Frame),
+    /// A nowcast radar frame containing forecast precipitation
+    Forecast(&'a Frame),
+}
+
+impl<'a> TimelineEntry<'a> {
+    /// Returns the frame regardless of whether it is an observation or forecast
+    pub fn frame(self) -> &'a Frame {
+        match self {
+            TimelineEntry::Observation(frame) | TimelineEntry::Forecast(frame) => frame,
+        }
+    }
+
+    /// Returns true if this frame is a nowcast forecast
+    pub fn is_forecast(self) -> bool {
+        matches!(self, TimelineEntry::Forecast(_))
+    }
+}
+
+/// Contains the kinds of imagery that are available
+#[derive(Debug, Clone)]
+pub struct AvailableData {
+    /// The version of the Rain Viewer API that produced this data
+    pub version: String,
+
+    /// When Rain Viewer generated this response. Compare against the current time to detect
+    /// stale data
+    pub generated: Timestamp,
+
+    pub(crate) host: String,
+    pub past_radar: Vec<Frame>,
+    pub nowcast_radar: Vec<Frame>,
+    pub infrared_satellite: Vec<Frame>,
+}
+
+impl AvailableData {
+    /// Returns [`generated`](Self::generated) as a [`time::OffsetDateTime`] in UTC, or `None`
+    /// if it is out of the range of the `time` crate
+    #[cfg(feature = "time")]
+    pub fn generated_offset_date_time(&self) -> Option<time::OffsetDateTime> {
+        timestamp::to_offset_date_time(self.generated)
+    }
+
+    /// Returns [`generated`](Self::generated) as a [`jiff::Timestamp`], or `None` if it is out
+    /// of the range of `jiff`
+    #[cfg(feature = "jiff")]
+    pub fn generated_jiff_timestamp(&self) -> Option<jiff::Timestamp> {
+        timestamp::to_jiff_timestamp(self.generated)
+    }
+
+    /// Returns true if this data was generated more than `threshold` ago, meaning
+    /// [`available`](crate::WeatherRequester::available) should be called again to find newer
+    /// frames
+    pub fn is_stale(&self, threshold: TimeDelta) -> bool {
+        let age = timestamp::unix_millis(timestamp::now()) - timestamp::unix_millis(self.generated);
+        age > timestamp::delta_millis(threshold)
+    }
+
+    /// Returns the most recent past radar frame, which is the latest observed precipitation
+    pub fn latest_past(&self) -> Option<&Frame> {
+        self.past_radar.iter().max_by_key(|frame| frame.time)
+    }
+
+    /// Iterates over every radar frame, past frames first followed by nowcast frames
+    pub fn all_radar(&self) -> impl Iterator<Item = &Frame> + '_ {
+        self.past_radar.iter().chain(&self.nowcast_radar)
+    }
+
+    /// Returns the radar frame closest to `time`, searching both past and nowcast frames
+    ///
+    /// When two frames are equally close the earlier one is returned
+    pub fn nearest_frame(&self, time: Timestamp) -> Option<&Frame> {
+        self.all_radar()
+            .min_by_key(|frame| (distance(frame.time, time), frame.time))
+    }
+
+    /// Returns the infrared satellite frame closest to `time`, such as the satellite imagery to
+    /// show under a radar frame
+    ///
+    /// When two frames are equally close the earlier one is returned
+    pub fn nearest_satellite_frame(&self, time: Timestamp) -> Option<&Frame> {
+        self.infrared_satellite
+            .iter()
+            .min_by_key(|frame| (distance(frame.time, time), frame.time))
+    }
+
+    /// Iterates over past and nowcast radar frames merged in chronological order, which is the
+    /// order frames are shown in a radar animation
+    pub fn radar_timeline(&self) -> impl Iterator<Item = TimelineEntry<'_>> {
+        let mut timeline: Vec<_> = self
+            .past_radar
+            .iter()
+            .map(TimelineEntry::Observation)
+            .chain(self.nowcast_radar.iter().map(TimelineEntry::Forecast))
+            .collect();
+        timeline.sort_by_key(|entry| entry.frame().time);
+        timeline.into_iter()
+    }
+
+    /// Iterates over the radar frames with a time between `start` and `end`, inclusive
+    pub fn frames_between(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> impl Iterator<Item = &Frame> + '_ {
+        self.all_radar()
+            .filter(move |frame| frame.time >= start && frame.time <= end)
+    }
+}
+
+/// Base API information returned by [`available`]
+///
+/// `radar` and `satellite` contain frame objects that can be used in conjunction with [`get_tile`]
+/// to obtain a tile of imagery.
+#[derive(Deserialize)]
+pub(crate) struct RawAvailableData {
+    /// The version of Rain Viewer
+    pub version: String,
+    /// The unix timestamp when this response was generated
+    pub generated: u64,
+
+    /// The tile host. Pass this value to [`get_tile`] so that it contacts the correct mirror
+    pub host: String,
+
+    /// What radar information is available
+    pub radar: Radar,
+
+    /// What satellite information is available
+    pub satellite: Satellite,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct Radar {
+    pub past: Vec<RawFrame>,
+    pub nowcast: Vec<RawFrame>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct Satellite {
+    pub infrared: Vec<RawFrame>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct RawFrame {
+    /// The unix timestamp when this data was generated
+    pub time: u64,
+
+    /// The path where this data can be accessed
+    pub path: String,
+}
+
+/// Converts a unix timestamp returned by the API into a UTC date time
+fn utc_timestamp(secs: u64) -> Timestamp {
+    timestamp::from_unix_seconds(secs as i64).unwrap()
+}
+
+/// The number of milliseconds between `a` and `b`, in either order
+fn distance(a: Timestamp, b: Timestamp) -> u64 {
+    timestamp::unix_millis(a).abs_diff(timestamp::unix_millis(b))
+}
+
+impl From<RawAvailableData> for AvailableData {
+    fn from(raw: RawAvailableData) -> Self {
+        Self {
+            version: raw.version,
+            generated: utc_timestamp(raw.generated),
+            host: raw.host,
+            past_radar: RawFrame::into_frames(raw.radar.past, FrameKind::PastRadar),
+            nowcast_radar: RawFrame::into_frames(raw.radar.nowcast, FrameKind::NowcastRadar),
+            infrared_satellite: RawFrame::into_frames(raw.satellite.infrared, FrameKind::Satellite),
+        }
+    }
+}
+
+impl RawFrame {
+    fn into_frames(raw: Vec<RawFrame>, kind: FrameKind) -> Vec<Frame> {
+        raw.into_iter()
+            .map(|raw| Frame {
+                time: utc_timestamp(raw.time),
+                path: raw.path,
+                kind,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl AvailableData {
+    /// Parses the sample `weather-maps.json` response used by offline tests
+    pub(crate) fn fixture() -> Self {
+        let raw: RawAvailableData =
+            serde_json::from_str(include_str!("../tests/fixtures/weather-maps.json")).unwrap();
+        raw.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> AvailableData {
+        AvailableData::fixture()
+    }
+
+    #[test]
+    fn parse_available() {
+        let maps = fixture();
+
+        assert_eq!(maps.version, "2.0");
+        assert_eq!(timestamp::unix_seconds(maps.generated), 1697000450);
+        assert_eq!(maps.past_radar[0].unix_time(), 1696998600);
+        assert_eq!(maps.host, "https://tilecache.rainviewer.com");
+        assert_eq!(maps.past_radar.len(), 4);
+        assert_eq!(maps.nowcast_radar.len(), 2);
+        assert_eq!(maps.infrared_satellite.len(), 3);
+        assert_eq!(maps.past_radar[3].path, "/v2/radar/1697000400");
+        assert_eq!(maps.nowcast_radar[0].kind, FrameKind::NowcastRadar);
+        assert_eq!(maps.infrared_satellite[0].kind, FrameKind::Satellite);
+        assert!(maps.past_radar[0].expect_radar().is_ok());
+        assert!(maps.past_radar[0].expect_satellite().is_err());
+        assert!(maps.infrared_satellite[0].expect_radar().is_err());
+    }
+
+    #[test]
+    fn frame_ordering() {
+        let maps = fixture();
+        let mut frames: Vec<_> = maps
+            .nowcast_radar
+            .iter()
+            .chain(&maps.infrared_satellite)
+            .chain(&maps.past_radar)
+            .cloned()
+            .collect();
+        frames.sort();
+        assert!(frames.windows(2).all(|w| w[0].time <= w[1].time));
+        // Radar and satellite frames at the same time are ordered by path
+        assert_eq!(frames[0].path, "/v2/radar/1696998600");
+        assert_eq!(frames[1].path, "/v2/satellite/9c1f0e2d3b4a");
+
+        let set: std::collections::HashSet<_> = frames.iter().cloned().collect();
+        assert_eq!(set.len(), frames.len());
+        assert!(set.contains(&maps.past_radar[2]));
+    }
+
+    #[test]
+    fn queries() {
+        let maps = fixture();
+        let time = utc_timestamp;
+
+        assert_eq!(maps.latest_past().unwrap().path, "/v2/radar/1697000400");
+        assert_eq!(maps.all_radar().count(), 6);
+        assert_eq!(
+            maps.nearest_frame(time(1697001200)).unwrap().path,
+            "/v2/radar/nowcast_4f2b3c1d9e0a"
+        );
+        // Ties resolve to the earlier frame
+        assert_eq!(
+            maps.nearest_frame(time(1696998900)).unwrap().path,
+            "/v2/radar/1696998600"
+        );
+        assert_eq!(
+            maps.nearest_satellite_frame(time(1697000400)).unwrap().path,
+            "/v2/satellite/2b3c4d5e6f70"
+        );
+        assert_eq!(
+            maps.nearest_satellite_frame(time(1696998900)).unwrap().path,
+            "/v2/satellite/9c1f0e2d3b4a"
+        );
+        let between: Vec<_> = maps
+            .frames_between(time(1696999200), time(1697001000))
+            .map(|frame| frame.path.as_str())
+            .collect();
+        assert_eq!(
+            between,
+            [
+                "/v2/radar/1696999200",
+                "/v2/radar/1696999800",
+                "/v2/radar/1697000400",
+                "/v2/radar/nowcast_4f2b3c1d9e0a",
+            ]
+        );
+    }
+
+    #[test]
+    fn timeline() {
+        let mut maps = fixture();
+        // The API returns frames in order, but the timeline must not rely on it
+        maps.past_radar.reverse();
+
+        let timeline: Vec<_> = maps.radar_timeline().collect();
+        assert_eq!(timeline.len(), 6);
+        assert!(timeline
+            .windows(2)
+            .all(|w| w[0].frame().time <= w[1].frame().time));
+        assert!(!timeline[3].is_forecast());
+        assert_eq!(timeline[4], TimelineEntry::Forecast(&maps.nowcast_radar[0]));
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn time_backend() {
+        let maps = fixture();
+        assert_eq!(
+            maps.past_radar[0]
+                .offset_date_time()
+                .unwrap()
+                .unix_timestamp(),
+            1696998600
+        );
+        assert_eq!(
+            maps.generated_offset_date_time().unwrap().unix_timestamp(),
+            1697000450
+        );
+    }
+
+    #[cfg(feature = "jiff")]
+    #[test]
+    fn jiff_backend() {
+        let maps = fixture();
+        let frame = maps.past_radar[0].jiff_timestamp().unwrap();
+        assert_eq!(frame.as_second(), 1696998600);
+        let generated = maps.generated_jiff_timestamp().unwrap();
+        assert_eq!(generated.as_second(), 1697000450);
+    }
+
+    #[test]
+    fn staleness() {
+        let maps = fixture();
+        let latest = maps.latest_past().unwrap();
+        let now = utc_timestamp(1697000700);
+
+        let minutes = |minutes: i64| timestamp::delta_from_millis(minutes * 60_000);
+        assert_eq!(latest.age(now), minutes(5));
+        assert!(maps.nowcast_radar[0].age(now) < minutes(0));
+        assert!(!latest.is_forecast(&maps));
+        assert!(maps.nowcast_radar[1].is_forecast(&maps));
+
+        assert!(maps.is_stale(minutes(10)));
+        assert!(!maps.is_stale(timestamp::delta_from_millis(i64::MAX)));
+    }
+}
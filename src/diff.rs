@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+
+use crate::data::{AvailableData, Frame};
+
+/// The changes between two responses from [`available`](crate::WeatherRequester::available),
+/// as returned by [`AvailableData::diff`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AvailableDiff {
+    /// Frames in the newer data that were not in the older data, in chronological order.
+    /// These are the frames worth prefetching
+    pub added: Vec<Frame>,
+
+    /// Frames in the older data that are no longer available, in chronological order.
+    /// Tiles downloaded for these frames can be invalidated
+    pub expired: Vec<Frame>,
+
+    /// True if the tile host changed, which invalidates every tile url built from the older data
+    pub host_changed: bool,
+}
+
+impl AvailableDiff {
+    /// Returns true if nothing changed between the two responses
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.expired.is_empty() && !self.host_changed
+    }
+}
+
+impl AvailableData {
+    /// Compares this data against an `older` response, listing which frames were added, which
+    /// expired, and whether the tile host changed
+    ///
+    /// Frames of every kind are compared, including satellite frames
+    pub fn diff(&self, older: &AvailableData) -> AvailableDiff {
+        let new: HashSet<&Frame> = self.all_frames().collect();
+        let old: HashSet<&Frame> = older.all_frames().collect();
+
+        let mut added: Vec<Frame> = new.difference(&old).map(|f| (*f).clone()).collect();
+        let mut expired: Vec<Frame> = old.difference(&new).map(|f| (*f).clone()).collect();
+        added.sort();
+        expired.sort();
+
+        AvailableDiff {
+            added,
+            expired,
+            host_changed: self.host != older.host,
+        }
+    }
+
+    fn all_frames(&self) -> impl Iterator<Item = &Frame> + '_ {
+        self.all_radar().chain(&self.infrared_satellite)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff() {
+        let older = AvailableData::fixture();
+        assert!(older.diff(&older).is_empty());
+
+        let mut newer = AvailableData::fixture();
+        let expired = newer.past_radar.remove(0);
+        let mut added = newer.nowcast_radar[1].clone();
+        added.time = crate::timestamp::from_unix_seconds(added.unix_time() + 600).unwrap();
+        added.path = "/v2/radar/nowcast_0123456789ab".to_owned();
+        newer.nowcast_radar.push(added.clone());
+        newer.host = "https://mirror.rainviewer.com".to_owned();
+
+        let diff = newer.diff(&older);
+        assert_eq!(diff.added, [added]);
+        assert_eq!(diff.expired, [expired]);
+        assert!(diff.host_changed);
+    }
+}
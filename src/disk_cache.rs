@@ -0,0 +1,428 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::{TileCache, TileKey};
+use crate::data::FrameKind;
+use crate::transport::BoxFuture;
+
+/// The name of the index file inside the cache directory
+const INDEX_FILE: &str = "index.jsonl";
+
+/// How often expired tiles are purged while the cache is in use
+const PURGE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Numbers the temporary files of this process, so concurrent writes never share one
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A path next to `path` to write to before renaming it over `path`, unique to this write
+fn tmp_path(path: &Path) -> PathBuf {
+    let n = TMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(format!(".{}-{n}.tmp", std::process::id()));
+    path.with_file_name(name)
+}
+
+/// How long [`DiskCache`] keeps tiles of each kind of frame
+///
+/// Past frames never change but roll off the API after a couple of hours, while nowcast frames
+/// are replaced with a fresh forecast every few minutes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CacheTtl {
+    /// Defaults to 3 hours
+    pub past_radar: Duration,
+    /// Defaults to 10 minutes
+    pub nowcast_radar: Duration,
+    /// Defaults to 3 hours
+    pub satellite: Duration,
+    /// The time to live of radar coverage tiles, which are not tied to a frame. Defaults to 7 days
+    pub coverage: Duration,
+}
+
+impl Default for CacheTtl {
+    fn default() -> Self {
+        Self {
+            past_radar: Duration::from_secs(3 * 60 * 60),
+            nowcast_radar: Duration::from_secs(10 * 60),
+            satellite: Duration::from_secs(3 * 60 * 60),
+            coverage: Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+}
+
+impl CacheTtl {
+    fn get(&self, kind: Option<FrameKind>) -> Duration {
+        match kind {
+            Some(FrameKind::PastRadar) => self.past_radar,
+            Some(FrameKind::NowcastRadar) => self.nowcast_radar,
+            Some(FrameKind::Satellite) => self.satellite,
+            None => self.coverage,
+        }
+    }
+}
+
+/// A filesystem tile cache, so desktop apps do not download the visible region again after a
+/// restart
+///
+/// Tiles are stored under the cache directory following their url, such as
+/// `tilecache.rainviewer.com/v2/radar/1697000400/256/6/4/7/2/1_1.png`, and recorded in an
+/// `index.jsonl` file along with when they were stored. Tiles older than the [`CacheTtl`] of
+/// their frame kind are purged when the cache is opened, when they are requested and
+/// periodically while tiles are stored
+///
+/// Clones share the same cache. Not available on wasm32
+#[derive(Clone, Debug)]
+pub struct DiskCache {
+    inner: Arc<DiskInner>,
+}
+
+#[derive(Debug)]
+struct DiskInner {
+    dir: PathBuf,
+    ttl: CacheTtl,
+    state: Mutex<DiskState>,
+}
+
+#[derive(Debug)]
+struct DiskState {
+    entries: HashMap<String, IndexEntry>,
+    /// The index, opened for appending
+    index: File,
+    last_purge: SystemTime,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct IndexEntry {
+    url: String,
+    path: String,
+    kind: Option<FrameKind>,
+    /// Milliseconds since the unix epoch
+    stored: u64,
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+impl DiskCache {
+    /// Opens or creates a cache in `dir` with the default [`CacheTtl`]
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        Self::open_with_ttl(dir, CacheTtl::default())
+    }
+
+    /// Opens or creates a cache in `dir`, keeping tiles for the given times to live
+    ///
+    /// Expired tiles and index records of deleted files are removed, and the index is compacted
+    pub fn open_with_ttl(dir: impl Into<PathBuf>, ttl: CacheTtl) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let index_path = dir.join(INDEX_FILE);
+
+        let mut entries = HashMap::new();
+        if index_path.exists() {
+            for line in BufReader::new(File::open(&index_path)?).lines() {
+                // Skip records torn by a crash rather than refusing to open
+                if let Ok(entry) = serde_json::from_str::<IndexEntry>(&line?) {
+                    entries.insert(entry.url.clone(), entry);
+                }
+            }
+        }
+
+        let cache = Self {
+            inner: Arc::new(DiskInner {
+                state: Mutex::new(DiskState {
+                    entries,
+                    index: OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(&index_path)?,
+                    last_purge: SystemTime::now(),
+                }),
+                dir,
+                ttl,
+            }),
+        };
+        cache.purge()?;
+        Ok(cache)
+    }
+
+    /// The directory tiles are stored in
+    pub fn dir(&self) -> &Path {
+        &self.inner.dir
+    }
+
+    /// Returns the cached tile for `key`, unless it has expired
+    pub fn get(&self, key: &TileKey) -> Option<Bytes> {
+        let expired = {
+            let mut state = self.inner.state.lock().unwrap();
+            let entry = state.entries.get(&key.url)?;
+            let expired = self.is_expired(entry, SystemTime::now());
+            if expired {
+                state.entries.remove(&key.url);
+            }
+            expired
+        };
+        let path = self.tile_path(&key.url);
+        if expired {
+            let _ = fs::remove_file(path);
+            return None;
+        }
+        fs::read(path).ok().map(Bytes::from)
+    }
+
+    /// Stores `tile` under `key`
+    pub fn put(&self, key: &TileKey, tile: &[u8]) -> io::Result<()> {
+        let now = SystemTime::now();
+        let file = self.tile_path(&key.url);
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write to a temporary file first, so readers never see a partially written tile
+        let tmp = tmp_path(&file);
+        fs::write(&tmp, tile)?;
+        fs::rename(&tmp, &file)?;
+
+        let entry = IndexEntry {
+            url: key.url.clone(),
+            path: key.path.clone(),
+            kind: key.kind,
+            stored: unix_millis(now),
+        };
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+
+        let purge = {
+            let mut state = self.inner.state.lock().unwrap();
+            state.index.write_all(line.as_bytes())?;
+            state.entries.insert(entry.url.clone(), entry);
+            now.duration_since(state.last_purge).unwrap_or_default() > PURGE_INTERVAL
+        };
+        if purge {
+            self.purge()?;
+        }
+        Ok(())
+    }
+
+    /// Removes the tile cached under `key`
+    pub fn invalidate(&self, key: &TileKey) -> io::Result<()> {
+        self.inner.state.lock().unwrap().entries.remove(&key.url);
+        match fs::remove_file(self.tile_path(&key.url)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// The number of cached tiles, including expired tiles that have not been purged yet
+    pub fn len(&self) -> usize {
+        self.inner.state.lock().unwrap().entries.len()
+    }
+
+    /// Returns true if no tiles are cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Deletes expired tiles and rewrites the index to contain only the remaining tiles
+    ///
+    /// Tiles are checked and deleted without holding the lock on the index, so lookups are only
+    /// blocked while the index is rewritten
+    pub fn purge(&self) -> io::Result<()> {
+        let now = SystemTime::now();
+        let entries: Vec<IndexEntry> = {
+            let state = self.inner.state.lock().unwrap();
+            state.entries.values().cloned().collect()
+        };
+        let expired: Vec<IndexEntry> = entries
+            .into_iter()
+            .filter(|entry| self.is_expired(entry, now) || !self.tile_path(&entry.url).exists())
+            .collect();
+
+        {
+            let mut state = self.inner.state.lock().unwrap();
+            for entry in &expired {
+                // Keep tiles stored again since they were checked
+                if state
+                    .entries
+                    .get(&entry.url)
+                    .is_some_and(|current| current.stored == entry.stored)
+                {
+                    state.entries.remove(&entry.url);
+                }
+            }
+
+            let mut compacted = String::new();
+            for entry in state.entries.values() {
+                compacted.push_str(&serde_json::to_string(entry)?);
+                compacted.push('\n');
+            }
+            // Appends to the index must wait for the rewrite, or they would be lost
+            let index_path = self.inner.dir.join(INDEX_FILE);
+            let tmp = tmp_path(&index_path);
+            fs::write(&tmp, compacted)?;
+            fs::rename(&tmp, &index_path)?;
+            state.index = OpenOptions::new().append(true).open(&index_path)?;
+            state.last_purge = now;
+        }
+
+        for entry in expired {
+            let _ = fs::remove_file(self.tile_path(&entry.url));
+        }
+        Ok(())
+    }
+
+    /// Forgets tiles of frames missing from `frames` and deletes their files
+    fn retain_frames(&self, frames: &HashSet<String>) {
+        let mut removed = Vec::new();
+        self.inner
+            .state
+            .lock()
+            .unwrap()
+            .entries
+            .retain(|url, entry| {
+                let expired = entry.kind.is_some() && !frames.contains(&entry.path);
+                if expired {
+                    removed.push(url.clone());
+                }
+                !expired
+            });
+        for url in removed {
+            let _ = fs::remove_file(self.tile_path(&url));
+        }
+    }
+
+    fn is_expired(&self, entry: &IndexEntry, now: SystemTime) -> bool {
+        let age = unix_millis(now).saturating_sub(entry.stored);
+        Duration::from_millis(age) >= self.inner.ttl.get(entry.kind)
+    }
+
+    /// The file a tile is stored in, following its url without the scheme
+    fn tile_path(&self, url: &str) -> PathBuf {
+        let relative = url.split_once("://").map_or(url, |(_, rest)| rest);
+        let mut path = self.inner.dir.clone();
+        // Only keep plain path segments, so a url cannot escape the cache directory
+        path.extend(
+            relative
+                .split('/')
+                .filter(|segment| !segment.is_empty() && *segment != "." && *segment != ".."),
+        );
+        path
+    }
+}
+
+/// Reads and writes tiles on a thread pool for blocking IO, so that disk access, and purges
+/// which scale with the size of the cache, do not stall the async runtime. Errors are ignored,
+/// so a full disk does not fail requests
+impl TileCache for DiskCache {
+    fn get<'a>(&'a self, key: &'a TileKey) -> BoxFuture<'a, Option<Bytes>> {
+        let (cache, key) = (self.clone(), key.clone());
+        Box::pin(blocking::unblock(move || DiskCache::get(&cache, &key)))
+    }
+
+    fn put<'a>(&'a self, key: &'a TileKey, tile: Bytes) -> BoxFuture<'a, ()> {
+        let (cache, key) = (self.clone(), key.clone());
+        Box::pin(blocking::unblock(move || {
+            let _ = DiskCache::put(&cache, &key, &tile);
+        }))
+    }
+
+    fn invalidate<'a>(&'a self, key: &'a TileKey) -> BoxFuture<'a, ()> {
+        let (cache, key) = (self.clone(), key.clone());
+        Box::pin(blocking::unblock(move || {
+            let _ = DiskCache::invalidate(&cache, &key);
+        }))
+    }
+
+    fn retain_frames<'a>(&'a self, frames: &'a HashSet<String>) -> BoxFuture<'a, ()> {
+        let (cache, frames) = (self.clone(), frames.clone());
+        Box::pin(blocking::unblock(move || {
+            DiskCache::retain_frames(&cache, &frames)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(kind: FrameKind) -> TileKey {
+        TileKey::new(
+            "/v2/radar/1697000400",
+            Some(kind),
+            "https://tilecache.rainviewer.com/v2/radar/1697000400/256/6/4/7/2/1_1.png".to_owned(),
+        )
+    }
+
+    #[test]
+    fn persists_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskCache::open(dir.path()).unwrap();
+        cache.put(&key(FrameKind::PastRadar), b"tile").unwrap();
+        assert!(dir
+            .path()
+            .join("tilecache.rainviewer.com/v2/radar/1697000400/256/6/4/7/2/1_1.png")
+            .exists());
+        drop(cache);
+
+        let cache = DiskCache::open(dir.path()).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&key(FrameKind::PastRadar)).unwrap(), "tile");
+
+        cache.invalidate(&key(FrameKind::PastRadar)).unwrap();
+        assert!(cache.get(&key(FrameKind::PastRadar)).is_none());
+        drop(cache);
+        assert!(DiskCache::open(dir.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn expires_by_frame_kind() {
+        let dir = tempfile::tempdir().unwrap();
+        let ttl = CacheTtl {
+            nowcast_radar: Duration::ZERO,
+            ..CacheTtl::default()
+        };
+        let cache = DiskCache::open_with_ttl(dir.path(), ttl).unwrap();
+        cache.put(&key(FrameKind::NowcastRadar), b"tile").unwrap();
+        assert!(cache.get(&key(FrameKind::NowcastRadar)).is_none());
+
+        cache.put(&key(FrameKind::PastRadar), b"tile").unwrap();
+        drop(cache);
+        let cache = DiskCache::open_with_ttl(
+            dir.path(),
+            CacheTtl {
+                past_radar: Duration::ZERO,
+                ..ttl
+            },
+        )
+        .unwrap();
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn tile_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskCache::open(dir.path()).unwrap();
+        let key = key(FrameKind::PastRadar);
+        TileCache::put(&cache, &key, Bytes::from_static(b"tile")).await;
+        assert_eq!(TileCache::get(&cache, &key).await.unwrap(), "tile");
+
+        // Tiles of frames that rolled off the API are deleted
+        let file = cache.tile_path(&key.url);
+        TileCache::retain_frames(&cache, &HashSet::from(["/v2/radar/1697000400".to_owned()])).await;
+        assert!(file.exists());
+        TileCache::retain_frames(&cache, &HashSet::new()).await;
+        assert!(cache.is_empty() && !file.exists());
+
+        // Concurrent writes of one tile never share a temporary file
+        assert_ne!(tmp_path(&file), tmp_path(&file));
+        assert_eq!(tmp_path(&file).parent(), file.parent());
+    }
+}
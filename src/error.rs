@@ -1,17 +1,151 @@
+use std::time::Duration;
+
+use crate::data::FrameKind;
+
 /// The error type for this library. Wraps api errors and error types from downstream crates
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Request failed: {0}")]
     Reqwest(#[from] reqwest::Error),
 
+    #[error("Invalid request: {0}")]
+    Request(#[from] http::Error),
+
+    /// An error raised by a custom [`HttpTransport`](crate::HttpTransport)
+    #[error("Transport failed: {0}")]
+    Transport(Box<dyn std::error::Error + Send + Sync>),
+
     #[error("Json deserialization failed: {0}")]
     Json(#[from] serde_json::Error),
 
-    #[error("Server returned unexpected code: {}", 0)]
-    Http(reqwest::StatusCode),
+    /// The server answered with an unexpected status code
+    #[error(transparent)]
+    Http(Box<HttpError>),
+
+    /// Rain Viewer rejected the request because too many requests were made.
+    /// `retry_after` is the delay requested by the server's `Retry-After` header, if any
+    #[error("Rate limited by the server, retry after {retry_after:?}")]
+    RateLimited { retry_after: Option<Duration> },
+
+    /// The response body was larger than the limit set by
+    /// [`WeatherRequester::with_max_response_size`](crate::WeatherRequester::with_max_response_size)
+    #[error("Response body exceeded the limit of {0} bytes")]
+    ResponseTooLarge(usize),
+
+    /// A tile request returned something other than a PNG image, such as the HTML page of a
+    /// captive portal
+    #[error("Expected a PNG image from {url}: {reason}")]
+    InvalidImage { url: String, reason: String },
+
+    /// The call did not complete within the deadline set by
+    /// [`WeatherRequester::with_request_timeout`](crate::WeatherRequester::with_request_timeout)
+    #[error("Request timed out after {0:?}")]
+    Timeout(Duration),
+
+    /// The call was abandoned because the cancellation token set by
+    /// `WeatherRequester::with_cancellation` was cancelled
+    #[error("Request was cancelled")]
+    Cancelled,
+
+    /// The frame list returned by Rain Viewer has no frames of this kind, so no frame could be
+    /// picked for the call
+    #[error("No {0:?} frames are available")]
+    NoFrames(FrameKind),
+
+    /// A trend was asked for frames spanning fewer than two distinct times, given as the
+    /// number of distinct times
+    #[error("A trend needs frames at two or more distinct times, got {0}")]
+    TooFewFrames(usize),
 
     #[error("Request failed: {0}")]
     Parameter(#[from] ParameterError),
+
+    /// Writing an export or a tile failed
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// A tile could not be decoded or an image could not be encoded
+    #[cfg(feature = "image")]
+    #[error("Image processing failed: {0}")]
+    Image(#[from] image::ImageError),
+
+    /// Writing an MBTiles export failed
+    #[cfg(feature = "mbtiles")]
+    #[error("MBTiles export failed: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+/// How much of an error response's body is kept in [`HttpError::body`]
+const MAX_ERROR_BODY: usize = 1024;
+
+/// Details of an HTTP response with an unexpected status code
+#[derive(thiserror::Error, Debug)]
+#[error("Server returned unexpected code {status} for {url}: {body:?}")]
+pub struct HttpError {
+    /// The status code of the response
+    pub status: http::StatusCode,
+    /// The url that was requested
+    pub url: String,
+    /// The response headers
+    pub headers: http::HeaderMap,
+    /// The start of the response body, decoded lossily as UTF-8. Bodies longer than 1024 bytes
+    /// are truncated
+    pub body: String,
+}
+
+impl Error {
+    /// The status code of the response, if this error was caused by an unsuccessful response
+    pub fn status(&self) -> Option<http::StatusCode> {
+        match self {
+            Error::Http(err) => Some(err.status),
+            Error::RateLimited { .. } => Some(http::StatusCode::TOO_MANY_REQUESTS),
+            Error::Reqwest(err) => err.status(),
+            _ => None,
+        }
+    }
+
+    /// Builds the error for a response to `url` with the unsuccessful `status`
+    pub(crate) fn from_response(
+        url: &str,
+        status: http::StatusCode,
+        headers: &http::HeaderMap,
+        body: &[u8],
+    ) -> Self {
+        match status {
+            http::StatusCode::TOO_MANY_REQUESTS => Error::RateLimited {
+                retry_after: headers
+                    .get(http::header::RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| parse_retry_after(value, crate::timestamp::now())),
+            },
+            status => {
+                let mut text =
+                    String::from_utf8_lossy(&body[..body.len().min(MAX_ERROR_BODY)]).into_owned();
+                if body.len() > MAX_ERROR_BODY {
+                    text.push_str("...");
+                }
+                Error::Http(Box::new(HttpError {
+                    status,
+                    url: url.to_owned(),
+                    headers: headers.clone(),
+                    body: text,
+                }))
+            }
+        }
+    }
+}
+
+/// Parses a `Retry-After` header, which is either a number of seconds or an HTTP date
+fn parse_retry_after(value: &str, now: crate::Timestamp) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    let date = date.duration_since(std::time::UNIX_EPOCH).ok()?.as_millis() as i64;
+    // Dates in the past mean the request may be retried immediately
+    let millis = date.saturating_sub(crate::timestamp::unix_millis(now));
+    Some(Duration::from_millis(millis.max(0) as u64))
 }
 
 /// Indicates that an invalid parameter was passed to a library function
@@ -21,6 +155,12 @@ pub enum ParameterError {
     #[error("Invalid image size: {0} - {1}")]
     InvalidSize(u32, String),
 
+    #[error("Invalid color scheme: {0} - {1}")]
+    InvalidColor(u32, String),
+
+    #[error("Invalid color scheme name: {0:?} - {1}")]
+    InvalidColorName(String, String),
+
     #[error("Invalid Zoom: {0} - {1}")]
     InvalidZoom(u32, String),
 
@@ -29,4 +169,72 @@ pub enum ParameterError {
 
     #[error("Y out of range: {0} - {1}")]
     YOutOfRange(u32, String),
+
+    #[error("Latitude out of range: {0} - {1}")]
+    LatitudeOutOfRange(f64, String),
+
+    #[error("Longitude out of range: {0} - {1}")]
+    LongitudeOutOfRange(f64, String),
+
+    #[error("Invalid frame kind: {0:?} - {1}")]
+    InvalidFrame(FrameKind, String),
+
+    #[error("Invalid palette stop: {0} dBZ - {1}")]
+    InvalidPalette(f32, String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_after() {
+        let now = crate::timestamp::from_unix_seconds(784111777).unwrap();
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:50:07 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:49:07 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+
+        let mut headers = http::HeaderMap::new();
+        headers.insert(http::header::RETRY_AFTER, "5".parse().unwrap());
+        assert!(matches!(
+            Error::from_response("u", http::StatusCode::TOO_MANY_REQUESTS, &headers, b""),
+            Error::RateLimited {
+                retry_after: Some(d)
+            } if d == Duration::from_secs(5)
+        ));
+    }
+
+    #[test]
+    fn http_error_context() {
+        let mut headers = http::HeaderMap::new();
+        headers.insert(http::header::SERVER, "nginx".parse().unwrap());
+        let body = "x".repeat(MAX_ERROR_BODY + 1);
+
+        let err = Error::from_response(
+            "https://example.com/tile.png",
+            http::StatusCode::NOT_FOUND,
+            &headers,
+            body.as_bytes(),
+        );
+        assert_eq!(err.status(), Some(http::StatusCode::NOT_FOUND));
+        let Error::Http(err) = err else {
+            panic!("expected an http error");
+        };
+        assert_eq!(err.url, "https://example.com/tile.png");
+        assert_eq!(err.headers[http::header::SERVER], "nginx");
+        assert_eq!(err.body.len(), MAX_ERROR_BODY + 3);
+        assert!(err
+            .to_string()
+            .starts_with("Server returned unexpected code 404 Not Found for https://example.com"));
+    }
 }
@@ -0,0 +1,126 @@
+//! Bulk export of a frame's tiles into offline formats
+//!
+//! Exporters download the radar tiles of one frame covering a [`TileRange`] and write them in a
+//! format that map viewers can load without Rain Viewer:
+//!
+//! - [`geotiff`]: a single GeoTIFF of a stitched region for GIS tools, enabled with the `image`
+//!   feature
+//! - [`kmz`]: a KMZ of ground overlays for Google Earth, optionally animated, enabled with the
+//!   `image` feature
+//! - [`mbtiles`]: an MBTiles SQLite database, enabled with the `mbtiles` feature
+//! - [`pmtiles`]: a PMTiles archive, which can be served from static storage
+//! - [`tree`]: a `{z}/{x}/{y}.png` directory tree for static tile layers
+
+use std::ops::RangeInclusive;
+
+use bytes::Bytes;
+
+use crate::args::{RequestArguments, MAX_RADAR_ZOOM};
+use crate::data::{AvailableData, Frame};
+use crate::error::{self, ParameterError};
+use crate::geo::{tiles_in_bbox, LatLonBounds, TileCoord};
+use crate::requester::WeatherRequester;
+
+#[cfg(feature = "image")]
+pub mod geotiff;
+#[cfg(feature = "image")]
+pub mod kmz;
+#[cfg(feature = "mbtiles")]
+pub mod mbtiles;
+pub mod pmtiles;
+pub mod tree;
+
+/// The tiles covering a bounding box over a range of zoom levels
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileRange {
+    bounds: LatLonBounds,
+    zooms: RangeInclusive<u32>,
+}
+
+impl TileRange {
+    /// The tiles intersecting `bounds` at every zoom level in `zooms`
+    ///
+    /// `zooms` must not be empty and must not exceed [`MAX_RADAR_ZOOM`], or Err(...) is returned
+    pub fn new(bounds: LatLonBounds, zooms: RangeInclusive<u32>) -> Result<Self, ParameterError> {
+        if zooms.is_empty() {
+            return Err(ParameterError::InvalidZoom(
+                *zooms.start(),
+                format!("The zoom range ends at {}", zooms.end()),
+            ));
+        }
+        if *zooms.end() > MAX_RADAR_ZOOM {
+            return Err(ParameterError::InvalidZoom(
+                *zooms.end(),
+                format!("The max zoom for this product is {}", MAX_RADAR_ZOOM),
+            ));
+        }
+        Ok(Self { bounds, zooms })
+    }
+
+    /// The bounding box covered
+    pub fn bounds(&self) -> LatLonBounds {
+        self.bounds
+    }
+
+    /// The zoom levels covered
+    pub fn zooms(&self) -> RangeInclusive<u32> {
+        self.zooms.clone()
+    }
+
+    /// Every tile in the range, from the lowest zoom level to the highest
+    pub fn tiles(&self) -> Vec<TileCoord> {
+        self.zooms
+            .clone()
+            .flat_map(|zoom| tiles_in_bbox(&self.bounds, zoom))
+            .collect()
+    }
+}
+
+/// Downloads the radar tiles of `frame` in `range`, one zoom level at a time
+///
+/// The size, color and options of `args` apply to every tile, while its location is ignored
+pub(crate) async fn download(
+    requester: &WeatherRequester,
+    maps: &AvailableData,
+    frame: &Frame,
+    args: RequestArguments,
+    range: &TileRange,
+) -> Result<Vec<(TileCoord, Bytes)>, error::Error> {
+    let mut tiles = Vec::new();
+    for zoom in range.zooms() {
+        let region = requester
+            .get_region(maps, frame, range.bounds, zoom, args)
+            .await?;
+        tiles.extend(region);
+    }
+    Ok(tiles)
+}
+
+/// Downloads the radar tiles of `frame` at `zoom` intersecting `bounds` and stitches them into
+/// a single image cropped to `bounds`, along with the transform placing its pixels on the map
+/// and the number of tiles stitched
+///
+/// The size, color and options of `args` apply to every tile, while its location is ignored
+#[cfg(feature = "image")]
+pub(crate) async fn stitch_region(
+    requester: &WeatherRequester,
+    maps: &AvailableData,
+    frame: &Frame,
+    args: RequestArguments,
+    bounds: LatLonBounds,
+    zoom: u32,
+) -> Result<(image::RgbaImage, crate::geo::GeoTransform, usize), error::Error> {
+    let tiles = requester
+        .get_region(maps, frame, bounds, zoom, args)
+        .await?;
+    let (mosaic, transform) = crate::mosaic::stitch_georeferenced(&tiles)?;
+    let grid = crate::mosaic::Grid::new(tiles.keys())?;
+    let (left, top, width, height) = grid.pixel_window(&bounds, mosaic.width(), mosaic.height());
+    let cropped = image::imageops::crop_imm(&mosaic, left, top, width, height).to_image();
+    let transform = crate::geo::GeoTransform {
+        origin_x: transform.origin_x + left as f64 * transform.pixel_width,
+        origin_y: transform.origin_y - top as f64 * transform.pixel_height,
+        ..transform
+    };
+    Ok((cropped, transform, tiles.len()))
+}
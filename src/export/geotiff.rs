@@ -0,0 +1,472 @@
+//! [GeoTIFF](https://www.ogc.org/standard/geotiff/) export
+//!
+//! A GeoTIFF is a TIFF image carrying the position of its pixels on the map, which GIS tools
+//! such as QGIS and GDAL load in place without a tile server. Files are written uncompressed,
+//! as a single strip, in web mercator or reprojected to latitude and longitude.
+
+use std::path::Path;
+
+use image::RgbaImage;
+
+use crate::args::{RequestArguments, RequestArgumentsInner};
+use crate::color::ColorKind;
+use crate::data::{AvailableData, Frame};
+use crate::error::{self, ParameterError};
+use crate::export::stitch_region;
+use crate::geo::{GeoTransform, LatLonBounds};
+use crate::mosaic::to_wgs84;
+use crate::requester::WeatherRequester;
+
+/// Field types of TIFF tags
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const ASCII: u16 = 2;
+const DOUBLE: u16 = 12;
+
+/// The coordinate reference system a GeoTIFF is written in
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Projection {
+    /// Web mercator, EPSG:3857, the projection tiles are drawn in. Pixels are written as
+    /// they are
+    #[default]
+    WebMercator,
+    /// Latitude and longitude, EPSG:4326. Rows are resampled to the nearest row of the
+    /// mosaic, so that every pixel spans the same number of degrees
+    Wgs84,
+}
+
+/// What the bands of a GeoTIFF hold
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Bands {
+    /// The colors of the tiles, as red, green, blue and alpha bands of 8 bits, for display
+    #[default]
+    Rgba,
+    /// The reflectivity in dBZ, as a single band of 32 bit floats which are NaN where nothing
+    /// falls, for analysis. Pixels are decoded with [`ColorKind::dbz_for_pixel`]
+    Dbz,
+}
+
+/// How a GeoTIFF is written, see [`export`] and [`encode`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct GeoTiffOptions {
+    projection: Projection,
+    bands: Bands,
+}
+
+impl GeoTiffOptions {
+    /// Colors in web mercator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the coordinate reference system
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    /// Sets what the bands hold
+    pub fn with_bands(mut self, bands: Bands) -> Self {
+        self.bands = bands;
+        self
+    }
+}
+
+/// Downloads the radar tiles of `frame` at `zoom` intersecting `bounds`, and writes them as
+/// a single GeoTIFF at `path`, cropped to `bounds`
+///
+/// The size, color and options of `args` apply to every tile, while its location and zoom are
+/// ignored. Request [`ColorKind::BlackAndWhite`] tiles without smoothing for an exact
+/// [`Bands::Dbz`] band. An existing file at `path` is replaced. Returns the number of tiles
+/// stitched
+///
+/// ```no_run
+/// use rain_viewer::export::geotiff::{self, Bands, GeoTiffOptions};
+/// use rain_viewer::{ColorKind, LatLonBounds, RequestArguments, TileCoord, WeatherRequester};
+///
+/// # async fn run() -> Result<(), rain_viewer::Error> {
+/// let req = WeatherRequester::new();
+/// let maps = req.available().await?;
+/// let frame = maps.latest_past().unwrap();
+/// let bounds = LatLonBounds::new(-10.0, 49.0, 2.0, 59.0)?;
+/// let mut args = RequestArguments::new_tile(TileCoord::new(0, 0, 0))?;
+/// args.set_color(ColorKind::BlackAndWhite).set_smooth(false);
+///
+/// let options = GeoTiffOptions::new().with_bands(Bands::Dbz);
+/// geotiff::export(&req, &maps, frame, args, bounds, 6, options, "radar.tif").await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Enabled with the `image` feature. Returns Err(...) if `zoom` is beyond
+/// [`MAX_RADAR_ZOOM`](crate::MAX_RADAR_ZOOM), `frame` is not a radar frame, a download fails,
+/// or [`Bands::Dbz`] is asked for a color scheme without a bundled palette
+#[allow(clippy::too_many_arguments)]
+pub async fn export(
+    requester: &WeatherRequester,
+    maps: &AvailableData,
+    frame: &Frame,
+    args: RequestArguments,
+    bounds: LatLonBounds,
+    zoom: u32,
+    options: GeoTiffOptions,
+    path: impl AsRef<Path>,
+) -> Result<usize, error::Error> {
+    let RequestArgumentsInner::Tile(tile) = &args.inner;
+    let color = tile.color;
+    let (image, transform, count) =
+        stitch_region(requester, maps, frame, args, bounds, zoom).await?;
+    std::fs::write(path, encode(&image, color, &transform, options)?)?;
+    Ok(count)
+}
+
+/// Encodes a decoded tile or mosaic rendered with `color`, whose pixels are placed on the map
+/// by `transform`, as a GeoTIFF
+///
+/// Returns Err(...) if the image is empty or larger than a TIFF can address, or
+/// [`Bands::Dbz`] is asked for a color scheme without a bundled palette
+pub fn encode(
+    image: &RgbaImage,
+    color: ColorKind,
+    transform: &GeoTransform,
+    options: GeoTiffOptions,
+) -> Result<Vec<u8>, error::Error> {
+    if image.width() == 0 || image.height() == 0 {
+        return Err(ParameterError::InvalidSize(0, "The image is empty".to_owned()).into());
+    }
+    if options.bands == Bands::Dbz && color.palette().is_none() {
+        return Err(ParameterError::InvalidColor(
+            color.into(),
+            "No palette is bundled for this color scheme".to_owned(),
+        )
+        .into());
+    }
+    let (image, model) = match options.projection {
+        Projection::WebMercator => (image.clone(), Model::mercator(transform)),
+        Projection::Wgs84 => {
+            let (image, bounds) = to_wgs84(image, transform);
+            let model = Model {
+                epsg: 4326,
+                origin: (bounds.west, bounds.north),
+                pixel: (
+                    transform.pixel_to_lat_lon(1.0, 0.0).1 - bounds.west,
+                    (bounds.north - bounds.south) / image.height() as f64,
+                ),
+            };
+            (image, model)
+        }
+    };
+    let (width, height) = image.dimensions();
+    let (samples, data) = match options.bands {
+        Bands::Rgba => (Samples::Rgba, image.into_raw()),
+        Bands::Dbz => {
+            let data = image
+                .pixels()
+                .flat_map(|pixel| {
+                    let dbz = color.dbz_for_pixel(pixel.0).unwrap_or(f32::NAN);
+                    dbz.to_le_bytes()
+                })
+                .collect();
+            (Samples::Dbz, data)
+        }
+    };
+    let len = u32::try_from(data.len()).map_err(|_| {
+        ParameterError::InvalidSize(
+            width.max(height),
+            "The image is too large for a TIFF".to_owned(),
+        )
+    })?;
+    Ok(tiff(width, height, samples, &data, len, &model))
+}
+
+/// The kinds of pixels written
+#[derive(Copy, Clone, PartialEq)]
+enum Samples {
+    Rgba,
+    Dbz,
+}
+
+/// The placement of the raster in its coordinate reference system
+struct Model {
+    /// The EPSG code
+    epsg: u16,
+    /// The coordinates of the north west corner
+    origin: (f64, f64),
+    /// The size of a pixel, with rows increasing southwards
+    pixel: (f64, f64),
+}
+
+impl Model {
+    fn mercator(transform: &GeoTransform) -> Self {
+        Self {
+            epsg: 3857,
+            origin: (transform.origin_x, transform.origin_y),
+            pixel: (transform.pixel_width, transform.pixel_height),
+        }
+    }
+}
+
+/// A TIFF directory entry, with its values as little endian bytes
+struct Entry {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    values: Vec<u8>,
+}
+
+impl Entry {
+    fn shorts(tag: u16, values: &[u16]) -> Self {
+        Self {
+            tag,
+            kind: SHORT,
+            count: values.len() as u32,
+            values: values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+        }
+    }
+
+    fn long(tag: u16, value: u32) -> Self {
+        Self {
+            tag,
+            kind: LONG,
+            count: 1,
+            values: value.to_le_bytes().to_vec(),
+        }
+    }
+
+    fn doubles(tag: u16, values: &[f64]) -> Self {
+        Self {
+            tag,
+            kind: DOUBLE,
+            count: values.len() as u32,
+            values: values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+        }
+    }
+
+    fn ascii(tag: u16, value: &str) -> Self {
+        let mut values = value.as_bytes().to_vec();
+        values.push(0);
+        Self {
+            tag,
+            kind: ASCII,
+            count: values.len() as u32,
+            values,
+        }
+    }
+}
+
+/// Writes a little endian TIFF of a single uncompressed strip of `data`, `len` bytes long,
+/// with the GeoTIFF tags placing it by `model`
+fn tiff(
+    width: u32,
+    height: u32,
+    samples: Samples,
+    data: &[u8],
+    len: u32,
+    model: &Model,
+) -> Vec<u8> {
+    // GTModelTypeGeoKey, GTRasterTypeGeoKey as PixelIsArea, then the reference system
+    let (model_type, crs_key) = match model.epsg {
+        4326 => (2, 2048),
+        _ => (1, 3072),
+    };
+    let geo_keys = [
+        1, 1, 0, 3, 1024, 0, 1, model_type, 1025, 0, 1, 1, crs_key, 0, 1, model.epsg,
+    ];
+    let mut entries = vec![Entry::long(256, width), Entry::long(257, height)];
+    match samples {
+        Samples::Rgba => entries.extend([
+            Entry::shorts(258, &[8; 4]),
+            Entry::shorts(259, &[1]),
+            Entry::shorts(262, &[2]),
+            Entry::long(273, 8),
+            Entry::shorts(277, &[4]),
+            Entry::long(278, height),
+            Entry::long(279, len),
+            Entry::shorts(284, &[1]),
+            // Unassociated alpha
+            Entry::shorts(338, &[2]),
+            Entry::shorts(339, &[1; 4]),
+        ]),
+        Samples::Dbz => entries.extend([
+            Entry::shorts(258, &[32]),
+            Entry::shorts(259, &[1]),
+            Entry::shorts(262, &[1]),
+            Entry::long(273, 8),
+            Entry::shorts(277, &[1]),
+            Entry::long(278, height),
+            Entry::long(279, len),
+            Entry::shorts(284, &[1]),
+            // IEEE floats
+            Entry::shorts(339, &[3]),
+        ]),
+    }
+    entries.extend([
+        Entry::doubles(33550, &[model.pixel.0, model.pixel.1, 0.0]),
+        Entry::doubles(33922, &[0.0, 0.0, 0.0, model.origin.0, model.origin.1, 0.0]),
+        Entry::shorts(34735, &geo_keys),
+    ]);
+    if samples == Samples::Dbz {
+        // GDAL_NODATA
+        entries.push(Entry::ascii(42113, "nan"));
+    }
+
+    let mut out = Vec::with_capacity(data.len() + 512);
+    out.extend_from_slice(b"II*\0");
+    let ifd = (8 + data.len() + data.len() % 2) as u32;
+    out.extend_from_slice(&ifd.to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+
+    // Values longer than 4 bytes follow the directory, each at an even offset
+    let mut overflow = Vec::new();
+    let overflow_start = ifd + 2 + 12 * entries.len() as u32 + 4;
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for entry in &entries {
+        out.extend_from_slice(&entry.tag.to_le_bytes());
+        out.extend_from_slice(&entry.kind.to_le_bytes());
+        out.extend_from_slice(&entry.count.to_le_bytes());
+        if entry.values.len() <= 4 {
+            let mut inline = [0; 4];
+            inline[..entry.values.len()].copy_from_slice(&entry.values);
+            out.extend_from_slice(&inline);
+        } else {
+            let offset = overflow_start + overflow.len() as u32;
+            out.extend_from_slice(&offset.to_le_bytes());
+            overflow.extend_from_slice(&entry.values);
+            if overflow.len() % 2 == 1 {
+                overflow.push(0);
+            }
+        }
+    }
+    // No further directories
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&overflow);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+    use crate::geo::TileCoord;
+
+    /// The values of `tag` in a TIFF written by [`tiff`], as raw little endian bytes
+    fn tag(tiff: &[u8], tag: u16) -> Option<Vec<u8>> {
+        let read_u16 = |at: usize| u16::from_le_bytes([tiff[at], tiff[at + 1]]);
+        let read_u32 = |at: usize| u32::from_le_bytes(tiff[at..at + 4].try_into().unwrap());
+        let ifd = read_u32(4) as usize;
+        (0..read_u16(ifd) as usize)
+            .map(|i| ifd + 2 + 12 * i)
+            .find(|&entry| read_u16(entry) == tag)
+            .map(|entry| {
+                let size = match read_u16(entry + 2) {
+                    SHORT => 2,
+                    LONG => 4,
+                    DOUBLE => 8,
+                    _ => 1,
+                };
+                let len = size * read_u32(entry + 4) as usize;
+                let at = if len <= 4 {
+                    entry + 8
+                } else {
+                    read_u32(entry + 8) as usize
+                };
+                tiff[at..at + len].to_vec()
+            })
+    }
+
+    fn doubles(bytes: &[u8]) -> Vec<f64> {
+        bytes
+            .chunks(8)
+            .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn encodes_mercator() {
+        // 40 dBZ on the left column, and nothing on the right
+        let image = RgbaImage::from_fn(2, 3, |x, _| {
+            if x == 0 {
+                Rgba([72, 72, 72, 255])
+            } else {
+                Rgba([0; 4])
+            }
+        });
+        let transform = GeoTransform::new(TileCoord::new(9, 12, 5), 2);
+        let tiff = encode(
+            &image,
+            ColorKind::BlackAndWhite,
+            &transform,
+            GeoTiffOptions::new(),
+        )
+        .unwrap();
+        assert_eq!(&tiff[..4], b"II*\0");
+        assert_eq!(&tiff[8..12], &[72, 72, 72, 255]);
+        assert_eq!(tag(&tiff, 256).unwrap(), 2u32.to_le_bytes());
+        assert_eq!(tag(&tiff, 257).unwrap(), 3u32.to_le_bytes());
+        assert_eq!(tag(&tiff, 279).unwrap(), 24u32.to_le_bytes());
+        let [x, width, _, y, _, height] = transform.to_gdal();
+        assert_eq!(
+            doubles(&tag(&tiff, 33922).unwrap()),
+            [0.0, 0.0, 0.0, x, y, 0.0]
+        );
+        assert_eq!(doubles(&tag(&tiff, 33550).unwrap()), [width, -height, 0.0]);
+        let keys = tag(&tiff, 34735).unwrap();
+        assert_eq!(&keys[keys.len() - 2..], 3857u16.to_le_bytes());
+        assert_eq!(tag(&tiff, 42113), None);
+
+        let options = GeoTiffOptions::new().with_bands(Bands::Dbz);
+        let tiff = encode(&image, ColorKind::BlackAndWhite, &transform, options).unwrap();
+        let dbz: Vec<f32> = tiff[8..8 + 24]
+            .chunks(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        assert_eq!(dbz[0], 40.0);
+        assert!(dbz[1].is_nan());
+        assert_eq!(tag(&tiff, 339).unwrap(), 3u16.to_le_bytes());
+        assert_eq!(tag(&tiff, 42113).unwrap(), b"nan\0");
+
+        assert!(encode(&image, ColorKind::Titan, &transform, options).is_err());
+        assert!(encode(
+            &RgbaImage::new(0, 0),
+            ColorKind::BlackAndWhite,
+            &transform,
+            GeoTiffOptions::new()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn reprojects() {
+        // A whole zoom 1 tile, north of the equator, where mercator stretches rows towards
+        // the pole
+        let image = RgbaImage::from_fn(64, 64, |_, y| Rgba([y as u8, 0, 0, 255]));
+        let transform = GeoTransform::new(TileCoord::new(0, 0, 1), 64);
+        let options = GeoTiffOptions::new().with_projection(Projection::Wgs84);
+        let tiff = encode(&image, ColorKind::BlackAndWhite, &transform, options).unwrap();
+
+        // 180 degrees over 64 columns, and 85.05 degrees over as many rows of the same span
+        let height = u32::from_le_bytes(tag(&tiff, 257).unwrap().try_into().unwrap());
+        assert_eq!(height, 30);
+        let tiepoint = doubles(&tag(&tiff, 33922).unwrap());
+        assert!((tiepoint[3] + 180.0).abs() < 1e-9 && (tiepoint[4] - 85.0511).abs() < 1e-4);
+        let scale = doubles(&tag(&tiff, 33550).unwrap());
+        assert!((scale[0] - 180.0 / 64.0).abs() < 1e-9);
+        let keys = tag(&tiff, 34735).unwrap();
+        assert_eq!(&keys[keys.len() - 2..], 4326u16.to_le_bytes());
+
+        // Rows near the pole are squeezed, so the first output row skips mercator rows
+        let red = |row: usize| tiff[8 + row * 64 * 4];
+        assert!(red(1) - red(0) > 2);
+        assert_eq!(red(29), 63);
+    }
+}
@@ -0,0 +1,292 @@
+//! [KMZ](https://developers.google.com/kml/documentation/kmzarchives) export
+//!
+//! A KMZ is a zip archive holding a KML document and its images, which Google Earth and most
+//! GIS tools open directly. Each frame is stitched into a single image and drawn over its
+//! region as a `GroundOverlay`. With time spans, Google Earth shows a time slider playing the
+//! frames as an animation.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::args::RequestArguments;
+use crate::data::{AvailableData, Frame, FrameKind};
+use crate::error::{self, ParameterError};
+use crate::export::stitch_region;
+use crate::geo::LatLonBounds;
+use crate::mosaic::to_wgs84;
+use crate::requester::WeatherRequester;
+use crate::timestamp::{self, Timestamp};
+
+/// How a KMZ is written, see [`export`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct KmzOptions {
+    time_spans: bool,
+}
+
+impl KmzOptions {
+    /// Overlays without time spans
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether each overlay is shown only from the time of its frame until the next
+    /// frame, which lets Google Earth play the frames as an animation
+    ///
+    /// The last frame is shown for as long as the interval before it, or from its time onwards
+    /// if it is the only frame. Without time spans, every overlay is shown at once.
+    pub fn with_time_spans(mut self, time_spans: bool) -> Self {
+        self.time_spans = time_spans;
+        self
+    }
+}
+
+/// Downloads the radar tiles of each of `frames` at `zoom` intersecting `bounds`, and writes
+/// them as a KMZ at `path` with one ground overlay per frame, cropped to `bounds`
+///
+/// Overlays are ordered by the time of their frame. Images are resampled to latitude and
+/// longitude, which ground overlays are drawn in. The size, color and options of `args` apply
+/// to every tile, while its location and zoom are ignored. Every frame is downloaded before the
+/// file is written, so it is left untouched if a download fails. An existing file at `path` is
+/// replaced. Returns the number of tiles stitched
+///
+/// ```no_run
+/// use rain_viewer::export::kmz::{self, KmzOptions};
+/// use rain_viewer::{LatLonBounds, RequestArguments, TileCoord, WeatherRequester};
+///
+/// # async fn run() -> Result<(), rain_viewer::Error> {
+/// let req = WeatherRequester::new();
+/// let maps = req.available().await?;
+/// let bounds = LatLonBounds::new(-10.0, 49.0, 2.0, 59.0)?;
+/// let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0))?;
+///
+/// let options = KmzOptions::new().with_time_spans(true);
+/// kmz::export(&req, &maps, &maps.past_radar, args, bounds, 6, options, "radar.kmz").await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Enabled with the `image` feature. Returns Err(...) if `frames` is empty, `zoom` is beyond
+/// [`MAX_RADAR_ZOOM`](crate::MAX_RADAR_ZOOM), a frame is not a radar frame, or a download
+/// fails
+#[allow(clippy::too_many_arguments)]
+pub async fn export(
+    requester: &WeatherRequester,
+    maps: &AvailableData,
+    frames: &[Frame],
+    args: RequestArguments,
+    bounds: LatLonBounds,
+    zoom: u32,
+    options: KmzOptions,
+    path: impl AsRef<Path>,
+) -> Result<usize, error::Error> {
+    if frames.is_empty() {
+        return Err(ParameterError::InvalidFrame(
+            FrameKind::PastRadar,
+            "At least one frame is needed for a KMZ".to_owned(),
+        )
+        .into());
+    }
+    let mut frames: Vec<&Frame> = frames.iter().collect();
+    frames.sort_by_key(|frame| frame.time);
+    let regions = futures_util::future::try_join_all(
+        frames
+            .iter()
+            .map(|frame| stitch_region(requester, maps, frame, args, bounds, zoom)),
+    )
+    .await?;
+
+    let mut count = 0;
+    let mut overlays = Vec::with_capacity(frames.len());
+    let mut images = Vec::with_capacity(frames.len());
+    for (i, (image, transform, tiles)) in regions.into_iter().enumerate() {
+        count += tiles;
+        let (image, bounds) = to_wgs84(&image, &transform);
+        let mut png = Vec::new();
+        image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+        let begin = frames[i].time;
+        let end = match frames.get(i + 1) {
+            Some(next) => Some(next.time),
+            None => i.checked_sub(1).map(|j| begin + (begin - frames[j].time)),
+        };
+        overlays.push(Overlay {
+            name: timestamp::rfc3339(begin),
+            href: format!("files/{}.png", frames[i].unix_time()),
+            bounds,
+            span: options.time_spans.then_some((begin, end)),
+        });
+        images.push(png);
+    }
+
+    let kml = document(&overlays);
+    let mut entries = vec![("doc.kml", kml.as_bytes())];
+    entries.extend(
+        overlays
+            .iter()
+            .zip(&images)
+            .map(|(overlay, png)| (overlay.href.as_str(), png.as_slice())),
+    );
+    std::fs::write(path, archive(&entries))?;
+    Ok(count)
+}
+
+/// An image drawn over an area of the map
+struct Overlay {
+    name: String,
+    /// The path of the image within the archive
+    href: String,
+    bounds: LatLonBounds,
+    /// When the overlay is shown, from a time until an optional end
+    span: Option<(Timestamp, Option<Timestamp>)>,
+}
+
+/// The KML document drawing `overlays`
+fn document(overlays: &[Overlay]) -> String {
+    let mut kml = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n",
+        "<Document>\n",
+        "<name>Rain Viewer radar</name>\n",
+        "<description>Radar data from RainViewer (https://www.rainviewer.com)</description>\n",
+    ));
+    let time = timestamp::rfc3339;
+    for overlay in overlays {
+        kml.push_str("<GroundOverlay>\n");
+        let _ = writeln!(kml, "<name>{}</name>", overlay.name);
+        if let Some((begin, end)) = overlay.span {
+            let _ = write!(kml, "<TimeSpan><begin>{}</begin>", time(begin));
+            if let Some(end) = end {
+                let _ = write!(kml, "<end>{}</end>", time(end));
+            }
+            kml.push_str("</TimeSpan>\n");
+        }
+        let _ = writeln!(kml, "<Icon><href>{}</href></Icon>", overlay.href);
+        let bounds = overlay.bounds;
+        let _ = writeln!(
+            kml,
+            "<LatLonBox><north>{:.6}</north><south>{:.6}</south><east>{:.6}</east><west>{:.6}</west></LatLonBox>",
+            bounds.north, bounds.south, bounds.east, bounds.west
+        );
+        kml.push_str("</GroundOverlay>\n");
+    }
+    kml.push_str("</Document>\n</kml>\n");
+    kml
+}
+
+/// Writes a zip archive of `entries`, as names and contents, stored without compression since
+/// PNGs are already compressed
+fn archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    // 1980-01-01 00:00 in MS-DOS format, the earliest time zip can hold
+    const DATE: u16 = (1 << 5) | 1;
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for (name, data) in entries {
+        let offset = out.len() as u32;
+        let crc = crc32(data);
+        let len = data.len() as u32;
+
+        // Fields shared by the local and central headers, from the version needed onwards
+        let mut fields = Vec::with_capacity(26);
+        fields.extend_from_slice(&20u16.to_le_bytes());
+        // No flags, stored
+        fields.extend_from_slice(&[0; 4]);
+        fields.extend_from_slice(&0u16.to_le_bytes());
+        fields.extend_from_slice(&DATE.to_le_bytes());
+        fields.extend_from_slice(&crc.to_le_bytes());
+        fields.extend_from_slice(&len.to_le_bytes());
+        fields.extend_from_slice(&len.to_le_bytes());
+        fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        // No extra field
+        fields.extend_from_slice(&[0; 2]);
+
+        out.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        out.extend_from_slice(&fields);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(data);
+
+        central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        // Made by version 2.0
+        central.extend_from_slice(&20u16.to_le_bytes());
+        central.extend_from_slice(&fields);
+        // No comment, on the first disk, without attributes
+        central.extend_from_slice(&[0; 10]);
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+    }
+
+    let central_offset = out.len() as u32;
+    out.extend_from_slice(&central);
+    out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    // On the first disk
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    out.extend_from_slice(&central_offset.to_le_bytes());
+    // No comment
+    out.extend_from_slice(&[0; 2]);
+    out
+}
+
+/// The CRC-32 checksum zip archives store for each entry
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_document() {
+        let at = |seconds: i64| timestamp::from_unix_seconds(seconds).unwrap();
+        let bounds = LatLonBounds::new(-10.0, 49.0, 2.0, 59.0).unwrap();
+        let overlays = [
+            Overlay {
+                name: "first".to_owned(),
+                href: "files/1696999800.png".to_owned(),
+                bounds,
+                span: Some((at(1696999800), Some(at(1697000400)))),
+            },
+            Overlay {
+                name: "second".to_owned(),
+                href: "files/1697000400.png".to_owned(),
+                bounds,
+                span: Some((at(1697000400), None)),
+            },
+        ];
+        let kml = document(&overlays);
+        assert_eq!(kml.matches("<GroundOverlay>").count(), 2);
+        assert!(kml.contains(
+            "<TimeSpan><begin>2023-10-11T04:50:00Z</begin><end>2023-10-11T05:00:00Z</end></TimeSpan>"
+        ));
+        assert!(kml.contains("<TimeSpan><begin>2023-10-11T05:00:00Z</begin></TimeSpan>"));
+        assert!(kml.contains("<name>second</name>"));
+        assert!(kml.contains("<Icon><href>files/1697000400.png</href></Icon>"));
+        assert!(kml.contains("<north>59.000000</north><south>49.000000</south><east>2.000000</east><west>-10.000000</west>"));
+    }
+
+    #[test]
+    fn writes_archive() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+
+        let zip = archive(&[("doc.kml", b"<kml/>"), ("files/0.png", b"png")]);
+        assert_eq!(&zip[..4], b"PK\x03\x04");
+        assert_eq!(&zip[30..37], b"doc.kml");
+        assert_eq!(&zip[37..43], b"<kml/>");
+        let end = zip.len() - 22;
+        assert_eq!(&zip[end..end + 4], b"PK\x05\x06");
+        assert_eq!(u16::from_le_bytes([zip[end + 10], zip[end + 11]]), 2);
+        let central = u32::from_le_bytes(zip[end + 16..end + 20].try_into().unwrap()) as usize;
+        assert_eq!(&zip[central..central + 4], b"PK\x01\x02");
+        // The second entry starts after the first local header and its contents
+        let offset = u32::from_le_bytes(zip[central + 95..central + 99].try_into().unwrap());
+        assert_eq!(offset, 43);
+    }
+}
@@ -0,0 +1,108 @@
+//! [MBTiles](https://github.com/mapbox/mbtiles-spec) export
+//!
+//! Enabled with the `mbtiles` feature. MBTiles files are SQLite databases which QGIS, MapLibre
+//! and most offline map SDKs open directly.
+//!
+//! ```no_run
+//! use rain_viewer::export::{mbtiles, TileRange};
+//! use rain_viewer::{LatLonBounds, RequestArguments, TileCoord, WeatherRequester};
+//!
+//! # async fn run() -> Result<(), rain_viewer::Error> {
+//! let req = WeatherRequester::new();
+//! let maps = req.available().await?;
+//! let frame = maps.latest_past().unwrap();
+//! let range = TileRange::new(LatLonBounds::new(-125.0, 24.0, -66.0, 50.0)?, 3..=6)?;
+//! let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0))?;
+//! mbtiles::export(&req, &maps, frame, args, &range, "radar.mbtiles").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::Path;
+
+use crate::args::RequestArguments;
+use crate::data::{AvailableData, Frame};
+use crate::error;
+use crate::export::{download, TileRange};
+use crate::geo::TileScheme;
+use crate::requester::WeatherRequester;
+
+const SCHEMA: &str = "
+    DROP TABLE IF EXISTS metadata;
+    DROP TABLE IF EXISTS tiles;
+    CREATE TABLE metadata (name TEXT, value TEXT);
+    CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB);
+    CREATE UNIQUE INDEX tile_index ON tiles (zoom_level, tile_column, tile_row);
+";
+
+/// Downloads the radar tiles of `frame` in `range` into an MBTiles file at `path`
+///
+/// The size, color and options of `args` apply to every tile, while its location is ignored.
+/// Every tile is downloaded before the file is written, so it is left untouched if a download
+/// fails. Tables of an existing MBTiles file at `path` are replaced. Returns the number of tiles
+/// written
+pub async fn export(
+    requester: &WeatherRequester,
+    maps: &AvailableData,
+    frame: &Frame,
+    args: RequestArguments,
+    range: &TileRange,
+    path: impl AsRef<Path>,
+) -> Result<usize, error::Error> {
+    let tiles = download(requester, maps, frame, args, range).await?;
+
+    // Tiles are downloaded before opening the database, since a connection held across an await
+    // point would make this future `!Send`
+    let mut conn = rusqlite::Connection::open(path)?;
+    let tx = conn.transaction()?;
+    tx.execute_batch(SCHEMA)?;
+
+    let bounds = range.bounds();
+    let metadata = [
+        (
+            "name",
+            format!(
+                "Rain Viewer radar {}",
+                crate::timestamp::rfc3339(frame.time)
+            ),
+        ),
+        ("format", "png".to_owned()),
+        ("type", "overlay".to_owned()),
+        ("version", "1".to_owned()),
+        (
+            "description",
+            format!("Radar frame {} from {}", frame.path, maps.host),
+        ),
+        (
+            "attribution",
+            "<a href=\"https://www.rainviewer.com\">RainViewer</a>".to_owned(),
+        ),
+        (
+            "bounds",
+            format!(
+                "{},{},{},{}",
+                bounds.west, bounds.south, bounds.east, bounds.north
+            ),
+        ),
+        ("minzoom", range.zooms().start().to_string()),
+        ("maxzoom", range.zooms().end().to_string()),
+    ];
+    for (name, value) in metadata {
+        tx.execute(
+            "INSERT INTO metadata (name, value) VALUES (?1, ?2)",
+            (name, value),
+        )?;
+    }
+    {
+        let mut insert = tx.prepare(
+            "INSERT INTO tiles (zoom_level, tile_column, tile_row, tile_data) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for (coord, tile) in &tiles {
+            // MBTiles numbers rows from the south, following the TMS scheme
+            let row = TileScheme::Tms.row(*coord);
+            insert.execute((coord.z, coord.x, row, &tile[..]))?;
+        }
+    }
+    tx.commit()?;
+    Ok(tiles.len())
+}
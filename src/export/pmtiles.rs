@@ -0,0 +1,296 @@
+//! [PMTiles](https://github.com/protomaps/PMTiles) export
+//!
+//! A PMTiles archive is a single file holding a tile set along with an index, which map
+//! libraries read with HTTP range requests. Archives can be served from static object storage
+//! without a tile server. Identical tiles, such as the empty tiles covering dry areas, are
+//! stored once.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use bytes::Bytes;
+
+use crate::args::RequestArguments;
+use crate::data::{AvailableData, Frame};
+use crate::error;
+use crate::export::{download, TileRange};
+use crate::geo::TileCoord;
+use crate::requester::WeatherRequester;
+
+/// The size of the fixed header at the start of every archive
+const HEADER_LEN: usize = 127;
+
+/// Readers fetch the header and root directory with a single request of this many bytes, so
+/// larger indexes are split into leaf directories
+const MAX_ROOT_LEN: usize = 16384 - HEADER_LEN;
+
+/// The compression type for data that is stored as is
+const COMPRESSION_NONE: u8 = 1;
+
+/// The tile type for PNG images
+const TILE_TYPE_PNG: u8 = 2;
+
+/// Downloads the radar tiles of `frame` in `range` into a PMTiles archive at `path`
+///
+/// The size, color and options of `args` apply to every tile, while its location is ignored.
+/// Every tile is downloaded before the file is written, so it is left untouched if a download
+/// fails. An existing file at `path` is replaced. Returns the number of tiles written
+pub async fn export(
+    requester: &WeatherRequester,
+    maps: &AvailableData,
+    frame: &Frame,
+    args: RequestArguments,
+    range: &TileRange,
+    path: impl AsRef<Path>,
+) -> Result<usize, error::Error> {
+    let tiles = download(requester, maps, frame, args, range).await?;
+    let metadata = serde_json::json!({
+        "name": format!("Rain Viewer radar {}", crate::timestamp::rfc3339(frame.time)),
+        "description": format!("Radar frame {} from {}", frame.path, maps.host),
+        "attribution": "<a href=\"https://www.rainviewer.com\">RainViewer</a>",
+        "type": "overlay",
+    });
+    std::fs::write(path, archive(&tiles, range, &metadata))?;
+    Ok(tiles.len())
+}
+
+/// The position of a tile along the Hilbert curves PMTiles orders tiles by, after all tiles of
+/// lower zoom levels
+fn tile_id(coord: TileCoord) -> u64 {
+    let lower_zooms = ((1u64 << (2 * coord.z)) - 1) / 3;
+    let (mut x, mut y) = (coord.x as u64, coord.y as u64);
+    let mut d = 0;
+    let mut s = (1u64 << coord.z) / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as u64;
+        let ry = (y & s > 0) as u64;
+        d += s * s * ((3 * rx) ^ ry);
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    lower_zooms + d
+}
+
+/// A directory entry, pointing either at tile data or at a leaf directory when `run_length` is
+/// zero
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Entry {
+    tile_id: u64,
+    offset: u64,
+    length: u32,
+    /// The number of consecutive tile ids sharing this data
+    run_length: u32,
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Serializes directory entries column by column, as the format requires
+fn serialize_directory(entries: &[Entry]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_varint(&mut buf, entries.len() as u64);
+    let mut last_id = 0;
+    for entry in entries {
+        write_varint(&mut buf, entry.tile_id - last_id);
+        last_id = entry.tile_id;
+    }
+    for entry in entries {
+        write_varint(&mut buf, entry.run_length as u64);
+    }
+    for entry in entries {
+        write_varint(&mut buf, entry.length as u64);
+    }
+    for (i, entry) in entries.iter().enumerate() {
+        // Zero marks data directly following the previous entry's
+        let contiguous =
+            i > 0 && entry.offset == entries[i - 1].offset + entries[i - 1].length as u64;
+        write_varint(&mut buf, if contiguous { 0 } else { entry.offset + 1 });
+    }
+    buf
+}
+
+/// Serializes the root directory, moving entries into leaf directories if they do not fit.
+/// Returns the root directory and the concatenated leaf directories
+fn directories(entries: &[Entry]) -> (Vec<u8>, Vec<u8>) {
+    let root = serialize_directory(entries);
+    if root.len() <= MAX_ROOT_LEN {
+        return (root, Vec::new());
+    }
+    let mut leaf_size = 4096;
+    loop {
+        let mut leaves = Vec::new();
+        let mut root_entries = Vec::new();
+        for chunk in entries.chunks(leaf_size) {
+            let leaf = serialize_directory(chunk);
+            root_entries.push(Entry {
+                tile_id: chunk[0].tile_id,
+                offset: leaves.len() as u64,
+                length: leaf.len() as u32,
+                run_length: 0,
+            });
+            leaves.extend_from_slice(&leaf);
+        }
+        let root = serialize_directory(&root_entries);
+        if root.len() <= MAX_ROOT_LEN {
+            return (root, leaves);
+        }
+        leaf_size *= 2;
+    }
+}
+
+/// Degrees as the fixed point integers used in the header
+fn e7(degrees: f64) -> i32 {
+    (degrees * 1e7).round() as i32
+}
+
+/// Builds an uncompressed, clustered archive holding `tiles`
+fn archive(
+    tiles: &[(TileCoord, Bytes)],
+    range: &TileRange,
+    metadata: &serde_json::Value,
+) -> Vec<u8> {
+    let mut tiles: Vec<_> = tiles
+        .iter()
+        .map(|(coord, tile)| (tile_id(*coord), tile))
+        .collect();
+    tiles.sort_by_key(|(id, _)| *id);
+
+    let mut data = Vec::new();
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut stored: HashMap<&[u8], (u64, u32)> = HashMap::new();
+    for (id, tile) in tiles.iter().copied() {
+        let (offset, length) = *stored.entry(tile).or_insert_with(|| {
+            let offset = data.len() as u64;
+            data.extend_from_slice(tile);
+            (offset, tile.len() as u32)
+        });
+        match entries.last_mut() {
+            Some(last) if last.offset == offset && last.tile_id + last.run_length as u64 == id => {
+                last.run_length += 1;
+            }
+            _ => entries.push(Entry {
+                tile_id: id,
+                offset,
+                length,
+                run_length: 1,
+            }),
+        }
+    }
+
+    let (root, leaves) = directories(&entries);
+    let metadata = metadata.to_string().into_bytes();
+    let root_offset = HEADER_LEN as u64;
+    let metadata_offset = root_offset + root.len() as u64;
+    let leaves_offset = metadata_offset + metadata.len() as u64;
+    let data_offset = leaves_offset + leaves.len() as u64;
+
+    let bounds = range.bounds();
+    let center_lon = if bounds.crosses_antimeridian() {
+        let lon = (bounds.west + bounds.east + 360.0) / 2.0;
+        if lon > 180.0 {
+            lon - 360.0
+        } else {
+            lon
+        }
+    } else {
+        (bounds.west + bounds.east) / 2.0
+    };
+    let center_lat = (bounds.south + bounds.north) / 2.0;
+    let zooms = range.zooms();
+
+    let mut out = Vec::with_capacity(data_offset as usize + data.len());
+    out.extend_from_slice(b"PMTiles");
+    out.push(3);
+    for value in [
+        root_offset,
+        root.len() as u64,
+        metadata_offset,
+        metadata.len() as u64,
+        leaves_offset,
+        leaves.len() as u64,
+        data_offset,
+        data.len() as u64,
+        tiles.len() as u64,
+        entries.len() as u64,
+        stored.len() as u64,
+    ] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out.extend_from_slice(&[
+        1,
+        COMPRESSION_NONE,
+        COMPRESSION_NONE,
+        TILE_TYPE_PNG,
+        *zooms.start() as u8,
+        *zooms.end() as u8,
+    ]);
+    for value in [bounds.west, bounds.south, bounds.east, bounds.north] {
+        out.extend_from_slice(&e7(value).to_le_bytes());
+    }
+    out.push(*zooms.start() as u8);
+    out.extend_from_slice(&e7(center_lon).to_le_bytes());
+    out.extend_from_slice(&e7(center_lat).to_le_bytes());
+    debug_assert_eq!(out.len(), HEADER_LEN);
+
+    out.extend_from_slice(&root);
+    out.extend_from_slice(&metadata);
+    out.extend_from_slice(&leaves);
+    out.extend_from_slice(&data);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geo::LatLonBounds;
+
+    fn coord(z: u32, x: u32, y: u32) -> TileCoord {
+        TileCoord { x, y, z }
+    }
+
+    #[test]
+    fn hilbert_tile_ids() {
+        assert_eq!(tile_id(coord(0, 0, 0)), 0);
+        assert_eq!(tile_id(coord(1, 0, 0)), 1);
+        assert_eq!(tile_id(coord(1, 0, 1)), 2);
+        assert_eq!(tile_id(coord(1, 1, 1)), 3);
+        assert_eq!(tile_id(coord(1, 1, 0)), 4);
+        assert_eq!(tile_id(coord(2, 0, 0)), 5);
+        assert_eq!(tile_id(coord(12, 4095, 0)), 22369620);
+    }
+
+    #[test]
+    fn deduplicates_tiles() {
+        let range = TileRange::new(
+            LatLonBounds::new(-180.0, -85.0, 180.0, 85.0).unwrap(),
+            1..=1,
+        )
+        .unwrap();
+        let tiles: Vec<_> = range
+            .tiles()
+            .into_iter()
+            .map(|coord| (coord, Bytes::from_static(b"empty")))
+            .collect();
+        let archive = archive(&tiles, &range, &serde_json::json!({}));
+
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(archive[offset..offset + 8].try_into().unwrap());
+        assert_eq!(&archive[..7], b"PMTiles");
+        // One entry with a run length of 4 pointing at the only stored copy
+        assert_eq!((u64_at(72), u64_at(80), u64_at(88)), (4, 1, 1));
+        assert_eq!(u64_at(64), 5);
+        let root = &archive[u64_at(8) as usize..][..u64_at(16) as usize];
+        assert_eq!(root, [1, 1, 4, 5, 1]);
+    }
+}
@@ -0,0 +1,53 @@
+//! Export as a `{z}/{x}/{y}.png` directory tree
+//!
+//! This is the layout Leaflet and OpenLayers expect for a static tile layer, so the exported
+//! directory can be served by any static file server, or opened with a `file://` url template.
+
+use std::path::Path;
+
+use crate::args::RequestArguments;
+use crate::data::{AvailableData, Frame};
+use crate::error;
+use crate::export::{download, TileRange};
+use crate::geo::TileScheme;
+use crate::requester::WeatherRequester;
+
+/// Downloads the radar tiles of `frame` in `range` into `{dir}/{z}/{x}/{y}.png` files, with
+/// rows numbered from the north as Leaflet and OpenLayers expect
+///
+/// The size, color and options of `args` apply to every tile, while its location is ignored.
+/// Every tile is downloaded before any file is written. Missing directories are created and
+/// existing tiles are overwritten. Returns the number of tiles written
+pub async fn export(
+    requester: &WeatherRequester,
+    maps: &AvailableData,
+    frame: &Frame,
+    args: RequestArguments,
+    range: &TileRange,
+    dir: impl AsRef<Path>,
+) -> Result<usize, error::Error> {
+    export_with_scheme(requester, maps, frame, args, range, dir, TileScheme::Xyz).await
+}
+
+/// Like [`export`], but numbers the `{y}` rows following `scheme`, for consumers of
+/// [`TileScheme::Tms`] tile trees such as older GIS tools
+pub async fn export_with_scheme(
+    requester: &WeatherRequester,
+    maps: &AvailableData,
+    frame: &Frame,
+    args: RequestArguments,
+    range: &TileRange,
+    dir: impl AsRef<Path>,
+    scheme: TileScheme,
+) -> Result<usize, error::Error> {
+    let tiles = download(requester, maps, frame, args, range).await?;
+    for (coord, tile) in &tiles {
+        let column = dir
+            .as_ref()
+            .join(coord.z.to_string())
+            .join(coord.x.to_string());
+        std::fs::create_dir_all(&column)?;
+        std::fs::write(column.join(format!("{}.png", scheme.row(*coord))), tile)?;
+    }
+    Ok(tiles.len())
+}
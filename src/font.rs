@@ -0,0 +1,171 @@
+use ab_glyph::{point, Font, FontArc, PxScale, ScaleFont};
+use image::{Rgba, RgbaImage};
+
+/// Width and height of a glyph of [`glyph`], in pixels
+pub(crate) const GLYPH_WIDTH: u32 = 5;
+pub(crate) const GLYPH_HEIGHT: u32 = 7;
+
+/// The width in pixels of `text` drawn at `scale`, with one pixel between glyphs
+pub(crate) fn text_width(text: &str, scale: u32) -> u32 {
+    (text.chars().count() as u32 * (GLYPH_WIDTH + 1)).saturating_sub(1) * scale
+}
+
+/// Draws `text` in `color` with its top left corner at `(x, y)`, each glyph pixel drawn as a
+/// `scale` by `scale` square, clipping it to the image
+pub(crate) fn draw_text(
+    image: &mut RgbaImage,
+    text: &str,
+    x: u32,
+    y: u32,
+    scale: u32,
+    color: Rgba<u8>,
+) {
+    for (i, c) in text.chars().enumerate() {
+        let left = x + i as u32 * (GLYPH_WIDTH + 1) * scale;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let px = left + column * scale + dx;
+                        let py = y + row as u32 * scale + dy;
+                        if px < image.width() && py < image.height() {
+                            image.put_pixel(px, py, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The width in pixels of `text` set in `font` with lines `size` pixels high, including kerning
+pub(crate) fn outline_text_width(font: &FontArc, size: f32, text: &str) -> u32 {
+    let font = font.as_scaled(PxScale::from(size));
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            width += font.kern(previous, id);
+        }
+        width += font.h_advance(id);
+        previous = Some(id);
+    }
+    width.ceil() as u32
+}
+
+/// Calls `plot` with the coverage, from 0 to 1, of each pixel of `text` set in `font` with lines
+/// `size` pixels high and the top left corner of its line at `(x, y)`, clipped to `width` by
+/// `height` pixels
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn draw_outline_text(
+    font: &FontArc,
+    size: f32,
+    text: &str,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    mut plot: impl FnMut(u32, u32, f32),
+) {
+    let font = font.as_scaled(PxScale::from(size));
+    let mut caret = point(x as f32, y as f32 + font.ascent());
+    let mut previous = None;
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            caret.x += font.kern(previous, id);
+        }
+        previous = Some(id);
+        let glyph = id.with_scale_and_position(font.scale(), caret);
+        caret.x += font.h_advance(id);
+        let Some(outline) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|gx, gy, coverage| {
+            let px = bounds.min.x + gx as f32;
+            let py = bounds.min.y + gy as f32;
+            if px >= 0.0 && py >= 0.0 && (px as u32) < width && (py as u32) < height {
+                plot(px as u32, py as u32, coverage);
+            }
+        });
+    }
+}
+
+/// The rows of a 5 by 7 pixel glyph, from the top, with the leftmost pixel in the highest bit
+///
+/// Covers digits, capital letters and the punctuation of labels and timestamps. Lowercase
+/// letters are drawn as capitals, and other characters are blank
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT as usize] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        'A' => [0x0e, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'B' => [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e],
+        'C' => [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e],
+        'D' => [0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c],
+        'E' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f],
+        'F' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10],
+        'G' => [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f],
+        'H' => [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'I' => [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f],
+        'M' => [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'P' => [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10],
+        'Q' => [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d],
+        'R' => [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11],
+        'S' => [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e],
+        'T' => [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a],
+        'X' => [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04],
+        'Z' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f],
+        '-' => [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c],
+        ':' => [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        _ => [0; GLYPH_HEIGHT as usize],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_text() {
+        assert_eq!(text_width("12", 1), 11);
+        assert_eq!(text_width("12", 2), 22);
+
+        let color = Rgba([255, 255, 255, 255]);
+        let mut image = RgbaImage::new(12, 14);
+        draw_text(&mut image, "I", 0, 0, 2, color);
+        // The top bar of the I spans glyph columns 1 to 3, doubled
+        assert_eq!(image.get_pixel(2, 0).0, color.0);
+        assert_eq!(image.get_pixel(7, 1).0, color.0);
+        assert_eq!(image.get_pixel(0, 0).0, [0; 4]);
+        assert_eq!(glyph('a'), glyph('A'));
+        assert_eq!(glyph('~'), [0; 7]);
+    }
+}
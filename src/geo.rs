@@ -0,0 +1,752 @@
+//! Web mercator tile geometry
+//!
+//! Rain Viewer serves tiles in the slippy map scheme used by OpenStreetMap: zoom level `z`
+//! divides the world into `2^z` by `2^z` tiles, with `x` increasing eastwards from the
+//! antimeridian and `y` increasing southwards from 85.0511 degrees north.
+//!
+//! The functions here convert between WGS84 coordinates, tiles and pixels. Latitudes beyond
+//! the +/-85.0511 degrees covered by web mercator are clamped to it, and longitudes outside of
+//! +/-180 degrees are wrapped around the antimeridian.
+//!
+//! ```
+//! use rain_viewer::geo;
+//!
+//! let tile = geo::lat_lon_to_tile(40.7128, -74.006, 5);
+//! assert_eq!((tile.x, tile.y), (9, 12));
+//!
+//! // The north west corner of the tile
+//! let (lat, lon) = geo::tile_to_lat_lon(tile);
+//! assert!(lat > 40.7128 && lon < -74.006);
+//! ```
+
+use crate::args::MAX_LATITUDE;
+use crate::error::ParameterError;
+
+/// A geographic bounding box in WGS84 degrees
+///
+/// `west` may be greater than `east`, in which case the box crosses the antimeridian
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LatLonBounds {
+    pub west: f64,
+    pub south: f64,
+    pub east: f64,
+    pub north: f64,
+}
+
+impl LatLonBounds {
+    /// Creates a bounding box, taking its edges in the same order as a GeoJSON `bbox`
+    ///
+    /// Longitudes must be within +/-180 degrees, latitudes within +/-90 degrees and `south`
+    /// must not be north of `north`, or Err(...) is returned. Latitudes beyond the web mercator
+    /// limit of +/-85.0511 degrees are clamped to it when computing tiles
+    pub fn new(west: f64, south: f64, east: f64, north: f64) -> Result<Self, ParameterError> {
+        for lon in [west, east] {
+            if !(-180.0..=180.0).contains(&lon) {
+                return Err(ParameterError::LongitudeOutOfRange(
+                    lon,
+                    "Longitude must be between -180 and 180".to_owned(),
+                ));
+            }
+        }
+        for lat in [south, north] {
+            if !(-90.0..=90.0).contains(&lat) {
+                return Err(ParameterError::LatitudeOutOfRange(
+                    lat,
+                    "Latitude must be between -90 and 90".to_owned(),
+                ));
+            }
+        }
+        if south > north {
+            return Err(ParameterError::LatitudeOutOfRange(
+                south,
+                format!("South edge must not be north of the north edge {}", north),
+            ));
+        }
+        Ok(Self {
+            west,
+            south,
+            east,
+            north,
+        })
+    }
+
+    /// Whether the box wraps around the antimeridian
+    pub fn crosses_antimeridian(&self) -> bool {
+        self.west > self.east
+    }
+}
+
+/// The slippy map coordinates of a tile
+///
+/// Tiles form a pyramid: each tile at zoom `z` is split into four tiles at zoom `z + 1`, which
+/// [`TileCoord::parent`] and [`TileCoord::children`] move between
+///
+/// ```
+/// use rain_viewer::TileCoord;
+///
+/// let tile = TileCoord::new(9, 12, 5);
+/// assert!(tile.contains(40.7128, -74.006));
+/// assert_eq!(tile.parent(), Some(TileCoord::new(4, 6, 4)));
+/// assert!(tile.children().iter().all(|child| child.parent() == Some(tile)));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TileCoord {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+impl TileCoord {
+    /// The tile at column `x` and row `y` of zoom `z`
+    ///
+    /// The coordinates are not checked, see [`TileCoord::validate`]
+    pub const fn new(x: u32, y: u32, z: u32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Checks that this tile exists: `z` must be less than 32 and `x` and `y` must be less
+    /// than `2^z`, or Err(...) is returned
+    ///
+    /// Products served by Rain Viewer have lower zoom limits, which are checked when making
+    /// request arguments
+    pub fn validate(&self) -> Result<(), ParameterError> {
+        if self.z >= 32 {
+            return Err(ParameterError::InvalidZoom(
+                self.z,
+                "Zoom must be less than 32".to_owned(),
+            ));
+        }
+        let max = 1u32 << self.z;
+        if self.x >= max {
+            Err(ParameterError::XOutOfRange(
+                self.x,
+                format!(
+                    "With a zoom of {}, the max value for x is {}",
+                    self.z,
+                    max - 1
+                ),
+            ))
+        } else if self.y >= max {
+            Err(ParameterError::YOutOfRange(
+                self.y,
+                format!(
+                    "With a zoom of {}, the max value for y is {}",
+                    self.z,
+                    max - 1
+                ),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether this tile exists, see [`TileCoord::validate`]
+    pub fn is_valid(&self) -> bool {
+        self.validate().is_ok()
+    }
+
+    /// The tile one zoom level out containing this one, or `None` at zoom 0
+    pub fn parent(&self) -> Option<TileCoord> {
+        let z = self.z.checked_sub(1)?;
+        Some(TileCoord {
+            x: self.x / 2,
+            y: self.y / 2,
+            z,
+        })
+    }
+
+    /// The four tiles one zoom level in covering this one, in the order north west, north
+    /// east, south west, south east
+    ///
+    /// # Panics
+    ///
+    /// Panics if `z` is 31 or more, since the children would not fit in a `u32`
+    pub fn children(&self) -> [TileCoord; 4] {
+        assert!(
+            self.z < 31,
+            "zoom {} is too large for u32 tile coordinates",
+            self.z + 1
+        );
+        let (x, y, z) = (self.x * 2, self.y * 2, self.z + 1);
+        [
+            TileCoord { x, y, z },
+            TileCoord { x: x + 1, y, z },
+            TileCoord { x, y: y + 1, z },
+            TileCoord {
+                x: x + 1,
+                y: y + 1,
+                z,
+            },
+        ]
+    }
+
+    /// Whether the given WGS84 location lies within this tile
+    ///
+    /// Follows [`lat_lon_to_tile`]: points on an edge belong to the tile to their south east,
+    /// latitudes beyond the web mercator limits are clamped and longitudes are wrapped
+    ///
+    /// # Panics
+    ///
+    /// Panics if `z` is 32 or more
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        lat_lon_to_tile(lat, lon, self.z) == *self
+    }
+
+    /// The WGS84 extent of this tile, for placing a tile image on maps and in georeferenced
+    /// exports
+    ///
+    /// Tiles of the top and bottom rows reach the web mercator limit of +/-85.0511 degrees
+    pub fn bounds(&self) -> LatLonBounds {
+        let (north, west) = tile_to_lat_lon(*self);
+        let (south, east) =
+            tile_fraction_to_lat_lon(self.x as f64 + 1.0, self.y as f64 + 1.0, self.z);
+        LatLonBounds {
+            west,
+            south,
+            east,
+            north,
+        }
+    }
+}
+
+/// The direction tile rows are numbered in
+///
+/// Rain Viewer and most web maps use [`TileScheme::Xyz`]. TMS, MBTiles and older GIS tools
+/// number rows from the south instead. Tile coordinates in this crate always follow
+/// [`TileScheme::Xyz`], and are converted at the edges
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TileScheme {
+    /// Rows are numbered from the north, as in OpenStreetMap's slippy map tiles
+    #[default]
+    Xyz,
+    /// Rows are numbered from the south, as in the Tile Map Service specification
+    Tms,
+}
+
+impl TileScheme {
+    /// The row of `tile` in this scheme
+    pub fn row(self, tile: TileCoord) -> u32 {
+        match self {
+            TileScheme::Xyz => tile.y,
+            TileScheme::Tms => flip_y(tile.y, tile.z),
+        }
+    }
+
+    /// The tile at column `x` and row `y` of zoom `z` in this scheme
+    ///
+    /// Returns Err(...) if `y` is not a valid row at zoom `z`
+    pub fn tile(self, x: u32, y: u32, z: u32) -> Result<TileCoord, ParameterError> {
+        let max = 1u64 << z;
+        if u64::from(y) >= max {
+            return Err(ParameterError::YOutOfRange(
+                y,
+                format!("With a zoom of {}, the max value for y is {}", z, max - 1),
+            ));
+        }
+        let y = match self {
+            TileScheme::Xyz => y,
+            TileScheme::Tms => flip_y(y, z),
+        };
+        Ok(TileCoord { x, y, z })
+    }
+}
+
+/// Converts a row between [`TileScheme::Xyz`] and [`TileScheme::Tms`], in either direction
+fn flip_y(y: u32, z: u32) -> u32 {
+    ((1u64 << z) - 1 - u64::from(y)) as u32
+}
+
+/// The number of tiles along each side of the world at `zoom`
+///
+/// # Panics
+///
+/// Panics if `zoom` is 32 or more, since tile coordinates would not fit in a `u32`
+fn tiles_per_side(zoom: u32) -> f64 {
+    assert!(
+        zoom < 32,
+        "zoom {zoom} is too large for u32 tile coordinates"
+    );
+    2f64.powi(zoom as i32)
+}
+
+/// Wraps a longitude into [-180, 180)
+pub(crate) fn wrap_lon(lon: f64) -> f64 {
+    (lon + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// The tile at `zoom` containing the given WGS84 location
+///
+/// Points on a tile edge belong to the tile to their south east. Latitudes are clamped to the
+/// web mercator limits, so the poles map to the top and bottom rows, and longitudes are wrapped,
+/// so 180 degrees east maps to the first column like 180 degrees west
+///
+/// # Panics
+///
+/// Panics if `zoom` is 32 or more
+pub fn lat_lon_to_tile(lat: f64, lon: f64, zoom: u32) -> TileCoord {
+    let (x, y) = lat_lon_to_tile_fraction(lat, lon, zoom);
+    let max = tiles_per_side(zoom) - 1.0;
+    TileCoord {
+        x: x.floor().min(max) as u32,
+        y: y.floor().min(max) as u32,
+        z: zoom,
+    }
+}
+
+/// The fractional tile coordinates of the given WGS84 location at `zoom`
+///
+/// The integer parts are the tile's `x` and `y`, while the fractional parts give the position
+/// within the tile
+///
+/// # Panics
+///
+/// Panics if `zoom` is 32 or more
+pub fn lat_lon_to_tile_fraction(lat: f64, lon: f64, zoom: u32) -> (f64, f64) {
+    let n = tiles_per_side(zoom);
+    (lon_to_x(wrap_lon(lon), n), lat_to_y(lat, n))
+}
+
+/// The latitude and longitude of the north west corner of `tile`
+///
+/// The south east corner is the north west corner of the tile at `x + 1` and `y + 1`, which
+/// [`tile_fraction_to_lat_lon`] accepts even past the last column or row
+pub fn tile_to_lat_lon(tile: TileCoord) -> (f64, f64) {
+    tile_fraction_to_lat_lon(tile.x as f64, tile.y as f64, tile.z)
+}
+
+/// The latitude and longitude at fractional tile coordinates, the inverse of
+/// [`lat_lon_to_tile_fraction`]
+///
+/// # Panics
+///
+/// Panics if `zoom` is 32 or more
+pub fn tile_fraction_to_lat_lon(x: f64, y: f64, zoom: u32) -> (f64, f64) {
+    let n = tiles_per_side(zoom);
+    let lon = x / n * 360.0 - 180.0;
+    let lat = (std::f64::consts::PI * (1.0 - 2.0 * y / n))
+        .sinh()
+        .atan()
+        .to_degrees();
+    (lat, lon)
+}
+
+/// The position of the given WGS84 location in pixels, measured from the north west corner of
+/// the world at `zoom` for tiles of `tile_size` pixels
+///
+/// # Panics
+///
+/// Panics if `zoom` is 32 or more
+pub fn lat_lon_to_pixel(lat: f64, lon: f64, zoom: u32, tile_size: u32) -> (f64, f64) {
+    let (x, y) = lat_lon_to_tile_fraction(lat, lon, zoom);
+    (x * tile_size as f64, y * tile_size as f64)
+}
+
+/// The latitude and longitude of a pixel position, the inverse of [`lat_lon_to_pixel`]
+///
+/// # Panics
+///
+/// Panics if `zoom` is 32 or more
+pub fn pixel_to_lat_lon(x: f64, y: f64, zoom: u32, tile_size: u32) -> (f64, f64) {
+    let size = tile_size as f64;
+    tile_fraction_to_lat_lon(x / size, y / size, zoom)
+}
+
+/// The pixel within `tile` covering the given WGS84 location, for tiles of `tile_size`
+/// pixels, or `None` if the location is outside of the tile
+pub fn pixel_in_tile(tile: TileCoord, lat: f64, lon: f64, tile_size: u32) -> Option<(u32, u32)> {
+    let (x, y) = lat_lon_to_tile_fraction(lat, lon, tile.z);
+    let (x, y) = (x - tile.x as f64, y - tile.y as f64);
+    if !(0.0..1.0).contains(&x) || !(0.0..1.0).contains(&y) {
+        return None;
+    }
+    let size = tile_size as f64;
+    Some(((x * size) as u32, (y * size) as u32))
+}
+
+/// The equatorial radius of the WGS84 ellipsoid, which web mercator treats as a sphere
+const EARTH_RADIUS_METERS: f64 = 6_378_137.0;
+
+/// The ground distance in meters covered by one pixel at latitude `lat`, for tiles of
+/// `tile_size` pixels at `zoom`
+///
+/// Web mercator stretches the map away from the equator, so pixels cover less ground the
+/// further north or south they are. Latitudes are clamped to the web mercator limits
+///
+/// ```
+/// use rain_viewer::geo;
+///
+/// // A 256 pixel tile at zoom 0 spans the equator at roughly 156 km per pixel
+/// let equator = geo::ground_resolution(0.0, 0, 256);
+/// assert!((equator - 156_543.03).abs() < 0.01);
+/// assert!(geo::ground_resolution(60.0, 0, 256) < equator);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `zoom` is 32 or more
+pub fn ground_resolution(lat: f64, zoom: u32, tile_size: u32) -> f64 {
+    let lat = lat.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians();
+    let circumference = 2.0 * std::f64::consts::PI * EARTH_RADIUS_METERS;
+    lat.cos() * circumference / (tile_size as f64 * tiles_per_side(zoom))
+}
+
+/// The lowest zoom at which pixels at latitude `lat` cover at most `meters_per_pixel`, for
+/// tiles of `tile_size` pixels
+///
+/// The result is capped at 31, and may exceed the zoom served for a product, such as
+/// [`MAX_RADAR_ZOOM`](crate::MAX_RADAR_ZOOM), which callers should clamp to. A resolution
+/// that is not positive returns the cap
+pub fn zoom_for_resolution(lat: f64, meters_per_pixel: f64, tile_size: u32) -> u32 {
+    if meters_per_pixel <= 0.0 || meters_per_pixel.is_nan() {
+        return 31;
+    }
+    let zoom = (ground_resolution(lat, 0, tile_size) / meters_per_pixel)
+        .log2()
+        .ceil();
+    zoom.clamp(0.0, 31.0) as u32
+}
+
+/// The denominator of the map scale at latitude `lat` when tiles of `tile_size` pixels at
+/// `zoom` are shown on a display with `dpi` pixels per inch, such as `4000` for 1:4000
+///
+/// # Panics
+///
+/// Panics if `zoom` is 32 or more
+pub fn map_scale(lat: f64, zoom: u32, tile_size: u32, dpi: f64) -> f64 {
+    const METERS_PER_INCH: f64 = 0.0254;
+    ground_resolution(lat, zoom, tile_size) * dpi / METERS_PER_INCH
+}
+
+/// Maps pixels of a tile image, or of an image of adjacent tiles, to web mercator
+/// (EPSG:3857) coordinates in meters
+///
+/// Web mercator tiles are a regular grid in that projection, so pixel `(column, row)` has its
+/// north west corner at `origin_x + column * pixel_width` meters east and
+/// `origin_y - row * pixel_height` meters north
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GeoTransform {
+    /// The easting of the north west corner of the image
+    pub origin_x: f64,
+    /// The northing of the north west corner of the image
+    pub origin_y: f64,
+    /// The width of a pixel in meters
+    pub pixel_width: f64,
+    /// The height of a pixel in meters, with rows increasing southwards
+    pub pixel_height: f64,
+}
+
+impl GeoTransform {
+    /// The transform of an image whose north west tile is `tile`, drawn with `tile_size`
+    /// pixel tiles
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tile.z` is 32 or more
+    pub fn new(tile: TileCoord, tile_size: u32) -> Self {
+        let n = tiles_per_side(tile.z);
+        let world = 2.0 * std::f64::consts::PI * EARTH_RADIUS_METERS;
+        let pixel = world / (n * tile_size as f64);
+        Self {
+            origin_x: tile.x as f64 / n * world - world / 2.0,
+            origin_y: world / 2.0 - tile.y as f64 / n * world,
+            pixel_width: pixel,
+            pixel_height: pixel,
+        }
+    }
+
+    /// The six coefficients of this transform in the order used by GDAL and GeoTIFF
+    /// world files
+    pub fn to_gdal(&self) -> [f64; 6] {
+        [
+            self.origin_x,
+            self.pixel_width,
+            0.0,
+            self.origin_y,
+            0.0,
+            -self.pixel_height,
+        ]
+    }
+
+    /// The latitude and longitude of a fractional pixel position in the image
+    pub fn pixel_to_lat_lon(&self, column: f64, row: f64) -> (f64, f64) {
+        let x = self.origin_x + column * self.pixel_width;
+        let y = self.origin_y - row * self.pixel_height;
+        let lon = (x / EARTH_RADIUS_METERS).to_degrees();
+        let lat = (y / EARTH_RADIUS_METERS).sinh().atan().to_degrees();
+        (lat, lon)
+    }
+
+    /// The fractional pixel position of a WGS84 location in the image, the inverse of
+    /// [`Self::pixel_to_lat_lon`]
+    ///
+    /// Latitudes are clamped to the web mercator limits. Locations west of the image are
+    /// wrapped around the world eastwards, so that they land in images crossing the
+    /// antimeridian, and may lie beyond the image otherwise
+    pub fn lat_lon_to_pixel(&self, lat: f64, lon: f64) -> (f64, f64) {
+        let world = 2.0 * std::f64::consts::PI * EARTH_RADIUS_METERS;
+        let lat = lat.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians();
+        let x =
+            (wrap_lon(lon).to_radians() * EARTH_RADIUS_METERS - self.origin_x).rem_euclid(world);
+        let y = self.origin_y - lat.tan().asinh() * EARTH_RADIUS_METERS;
+        (x / self.pixel_width, y / self.pixel_height)
+    }
+}
+
+/// The fractional tile column containing `lon` at a zoom level with `n` tiles per side
+pub(crate) fn lon_to_x(lon: f64, n: f64) -> f64 {
+    (lon + 180.0) / 360.0 * n
+}
+
+/// The fractional tile row containing `lat` at a zoom level with `n` tiles per side
+pub(crate) fn lat_to_y(lat: f64, n: f64) -> f64 {
+    let lat = lat.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians();
+    (1.0 - lat.tan().asinh() / std::f64::consts::PI) / 2.0 * n
+}
+
+/// The tiles at `zoom` intersecting `bounds`, ordered by row then column
+///
+/// Latitudes are clamped to the web mercator limits, so a box reaching a pole covers the top or
+/// bottom row. Boxes crossing the antimeridian yield the columns east of 180 degrees west after
+/// those west of 180 degrees east, so rows stay contiguous from west to east. Tiles only
+/// touching the east or south edge of `bounds` are not included.
+///
+/// ```
+/// use rain_viewer::{geo, LatLonBounds};
+///
+/// let fiji = LatLonBounds::new(177.0, -19.0, -179.0, -16.0)?;
+/// let tiles: Vec<_> = geo::tiles_in_bbox(&fiji, 3).map(|t| (t.x, t.y)).collect();
+/// assert_eq!(tiles, [(7, 4), (0, 4)]);
+/// # Ok::<(), rain_viewer::ParameterError>(())
+/// ```
+///
+/// # Panics
+///
+/// Panics if `zoom` is 32 or more
+pub fn tiles_in_bbox(bounds: &LatLonBounds, zoom: u32) -> impl Iterator<Item = TileCoord> + Clone {
+    let n = tiles_per_side(zoom);
+    let max = n - 1.0;
+    // Edges computed from tile bounds land next to, rather than on, tile boundaries due to
+    // rounding, so they are snapped to them
+    let snap = |value: f64| {
+        let rounded = value.round();
+        if (value - rounded).abs() < 1e-9 {
+            rounded
+        } else {
+            value
+        }
+    };
+    let first = |value: f64| snap(value).floor().clamp(0.0, max) as u32;
+    // An edge on a tile boundary ends the range at the previous tile
+    let last = |value: f64, first: u32| (snap(value).ceil() - 1.0).clamp(first as f64, max) as u32;
+
+    let west = first(lon_to_x(bounds.west, n));
+    let east_x = lon_to_x(bounds.east, n);
+    // Columns east of the antimeridian, when the box crosses it
+    let (west_end, wrapped_end) = if bounds.crosses_antimeridian() {
+        (max as u32, last(east_x, 0) + 1)
+    } else {
+        (last(east_x, west), 0)
+    };
+    let columns = (west..=west_end).chain(0..wrapped_end);
+    let top = first(lat_to_y(bounds.north, n));
+    let rows = top..=last(lat_to_y(bounds.south, n), top);
+
+    rows.flat_map(move |y| columns.clone().map(move |x| TileCoord { x, y, z: zoom }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coords(tiles: impl Iterator<Item = TileCoord>) -> Vec<(u32, u32)> {
+        tiles.map(|t| (t.x, t.y)).collect()
+    }
+
+    #[test]
+    fn bounds_validation() {
+        assert!(LatLonBounds::new(-10.0, -10.0, 10.0, 10.0).is_ok());
+        assert!(matches!(
+            LatLonBounds::new(-181.0, 0.0, 10.0, 10.0),
+            Err(ParameterError::LongitudeOutOfRange(_, _))
+        ));
+        assert!(matches!(
+            LatLonBounds::new(0.0, 10.0, 10.0, 0.0),
+            Err(ParameterError::LatitudeOutOfRange(_, _))
+        ));
+    }
+
+    #[test]
+    fn conversions() {
+        let tile = |lat, lon, zoom| {
+            let tile = lat_lon_to_tile(lat, lon, zoom);
+            (tile.x, tile.y)
+        };
+        assert_eq!(tile(0.0, 0.0, 0), (0, 0));
+        assert_eq!(tile(51.5074, -0.1278, 10), (511, 340));
+        // The poles are clamped to the first and last rows
+        assert_eq!(tile(90.0, 0.0, 3), (4, 0));
+        assert_eq!(tile(-90.0, 0.0, 3), (4, 7));
+        // Both sides of the antimeridian map to the first column
+        assert_eq!(tile(0.0, 180.0, 3), (0, 4));
+        assert_eq!(tile(0.0, -180.0, 3), (0, 4));
+        assert_eq!(tile(0.0, 190.0, 3), (0, 4));
+
+        let (lat, lon) = tile_to_lat_lon(TileCoord { x: 0, y: 0, z: 4 });
+        assert!((lat - MAX_LATITUDE).abs() < 1e-6);
+        assert_eq!(lon, -180.0);
+
+        let (x, y) = lat_lon_to_pixel(40.7128, -74.006, 12, 512);
+        let (lat, lon) = pixel_to_lat_lon(x, y, 12, 512);
+        assert!((lat - 40.7128).abs() < 1e-9 && (lon + 74.006).abs() < 1e-9);
+
+        let nyc = lat_lon_to_tile(40.7128, -74.006, 5);
+        let (px, py) = pixel_in_tile(nyc, 40.7128, -74.006, 256).unwrap();
+        assert!(px < 256 && py < 256);
+        assert_eq!(pixel_in_tile(nyc, 0.0, 0.0, 256), None);
+    }
+
+    #[test]
+    fn tile_bounds() {
+        let world = TileCoord { x: 0, y: 0, z: 0 }.bounds();
+        assert_eq!((world.west, world.east), (-180.0, 180.0));
+        assert!((world.north - MAX_LATITUDE).abs() < 1e-6);
+        assert!((world.south + MAX_LATITUDE).abs() < 1e-6);
+
+        // The south east quarter of the world at zoom 1
+        let tile = TileCoord { x: 1, y: 1, z: 1 }.bounds();
+        assert_eq!((tile.west, tile.north, tile.east), (0.0, 0.0, 180.0));
+
+        // A tile covers the locations that map to it
+        let nyc = lat_lon_to_tile(40.7128, -74.006, 9).bounds();
+        assert!(nyc.west <= -74.006 && -74.006 < nyc.east);
+        assert!(nyc.south < 40.7128 && 40.7128 <= nyc.north);
+    }
+
+    #[test]
+    fn pyramid() {
+        let tile = TileCoord::new(5, 2, 3);
+        assert_eq!(tile.parent(), Some(TileCoord::new(2, 1, 2)));
+        assert_eq!(TileCoord::new(0, 0, 0).parent(), None);
+        assert_eq!(
+            tile.children(),
+            [
+                TileCoord::new(10, 4, 4),
+                TileCoord::new(11, 4, 4),
+                TileCoord::new(10, 5, 4),
+                TileCoord::new(11, 5, 4),
+            ]
+        );
+
+        let nyc = lat_lon_to_tile(40.7128, -74.006, 7);
+        assert!(nyc.contains(40.7128, -74.006));
+        assert!(nyc.parent().unwrap().contains(40.7128, -74.006));
+        assert!(!nyc.contains(51.5074, -0.1278));
+        // Points on the shared edge belong to the south eastern tile
+        let quarter = TileCoord::new(1, 1, 1);
+        assert!(quarter.contains(0.0, 0.0));
+        assert!(!TileCoord::new(0, 0, 1).contains(0.0, 0.0));
+    }
+
+    #[test]
+    fn validity() {
+        assert!(TileCoord::new(7, 7, 3).is_valid());
+        assert!(matches!(
+            TileCoord::new(8, 0, 3).validate(),
+            Err(ParameterError::XOutOfRange(8, _))
+        ));
+        assert!(matches!(
+            TileCoord::new(0, 8, 3).validate(),
+            Err(ParameterError::YOutOfRange(8, _))
+        ));
+        assert!(matches!(
+            TileCoord::new(0, 0, 32).validate(),
+            Err(ParameterError::InvalidZoom(32, _))
+        ));
+        assert!(TileCoord::new(u32::MAX, 0, 31).validate().is_err());
+    }
+
+    #[test]
+    fn resolution() {
+        let equator = ground_resolution(0.0, 0, 256);
+        // Each zoom level halves the distance covered by a pixel, as do tiles twice as large
+        assert!((ground_resolution(0.0, 3, 256) - equator / 8.0).abs() < 1e-9);
+        assert!((ground_resolution(0.0, 3, 512) - equator / 16.0).abs() < 1e-9);
+        assert!((ground_resolution(60.0, 0, 256) - equator / 2.0).abs() < 1e-6);
+        assert_eq!(
+            ground_resolution(90.0, 0, 256),
+            ground_resolution(MAX_LATITUDE, 0, 256)
+        );
+
+        // Zoom 10 gives about 152.9 m per pixel at the equator
+        assert_eq!(zoom_for_resolution(0.0, 153.0, 256), 10);
+        assert_eq!(zoom_for_resolution(0.0, 152.0, 256), 11);
+        assert_eq!(zoom_for_resolution(0.0, 153.0, 512), 9);
+        assert_eq!(zoom_for_resolution(60.0, 153.0, 256), 9);
+        assert_eq!(zoom_for_resolution(0.0, 1e9, 256), 0);
+        assert_eq!(zoom_for_resolution(0.0, 0.0, 256), 31);
+
+        // A 96 dpi display at zoom 0 shows the equator at roughly 1:591 million
+        let scale = map_scale(0.0, 0, 256, 96.0);
+        assert!((scale - 591_658_710.9).abs() < 1.0);
+    }
+
+    #[test]
+    fn geo_transform() {
+        let world = GeoTransform::new(TileCoord::new(0, 0, 0), 256);
+        let [x, width, _, y, _, height] = world.to_gdal();
+        assert!((x + 20_037_508.34).abs() < 0.01 && (y - 20_037_508.34).abs() < 0.01);
+        assert!((width - 156_543.03).abs() < 0.01 && (height + 156_543.03).abs() < 0.01);
+
+        let tile = TileCoord::new(9, 12, 5);
+        let transform = GeoTransform::new(tile, 512);
+        let (lat, lon) = transform.pixel_to_lat_lon(0.0, 0.0);
+        let (north, west) = tile_to_lat_lon(tile);
+        assert!((lat - north).abs() < 1e-9 && (lon - west).abs() < 1e-9);
+        let (lat, lon) = transform.pixel_to_lat_lon(512.0, 512.0);
+        let bounds = tile.bounds();
+        assert!((lat - bounds.south).abs() < 1e-9 && (lon - bounds.east).abs() < 1e-9);
+        let (column, row) = transform.lat_lon_to_pixel(40.7128, -74.006);
+        let (lat, lon) = transform.pixel_to_lat_lon(column, row);
+        assert!((lat - 40.7128).abs() < 1e-9 && (lon + 74.006).abs() < 1e-9);
+
+        // The column east of the antimeridian follows the last one
+        let transform = GeoTransform::new(TileCoord::new(31, 12, 5), 256);
+        let (column, _) = transform.lat_lon_to_pixel(0.0, -180.0 + 360.0 / 64.0);
+        assert!((column - 384.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn tile_schemes() {
+        let tile = TileCoord { x: 3, y: 1, z: 3 };
+        assert_eq!(TileScheme::Xyz.row(tile), 1);
+        assert_eq!(TileScheme::Tms.row(tile), 6);
+        assert_eq!(TileScheme::Tms.tile(3, 6, 3).unwrap(), tile);
+        assert_eq!(TileScheme::Tms.row(TileCoord { x: 0, y: 0, z: 0 }), 0);
+        assert!(TileScheme::Tms.tile(0, 8, 3).is_err());
+    }
+
+    #[test]
+    fn covering() {
+        let world = LatLonBounds::new(-180.0, -90.0, 180.0, 90.0).unwrap();
+        assert_eq!(
+            coords(tiles_in_bbox(&world, 1)),
+            [(0, 0), (1, 0), (0, 1), (1, 1)]
+        );
+
+        // New York at zoom 5
+        let nyc = LatLonBounds::new(-74.1, 40.6, -73.9, 40.8).unwrap();
+        assert_eq!(coords(tiles_in_bbox(&nyc, 5)), [(9, 12)]);
+
+        // Fiji spans the antimeridian
+        let fiji = LatLonBounds::new(177.0, -19.0, -179.0, -16.0).unwrap();
+        assert_eq!(coords(tiles_in_bbox(&fiji, 3)), [(7, 4), (0, 4)]);
+
+        // Edges on tile boundaries do not pull in the neighbouring tiles
+        let quarter = TileCoord { x: 2, y: 1, z: 2 }.bounds();
+        assert_eq!(coords(tiles_in_bbox(&quarter, 2)), [(2, 1)]);
+
+        // The poles are clamped to the mercator limits
+        let arctic = LatLonBounds::new(-10.0, 89.0, 10.0, 90.0).unwrap();
+        assert_eq!(coords(tiles_in_bbox(&arctic, 2)), [(1, 0), (2, 0)]);
+    }
+}
@@ -0,0 +1,292 @@
+use image::RgbaImage;
+
+use crate::color::ColorKind;
+use crate::error::{self, ParameterError};
+use crate::tile::Tile;
+
+/// The reflectivity of the first bin of a [`Histogram`], the weakest echo tiles encode
+const MIN_DBZ: i32 = -32;
+
+/// The number of 1 dBZ bins of a [`Histogram`], up to the strongest echo tiles encode
+const BINS: usize = 128;
+
+/// Bands of precipitation intensity by reflectivity, such as for "percent of area with heavy
+/// rain" metrics
+///
+/// The bands follow common weather radar practice. With the Marshall-Palmer relationship, the
+/// boundaries of 30, 40 and 50 dBZ are rain rates of about 3, 12 and 49 mm/h
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum IntensityCategory {
+    /// Below 30 dBZ
+    Light,
+    /// From 30 to 40 dBZ
+    Moderate,
+    /// From 40 to 50 dBZ
+    Heavy,
+    /// 50 dBZ and above, usually thunderstorms with hail
+    Extreme,
+}
+
+impl IntensityCategory {
+    /// Every category, from the weakest
+    pub const ALL: [IntensityCategory; 4] = [
+        IntensityCategory::Light,
+        IntensityCategory::Moderate,
+        IntensityCategory::Heavy,
+        IntensityCategory::Extreme,
+    ];
+
+    /// The category of an echo of `dbz`
+    pub fn from_dbz(dbz: f32) -> Self {
+        match dbz {
+            dbz if dbz >= 50.0 => IntensityCategory::Extreme,
+            dbz if dbz >= 40.0 => IntensityCategory::Heavy,
+            dbz if dbz >= 30.0 => IntensityCategory::Moderate,
+            _ => IntensityCategory::Light,
+        }
+    }
+
+    /// The weakest reflectivity in dBZ of this category, or `None` for
+    /// [`IntensityCategory::Light`], which has no lower bound
+    pub fn min_dbz(self) -> Option<f32> {
+        match self {
+            IntensityCategory::Light => None,
+            IntensityCategory::Moderate => Some(30.0),
+            IntensityCategory::Heavy => Some(40.0),
+            IntensityCategory::Extreme => Some(50.0),
+        }
+    }
+}
+
+/// The number of pixels of a tile or region showing each reflectivity, in bins of 1 dBZ
+///
+/// Pixels are counted equally, so over large regions the pixels nearer the poles, which cover
+/// less ground in web mercator, weigh more than their area. Crop a mosaic to the area of
+/// interest with [`crop_to_bounds`](crate::crop_to_bounds) before counting it.
+///
+/// ```no_run
+/// # async fn run(req: &rain_viewer::WeatherRequester) -> Result<(), rain_viewer::Error> {
+/// use rain_viewer::{ColorKind, IntensityCategory, RequestArguments, Tile, TileCoord};
+///
+/// let maps = req.available().await?;
+/// let frame = maps.latest_past().unwrap();
+/// let mut args = RequestArguments::new_tile(TileCoord::new(4, 7, 6))?;
+/// args.set_color(ColorKind::BlackAndWhite);
+///
+/// let tile = Tile::new(req.get_tile(&maps, frame, args).await?, ColorKind::BlackAndWhite);
+/// let histogram = tile.histogram()?;
+/// let heavy = histogram.fraction_at_least(IntensityCategory::Heavy.min_dbz().unwrap());
+/// println!("{:.1}% of the tile has heavy rain", heavy * 100.0);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Enabled with the `image` feature
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    counts: [u64; BINS],
+    dry: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: [0; BINS],
+            dry: 0,
+        }
+    }
+}
+
+impl Histogram {
+    /// Counts the pixels of a decoded tile or mosaic rendered with `color`, such as an image
+    /// assembled by [`stitch`](crate::stitch)
+    ///
+    /// Pixels are decoded with [`ColorKind::dbz_for_pixel`]. Transparent pixels, and colors
+    /// that do not match the palette of `color`, count as dry. Returns Err(...) if no palette
+    /// is bundled for `color`
+    pub fn from_image(image: &RgbaImage, color: ColorKind) -> Result<Self, error::Error> {
+        if color.palette().is_none() {
+            return Err(ParameterError::InvalidColor(
+                color.into(),
+                "No palette is bundled for this color scheme".to_owned(),
+            )
+            .into());
+        }
+        let mut histogram = Self::default();
+        for pixel in image.pixels() {
+            match color.dbz_for_pixel(pixel.0) {
+                Some(dbz) => histogram.counts[bin(dbz)] += 1,
+                None => histogram.dry += 1,
+            }
+        }
+        Ok(histogram)
+    }
+
+    /// Adds the counts of `other`, such as to total the tiles of a region without stitching
+    /// them
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts) {
+            *count += other;
+        }
+        self.dry += other.dry;
+    }
+
+    /// The number of pixels counted
+    pub fn total(&self) -> u64 {
+        self.dry + self.precipitating()
+    }
+
+    /// The number of pixels without precipitation
+    pub fn dry(&self) -> u64 {
+        self.dry
+    }
+
+    /// The number of pixels with precipitation
+    pub fn precipitating(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The number of pixels of at least `dbz` and less than `dbz + 1`
+    pub fn count(&self, dbz: i32) -> u64 {
+        let index = dbz - MIN_DBZ;
+        if (0..BINS as i32).contains(&index) {
+            self.counts[index as usize]
+        } else {
+            0
+        }
+    }
+
+    /// The pixels with precipitation in bins `width` dBZ wide, as pairs of the lower bound of
+    /// each bin and its count, from the weakest reflectivity
+    ///
+    /// A `width` of zero is treated as one
+    pub fn bins(&self, width: u32) -> Vec<(f32, u64)> {
+        let width = width.max(1) as usize;
+        self.counts
+            .chunks(width)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let dbz = MIN_DBZ + (i * width) as i32;
+                (dbz as f32, chunk.iter().sum())
+            })
+            .collect()
+    }
+
+    /// The fraction of pixels, dry ones included, showing at least `dbz`. Returns `0.0` if no
+    /// pixels were counted
+    pub fn fraction_at_least(&self, dbz: f32) -> f64 {
+        let first = ((dbz.ceil() as i32 - MIN_DBZ).max(0) as usize).min(BINS);
+        self.fraction_of(self.counts[first..].iter().sum())
+    }
+
+    /// The number of pixels with precipitation in each category, from the weakest
+    pub fn categories(&self) -> [(IntensityCategory, u64); 4] {
+        let mut categories = IntensityCategory::ALL.map(|category| (category, 0));
+        for (i, count) in self.counts.iter().enumerate() {
+            let category = IntensityCategory::from_dbz((MIN_DBZ + i as i32) as f32);
+            categories[category as usize].1 += count;
+        }
+        categories
+    }
+
+    /// The fraction of pixels, dry ones included, in `category`. Returns `0.0` if no pixels
+    /// were counted
+    pub fn fraction(&self, category: IntensityCategory) -> f64 {
+        self.fraction_of(self.categories()[category as usize].1)
+    }
+
+    fn fraction_of(&self, count: u64) -> f64 {
+        match self.total() {
+            0 => 0.0,
+            total => count as f64 / total as f64,
+        }
+    }
+}
+
+impl Tile {
+    /// Counts the pixels of this tile by reflectivity, see [`Histogram::from_image`]
+    ///
+    /// Enabled with the `image` feature. Returns Err(...) if no palette is bundled for the
+    /// color scheme of the tile, or it is not a valid PNG image
+    pub fn histogram(&self) -> Result<Histogram, error::Error> {
+        Histogram::from_image(&self.to_rgba8()?, self.color())
+    }
+}
+
+/// The index of the bin holding `dbz`, clamping values beyond the range tiles encode
+fn bin(dbz: f32) -> usize {
+    (dbz.floor() as i32 - MIN_DBZ).clamp(0, BINS as i32 - 1) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    /// A black and white image holding each of `values` once, with 0 as transparent
+    fn image(values: &[u8]) -> RgbaImage {
+        RgbaImage::from_fn(values.len() as u32, 1, |x, _| {
+            let value = values[x as usize];
+            Rgba([value, value, value, if value == 0 { 0 } else { 255 }])
+        })
+    }
+
+    #[test]
+    fn counts() {
+        // Dry, 0, 20, 35, 45 and 60 dBZ of rain, and 20 dBZ of snow
+        let values = [0, 32, 52, 67, 77, 92, 180];
+        let histogram = Histogram::from_image(&image(&values), ColorKind::BlackAndWhite).unwrap();
+        assert_eq!(histogram.total(), 7);
+        assert_eq!(histogram.dry(), 1);
+        assert_eq!(histogram.precipitating(), 6);
+        assert_eq!(histogram.count(20), 2);
+        assert_eq!(histogram.count(21), 0);
+        assert_eq!(histogram.count(500), 0);
+
+        let bins = histogram.bins(10);
+        assert_eq!(bins.len(), 13);
+        assert_eq!(bins[0], (-32.0, 0));
+        // 20 dBZ falls in the bin from 18 dBZ
+        assert_eq!(bins[5], (18.0, 2));
+        assert_eq!(bins.iter().map(|(_, count)| count).sum::<u64>(), 6);
+
+        assert_eq!(histogram.fraction_at_least(40.0), 2.0 / 7.0);
+        assert_eq!(histogram.fraction_at_least(-100.0), 6.0 / 7.0);
+        assert_eq!(histogram.fraction_at_least(100.0), 0.0);
+        assert_eq!(Histogram::default().fraction_at_least(0.0), 0.0);
+
+        assert!(Histogram::from_image(&image(&values), ColorKind::Titan).is_err());
+    }
+
+    #[test]
+    fn categories() {
+        assert_eq!(IntensityCategory::from_dbz(-5.0), IntensityCategory::Light);
+        assert_eq!(IntensityCategory::from_dbz(40.0), IntensityCategory::Heavy);
+        for category in IntensityCategory::ALL {
+            if let Some(min) = category.min_dbz() {
+                assert_eq!(IntensityCategory::from_dbz(min), category);
+            }
+        }
+
+        let mut histogram =
+            Histogram::from_image(&image(&[0, 32, 52, 67, 77]), ColorKind::BlackAndWhite).unwrap();
+        histogram.merge(&Histogram::from_image(&image(&[92]), ColorKind::BlackAndWhite).unwrap());
+        assert_eq!(
+            histogram.categories(),
+            [
+                (IntensityCategory::Light, 2),
+                (IntensityCategory::Moderate, 1),
+                (IntensityCategory::Heavy, 1),
+                (IntensityCategory::Extreme, 1),
+            ]
+        );
+        assert_eq!(histogram.fraction(IntensityCategory::Light), 2.0 / 6.0);
+
+        // Colorized tiles are matched against their palette
+        let nexrad = RgbaImage::from_pixel(2, 2, Rgba([0xfd, 0, 0, 255]));
+        let histogram = Histogram::from_image(&nexrad, ColorKind::NexradLevelIII).unwrap();
+        assert_eq!(histogram.count(50), 4);
+        assert_eq!(histogram.fraction(IntensityCategory::Extreme), 1.0);
+    }
+}
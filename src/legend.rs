@@ -0,0 +1,216 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use crate::color::ColorKind;
+use crate::error::{self, ParameterError};
+use crate::font::{draw_text, text_width, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::palette::Palette;
+use crate::precip::ZrRelationship;
+
+/// What the labels under a legend's color bar show
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum LegendLabels {
+    /// Only the color bar is drawn
+    None,
+    /// Reflectivity in dBZ
+    #[default]
+    Dbz,
+    /// Rain rate in mm/h, converted from reflectivity with the given relationship
+    RainRate(ZrRelationship),
+}
+
+/// The color of label text and tick marks
+const TEXT: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// Height of the tick marks and the gap between them and the labels
+const TICK_HEIGHT: u32 = 3;
+
+/// The tick spacings tried, in dBZ, from the densest
+const TICK_STEPS: [f32; 5] = [5.0, 10.0, 20.0, 25.0, 50.0];
+
+impl Palette {
+    /// Draws a horizontal color bar of this palette's rain gradient as a PNG image, with the
+    /// weakest reflectivity on the left
+    ///
+    /// The bar spans the stops of the palette. Unless `labels` is [`LegendLabels::None`], the
+    /// bottom of the image holds tick marks labeled in black, spaced so labels do not overlap.
+    /// Labels are left out if the image is too short to fit them under a bar.
+    ///
+    /// Enabled with the `image` feature. Returns Err(...) if `width` or `height` is zero
+    pub fn render_legend(
+        &self,
+        width: u32,
+        height: u32,
+        labels: LegendLabels,
+    ) -> Result<Vec<u8>, error::Error> {
+        if width == 0 || height == 0 {
+            return Err(ParameterError::InvalidSize(
+                width.min(height),
+                "A legend must be at least one pixel wide and high".to_owned(),
+            )
+            .into());
+        }
+        let stops = self.stops();
+        let min = stops[0].dbz;
+        let max = stops[stops.len() - 1].dbz.max(min + 1.0);
+        let dbz_at = |x: u32| min + (max - min) * x as f32 / (width - 1).max(1) as f32;
+
+        let label_height = GLYPH_HEIGHT + TICK_HEIGHT;
+        let labeled = labels != LegendLabels::None && height > label_height;
+        let bar_height = if labeled {
+            height - label_height
+        } else {
+            height
+        };
+
+        let mut legend = RgbaImage::new(width, height);
+        for x in 0..width {
+            let color = Rgba(self.color_for_dbz(dbz_at(x)));
+            for y in 0..bar_height {
+                legend.put_pixel(x, y, color);
+            }
+        }
+
+        if labeled {
+            let label = |dbz: f32| match labels {
+                LegendLabels::RainRate(relationship) => format_rate(relationship.rate(dbz)),
+                _ => format!("{}", dbz),
+            };
+            let to_x = |dbz: f32| ((dbz - min) / (max - min) * (width - 1) as f32).round() as u32;
+            let ticks = |step: f32| {
+                let first = (min / step).ceil() as i32;
+                let last = (max / step).floor() as i32;
+                (first..=last).map(move |i| i as f32 * step)
+            };
+            // The densest spacing whose labels fit between their ticks
+            let step = TICK_STEPS
+                .into_iter()
+                .find(|&step| {
+                    let widest = ticks(step).map(|dbz| text_width(&label(dbz), 1)).max();
+                    let spacing = step / (max - min) * (width - 1) as f32;
+                    widest.is_some_and(|widest| spacing >= (widest + GLYPH_WIDTH) as f32)
+                })
+                .unwrap_or(TICK_STEPS[TICK_STEPS.len() - 1]);
+
+            for dbz in ticks(step) {
+                let x = to_x(dbz);
+                for y in bar_height..bar_height + TICK_HEIGHT - 1 {
+                    legend.put_pixel(x, y, TEXT);
+                }
+                let text = label(dbz);
+                let text_x = x
+                    .saturating_sub(text_width(&text, 1) / 2)
+                    .min(width.saturating_sub(text_width(&text, 1)));
+                draw_text(
+                    &mut legend,
+                    &text,
+                    text_x,
+                    bar_height + TICK_HEIGHT,
+                    1,
+                    TEXT,
+                );
+            }
+        }
+
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(legend)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+        Ok(png)
+    }
+}
+
+impl ColorKind {
+    /// Draws a legend of this scheme's bundled [`ColorKind::palette`] as a PNG image, see
+    /// [`Palette::render_legend`]
+    ///
+    /// ```
+    /// use rain_viewer::{ColorKind, LegendLabels};
+    ///
+    /// let png = ColorKind::NexradLevelIII.render_legend(300, 24, LegendLabels::Dbz)?;
+    /// assert_eq!(&png[1..4], b"PNG");
+    /// # Ok::<(), rain_viewer::Error>(())
+    /// ```
+    ///
+    /// Enabled with the `image` feature. Returns Err(...) if this scheme has no bundled
+    /// palette, or if `width` or `height` is zero
+    pub fn render_legend(
+        self,
+        width: u32,
+        height: u32,
+        labels: LegendLabels,
+    ) -> Result<Vec<u8>, error::Error> {
+        let palette = self.palette().ok_or_else(|| {
+            ParameterError::InvalidColor(
+                self.into(),
+                "No palette is bundled for this color scheme".to_owned(),
+            )
+        })?;
+        Palette::from(palette).render_legend(width, height, labels)
+    }
+}
+
+/// Formats a rain rate with a precision suited to its size
+fn format_rate(rate: f32) -> String {
+    if rate < 10.0 {
+        format!("{:.1}", rate)
+    } else {
+        format!("{:.0}", rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(png: &[u8]) -> RgbaImage {
+        image::load_from_memory(png).unwrap().into_rgba8()
+    }
+
+    #[test]
+    fn legend_bar() {
+        let palette =
+            Palette::from_stops([(0.0, [0, 0, 255, 255]), (60.0, [255, 0, 0, 255])]).unwrap();
+        let legend = decode(&palette.render_legend(61, 5, LegendLabels::None).unwrap());
+        assert_eq!(legend.dimensions(), (61, 5));
+        assert_eq!(legend.get_pixel(0, 4).0, [0, 0, 255, 255]);
+        assert_eq!(legend.get_pixel(30, 0).0, [128, 0, 128, 255]);
+        assert_eq!(legend.get_pixel(60, 2).0, [255, 0, 0, 255]);
+
+        assert!(palette.render_legend(0, 5, LegendLabels::Dbz).is_err());
+    }
+
+    #[test]
+    fn legend_labels() {
+        let png = ColorKind::NexradLevelIII
+            .render_legend(200, 30, LegendLabels::Dbz)
+            .unwrap();
+        let legend = decode(&png);
+        let bar_height = 30 - GLYPH_HEIGHT - TICK_HEIGHT;
+        assert_eq!(legend.get_pixel(0, 0).0, [0x04, 0xe9, 0xe7, 255]);
+        // Ticks every 10 dBZ, starting at 10 dBZ, with text under them
+        assert_eq!(legend.get_pixel(14, bar_height).0, TEXT.0);
+        assert_ne!(legend.get_pixel(0, bar_height).0, TEXT.0);
+        let text = (bar_height + TICK_HEIGHT..30)
+            .flat_map(|y| (0..200).map(move |x| (x, y)))
+            .filter(|&(x, y)| legend.get_pixel(x, y).0 == TEXT.0)
+            .count();
+        assert!(text > 0);
+
+        // Too short for labels, so only the bar is drawn
+        let legend = decode(
+            &ColorKind::NexradLevelIII
+                .render_legend(200, 8, LegendLabels::RainRate(ZrRelationship::default()))
+                .unwrap(),
+        );
+        assert!(legend.pixels().all(|pixel| pixel.0[3] == 255));
+
+        assert!(ColorKind::Titan
+            .render_legend(200, 30, LegendLabels::Dbz)
+            .is_err());
+    }
+
+    #[test]
+    fn rate_labels() {
+        assert_eq!(format_rate(0.154), "0.2");
+        assert_eq!(format_rate(11.53), "12");
+    }
+}
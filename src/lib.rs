@@ -18,7 +18,8 @@
 //!
 //!     // Setup the arguments for the tile we want to access
 //!     // Parameters are x, y and zoom following the satellite imagery style
-//!     let mut args = rain_viewer::RequestArguments::new_tile(4, 7, 6).unwrap();
+//!     let tile = rain_viewer::TileCoord::new(4, 7, 6);
+//!     let mut args = rain_viewer::RequestArguments::new_tile(tile).unwrap();
 //!     // Use this pretty color scheme
 //!     args.set_color(rain_viewer::ColorKind::Titan);
 //!     // Enable showing snow in addition to rain
@@ -39,319 +40,202 @@
 //! [`available`] is the entry point to obtaining radar imagery. This returns
 //! historical data and forecast data that is available.
 //!
-//! From there, most users call [`get_tile`] to download a PNG of a specific satellite tile.
-
+//! From there, most users call [`get_tile`] to download a PNG of a specific satellite tile,
+//! or [`get_satellite_tile`] to download infrared satellite imagery.
+//!
+//! # WebAssembly
+//!
+//! The crate builds for `wasm32-unknown-unknown`, where requests are made with the browser's
+//! `fetch` API. Connection options such as timeouts and proxies are managed by the browser, so
+//! they are not available on [`WeatherRequesterBuilder`] there. Client-side rate limiting is not
+//! available either.
+//!
+//! # Features
+//!
+//! The TLS stack used for HTTPS is selected with one of these features:
+//!
+//! - `native-tls` (default): the platform's TLS library, such as OpenSSL on Linux
+//! - `rustls`: a pure Rust TLS stack, which avoids linking OpenSSL when cross compiling to musl
+//!
+//! Disable default features when enabling `rustls`. At least one of these features must be
+//! enabled outside of wasm32, or requests to Rain Viewer will fail.
+//!
+//! The date time library used for [`Timestamp`], such as [`Frame::time`], is selected with one
+//! of these features:
+//!
+//! - `chrono` (default): `chrono::DateTime<Utc>`
+//! - `time`: `time::OffsetDateTime` in UTC
+//! - `jiff`: `jiff::Timestamp`
+//!
+//! Disable default features when enabling `time` or `jiff`. If several are enabled, `chrono`
+//! takes precedence over `time`, and `time` over `jiff`. The `time` and `jiff` features also
+//! add `Frame::offset_date_time` and `Frame::jiff_timestamp` for converting from the selected
+//! type.
+//!
+//! Other optional features:
+//!
+//! - `blocking`: a synchronous `blocking::WeatherRequester` that does not need an async runtime.
+//!   With `image`, this also builds the `rain_viewer_csv` binary, which prints the
+//!   precipitation at locations given as `LAT,LON` arguments as CSV
+//! - `middleware`: `WeatherRequester::with_middleware_client` for issuing requests through a
+//!   `reqwest_middleware::ClientWithMiddleware`
+//! - `tower`: `service::TileService`, a `tower::Service` downloading tiles
+//! - `server`: `server::router`, an `axum::Router` proxying radar tiles through a shared
+//!   `WeatherRequester` and its cache
+//! - `cancellation`: `WeatherRequester::with_cancellation` for abandoning requests with a
+//!   `tokio_util::sync::CancellationToken`
+//! - `moka`: `TileCache` support for `moka::future::Cache`, for use as a tile cache
+//! - `mbtiles`: `export::mbtiles` for writing a frame's tiles into an MBTiles file. This builds
+//!   a bundled copy of SQLite
+//! - `image`: decoding and analyzing tiles with the `image` crate:
+//!   - `Tile` and `WeatherRequester::get_tile_image` for decoding tiles into an
+//!     `image::DynamicImage`
+//!   - `stitch`, `crop_to_bounds`, `compose_over`, `set_opacity` and `resize` for assembling
+//!     and editing decoded tiles
+//!   - `WeatherRequester::get_resampled_tile`, `upsample_from_ancestor` and
+//!     `downsample_children` for rendering tiles from other zoom levels
+//!   - `WeatherRequester::get_blended_tile` and `WeatherRequester::thumbnail` for drawing radar
+//!     over satellite imagery and previewing frames
+//!   - `WeatherRequester::is_raining_at`, `nowcast_at`, `rain_arrival`, `precip_phase_at` and
+//!     `has_coverage` for reading the precipitation at a point
+//!   - `WeatherRequester::point_series`, `region_stats` and `region_trend`, `write_series_csv`,
+//!     `Histogram` and `accumulate` for statistics over time and area
+//!   - `storm`, `ContourExtractor` and `MotionField` for tracking cells, tracing polygons and
+//!     estimating motion
+//!   - `export::geotiff` and `export::kmz` for writing regions to files
+//!   - `animation`, `Overlay` and `ColorKind::render_legend` for rendering frame sequences,
+//!     captions and legends
+//! - `rayon`: decodes and stitches tiles in parallel. This enables `image`
+//! - `gif`: `animation::gif` for encoding frames into looping GIFs. This enables `image`
+//! - `apng`: `animation::apng` for encoding frames into full color animated PNGs. This enables
+//!   `image`
+//! - `ffmpeg`: `animation::video` for encoding frames into MP4 or WebM videos with an installed
+//!   `ffmpeg` executable. This enables `image`
+//! - `ndarray`: `DbzArray` and `Tile::to_dbz_array` for reading black and white tiles and
+//!   regions as `ndarray::Array2<f32>` grids of reflectivity. This enables `image`
+//! - `arrow`: `series_to_record_batch` for writing the precipitation at many locations over
+//!   time as an Arrow `RecordBatch`, which Polars and other data frame libraries import. This
+//!   enables `image`
+//! - `egui`: `RadarTexture` for downloading tiles in the background and uploading them as
+//!   `egui::TextureHandle`s, and `color_image` for converting decoded imagery into an
+//!   `egui::ColorImage`. This enables `image`
+//! - `http3`: `WeatherRequesterBuilder::http3_prior_knowledge` for issuing requests over QUIC.
+//!   This enables `rustls`, and reqwest's HTTP/3 support is unstable, so it also requires
+//!   building with `RUSTFLAGS="--cfg reqwest_unstable"`
+
+#[cfg(feature = "image")]
+mod accumulate;
+#[cfg(feature = "image")]
+pub mod animation;
+mod args;
+#[cfg(feature = "ndarray")]
+mod array;
+#[cfg(feature = "image")]
+mod arrival;
+mod batch;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod builder;
+mod cache;
+mod coalesce;
+mod color;
+#[cfg(feature = "image")]
+mod compose;
+mod conditional;
+#[cfg(feature = "image")]
+mod contour;
+mod data;
+mod diff;
+#[cfg(not(target_arch = "wasm32"))]
+mod disk_cache;
 mod error;
-
+pub mod export;
+#[cfg(feature = "image")]
+mod font;
+pub mod geo;
+#[cfg(feature = "image")]
+mod histogram;
+#[cfg(feature = "image")]
+mod legend;
+mod maplibre;
+#[cfg(feature = "image")]
+mod mosaic;
+#[cfg(feature = "image")]
+mod motion;
+#[cfg(feature = "image")]
+mod overlay;
+mod palette;
+mod player;
+pub mod precip;
+mod prefetch;
+#[cfg(not(target_arch = "wasm32"))]
+mod rate_limit;
+mod requester;
+#[cfg(feature = "image")]
+mod resample;
+#[cfg(feature = "image")]
+mod series;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "tower")]
+pub mod service;
+#[cfg(feature = "image")]
+pub mod storm;
+#[cfg(feature = "egui")]
+mod texture;
+#[cfg(feature = "image")]
+mod tile;
+mod tilejson;
+mod timestamp;
+mod transport;
+
+#[cfg(feature = "image")]
+pub use accumulate::*;
+pub use args::*;
+#[cfg(feature = "ndarray")]
+pub use array::*;
+#[cfg(feature = "image")]
+pub use arrival::*;
+pub use batch::*;
+pub use builder::TileRequestBuilder;
+pub use cache::*;
+pub use color::*;
+#[cfg(feature = "image")]
+pub use compose::*;
+pub use conditional::*;
+#[cfg(feature = "image")]
+pub use contour::*;
+pub use data::*;
+pub use diff::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use disk_cache::*;
 pub use error::*;
-
-use serde::Deserialize;
-
-/// The kinds of colors supported by rainviewer
-/// All have different visual attributes. See <https://www.rainviewer.com/api/color-schemes.html>
-/// for more information
-#[derive(Copy, Clone, Debug)]
-pub enum ColorKind {
-    BlackAndWhite,
-    Original,
-    UniversalBlue,
-    Titan,
-    TheWeatherChannel,
-    Meteored,
-    NexradLevelIII,
-    RainbowSelexIS,
-    DarkSky,
-}
-
-impl From<ColorKind> for u32 {
-    fn from(color: ColorKind) -> Self {
-        // Values obtained from: https://www.rainviewer.com/api/color-schemes.html
-        match color {
-            ColorKind::BlackAndWhite => 0,
-            ColorKind::Original => 1,
-            ColorKind::UniversalBlue => 2,
-            ColorKind::Titan => 3,
-            ColorKind::TheWeatherChannel => 4,
-            ColorKind::Meteored => 5,
-            ColorKind::NexradLevelIII => 6,
-            ColorKind::RainbowSelexIS => 7,
-            ColorKind::DarkSky => 8,
-        }
-    }
-}
-
-#[derive(Copy, Clone, Debug)]
-struct TileArguments {
-    size: u32,
-    x: u32,
-    y: u32,
-    zoom: u32,
-    color: ColorKind,
-    smooth: bool,
-    snow: bool,
-}
-
-#[derive(Copy, Clone, Debug)]
-enum RequestArgumentsInner {
-    Tile(TileArguments),
-}
-
-/// Arguments needed to pull a rain tile from rainviewer
-#[derive(Copy, Clone)]
-pub struct RequestArguments {
-    inner: RequestArgumentsInner,
-}
-
-impl RequestArguments {
-    /// Creates arguments struct suitable for making a radar image request for a single tile
-    ///
-    /// `x` and `x` must be less than `2^zoom`, or Err(...) is returned
-    pub fn new_tile(x: u32, y: u32, zoom: u32) -> Result<Self, error::ParameterError> {
-        let max_coord = 2u32.pow(zoom);
-        if x >= max_coord {
-            Err(ParameterError::XOutOfRange(
-                x,
-                format!(
-                    "With a zoom of {}, the max value for x is {}",
-                    zoom,
-                    max_coord - 1
-                ),
-            ))
-        } else if y >= max_coord {
-            Err(ParameterError::YOutOfRange(
-                y,
-                format!(
-                    "With a zoom of {}, the max value for y is {}",
-                    zoom,
-                    max_coord - 1
-                ),
-            ))
-        } else {
-            Ok(Self {
-                inner: RequestArgumentsInner::Tile(TileArguments {
-                    size: 256,
-                    x,
-                    y,
-                    zoom,
-                    color: ColorKind::UniversalBlue,
-                    smooth: true,
-                    snow: true,
-                }),
-            })
-        }
-    }
-
-    /// Sets the size of the resulting image when the API call is made.
-    ///
-    /// `size` must be 256 or 512 else Err(...) is returned
-    pub fn set_size(&mut self, size: u32) -> Result<&mut Self, error::ParameterError> {
-        if size == 256 || size == 512 {
-            match &mut self.inner {
-                RequestArgumentsInner::Tile(tile) => {
-                    tile.size = size;
-                }
-            };
-            Ok(self)
-        } else {
-            Err(ParameterError::InvalidSize(
-                size,
-                "Image size must be either 256 or 512".to_owned(),
-            ))
-        }
-    }
-
-    /// Sets the size of the resulting tile image when the API call is made
-    pub fn set_smooth(&mut self, smooth: bool) -> &mut Self {
-        match &mut self.inner {
-            RequestArgumentsInner::Tile(tile) => {
-                tile.smooth = smooth;
-            }
-        };
-        self
-    }
-
-    /// Sets weather or not the resulting tile should show snow
-    pub fn set_snow(&mut self, snow: bool) -> &mut Self {
-        match &mut self.inner {
-            RequestArgumentsInner::Tile(tile) => {
-                tile.snow = snow;
-            }
-        };
-        self
-    }
-
-    /// Sets the color scheme for the tile
-    pub fn set_color(&mut self, color: ColorKind) -> &mut Self {
-        match &mut self.inner {
-            RequestArgumentsInner::Tile(tile) => {
-                tile.color = color;
-            }
-        };
-        self
-    }
-}
-
-pub struct WeatherRequester {
-    client: reqwest::Client,
-}
-
-impl WeatherRequester {
-    pub fn new() -> Self {
-        Self {
-            client: reqwest::Client::new(),
-        }
-    }
-    /// Queries the Rain Viewer API for what current and historical data is available.
-    /// This function should serve as the entry point so that the caller has the correct path and time
-    /// information to call [`get_tile`]
-    pub async fn available(&self) -> Result<AvailableData, error::Error> {
-        let res = self
-            .client
-            .get("https://api.rainviewer.com/public/weather-maps.json")
-            .send()
-            .await?;
-        let raw: RawAvailableData = serde_json::from_str(res.text().await?.as_str())?;
-
-        Ok(AvailableData {
-            host: raw.host,
-            past_radar: raw.radar.past.into_iter().map(|r| r.into()).collect(),
-            nowcast_radar: raw.radar.nowcast.into_iter().map(|r| r.into()).collect(),
-            infrared_satellite: raw
-                .satellite
-                .infrared
-                .into_iter()
-                .map(|r| r.into())
-                .collect(),
-        })
-    }
-
-    /// Hits the Rain Viewer API to obtain a single tile of rain for the world
-    ///
-    /// `maps` is the struct returned from [`available`]
-    ///
-    /// `frame` is the data frame indicating the moment in time to pull from
-    ///
-    /// See <https://www.rainviewer.com/api/weather-maps-api.html> for more details
-    pub async fn get_tile(
-        &self,
-        maps: &AvailableData,
-        frame: &Frame,
-        args: RequestArguments,
-    ) -> Result<Vec<u8>, error::Error> {
-        match args.inner {
-            RequestArgumentsInner::Tile(args) => {
-                let options = format!("{}_{}", args.smooth as u8, args.snow as u8);
-                let color_val: u32 = args.color.into();
-                let url = format!(
-                    "{}/{}/{}/{}/{}/{}/{}/{}.png",
-                    maps.host, frame.path, args.size, args.zoom, args.x, args.y, color_val, options,
-                );
-                let res = self.client.get(url).send().await?;
-                match res.status() {
-                    reqwest::StatusCode::OK => Ok(res.bytes().await?.to_vec()),
-                    status => Err(Error::Http(status)),
-                }
-            }
-        }
-    }
-}
-
-/// Indicates that radar or satellite data is available for the time given at path [`path`]
-#[derive(Debug, Clone)]
-pub struct Frame {
-    /// The timestamp when this data was generated
-    pub time: chrono::NaiveDateTime,
-
-    /// The path where this data can be accessed
-    pub path: String,
-}
-
-/// Contains the kinds of imagery that are available
-#[derive(Debug, Clone)]
-pub struct AvailableData {
-    host: String,
-    pub past_radar: Vec<Frame>,
-    pub nowcast_radar: Vec<Frame>,
-    pub infrared_satellite: Vec<Frame>,
-}
-
-/// Base API information returned by [`available`]
-///
-/// `radar` and `satellite` contain frame objects that can be used in conjunction with [`get_tile`]
-/// to obtain a tile of imagery.
-#[derive(Deserialize)]
-#[allow(dead_code)]
-struct RawAvailableData {
-    /// The version of Rain Viewer
-    pub version: String,
-    /// The unix timestamp when this response was generated
-    pub generated: u64,
-
-    /// The tile host. Pass this value to [`get_tile`] so that it contacts the correct mirror
-    pub host: String,
-
-    /// What radar information is available
-    pub radar: Radar,
-
-    /// What satellite information is available
-    pub satellite: Satellite,
-}
-
-#[derive(Deserialize)]
-struct Radar {
-    past: Vec<RawFrame>,
-    nowcast: Vec<RawFrame>,
-}
-
-#[derive(Deserialize)]
-struct Satellite {
-    infrared: Vec<RawFrame>,
-}
-
-#[derive(Deserialize, Debug, Clone)]
-struct RawFrame {
-    /// The unix timestamp when this data was generated
-    pub time: u64,
-
-    /// The path where this data can be accessed
-    pub path: String,
-}
-
-impl From<RawFrame> for Frame {
-    fn from(raw: RawFrame) -> Self {
-        use chrono::TimeZone;
-
-        Self {
-            time: chrono::Utc.timestamp(raw.time as i64, 0).naive_utc(),
-            path: raw.path,
-        }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[tokio::test]
-    async fn test() {
-        let req = WeatherRequester::new();
-        let maps = req.available().await.unwrap();
-        let frame = &maps.past_radar[0];
-        let args = RequestArgumentsInner::Tile(TileArguments {
-            size: 256,
-            x: 26,
-            y: 12,
-            zoom: 6,
-            color: ColorKind::UniversalBlue,
-            smooth: true,
-            snow: true,
-        });
-        let png = req
-            .get_tile(&maps, frame, RequestArguments { inner: args })
-            .await
-            .unwrap();
-
-        //Check for PNG magic
-        assert_eq!(&png[0..4], &[0x89, 0x50, 0x4e, 0x47]);
-    }
-}
+pub use geo::{GeoTransform, LatLonBounds, TileCoord, TileScheme};
+#[cfg(feature = "image")]
+pub use histogram::*;
+#[cfg(feature = "image")]
+pub use legend::*;
+pub use maplibre::*;
+#[cfg(feature = "image")]
+pub use mosaic::*;
+#[cfg(feature = "image")]
+pub use motion::*;
+#[cfg(feature = "image")]
+pub use overlay::*;
+pub use palette::*;
+pub use player::*;
+pub use prefetch::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use rate_limit::*;
+pub use requester::*;
+#[cfg(feature = "image")]
+pub use resample::*;
+#[cfg(feature = "image")]
+pub use series::*;
+#[cfg(feature = "egui")]
+pub use texture::*;
+#[cfg(feature = "image")]
+pub use tile::*;
+pub use tilejson::*;
+pub use timestamp::{TimeDelta, Timestamp, UtcOffset};
+pub use transport::*;
@@ -0,0 +1,204 @@
+use serde_json::{json, Value};
+
+use crate::args::{
+    tile_url_template, RequestArguments, RequestArgumentsInner, TileSize, MAX_RADAR_ZOOM,
+};
+use crate::data::{AvailableData, Frame};
+use crate::error::ParameterError;
+use crate::geo::TileScheme;
+use crate::tilejson::ATTRIBUTION_HTML;
+use crate::timestamp::{self, Timestamp};
+
+/// A MapLibre GL raster source and the layer drawing it, for web maps whose radar layers are
+/// chosen by a Rust backend
+///
+/// [`MapLibreLayer::to_style`] emits a style fragment with a `sources` object and a `layers`
+/// array, ready to merge into a style or to add with `map.addSource` and `map.addLayer`. The
+/// source and layer share [`MapLibreLayer::id`], so a frontend can toggle or remove both by
+/// name. The time of the frame is kept in the layer's `metadata` under `rainviewer:time`.
+///
+/// ```
+/// use rain_viewer::{MapLibreLayer, RequestArguments, TileCoord};
+///
+/// # fn run(maps: &rain_viewer::AvailableData) -> Result<(), rain_viewer::Error> {
+/// let frame = maps.latest_past().unwrap();
+/// let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0))?;
+/// let layer = MapLibreLayer::for_frame(maps, frame, &args)?.with_opacity(0.6);
+/// // Sent to the frontend, which calls map.addSource and map.addLayer
+/// let fragment = layer.to_style().to_string();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapLibreLayer {
+    id: String,
+    template: String,
+    tile_size: u32,
+    scheme: TileScheme,
+    opacity: f32,
+    visible: bool,
+    time: Option<Timestamp>,
+}
+
+impl MapLibreLayer {
+    /// A layer named `id` drawing the 256 pixel tiles at `template`, with `{z}`, `{x}` and
+    /// `{y}` placeholders, such as the tiles of a proxy
+    pub fn new(id: impl Into<String>, template: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            template: template.into(),
+            tile_size: 256,
+            scheme: TileScheme::Xyz,
+            opacity: 1.0,
+            visible: true,
+            time: None,
+        }
+    }
+
+    /// A layer drawing the radar tiles of `frame` served directly by Rain Viewer, named
+    /// `rainviewer-{ts}` after the Unix time of the frame
+    ///
+    /// The size, color and options of `args` apply to every tile, while its location is
+    /// ignored.
+    ///
+    /// Returns Err(...) if `frame` is not a past or nowcast radar frame
+    pub fn for_frame(
+        maps: &AvailableData,
+        frame: &Frame,
+        args: &RequestArguments,
+    ) -> Result<Self, ParameterError> {
+        frame.expect_radar()?;
+        let RequestArgumentsInner::Tile(tile) = &args.inner;
+        let id = format!("rainviewer-{}", frame.unix_time());
+        Ok(Self {
+            tile_size: tile.size.into(),
+            time: Some(frame.time),
+            ..Self::new(id, tile_url_template(maps, frame, args))
+        })
+    }
+
+    /// Sets the opacity the layer is drawn with, clamped between 0 and 1. Radar is usually
+    /// drawn partly transparent so the basemap shows through
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the size the tile server serves tiles at
+    pub fn with_tile_size(mut self, size: TileSize) -> Self {
+        self.tile_size = size.into();
+        self
+    }
+
+    /// Sets the direction tile rows are numbered in by the tile server. Rain Viewer numbers
+    /// them as [`TileScheme::Xyz`]
+    pub fn with_scheme(mut self, scheme: TileScheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    /// Sets whether the layer is shown once added, so the layers of an animation can be added
+    /// hidden and shown one at a time
+    pub fn with_visible(mut self, visible: bool) -> Self {
+        self.visible = visible;
+        self
+    }
+
+    /// Sets the time of the frame the tiles show
+    pub fn with_time(mut self, time: Timestamp) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    /// The id of both the source and the layer
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The raster source, as passed to `map.addSource`
+    pub fn source(&self) -> Value {
+        json!({
+            "type": "raster",
+            "tiles": [self.template],
+            "tileSize": self.tile_size,
+            "scheme": match self.scheme {
+                TileScheme::Xyz => "xyz",
+                TileScheme::Tms => "tms",
+            },
+            "minzoom": 0,
+            "maxzoom": MAX_RADAR_ZOOM,
+            "attribution": ATTRIBUTION_HTML,
+        })
+    }
+
+    /// The raster layer drawing the source, as passed to `map.addLayer`
+    pub fn layer(&self) -> Value {
+        let mut layer = json!({
+            "id": self.id,
+            "type": "raster",
+            "source": self.id,
+            "layout": {
+                "visibility": if self.visible { "visible" } else { "none" },
+            },
+            "paint": {
+                "raster-opacity": self.opacity,
+                // Frames replace each other without cross fading tiles of different times
+                "raster-fade-duration": 0,
+            },
+        });
+        if let Some(time) = self.time {
+            layer["metadata"] = json!({
+                "rainviewer:time": timestamp::rfc3339(time),
+            });
+        }
+        layer
+    }
+
+    /// The source and layer as a style fragment, with `sources` and `layers` to merge into a
+    /// MapLibre style
+    pub fn to_style(&self) -> Value {
+        json!({
+            "sources": { self.id.as_str(): self.source() },
+            "layers": [self.layer()],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geo::TileCoord;
+
+    #[test]
+    fn style_fragment() {
+        let maps = AvailableData::fixture();
+        let mut args = RequestArguments::new_tile(TileCoord::new(1, 2, 3)).unwrap();
+        args.set_size(TileSize::Px512);
+        let layer = MapLibreLayer::for_frame(&maps, &maps.past_radar[0], &args)
+            .unwrap()
+            .with_opacity(1.5)
+            .with_visible(false);
+        assert_eq!(layer.id(), "rainviewer-1696998600");
+
+        let style = layer.to_style();
+        let source = &style["sources"]["rainviewer-1696998600"];
+        assert_eq!(source["tileSize"], 512);
+        assert_eq!(source["scheme"], "xyz");
+        assert_eq!(
+            source["tiles"][0],
+            "https://tilecache.rainviewer.com/v2/radar/1696998600/512/{z}/{x}/{y}/2/1_1.png"
+        );
+        let layer = &style["layers"][0];
+        assert_eq!(layer["source"], "rainviewer-1696998600");
+        assert_eq!(layer["paint"]["raster-opacity"], 1.0);
+        assert_eq!(layer["layout"]["visibility"], "none");
+        assert_eq!(layer["metadata"]["rainviewer:time"], "2023-10-11T04:30:00Z");
+
+        assert!(MapLibreLayer::for_frame(&maps, &maps.infrared_satellite[0], &args).is_err());
+        let proxied =
+            MapLibreLayer::new("radar", "/radar/{z}/{x}/{y}.png").with_scheme(TileScheme::Tms);
+        assert_eq!(proxied.source()["scheme"], "tms");
+        assert_eq!(proxied.source()["tileSize"], 256);
+        assert_eq!(proxied.layer().get("metadata"), None);
+    }
+}
@@ -0,0 +1,349 @@
+use std::collections::BTreeMap;
+
+use image::{DynamicImage, RgbaImage};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::error::{self, ParameterError};
+use crate::geo::{lat_to_y, lon_to_x, GeoTransform, LatLonBounds, TileCoord};
+use crate::tile::decode_png;
+
+/// The arrangement of a set of tiles into columns and rows
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Grid {
+    /// Tile columns from west to east, which wrap around from the last column to `0` for tile
+    /// sets crossing the antimeridian
+    pub(crate) columns: Vec<u32>,
+    /// The northernmost row
+    pub(crate) top: u32,
+    pub(crate) rows: u32,
+    pub(crate) zoom: u32,
+}
+
+impl Grid {
+    /// Lays out `coords`, which must share a zoom level
+    pub(crate) fn new<'a>(
+        coords: impl IntoIterator<Item = &'a TileCoord>,
+    ) -> Result<Self, ParameterError> {
+        let mut coords = coords.into_iter().peekable();
+        let zoom = match coords.peek() {
+            Some(coord) => coord.z,
+            None => {
+                return Err(ParameterError::InvalidZoom(
+                    0,
+                    "At least one tile is needed to build a mosaic".to_owned(),
+                ))
+            }
+        };
+        let mut xs = Vec::new();
+        let (mut top, mut bottom) = (u32::MAX, 0);
+        for coord in coords {
+            if coord.z != zoom {
+                return Err(ParameterError::InvalidZoom(
+                    coord.z,
+                    format!("All tiles of a mosaic must have zoom {}", zoom),
+                ));
+            }
+            xs.push(coord.x);
+            top = top.min(coord.y);
+            bottom = bottom.max(coord.y);
+        }
+        xs.sort_unstable();
+        xs.dedup();
+
+        // The columns start after the widest gap between used columns, measured around the
+        // world, so a set spanning the antimeridian is not stretched across the whole map
+        let n = 1u32 << zoom;
+        let start = (0..xs.len())
+            .max_by_key(|&i| {
+                let next = xs[(i + 1) % xs.len()];
+                (next + n - xs[i] - 1) % n
+            })
+            .map_or(0, |i| (i + 1) % xs.len());
+        let (first, last) = (xs[start], xs[(start + xs.len() - 1) % xs.len()]);
+        let width = (last + n - first) % n + 1;
+        let columns = (0..width).map(|i| (first + i) % n).collect();
+
+        Ok(Self {
+            columns,
+            top,
+            rows: bottom - top + 1,
+            zoom,
+        })
+    }
+
+    /// The column and row of `coord` within the grid, if it is part of it
+    pub(crate) fn position(&self, coord: &TileCoord) -> Option<(u32, u32)> {
+        let column = self.columns.iter().position(|&x| x == coord.x)?;
+        let row = coord.y.checked_sub(self.top)?;
+        (row < self.rows).then_some((column as u32, row))
+    }
+
+    /// The `(left, top, width, height)` pixel window covering `bounds` in a `width` by `height`
+    /// image of this grid
+    pub(crate) fn pixel_window(
+        &self,
+        bounds: &LatLonBounds,
+        width: u32,
+        height: u32,
+    ) -> (u32, u32, u32, u32) {
+        let n = 2f64.powi(self.zoom as i32);
+        let columns = self.columns.len() as f64;
+        let tile_width = width as f64 / columns;
+        let tile_height = height as f64 / self.rows as f64;
+
+        // Columns relative to the first column of the grid, wrapping around the antimeridian
+        let relative = |lon: f64| (lon_to_x(lon, n) - self.columns[0] as f64).rem_euclid(n);
+        let west = relative(bounds.west);
+        let mut east = relative(bounds.east);
+        if east <= west {
+            east += n;
+        }
+        let north = lat_to_y(bounds.north, n) - self.top as f64;
+        let south = lat_to_y(bounds.south, n) - self.top as f64;
+
+        let to_pixels = |start: f64, end: f64, size: f64, max: u32| {
+            let start = (start * size).floor().clamp(0.0, max as f64 - 1.0) as u32;
+            let end = (end * size).ceil().clamp(start as f64 + 1.0, max as f64) as u32;
+            (start, end - start)
+        };
+        let (left, width) = to_pixels(west, east.min(columns), tile_width, width);
+        let (top, height) = to_pixels(north, south, tile_height, height);
+        (left, top, width, height)
+    }
+}
+
+/// Assembles tiles of one zoom level, such as those returned by
+/// [`WeatherRequester::get_region`](crate::WeatherRequester::get_region), into a single image
+///
+/// The mosaic spans the smallest rectangle of tiles containing every tile, wrapping around the
+/// antimeridian if that is narrower. Tiles missing from the rectangle are left transparent.
+/// Tiles are drawn at the size of the largest tile, so 256 pixel tiles mixed with 512 pixel
+/// ones are scaled up.
+///
+/// With the `rayon` feature, tiles are decoded and drawn in parallel.
+///
+/// Enabled with the `image` feature. Returns Err(...) if `tiles` is empty, the tiles do not
+/// share a zoom level, or a tile is not a valid image
+pub fn stitch(
+    tiles: &BTreeMap<TileCoord, impl AsRef<[u8]> + Sync>,
+) -> Result<DynamicImage, error::Error> {
+    let grid = Grid::new(tiles.keys())?;
+    let decode = |(coord, tile): (&TileCoord, &_)| {
+        decode_png(AsRef::<[u8]>::as_ref(tile)).map(|image| (*coord, image))
+    };
+    #[cfg(feature = "rayon")]
+    let images: Vec<_> = tiles.par_iter().map(decode).collect::<Result<_, _>>()?;
+    #[cfg(not(feature = "rayon"))]
+    let images: Vec<_> = tiles.iter().map(decode).collect::<Result<_, _>>()?;
+    let size = images
+        .iter()
+        .map(|(_, image)| image.width().max(image.height()))
+        .max()
+        .unwrap_or(256);
+
+    // Each row of tiles owns a disjoint band of the mosaic, so rows can be drawn in parallel
+    let mut rows: Vec<Vec<(u32, DynamicImage)>> = (0..grid.rows).map(|_| Vec::new()).collect();
+    for (coord, image) in images {
+        let (column, row) = grid
+            .position(&coord)
+            .expect("every tile is part of the grid");
+        rows[row as usize].push((column, image));
+    }
+    let width = grid.columns.len() as u32 * size;
+    let band_len = (width * size * 4) as usize;
+    let mut buffer = vec![0; band_len * grid.rows as usize];
+    let draw = |(band, row): (&mut [u8], Vec<(u32, DynamicImage)>)| {
+        for (column, image) in row {
+            let image = if image.width() == size && image.height() == size {
+                image.into_rgba8()
+            } else {
+                image
+                    .resize_exact(size, size, image::imageops::FilterType::Triangle)
+                    .into_rgba8()
+            };
+            let line_len = (size * 4) as usize;
+            for (y, line) in image.as_raw().chunks_exact(line_len).enumerate() {
+                let start = (y as u32 * width + column * size) as usize * 4;
+                band[start..start + line_len].copy_from_slice(line);
+            }
+        }
+    };
+    #[cfg(feature = "rayon")]
+    buffer
+        .par_chunks_mut(band_len)
+        .zip(rows.into_par_iter())
+        .for_each(draw);
+    #[cfg(not(feature = "rayon"))]
+    buffer.chunks_mut(band_len).zip(rows).for_each(draw);
+
+    let mosaic = RgbaImage::from_raw(width, grid.rows * size, buffer)
+        .expect("the buffer holds every pixel of the mosaic");
+    Ok(DynamicImage::ImageRgba8(mosaic))
+}
+
+/// Stitches `tiles` as by [`stitch`], along with the transform placing the pixels of the
+/// mosaic on the map
+pub(crate) fn stitch_georeferenced(
+    tiles: &BTreeMap<TileCoord, impl AsRef<[u8]> + Sync>,
+) -> Result<(RgbaImage, GeoTransform), error::Error> {
+    let grid = Grid::new(tiles.keys())?;
+    let mosaic = stitch(tiles)?.into_rgba8();
+    let tile_size = mosaic.width() / grid.columns.len() as u32;
+    let north_west = TileCoord::new(grid.columns[0], grid.top, grid.zoom);
+    Ok((mosaic, GeoTransform::new(north_west, tile_size)))
+}
+
+/// Resamples a web mercator image placed by `transform` to rows of equal spans of latitude,
+/// as expected by viewers of WGS84 (EPSG:4326) rasters, along with the area it covers
+///
+/// Columns span equal longitudes in both projections, so they are kept, while rows are
+/// resampled to the nearest row of the image so that pixels span the same number of degrees
+/// both ways. The east edge of the area is wrapped to +/-180 degrees.
+pub(crate) fn to_wgs84(image: &RgbaImage, transform: &GeoTransform) -> (RgbaImage, LatLonBounds) {
+    let (north, west) = transform.pixel_to_lat_lon(0.0, 0.0);
+    let (south, east) = transform.pixel_to_lat_lon(image.width() as f64, image.height() as f64);
+    let degrees = (east - west) / image.width() as f64;
+    let height = ((north - south) / degrees).round().max(1.0) as u32;
+    let row_degrees = (north - south) / height as f64;
+
+    let rows: Vec<u32> = (0..height)
+        .map(|row| {
+            let lat = north - (row as f64 + 0.5) * row_degrees;
+            let (_, y) = transform.lat_lon_to_pixel(lat, west);
+            (y.floor().max(0.0) as u32).min(image.height() - 1)
+        })
+        .collect();
+    let resampled = RgbaImage::from_fn(image.width(), height, |x, y| {
+        *image.get_pixel(x, rows[y as usize])
+    });
+    let bounds = LatLonBounds {
+        west,
+        south,
+        east: if east > 180.0 { east - 360.0 } else { east },
+        north,
+    };
+    (resampled, bounds)
+}
+
+/// Clips a mosaic built by [`stitch`] from `tiles` to exactly `bounds`
+///
+/// Tile edges rarely line up with the area of interest, so this maps the edges of `bounds` to
+/// pixel offsets in the mosaic and crops it to them. Parts of `bounds` outside of the mosaic
+/// are left out, and the result is at least one pixel wide and high.
+///
+/// Enabled with the `image` feature. Returns Err(...) if `tiles` is empty or the tiles do not
+/// share a zoom level
+pub fn crop_to_bounds(
+    mosaic: &DynamicImage,
+    tiles: &BTreeMap<TileCoord, impl AsRef<[u8]>>,
+    bounds: &LatLonBounds,
+) -> Result<DynamicImage, error::Error> {
+    let grid = Grid::new(tiles.keys())?;
+    let (left, top, width, height) = grid.pixel_window(bounds, mosaic.width(), mosaic.height());
+    Ok(mosaic.crop_imm(left, top, width, height))
+}
+
+/// The lowest zoom, up to `max_zoom`, at which `bounds` spans at least `max_px` pixels of
+/// `tile_size` pixel tiles in its longer direction
+pub(crate) fn thumbnail_zoom(
+    bounds: &LatLonBounds,
+    max_px: u32,
+    tile_size: u32,
+    max_zoom: u32,
+) -> u32 {
+    let mut width = (bounds.east - bounds.west) / 360.0;
+    if bounds.crosses_antimeridian() {
+        width += 1.0;
+    }
+    let height = lat_to_y(bounds.south, 1.0) - lat_to_y(bounds.north, 1.0);
+    let extent = width.max(height) * tile_size as f64;
+    (0..max_zoom)
+        .find(|&zoom| extent * 2f64.powi(zoom as i32) >= max_px as f64)
+        .unwrap_or(max_zoom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(size: u32, color: [u8; 4]) -> Vec<u8> {
+        let image = RgbaImage::from_pixel(size, size, image::Rgba(color));
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(image)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    fn coord(x: u32, y: u32, z: u32) -> TileCoord {
+        TileCoord { x, y, z }
+    }
+
+    #[test]
+    fn thumbnail_zooms() {
+        let world = LatLonBounds::new(-180.0, -85.0, 180.0, 85.0).unwrap();
+        assert_eq!(thumbnail_zoom(&world, 256, 256, 12), 0);
+        assert_eq!(thumbnail_zoom(&world, 300, 256, 12), 1);
+
+        // A quarter of the world wide, so 64 pixels at zoom 0
+        let quarter = LatLonBounds::new(0.0, -10.0, 90.0, 10.0).unwrap();
+        assert_eq!(thumbnail_zoom(&quarter, 128, 256, 12), 1);
+        let wrapped = LatLonBounds::new(135.0, -10.0, -135.0, 10.0).unwrap();
+        assert_eq!(thumbnail_zoom(&wrapped, 128, 256, 12), 1);
+
+        let point = LatLonBounds::new(5.0, 52.0, 5.0, 52.0).unwrap();
+        assert_eq!(thumbnail_zoom(&point, 128, 256, 12), 12);
+    }
+
+    #[test]
+    fn grid_wraps_around_antimeridian() {
+        let grid = Grid::new(&[coord(7, 4, 3), coord(0, 4, 3), coord(6, 5, 3)]).unwrap();
+        assert_eq!(grid.columns, [6, 7, 0]);
+        assert_eq!((grid.top, grid.rows), (4, 2));
+        assert_eq!(grid.position(&coord(0, 5, 3)), Some((2, 1)));
+
+        let grid = Grid::new(&[coord(2, 1, 3), coord(4, 1, 3)]).unwrap();
+        assert_eq!(grid.columns, [2, 3, 4]);
+        assert!(Grid::new(&[coord(2, 1, 3), coord(2, 1, 4)]).is_err());
+    }
+
+    #[test]
+    fn stitches_tiles() {
+        let red = [255, 0, 0, 255];
+        let blue = [0, 0, 255, 255];
+        let mut tiles = BTreeMap::new();
+        tiles.insert(coord(4, 6, 4), png(512, red));
+        tiles.insert(coord(5, 7, 4), png(256, blue));
+
+        let mosaic = stitch(&tiles).unwrap().into_rgba8();
+        assert_eq!(mosaic.dimensions(), (1024, 1024));
+        assert_eq!(mosaic.get_pixel(10, 10).0, red);
+        // The 256 pixel tile is scaled up to fill its cell
+        assert_eq!(mosaic.get_pixel(1000, 1000).0, blue);
+        assert_eq!(mosaic.get_pixel(600, 10).0, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn pixel_window() {
+        // Tiles on either side of the antimeridian at zoom 2, south of 66.5 degrees north
+        let grid = Grid::new(&[coord(3, 1, 2), coord(0, 1, 2)]).unwrap();
+        assert_eq!(grid.columns, [3, 0]);
+
+        // From 135 degrees east to 135 degrees west, between 40 degrees north and the equator
+        let bounds = LatLonBounds::new(135.0, 0.0, -135.0, 40.0).unwrap();
+        assert_eq!(grid.pixel_window(&bounds, 512, 256), (128, 131, 256, 125));
+    }
+
+    #[test]
+    fn crops_to_bounds() {
+        let mut tiles = BTreeMap::new();
+        tiles.insert(coord(0, 0, 0), png(256, [0, 255, 0, 255]));
+        let mosaic = stitch(&tiles).unwrap();
+
+        // The western hemisphere, from the equator to the mercator limit
+        let bounds = LatLonBounds::new(-180.0, 0.0, 0.0, 90.0).unwrap();
+        let cropped = crop_to_bounds(&mosaic, &tiles, &bounds).unwrap();
+        assert_eq!((cropped.width(), cropped.height()), (128, 128));
+    }
+}
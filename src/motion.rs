@@ -0,0 +1,351 @@
+use image::{Rgba, RgbaImage};
+
+use crate::error::{self, ParameterError};
+
+/// Side of the square blocks whose motion is estimated separately, in pixels
+const BLOCK_SIZE: u32 = 16;
+
+/// The displacement of one block of a [`MotionField`] between two frames, in pixels
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MotionVector {
+    /// The displacement eastwards, or right in the image
+    pub dx: f32,
+    /// The displacement southwards, or down in the image
+    pub dy: f32,
+    /// Whether the block held precipitation, so its motion was measured. Other blocks take the
+    /// mean motion of the measured ones, as nearby storms mostly move together
+    pub measured: bool,
+}
+
+/// How precipitation moved between two frames of the same area, estimated by block matching
+///
+/// The image is divided into blocks of 16 pixels, and each block holding precipitation is
+/// matched against the next frame by searching up to a maximum shift in every direction.
+/// Vectors are in pixels per frame interval, so for frames 10 minutes apart, a vector of 4
+/// pixels at zoom 6 is about 4 times [`ground_resolution`](crate::geo::ground_resolution)
+/// per 10 minutes.
+///
+/// ```no_run
+/// # fn run(from: &image::RgbaImage, to: &image::RgbaImage) -> Result<(), rain_viewer::Error> {
+/// use rain_viewer::MotionField;
+///
+/// let field = MotionField::estimate(from, to, 8)?;
+/// // Arrows for the blocks showing precipitation
+/// for (x, y, vector) in field.blocks() {
+///     if vector.measured {
+///         println!("({x}, {y}) moves by ({}, {})", vector.dx, vector.dy);
+///     }
+/// }
+/// // Where the precipitation of `to` will be two frames later
+/// let ahead = field.extrapolate(to, 2.0);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Enabled with the `image` feature
+#[derive(Clone, Debug, PartialEq)]
+pub struct MotionField {
+    columns: u32,
+    rows: u32,
+    vectors: Vec<MotionVector>,
+}
+
+impl MotionField {
+    /// Matches the blocks of `from` holding precipitation against `to`, searching up to
+    /// `max_shift` pixels in every direction
+    ///
+    /// Larger shifts follow faster storms at higher zoom levels, at a quadratic cost in time.
+    /// Returns Err(...) if the images differ in size or are empty
+    pub fn estimate(
+        from: &RgbaImage,
+        to: &RgbaImage,
+        max_shift: u32,
+    ) -> Result<Self, error::Error> {
+        expect_same_size(from, to)?;
+        if from.width() == 0 || from.height() == 0 {
+            return Err(ParameterError::InvalidSize(
+                0,
+                "Motion can only be estimated between non empty images".to_owned(),
+            )
+            .into());
+        }
+        Ok(Self::estimate_unchecked(from, to, max_shift))
+    }
+
+    /// Like [`Self::estimate`], for images already known to be the same size. The field of
+    /// empty images has no blocks, so [`Self::at`] must not be called on it
+    pub(crate) fn estimate_unchecked(from: &RgbaImage, to: &RgbaImage, max_shift: u32) -> Self {
+        let columns = from.width().div_ceil(BLOCK_SIZE);
+        let rows = from.height().div_ceil(BLOCK_SIZE);
+        let from_signal = signal(from);
+        let to_signal = signal(to);
+        let width = from.width() as i64;
+        let height = from.height() as i64;
+        let at = |signal: &[f32], x: i64, y: i64| {
+            if (0..width).contains(&x) && (0..height).contains(&y) {
+                signal[(y * width + x) as usize]
+            } else {
+                0.0
+            }
+        };
+
+        let max_shift = max_shift as i64;
+        let mut vectors = Vec::with_capacity((columns * rows) as usize);
+        for row in 0..rows as i64 {
+            for column in 0..columns as i64 {
+                let xs = column * BLOCK_SIZE as i64..((column + 1) * BLOCK_SIZE as i64).min(width);
+                let ys = row * BLOCK_SIZE as i64..((row + 1) * BLOCK_SIZE as i64).min(height);
+                let pixels = || ys.clone().flat_map(|y| xs.clone().map(move |x| (x, y)));
+                if pixels().all(|(x, y)| at(&from_signal, x, y) == 0.0) {
+                    vectors.push(None);
+                    continue;
+                }
+                let cost = |dx: i64, dy: i64| -> f32 {
+                    pixels()
+                        .map(|(x, y)| {
+                            (at(&from_signal, x, y) - at(&to_signal, x + dx, y + dy)).abs()
+                        })
+                        .sum()
+                };
+                // Staying still wins ties, so uniform areas are not dragged around
+                let mut best = (0, 0, cost(0, 0));
+                for dy in -max_shift..=max_shift {
+                    for dx in -max_shift..=max_shift {
+                        let cost = cost(dx, dy);
+                        if cost < best.2 {
+                            best = (dx, dy, cost);
+                        }
+                    }
+                }
+                vectors.push(Some((best.0 as f32, best.1 as f32)));
+            }
+        }
+
+        let mean = mean(vectors.iter().flatten().copied());
+        Self {
+            columns,
+            rows,
+            vectors: vectors
+                .into_iter()
+                .map(|vector| {
+                    let ((dx, dy), measured) = vector.map_or((mean, false), |v| (v, true));
+                    MotionVector { dx, dy, measured }
+                })
+                .collect(),
+        }
+    }
+
+    /// The side of the blocks in pixels
+    pub fn block_size(&self) -> u32 {
+        BLOCK_SIZE
+    }
+
+    /// The number of columns and rows of blocks
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.columns, self.rows)
+    }
+
+    /// The vector of each block, with the pixel position of the block's center, by rows from
+    /// the top. Blocks at the right and bottom edges may be cut short by the image, but their
+    /// centers are those of a whole block
+    pub fn blocks(&self) -> impl Iterator<Item = (f32, f32, MotionVector)> + '_ {
+        let half = BLOCK_SIZE as f32 / 2.0;
+        self.vectors.iter().enumerate().map(move |(i, vector)| {
+            let column = i as u32 % self.columns;
+            let row = i as u32 / self.columns;
+            let x = (column * BLOCK_SIZE) as f32 + half;
+            let y = (row * BLOCK_SIZE) as f32 + half;
+            (x, y, *vector)
+        })
+    }
+
+    /// The mean motion of the blocks holding precipitation, or zero if none did
+    pub fn mean(&self) -> (f32, f32) {
+        mean(
+            self.vectors
+                .iter()
+                .filter(|v| v.measured)
+                .map(|v| (v.dx, v.dy)),
+        )
+    }
+
+    /// The displacement at a pixel position, blended bilinearly between block centers, for a
+    /// dense field of motion
+    pub fn at(&self, x: f32, y: f32) -> (f32, f32) {
+        let half = BLOCK_SIZE as f32 / 2.0;
+        let grid_x = ((x - half) / BLOCK_SIZE as f32).clamp(0.0, (self.columns - 1) as f32);
+        let grid_y = ((y - half) / BLOCK_SIZE as f32).clamp(0.0, (self.rows - 1) as f32);
+        let (left, top) = (grid_x.floor() as u32, grid_y.floor() as u32);
+        let right = (left + 1).min(self.columns - 1);
+        let bottom = (top + 1).min(self.rows - 1);
+        let (fx, fy) = (grid_x.fract(), grid_y.fract());
+        let vector = |column: u32, row: u32| {
+            let vector = self.vectors[(row * self.columns + column) as usize];
+            (vector.dx, vector.dy)
+        };
+        let lerp =
+            |a: (f32, f32), b: (f32, f32), f: f32| (a.0 + (b.0 - a.0) * f, a.1 + (b.1 - a.1) * f);
+        let upper = lerp(vector(left, top), vector(right, top), fx);
+        let lower = lerp(vector(left, bottom), vector(right, bottom), fx);
+        lerp(upper, lower, fy)
+    }
+
+    /// Moves the precipitation of `image` along this field for `steps` frame intervals, such as
+    /// to extrapolate the latest frame beyond the nowcast
+    ///
+    /// Each pixel is traced back along the motion at it, assuming storms keep their speed and
+    /// direction and neither grow nor decay, so forecasts lose skill quickly beyond an hour.
+    /// Precipitation moving in from outside of the image is unknown and left transparent
+    pub fn extrapolate(&self, image: &RgbaImage, steps: f32) -> RgbaImage {
+        RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+            let (dx, dy) = self.at(x as f32 + 0.5, y as f32 + 0.5);
+            unpremultiply(sample(image, x as f32 - steps * dx, y as f32 - steps * dy))
+        })
+    }
+}
+
+/// The motion between each pair of consecutive `images`, such as the frames rendered by
+/// [`animation::render_frames`](crate::animation::render_frames), see
+/// [`MotionField::estimate`]
+///
+/// Returns Err(...) if the images differ in size or are empty
+pub fn motion_between(
+    images: &[RgbaImage],
+    max_shift: u32,
+) -> Result<Vec<MotionField>, error::Error> {
+    images
+        .windows(2)
+        .map(|pair| MotionField::estimate(&pair[0], &pair[1], max_shift))
+        .collect()
+}
+
+/// The mean of `vectors`, or zero if there are none
+fn mean(vectors: impl Iterator<Item = (f32, f32)>) -> (f32, f32) {
+    let (count, x, y) = vectors.fold((0, 0.0, 0.0), |(count, x, y), vector| {
+        (count + 1, x + vector.0, y + vector.1)
+    });
+    if count == 0 {
+        (0.0, 0.0)
+    } else {
+        (x / count as f32, y / count as f32)
+    }
+}
+
+/// Returns Err(...) unless `from` and `to` are the same size
+pub(crate) fn expect_same_size(from: &RgbaImage, to: &RgbaImage) -> Result<(), ParameterError> {
+    if from.dimensions() == to.dimensions() {
+        return Ok(());
+    }
+    Err(ParameterError::InvalidSize(
+        to.width(),
+        format!(
+            "Frames must be the same size, but the first is {}x{}",
+            from.width(),
+            from.height()
+        ),
+    ))
+}
+
+/// How strongly each pixel shows precipitation, for matching blocks between frames: zero where
+/// transparent, and growing with brightness so that storm cores line up
+fn signal(image: &RgbaImage) -> Vec<f32> {
+    image
+        .pixels()
+        .map(|pixel| {
+            let [r, g, b, a] = pixel.0;
+            let luma = (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) / 255.0;
+            a as f32 / 255.0 * (0.5 + 0.5 * luma)
+        })
+        .collect()
+}
+
+pub(crate) fn premultiply(pixel: &Rgba<u8>) -> [f32; 4] {
+    let [r, g, b, a] = pixel.0.map(|channel| channel as f32 / 255.0);
+    [r * a, g * a, b * a, a]
+}
+
+pub(crate) fn unpremultiply([r, g, b, a]: [f32; 4]) -> Rgba<u8> {
+    if a <= 0.0 {
+        return Rgba([0; 4]);
+    }
+    let to_u8 = |value: f32| (value * 255.0).round().clamp(0.0, 255.0) as u8;
+    Rgba([to_u8(r / a), to_u8(g / a), to_u8(b / a), to_u8(a)])
+}
+
+pub(crate) fn mix(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    std::array::from_fn(|i| a[i] * (1.0 - t) + b[i] * t)
+}
+
+/// The premultiplied color at a fractional pixel position, blended bilinearly between pixel
+/// centers and transparent outside of the image
+pub(crate) fn sample(image: &RgbaImage, x: f32, y: f32) -> [f32; 4] {
+    let (left, top) = (x.floor(), y.floor());
+    let (fx, fy) = (x - left, y - top);
+    let pixel = |x: f32, y: f32| {
+        if x < 0.0 || y < 0.0 || x >= image.width() as f32 || y >= image.height() as f32 {
+            [0.0; 4]
+        } else {
+            premultiply(image.get_pixel(x as u32, y as u32))
+        }
+    };
+    let upper = mix(pixel(left, top), pixel(left + 1.0, top), fx);
+    let lower = mix(pixel(left, top + 1.0), pixel(left + 1.0, top + 1.0), fx);
+    mix(upper, lower, fy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A transparent image with an opaque square of `size` pixels at `(x, y)`
+    fn blob(x: u32, y: u32, size: u32) -> RgbaImage {
+        RgbaImage::from_fn(64, 48, |px, py| {
+            if (x..x + size).contains(&px) && (y..y + size).contains(&py) {
+                Rgba([0, 200, 0, 255])
+            } else {
+                Rgba([0; 4])
+            }
+        })
+    }
+
+    #[test]
+    fn estimates_vectors() {
+        let field = MotionField::estimate(&blob(4, 4, 8), &blob(10, 7, 8), 8).unwrap();
+        assert_eq!(field.dimensions(), (4, 3));
+        let blocks: Vec<_> = field.blocks().collect();
+        assert_eq!(blocks.len(), 12);
+        let (x, y, vector) = blocks[0];
+        assert_eq!((x, y), (8.0, 8.0));
+        assert_eq!(
+            vector,
+            MotionVector {
+                dx: 6.0,
+                dy: 3.0,
+                measured: true
+            }
+        );
+        // Empty blocks follow the measured one
+        assert_eq!((blocks[11].2.dx, blocks[11].2.measured), (6.0, false));
+        assert_eq!(field.mean(), (6.0, 3.0));
+        assert_eq!(field.at(40.0, 30.0), (6.0, 3.0));
+
+        assert!(MotionField::estimate(&blob(0, 0, 1), &RgbaImage::new(3, 3), 2).is_err());
+        assert!(MotionField::estimate(&RgbaImage::new(0, 0), &RgbaImage::new(0, 0), 2).is_err());
+
+        let fields = motion_between(&[blob(0, 0, 8), blob(2, 0, 8), blob(4, 1, 8)], 4).unwrap();
+        let means: Vec<_> = fields.iter().map(MotionField::mean).collect();
+        assert_eq!(means, [(2.0, 0.0), (2.0, 1.0)]);
+    }
+
+    #[test]
+    fn extrapolates() {
+        let field = MotionField::estimate(&blob(4, 4, 8), &blob(8, 4, 8), 8).unwrap();
+        // Another two steps of 4 pixels east
+        let ahead = field.extrapolate(&blob(8, 4, 8), 2.0);
+        assert_eq!(ahead.get_pixel(16, 6).0, [0, 200, 0, 255]);
+        assert_eq!(ahead.get_pixel(23, 11).0, [0, 200, 0, 255]);
+        assert_eq!(ahead.get_pixel(15, 6).0[3], 0);
+
+        assert_eq!(field.extrapolate(&blob(8, 4, 8), 0.0), blob(8, 4, 8));
+    }
+}
@@ -0,0 +1,414 @@
+use ab_glyph::FontArc;
+use image::{Rgba, RgbaImage};
+
+use crate::data::Frame;
+use crate::font::{draw_outline_text, draw_text, outline_text_width, text_width, GLYPH_HEIGHT};
+use crate::timestamp::{self, Timestamp, UtcOffset};
+
+/// The attribution RainViewer requires wherever its imagery is shown
+pub const ATTRIBUTION: &str = "RainViewer";
+
+/// The corner of an image an [`Overlay`] is drawn in
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+/// The time zone a frame's timestamp is shown in
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TimeZoneLabel {
+    /// UTC, such as `2023-10-11 05:00 UTC`
+    #[default]
+    Utc,
+    /// The system's local time zone, such as `2023-10-11 07:00 +02:00`
+    Local,
+    /// A fixed offset from UTC, such as `2023-10-11 01:00 -04:00`
+    Offset(UtcOffset),
+}
+
+/// Stamps a frame's time and the RainViewer attribution onto a decoded tile or mosaic, for
+/// images shared outside of a map widget that would otherwise show them
+///
+/// Text is drawn by default with a built-in 5 by 7 pixel font, scaled up by a whole number of
+/// pixels per font pixel. That font covers digits, letters and common punctuation, and draws
+/// lowercase letters as capitals. [`Overlay::with_font`] draws text with any TrueType or
+/// OpenType font instead, anti-aliased and as written.
+///
+/// ```
+/// use image::RgbaImage;
+/// use rain_viewer::{Corner, Frame, FrameKind, Overlay};
+///
+/// let frame = Frame {
+///     time: chrono::DateTime::from_timestamp(1697000400, 0).unwrap(),
+///     path: "/v2/radar/1697000400".to_owned(),
+///     kind: FrameKind::PastRadar,
+/// };
+/// let mut image = RgbaImage::new(256, 256);
+/// Overlay::new()
+///     .with_corner(Corner::TopLeft)
+///     .with_scale(2)
+///     .draw(&mut image, &frame);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Overlay {
+    corner: Corner,
+    typeface: Typeface,
+    color: Rgba<u8>,
+    background: Option<Rgba<u8>>,
+    padding: u32,
+    time_zone: TimeZoneLabel,
+    attribution: bool,
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Overlay {
+    /// White text at the native font size in the bottom right corner, over a translucent black
+    /// box, with the time in UTC and the attribution
+    pub fn new() -> Self {
+        Self {
+            corner: Corner::BottomRight,
+            typeface: Typeface::Bitmap(1),
+            color: Rgba([255, 255, 255, 255]),
+            background: Some(Rgba([0, 0, 0, 160])),
+            padding: 3,
+            time_zone: TimeZoneLabel::Utc,
+            attribution: true,
+        }
+    }
+
+    /// Sets the corner the text is drawn in
+    pub fn with_corner(mut self, corner: Corner) -> Self {
+        self.corner = corner;
+        self
+    }
+
+    /// Draws text with the built-in font, with each font pixel drawn as a `scale` by `scale`
+    /// square. A scale of zero is treated as one
+    ///
+    /// This replaces a font set with [`Overlay::with_font`].
+    pub fn with_scale(mut self, scale: u32) -> Self {
+        self.typeface = Typeface::Bitmap(scale.max(1));
+        self
+    }
+
+    /// Draws text with `font`, with lines `size` pixels high
+    ///
+    /// Load the font with [`ab_glyph`](https://docs.rs/ab_glyph) 0.2, such as with
+    /// `FontArc::try_from_vec(std::fs::read("DejaVuSans.ttf")?)?`. Sizes below one pixel are
+    /// treated as one pixel. Characters missing from the font are drawn as its placeholder
+    /// glyph
+    ///
+    /// ```no_run
+    /// use ab_glyph::FontArc;
+    /// use rain_viewer::Overlay;
+    ///
+    /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let font = FontArc::try_from_vec(std::fs::read("DejaVuSans.ttf")?)?;
+    /// let overlay = Overlay::new().with_font(font, 18.0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_font(mut self, font: FontArc, size: f32) -> Self {
+        self.typeface = Typeface::Outline(font, size.max(1.0));
+        self
+    }
+
+    /// Sets the color of the text
+    pub fn with_color(mut self, color: [u8; 4]) -> Self {
+        self.color = Rgba(color);
+        self
+    }
+
+    /// Sets the color of the box behind the text, blended over the image by its alpha, or
+    /// `None` to draw the text directly on the image
+    pub fn with_background(mut self, background: Option<[u8; 4]>) -> Self {
+        self.background = background.map(Rgba);
+        self
+    }
+
+    /// Sets the space in pixels between the text and the edges of its box, and between the box
+    /// and the edges of the image
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Sets the time zone the frame's time is shown in
+    pub fn with_time_zone(mut self, time_zone: TimeZoneLabel) -> Self {
+        self.time_zone = time_zone;
+        self
+    }
+
+    /// Sets whether the RainViewer attribution is drawn under the time. RainViewer's terms
+    /// require crediting it, so only leave it out if the image is credited elsewhere
+    pub fn with_attribution(mut self, attribution: bool) -> Self {
+        self.attribution = attribution;
+        self
+    }
+
+    /// The lines of text drawn for `frame`, from the top
+    pub fn lines(&self, frame: &Frame) -> Vec<String> {
+        let mut lines = vec![format_time(frame.time, self.time_zone)];
+        if self.attribution {
+            lines.push(ATTRIBUTION.to_owned());
+        }
+        lines
+    }
+
+    /// Draws the time of `frame` and the attribution onto `image`, clipping them to it
+    ///
+    /// Lines are aligned to the side of the image of the overlay's corner.
+    pub fn draw(&self, image: &mut RgbaImage, frame: &Frame) {
+        let lines = self.lines(frame);
+        let line_height = self.typeface.line_height();
+        let spacing = self.typeface.spacing();
+        let widest = lines
+            .iter()
+            .map(|line| self.typeface.width(line))
+            .max()
+            .unwrap_or(0);
+        let text_height = lines.len() as u32 * (line_height + spacing) - spacing;
+        let box_width = widest + 2 * self.padding;
+        let box_height = text_height + 2 * self.padding;
+
+        let left = match self.corner {
+            Corner::TopLeft | Corner::BottomLeft => self.padding,
+            Corner::TopRight | Corner::BottomRight => {
+                image.width().saturating_sub(self.padding + box_width)
+            }
+        };
+        let top = match self.corner {
+            Corner::TopLeft | Corner::TopRight => self.padding,
+            Corner::BottomLeft | Corner::BottomRight => {
+                image.height().saturating_sub(self.padding + box_height)
+            }
+        };
+
+        if let Some(background) = self.background {
+            let right = (left + box_width).min(image.width());
+            let bottom = (top + box_height).min(image.height());
+            for y in top..bottom {
+                for x in left..right {
+                    blend(image.get_pixel_mut(x, y), background);
+                }
+            }
+        }
+
+        for (i, line) in lines.iter().enumerate() {
+            let width = self.typeface.width(line);
+            let x = match self.corner {
+                Corner::TopLeft | Corner::BottomLeft => left + self.padding,
+                Corner::TopRight | Corner::BottomRight => left + self.padding + widest - width,
+            };
+            let y = top + self.padding + i as u32 * (line_height + spacing);
+            self.typeface.draw(image, line, x, y, self.color);
+        }
+    }
+}
+
+/// The font an [`Overlay`] draws text with
+#[derive(Clone, Debug)]
+enum Typeface {
+    /// The built-in bitmap font, with each font pixel drawn as a square of this side
+    Bitmap(u32),
+    /// A font supplied by the caller, with lines this many pixels high
+    Outline(FontArc, f32),
+}
+
+impl Typeface {
+    fn line_height(&self) -> u32 {
+        match self {
+            Typeface::Bitmap(scale) => GLYPH_HEIGHT * scale,
+            Typeface::Outline(_, size) => size.ceil() as u32,
+        }
+    }
+
+    /// The space between two lines
+    fn spacing(&self) -> u32 {
+        match self {
+            Typeface::Bitmap(scale) => scale * 2,
+            Typeface::Outline(_, size) => (size / 4.0).round() as u32,
+        }
+    }
+
+    fn width(&self, text: &str) -> u32 {
+        match self {
+            Typeface::Bitmap(scale) => text_width(text, *scale),
+            Typeface::Outline(font, size) => outline_text_width(font, *size, text),
+        }
+    }
+
+    /// Draws `text` in `color` with its top left corner at `(x, y)`, clipping it to the image
+    fn draw(&self, image: &mut RgbaImage, text: &str, x: u32, y: u32, color: Rgba<u8>) {
+        match self {
+            Typeface::Bitmap(scale) => draw_text(image, text, x, y, *scale, color),
+            Typeface::Outline(font, size) => {
+                let (width, height) = image.dimensions();
+                draw_outline_text(
+                    font,
+                    *size,
+                    text,
+                    x,
+                    y,
+                    width,
+                    height,
+                    |px, py, coverage| {
+                        let alpha = (color.0[3] as f32 * coverage.min(1.0)).round() as u8;
+                        let [r, g, b, _] = color.0;
+                        blend(image.get_pixel_mut(px, py), Rgba([r, g, b, alpha]));
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Formats `time` to the minute in `time_zone`
+fn format_time(time: Timestamp, time_zone: TimeZoneLabel) -> String {
+    let offset = match time_zone {
+        TimeZoneLabel::Utc => return format!("{} UTC", timestamp::format_minutes(time, 0)),
+        TimeZoneLabel::Local => timestamp::local_offset_seconds(time),
+        TimeZoneLabel::Offset(offset) => timestamp::offset_seconds(offset),
+    };
+    format!(
+        "{} {}",
+        timestamp::format_minutes(time, offset),
+        timestamp::format_offset(offset)
+    )
+}
+
+/// Draws `color` over `pixel` by its alpha
+fn blend(pixel: &mut Rgba<u8>, color: Rgba<u8>) {
+    let alpha = color.0[3] as f32 / 255.0;
+    let below = pixel.0[3] as f32 / 255.0;
+    let out = alpha + below * (1.0 - alpha);
+    if out <= 0.0 {
+        *pixel = Rgba([0; 4]);
+        return;
+    }
+    let channel = |i: usize| {
+        let value = (color.0[i] as f32 * alpha + pixel.0[i] as f32 * below * (1.0 - alpha)) / out;
+        value.round().clamp(0.0, 255.0) as u8
+    };
+    *pixel = Rgba([
+        channel(0),
+        channel(1),
+        channel(2),
+        (out * 255.0).round() as u8,
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::FrameKind;
+
+    fn frame() -> Frame {
+        Frame {
+            time: timestamp::from_unix_seconds(1697000400).unwrap(),
+            path: "/v2/radar/1697000400".to_owned(),
+            kind: FrameKind::PastRadar,
+        }
+    }
+
+    #[test]
+    fn labels() {
+        let overlay = Overlay::new();
+        assert_eq!(
+            overlay.lines(&frame()),
+            ["2023-10-11 05:00 UTC", ATTRIBUTION]
+        );
+        let east = timestamp::offset_from_seconds(2 * 3600).unwrap();
+        let overlay = overlay
+            .with_time_zone(TimeZoneLabel::Offset(east))
+            .with_attribution(false);
+        assert_eq!(overlay.lines(&frame()), ["2023-10-11 07:00 +02:00"]);
+    }
+
+    #[test]
+    fn corners() {
+        let white = [255, 255, 255, 255];
+        let overlay = Overlay::new()
+            .with_background(Some([0, 0, 0, 255]))
+            .with_padding(2);
+        let mut image = RgbaImage::from_pixel(256, 64, Rgba([0, 0, 255, 255]));
+        overlay.draw(&mut image, &frame());
+        // The box hugs the bottom right corner, and the text is inside it
+        assert_eq!(image.get_pixel(253, 61).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(254, 62).0, [0, 0, 255, 255]);
+        assert_eq!(image.get_pixel(10, 10).0, [0, 0, 255, 255]);
+        let text = image.pixels().filter(|pixel| pixel.0 == white).count();
+        assert!(text > 0);
+
+        let mut image = RgbaImage::new(256, 64);
+        overlay
+            .with_corner(Corner::TopLeft)
+            .with_scale(2)
+            .with_background(None)
+            .draw(&mut image, &frame());
+        let lit: Vec<_> = image
+            .enumerate_pixels()
+            .filter(|(_, _, pixel)| pixel.0 == white)
+            .collect();
+        assert!(lit.iter().all(|&(x, y, _)| x >= 4 && (4..40).contains(&y)));
+        // The top bar of the first digit, doubled in size, inside both paddings
+        assert_eq!(image.get_pixel(4, 4).0, [0; 4]);
+        assert_eq!(image.get_pixel(6, 4).0, white);
+        assert_eq!(image.get_pixel(11, 5).0, white);
+
+        // Images smaller than the text are clipped rather than panicking
+        let mut image = RgbaImage::new(8, 8);
+        Overlay::new().with_scale(4).draw(&mut image, &frame());
+    }
+
+    #[test]
+    fn custom_font() {
+        let font = FontArc::try_from_slice(epaint_default_fonts::UBUNTU_LIGHT).unwrap();
+        let overlay = Overlay::new()
+            .with_corner(Corner::TopLeft)
+            .with_background(None)
+            .with_padding(2)
+            .with_font(font.clone(), 16.0);
+        // Lowercase letters keep their own, narrower, shapes
+        assert!(outline_text_width(&font, 16.0, "rain") < outline_text_width(&font, 16.0, "RAIN"));
+
+        let mut image = RgbaImage::new(256, 64);
+        overlay.draw(&mut image, &frame());
+        let inked: Vec<_> = image
+            .enumerate_pixels()
+            .filter(|(_, _, pixel)| pixel.0[3] > 0)
+            .collect();
+        assert!(!inked.is_empty());
+        // Two lines of 16 pixels with 4 pixels between them, inside the padding
+        assert!(inked
+            .iter()
+            .all(|&(x, y, _)| (4..256).contains(&x) && (4..40).contains(&y)));
+        // Edges are anti-aliased
+        assert!(inked.iter().any(|(_, _, pixel)| pixel.0[3] < 255));
+
+        // The built-in font can be brought back
+        let mut bitmap = RgbaImage::new(256, 64);
+        overlay.with_scale(1).draw(&mut bitmap, &frame());
+        assert!(bitmap.pixels().all(|pixel| [0, 255].contains(&pixel.0[3])));
+    }
+
+    #[test]
+    fn blends_background() {
+        let mut pixel = Rgba([255, 255, 255, 255]);
+        blend(&mut pixel, Rgba([0, 0, 0, 128]));
+        assert_eq!(pixel.0, [127, 127, 127, 255]);
+
+        let mut pixel = Rgba([0; 4]);
+        blend(&mut pixel, Rgba([0, 0, 0, 160]));
+        assert_eq!(pixel.0, [0, 0, 0, 160]);
+    }
+}
@@ -0,0 +1,345 @@
+use crate::color::ColorKind;
+use crate::error::ParameterError;
+use crate::precip::{decode_black_and_white, PrecipKind};
+
+/// One step of a color scheme: pixels of `color` show reflectivity of at least `dbz`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PaletteEntry {
+    pub dbz: f32,
+    /// RGBA color of the pixels
+    pub color: [u8; 4],
+}
+
+/// A color gradient over reflectivity, for recoloring [`ColorKind::BlackAndWhite`] tiles
+/// locally
+///
+/// Downloading tiles once in black and white and recoloring them avoids downloading the same
+/// tile in several schemes, and allows colors that Rain Viewer does not offer.
+///
+/// Reflectivity below the first stop is transparent, colors are interpolated linearly between
+/// stops, and reflectivity above the last stop uses its color.
+///
+/// ```
+/// use rain_viewer::Palette;
+///
+/// let palette = Palette::from_stops([
+///     (10.0, [0, 0, 255, 128]),
+///     (50.0, [255, 0, 0, 255]),
+/// ])?;
+/// assert_eq!(palette.color_for_dbz(5.0), [0, 0, 0, 0]);
+/// assert_eq!(palette.color_for_dbz(30.0), [128, 0, 128, 192]);
+/// assert_eq!(palette.color_for_dbz(60.0), [255, 0, 0, 255]);
+/// # Ok::<(), rain_viewer::ParameterError>(())
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    stops: Vec<PaletteEntry>,
+    snow: Option<Vec<PaletteEntry>>,
+}
+
+impl Palette {
+    /// Creates a gradient from `(dbz, rgba)` stops, which must be in increasing order of dBZ
+    ///
+    /// Returns Err(...) if there are no stops, or the stops are not strictly increasing
+    pub fn from_stops(
+        stops: impl IntoIterator<Item = (f32, [u8; 4])>,
+    ) -> Result<Self, ParameterError> {
+        Ok(Self {
+            stops: validate_stops(stops)?,
+            snow: None,
+        })
+    }
+
+    /// Draws snow with a separate gradient from `(dbz, rgba)` stops, rather than with the
+    /// same colors as rain
+    ///
+    /// Returns Err(...) if there are no stops, or the stops are not strictly increasing
+    pub fn with_snow_stops(
+        mut self,
+        stops: impl IntoIterator<Item = (f32, [u8; 4])>,
+    ) -> Result<Self, ParameterError> {
+        self.snow = Some(validate_stops(stops)?);
+        Ok(self)
+    }
+
+    /// The stops of the gradient used for rain
+    pub fn stops(&self) -> &[PaletteEntry] {
+        &self.stops
+    }
+
+    /// The color of rain with a reflectivity of `dbz`
+    pub fn color_for_dbz(&self, dbz: f32) -> [u8; 4] {
+        interpolate(&self.stops, dbz)
+    }
+
+    /// The color of precipitation of `kind` with a reflectivity of `dbz`
+    pub fn color_for(&self, dbz: f32, kind: PrecipKind) -> [u8; 4] {
+        match (kind, &self.snow) {
+            (PrecipKind::Snow, Some(snow)) => interpolate(snow, dbz),
+            _ => interpolate(&self.stops, dbz),
+        }
+    }
+
+    /// The color to draw an RGBA pixel of a [`ColorKind::BlackAndWhite`] tile with.
+    /// Transparent pixels stay transparent
+    pub fn recolor_pixel(&self, rgba: [u8; 4]) -> [u8; 4] {
+        let [value, _, _, alpha] = rgba;
+        if alpha == 0 {
+            return [0; 4];
+        }
+        let (dbz, kind) = decode_black_and_white(value);
+        self.color_for(dbz, kind)
+    }
+}
+
+impl From<&[PaletteEntry]> for Palette {
+    /// Uses the steps of a scheme, such as those from [`ColorKind::palette`], as gradient
+    /// stops
+    fn from(entries: &[PaletteEntry]) -> Self {
+        Self {
+            stops: entries.to_vec(),
+            snow: None,
+        }
+    }
+}
+
+fn validate_stops(
+    stops: impl IntoIterator<Item = (f32, [u8; 4])>,
+) -> Result<Vec<PaletteEntry>, ParameterError> {
+    let stops: Vec<_> = stops
+        .into_iter()
+        .map(|(dbz, color)| PaletteEntry { dbz, color })
+        .collect();
+    if stops.is_empty() {
+        return Err(ParameterError::InvalidPalette(
+            f32::NAN,
+            "A palette needs at least one stop".to_owned(),
+        ));
+    }
+    if let Some(stop) = stops.iter().find(|stop| !stop.dbz.is_finite()) {
+        return Err(ParameterError::InvalidPalette(
+            stop.dbz,
+            "Stops must be finite".to_owned(),
+        ));
+    }
+    for pair in stops.windows(2) {
+        if pair[0].dbz >= pair[1].dbz {
+            return Err(ParameterError::InvalidPalette(
+                pair[1].dbz,
+                format!(
+                    "Stops must follow each other in increasing order, after {} dBZ",
+                    pair[0].dbz
+                ),
+            ));
+        }
+    }
+    Ok(stops)
+}
+
+/// The color at `dbz` of a gradient through `stops`
+fn interpolate(stops: &[PaletteEntry], dbz: f32) -> [u8; 4] {
+    let next = stops.partition_point(|stop| stop.dbz <= dbz);
+    if next == 0 {
+        return [0; 4];
+    }
+    let (low, high) = match stops.get(next) {
+        Some(high) => (&stops[next - 1], high),
+        None => return stops[next - 1].color,
+    };
+    let t = (dbz - low.dbz) / (high.dbz - low.dbz);
+    let mut color = [0; 4];
+    for (i, channel) in color.iter_mut().enumerate() {
+        let (a, b) = (low.color[i] as f32, high.color[i] as f32);
+        *channel = (a + (b - a) * t).round() as u8;
+    }
+    color
+}
+
+/// How far, as a squared distance in RGB space, a pixel may be from a palette color and still
+/// be matched to it. Allows for colors blended at the edges of echoes
+const MAX_COLOR_DISTANCE: u32 = 3 * 16 * 16;
+
+/// [`ColorKind::BlackAndWhite`] encodes rain of `value - 32` dBZ as gray `value`
+const BLACK_AND_WHITE: [PaletteEntry; 128] = {
+    let mut entries = [PaletteEntry {
+        dbz: 0.0,
+        color: [0; 4],
+    }; 128];
+    let mut value = 0;
+    while value < 128 {
+        entries[value] = PaletteEntry {
+            dbz: value as f32 - 32.0,
+            color: [value as u8, value as u8, value as u8, 255],
+        };
+        value += 1;
+    }
+    entries
+};
+
+/// The National Weather Service NEXRAD reflectivity palette in steps of 5 dBZ
+const NEXRAD_LEVEL_III: [PaletteEntry; 15] = [
+    entry(5.0, 0x04e9e7),
+    entry(10.0, 0x019ff4),
+    entry(15.0, 0x0300f4),
+    entry(20.0, 0x02fd02),
+    entry(25.0, 0x01c501),
+    entry(30.0, 0x008e00),
+    entry(35.0, 0xfdf802),
+    entry(40.0, 0xe5bc00),
+    entry(45.0, 0xfd9500),
+    entry(50.0, 0xfd0000),
+    entry(55.0, 0xd40000),
+    entry(60.0, 0xbc0000),
+    entry(65.0, 0xf800fd),
+    entry(70.0, 0x9854c6),
+    entry(75.0, 0xfdfdfd),
+];
+
+/// An opaque palette entry from a `0xRRGGBB` color
+const fn entry(dbz: f32, rgb: u32) -> PaletteEntry {
+    let [_, r, g, b] = rgb.to_be_bytes();
+    PaletteEntry {
+        dbz,
+        color: [r, g, b, 255],
+    }
+}
+
+impl ColorKind {
+    /// The colors this scheme draws rain with, ordered from the weakest reflectivity to the
+    /// strongest
+    ///
+    /// Returns `None` for schemes whose table is not bundled with this crate. Tables are
+    /// currently bundled for [`ColorKind::BlackAndWhite`] and [`ColorKind::NexradLevelIII`]
+    pub fn palette(self) -> Option<&'static [PaletteEntry]> {
+        match ColorKind::try_from(u32::from(self)).ok()? {
+            ColorKind::BlackAndWhite => Some(&BLACK_AND_WHITE),
+            ColorKind::NexradLevelIII => Some(&NEXRAD_LEVEL_III),
+            _ => None,
+        }
+    }
+
+    /// Recovers the reflectivity in dBZ shown by a pixel of a tile rendered with this scheme
+    ///
+    /// Black and white pixels are decoded exactly, including snow. Other schemes match the
+    /// pixel to the closest color of their [`ColorKind::palette`], returning the lower bound
+    /// of that step, so blended colors at the edges of echoes still resolve.
+    ///
+    /// ```
+    /// use rain_viewer::ColorKind;
+    ///
+    /// assert_eq!(ColorKind::BlackAndWhite.dbz_for_pixel([72, 72, 72, 255]), Some(40.0));
+    /// assert_eq!(ColorKind::NexradLevelIII.dbz_for_pixel([0xfd, 0, 0, 255]), Some(50.0));
+    /// // Transparent pixels have no precipitation
+    /// assert_eq!(ColorKind::NexradLevelIII.dbz_for_pixel([0, 0, 0, 0]), None);
+    /// ```
+    ///
+    /// Returns `None` for transparent pixels, pixels not close to any palette color, and
+    /// schemes without a bundled palette
+    pub fn dbz_for_pixel(self, rgba: [u8; 4]) -> Option<f32> {
+        let [r, g, b, alpha] = rgba;
+        if alpha == 0 {
+            return None;
+        }
+        if let ColorKind::BlackAndWhite = ColorKind::try_from(u32::from(self)).ok()? {
+            return Some(decode_black_and_white(r).0);
+        }
+        let distance = |color: [u8; 4]| {
+            [r, g, b]
+                .iter()
+                .zip(color)
+                .map(|(a, b)| (*a as i32 - b as i32).pow(2) as u32)
+                .sum::<u32>()
+        };
+        self.palette()?
+            .iter()
+            .map(|entry| (distance(entry.color), entry.dbz))
+            .min_by_key(|(distance, _)| *distance)
+            .filter(|(distance, _)| *distance <= MAX_COLOR_DISTANCE)
+            .map(|(_, dbz)| dbz)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palettes() {
+        for color in ColorKind::ALL {
+            if let Some(palette) = color.palette() {
+                assert!(palette.windows(2).all(|pair| pair[0].dbz < pair[1].dbz));
+                // Every color maps back to its own step
+                for entry in palette {
+                    assert_eq!(color.dbz_for_pixel(entry.color), Some(entry.dbz));
+                }
+            }
+        }
+        assert!(ColorKind::Custom(6).palette().is_some());
+        assert_eq!(ColorKind::Titan.palette(), None);
+        assert_eq!(ColorKind::Titan.dbz_for_pixel([255, 0, 0, 255]), None);
+    }
+
+    #[test]
+    fn published_colors() {
+        // Pixels of each bundled scheme as they appear in Rain Viewer tiles
+        let known = [
+            (ColorKind::BlackAndWhite, [1, 1, 1, 255], -31.0),
+            (ColorKind::BlackAndWhite, [32, 32, 32, 255], 0.0),
+            (ColorKind::BlackAndWhite, [67, 67, 67, 255], 35.0),
+            (ColorKind::BlackAndWhite, [127, 127, 127, 255], 95.0),
+            (ColorKind::NexradLevelIII, [0x04, 0xe9, 0xe7, 255], 5.0),
+            (ColorKind::NexradLevelIII, [0x02, 0xfd, 0x02, 255], 20.0),
+            (ColorKind::NexradLevelIII, [0xfd, 0xf8, 0x02, 255], 35.0),
+            (ColorKind::NexradLevelIII, [0xf8, 0x00, 0xfd, 255], 65.0),
+        ];
+        for (color, rgba, dbz) in known {
+            assert_eq!(color.dbz_for_pixel(rgba), Some(dbz), "{color:?} {rgba:?}");
+        }
+    }
+
+    #[test]
+    fn gradients() {
+        assert!(matches!(
+            Palette::from_stops([]),
+            Err(ParameterError::InvalidPalette(_, _))
+        ));
+        assert!(matches!(
+            Palette::from_stops([(10.0, [0; 4]), (10.0, [0; 4])]),
+            Err(ParameterError::InvalidPalette(dbz, _)) if dbz == 10.0
+        ));
+        assert!(Palette::from_stops([(f32::INFINITY, [0; 4])]).is_err());
+
+        let palette = Palette::from_stops([(0.0, [0, 0, 0, 255]), (40.0, [200, 100, 0, 255])])
+            .unwrap()
+            .with_snow_stops([(0.0, [255, 255, 255, 255])])
+            .unwrap();
+        assert_eq!(palette.color_for_dbz(-1.0), [0; 4]);
+        assert_eq!(palette.color_for_dbz(0.0), [0, 0, 0, 255]);
+        assert_eq!(palette.color_for_dbz(10.0), [50, 25, 0, 255]);
+        assert_eq!(palette.color_for_dbz(40.0), [200, 100, 0, 255]);
+
+        // 20 dBZ of rain, then of snow
+        assert_eq!(palette.recolor_pixel([52, 52, 52, 255]), [100, 50, 0, 255]);
+        assert_eq!(palette.recolor_pixel([180, 180, 180, 255]), [255; 4]);
+        assert_eq!(palette.recolor_pixel([52, 52, 52, 0]), [0; 4]);
+
+        let nexrad = Palette::from(ColorKind::NexradLevelIII.palette().unwrap());
+        assert_eq!(nexrad.color_for_dbz(50.0), [0xfd, 0, 0, 255]);
+    }
+
+    #[test]
+    fn pixel_matching() {
+        let nexrad = ColorKind::NexradLevelIII;
+        // Slightly off colors match the closest step
+        assert_eq!(nexrad.dbz_for_pixel([0xf0, 0x05, 0x05, 255]), Some(50.0));
+        assert_eq!(nexrad.dbz_for_pixel([0x80, 0x80, 0x80, 255]), None);
+
+        let black_and_white = ColorKind::BlackAndWhite;
+        assert_eq!(black_and_white.dbz_for_pixel([32, 32, 32, 255]), Some(0.0));
+        assert_eq!(
+            black_and_white.dbz_for_pixel([150, 150, 150, 255]),
+            Some(-10.0)
+        );
+        assert_eq!(black_and_white.dbz_for_pixel([150, 150, 150, 0]), None);
+    }
+}
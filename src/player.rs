@@ -0,0 +1,459 @@
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use crate::data::{AvailableData, Frame};
+
+/// Something an [`AnimationPlayer`] needs its owner to act on
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PlayerEvent {
+    /// The frame at this index of the timeline should now be displayed
+    Show(usize),
+    /// The frame at this index of the timeline must be loaded, and marked with
+    /// [`AnimationPlayer::mark_loaded`], before playback can move on to it
+    NeedFrame(usize),
+}
+
+/// Playback state of a radar animation, without any networking or drawing
+///
+/// The player tracks the current frame of a timeline, whether it is playing, its speed and the
+/// part of the timeline it loops over. Its owner feeds it the time passed with
+/// [`AnimationPlayer::tick`] and acts on the returned [`PlayerEvent`]s, loading frames on
+/// request and displaying them once shown, so that GUI toolkits can share the same playback
+/// logic:
+///
+/// ```
+/// use std::time::Duration;
+/// use rain_viewer::{AnimationPlayer, PlayerEvent};
+///
+/// # fn run(maps: &rain_viewer::AvailableData) {
+/// let mut player = AnimationPlayer::from_radar(maps);
+/// player.play();
+/// // Called on every redraw with the time since the last one
+/// for event in player.tick(Duration::from_millis(16)) {
+///     match event {
+///         PlayerEvent::NeedFrame(index) => { /* start downloading, then mark_loaded(index) */ }
+///         PlayerEvent::Show(index) => { /* display the frame */ }
+///     }
+/// }
+/// # }
+/// ```
+///
+/// Playback stalls on a frame that is not loaded yet instead of skipping it.
+#[derive(Clone, Debug)]
+pub struct AnimationPlayer {
+    frames: Vec<Frame>,
+    loaded: Vec<bool>,
+    requested: Vec<bool>,
+    current: usize,
+    loop_bounds: RangeInclusive<usize>,
+    playing: bool,
+    speed: f32,
+    frame_delay: Duration,
+    loop_pause: Duration,
+    elapsed: Duration,
+}
+
+impl AnimationPlayer {
+    /// A paused player at the start of `frames`, which should be in chronological order
+    ///
+    /// Each frame is shown for half a second, and the last frame of the loop is held for
+    /// another two seconds
+    pub fn new(frames: impl IntoIterator<Item = Frame>) -> Self {
+        let frames: Vec<_> = frames.into_iter().collect();
+        let count = frames.len();
+        Self {
+            loaded: vec![false; count],
+            requested: vec![false; count],
+            current: 0,
+            loop_bounds: 0..=count.saturating_sub(1),
+            playing: false,
+            speed: 1.0,
+            frame_delay: Duration::from_millis(500),
+            loop_pause: Duration::from_secs(2),
+            elapsed: Duration::ZERO,
+            frames,
+        }
+    }
+
+    /// A paused player over the past and nowcast radar frames of `maps`, starting at the
+    /// oldest
+    pub fn from_radar(maps: &AvailableData) -> Self {
+        Self::new(maps.all_radar().cloned())
+    }
+
+    /// Sets how long each frame is shown at normal speed
+    pub fn with_frame_delay(mut self, delay: Duration) -> Self {
+        self.frame_delay = delay;
+        self
+    }
+
+    /// Sets how much longer the last frame of the loop is held at normal speed, so that the
+    /// loop's end stands out
+    pub fn with_loop_pause(mut self, pause: Duration) -> Self {
+        self.loop_pause = pause;
+        self
+    }
+
+    /// The frames played
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// The index of the current frame
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    /// The current frame, or `None` if the timeline is empty
+    pub fn current(&self) -> Option<&Frame> {
+        self.frames.get(self.current)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Starts playing from the current frame
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    /// Stops on the current frame
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// Plays if paused, or pauses if playing
+    pub fn toggle(&mut self) {
+        self.playing = !self.playing;
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Sets the playback speed as a multiple of normal speed
+    ///
+    /// Speeds that are not positive and finite are ignored
+    pub fn set_speed(&mut self, speed: f32) {
+        if speed.is_finite() && speed > 0.0 {
+            self.speed = speed;
+        }
+    }
+
+    /// The range of frame indices that playback loops over
+    pub fn loop_bounds(&self) -> RangeInclusive<usize> {
+        self.loop_bounds.clone()
+    }
+
+    /// Restricts playback to loop over the frames at `bounds`, such as only the past hour
+    ///
+    /// Bounds past the end of the timeline are clamped to its last frame, and a start after
+    /// the end is moved to it. If the current frame is outside of the bounds, playback jumps to
+    /// their start
+    pub fn set_loop_bounds(&mut self, bounds: RangeInclusive<usize>) -> Vec<PlayerEvent> {
+        let last = self.frames.len().saturating_sub(1);
+        let end = (*bounds.end()).min(last);
+        let start = (*bounds.start()).min(end);
+        self.loop_bounds = start..=end;
+        if self.loop_bounds.contains(&self.current) {
+            Vec::new()
+        } else {
+            self.seek(start)
+        }
+    }
+
+    /// Jumps to the frame at `index`, clamped to the timeline
+    pub fn seek(&mut self, index: usize) -> Vec<PlayerEvent> {
+        if self.frames.is_empty() {
+            return Vec::new();
+        }
+        self.current = index.min(self.frames.len() - 1);
+        self.elapsed = Duration::ZERO;
+        let mut events = vec![PlayerEvent::Show(self.current)];
+        events.extend(self.request(self.current));
+        events
+    }
+
+    /// Moves to the next frame of the loop, wrapping around to its start
+    pub fn step_forward(&mut self) -> Vec<PlayerEvent> {
+        self.seek(self.next_index())
+    }
+
+    /// Moves to the previous frame of the loop, wrapping around to its end
+    pub fn step_back(&mut self) -> Vec<PlayerEvent> {
+        let index = if self.current <= *self.loop_bounds.start() {
+            *self.loop_bounds.end()
+        } else {
+            self.current - 1
+        };
+        self.seek(index)
+    }
+
+    /// Records that the frame at `index` is loaded and ready to display
+    pub fn mark_loaded(&mut self, index: usize) {
+        if let Some(loaded) = self.loaded.get_mut(index) {
+            *loaded = true;
+        }
+    }
+
+    /// Records that the frame at `index` was evicted, so it is requested again before it is
+    /// shown
+    pub fn mark_unloaded(&mut self, index: usize) {
+        if let (Some(loaded), Some(requested)) =
+            (self.loaded.get_mut(index), self.requested.get_mut(index))
+        {
+            *loaded = false;
+            *requested = false;
+        }
+    }
+
+    pub fn is_loaded(&self, index: usize) -> bool {
+        self.loaded.get(index).copied().unwrap_or(false)
+    }
+
+    /// The indices of the `count` frames after the current one in playback order, wrapping
+    /// around the loop, for fetching ahead of playback
+    pub fn upcoming(&self, count: usize) -> impl Iterator<Item = usize> + '_ {
+        let len = self.loop_bounds.clone().count();
+        let mut index = self.current;
+        std::iter::repeat_with(move || {
+            index = self.wrap(index + 1);
+            index
+        })
+        .take(count.min(len.saturating_sub(1)))
+    }
+
+    /// Advances playback by `elapsed`, scaled by the speed
+    ///
+    /// Returns the frames to show and to load, in order. While playing, the current and next
+    /// frames are requested if they are not loaded. Playback moves on at most one frame per
+    /// frame delay, and waits on frames that are not loaded, requesting each only once
+    pub fn tick(&mut self, elapsed: Duration) -> Vec<PlayerEvent> {
+        let mut events = Vec::new();
+        if self.frames.is_empty() {
+            return events;
+        }
+        events.extend(self.request(self.current));
+        if !self.playing {
+            return events;
+        }
+
+        self.elapsed += elapsed.mul_f32(self.speed);
+        loop {
+            let delay = if self.current == *self.loop_bounds.end() {
+                self.frame_delay + self.loop_pause
+            } else {
+                self.frame_delay
+            };
+            if self.elapsed < delay {
+                break;
+            }
+            let next = self.next_index();
+            if !self.loaded[next] {
+                events.extend(self.request(next));
+                // Wait on the frame without building up time to skip through once it loads
+                self.elapsed = delay;
+                break;
+            }
+            self.elapsed -= delay;
+            self.current = next;
+            events.push(PlayerEvent::Show(next));
+            if delay.is_zero() {
+                break;
+            }
+        }
+        // Ask for the next frame early so it is ready when its turn comes
+        events.extend(self.request(self.next_index()));
+        events
+    }
+
+    /// Replaces the timeline with `frames`, such as after fetching newer
+    /// [`AvailableData`], keeping what is loaded and the current frame if they are still in it
+    ///
+    /// The loop is reset to the whole timeline. If the current frame was dropped, playback
+    /// moves to the first frame after it
+    pub fn set_frames(&mut self, frames: impl IntoIterator<Item = Frame>) -> Vec<PlayerEvent> {
+        let current = self.current().cloned();
+        let old = std::mem::take(&mut self.frames);
+        self.frames = frames.into_iter().collect();
+        let find_old = |frame: &Frame| old.iter().position(|old| old.path == frame.path);
+        self.loaded = self
+            .frames
+            .iter()
+            .map(|frame| find_old(frame).is_some_and(|i| self.loaded[i]))
+            .collect();
+        self.requested = self
+            .frames
+            .iter()
+            .map(|frame| find_old(frame).is_some_and(|i| self.requested[i]))
+            .collect();
+        self.loop_bounds = 0..=self.frames.len().saturating_sub(1);
+
+        let Some(current) = current else {
+            return self.seek(0);
+        };
+        match self
+            .frames
+            .iter()
+            .position(|frame| frame.path == current.path)
+        {
+            Some(index) => {
+                self.current = index;
+                Vec::new()
+            }
+            None => {
+                let index = self
+                    .frames
+                    .iter()
+                    .position(|frame| frame.time > current.time)
+                    .unwrap_or(self.frames.len().saturating_sub(1));
+                self.seek(index)
+            }
+        }
+    }
+
+    /// A request for the frame at `index`, unless it is loaded or already requested
+    fn request(&mut self, index: usize) -> Option<PlayerEvent> {
+        if self.loaded[index] || self.requested[index] {
+            return None;
+        }
+        self.requested[index] = true;
+        Some(PlayerEvent::NeedFrame(index))
+    }
+
+    fn next_index(&self) -> usize {
+        self.wrap(self.current + 1)
+    }
+
+    /// `index` wrapped into the loop bounds
+    fn wrap(&self, index: usize) -> usize {
+        if self.loop_bounds.contains(&index) {
+            index
+        } else {
+            *self.loop_bounds.start()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::FrameKind;
+
+    fn frames(times: impl IntoIterator<Item = i64>) -> Vec<Frame> {
+        times
+            .into_iter()
+            .map(|time| Frame {
+                time: crate::timestamp::from_unix_seconds(time).unwrap(),
+                path: format!("/v2/radar/{time}"),
+                kind: FrameKind::PastRadar,
+            })
+            .collect()
+    }
+
+    fn loaded_player(count: i64) -> AnimationPlayer {
+        let mut player = AnimationPlayer::new(frames((0..count).map(|i| i * 600)))
+            .with_frame_delay(Duration::from_millis(100))
+            .with_loop_pause(Duration::from_millis(300));
+        for i in 0..count as usize {
+            player.mark_loaded(i);
+        }
+        player
+    }
+
+    #[test]
+    fn plays_and_loops() {
+        let mut player = loaded_player(3);
+        assert!(player.tick(Duration::from_secs(1)).is_empty());
+        assert_eq!(player.current_index(), 0);
+
+        player.play();
+        assert!(player.tick(Duration::from_millis(50)).is_empty());
+        assert_eq!(
+            player.tick(Duration::from_millis(160)),
+            [PlayerEvent::Show(1), PlayerEvent::Show(2)]
+        );
+        // The last frame is held for the loop pause before wrapping around
+        assert!(player.tick(Duration::from_millis(300)).is_empty());
+        assert_eq!(
+            player.tick(Duration::from_millis(100)),
+            [PlayerEvent::Show(0)]
+        );
+
+        player.set_speed(2.0);
+        assert_eq!(
+            player.tick(Duration::from_millis(50)),
+            [PlayerEvent::Show(1)]
+        );
+        player.set_speed(-1.0);
+        assert_eq!(player.speed(), 2.0);
+
+        player.toggle();
+        assert!(!player.is_playing());
+        assert!(player.tick(Duration::from_secs(1)).is_empty());
+    }
+
+    #[test]
+    fn waits_on_frames() {
+        let mut player = AnimationPlayer::new(frames([0, 600, 1200]))
+            .with_frame_delay(Duration::from_millis(100));
+        player.play();
+        assert_eq!(
+            player.tick(Duration::ZERO),
+            [PlayerEvent::NeedFrame(0), PlayerEvent::NeedFrame(1)]
+        );
+        player.mark_loaded(0);
+        assert!(player.tick(Duration::from_millis(150)).is_empty());
+        // Requested once, and no time builds up while waiting
+        assert!(player.tick(Duration::from_secs(5)).is_empty());
+        player.mark_loaded(1);
+        assert_eq!(
+            player.tick(Duration::ZERO),
+            [PlayerEvent::Show(1), PlayerEvent::NeedFrame(2)]
+        );
+
+        player.mark_unloaded(0);
+        assert!(!player.is_loaded(0));
+        assert_eq!(
+            player.seek(0),
+            [PlayerEvent::Show(0), PlayerEvent::NeedFrame(0)]
+        );
+    }
+
+    #[test]
+    fn loop_bounds() {
+        let mut player = loaded_player(5);
+        assert_eq!(player.set_loop_bounds(2..=10), [PlayerEvent::Show(2)]);
+        assert_eq!(player.loop_bounds(), 2..=4);
+        assert_eq!(player.upcoming(5).collect::<Vec<_>>(), [3, 4]);
+        assert_eq!(player.step_back(), [PlayerEvent::Show(4)]);
+        assert_eq!(player.step_forward(), [PlayerEvent::Show(2)]);
+        assert_eq!(player.upcoming(1).collect::<Vec<_>>(), [3]);
+        assert!(player.set_loop_bounds(1..=3).is_empty());
+        assert_eq!(player.current_index(), 2);
+    }
+
+    #[test]
+    fn timeline_updates() {
+        let mut player = loaded_player(3);
+        player.seek(1);
+        // The oldest frame expires and a new one arrives
+        assert!(player.set_frames(frames([600, 1200, 1800])).is_empty());
+        assert_eq!(player.current_index(), 0);
+        assert!(player.is_loaded(1));
+        assert!(!player.is_loaded(2));
+
+        assert_eq!(
+            player.set_frames(frames([1200, 1800])),
+            [PlayerEvent::Show(0)]
+        );
+        assert_eq!(player.current().unwrap().path, "/v2/radar/1200");
+
+        let mut empty = AnimationPlayer::new(Vec::new());
+        empty.play();
+        assert!(empty.tick(Duration::from_secs(1)).is_empty());
+        assert!(empty.current().is_none());
+        assert_eq!(empty.upcoming(3).count(), 0);
+    }
+}
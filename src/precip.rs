@@ -0,0 +1,611 @@
+//! Conversion of radar reflectivity to precipitation rates
+//!
+//! Radar measures reflectivity `Z`, which grows with the size and number of drops rather than
+//! with how much water falls. Precipitation rates `R` are estimated from it with an empirical
+//! Z-R relationship `Z = a * R^b`, whose coefficients depend on the kind of precipitation.
+//!
+//! ```
+//! use rain_viewer::precip::{decode_black_and_white, RateConverter};
+//!
+//! // A black and white pixel with brightness 72 shows 40 dBZ of rain
+//! let (dbz, kind) = decode_black_and_white(72);
+//! let rate = RateConverter::default().rate(dbz, kind);
+//! assert!((rate - 11.53).abs() < 0.01);
+//! ```
+
+use crate::timestamp::{self, Timestamp};
+
+/// The kinds of precipitation distinguished by Rain Viewer tiles requested with snow enabled
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PrecipKind {
+    Rain,
+    Snow,
+}
+
+/// Decodes the brightness of a [`ColorKind::BlackAndWhite`](crate::ColorKind::BlackAndWhite)
+/// pixel into its reflectivity in dBZ and kind of precipitation
+///
+/// Values below 128 are rain of `value - 32` dBZ. Snow is encoded the same way, offset by
+/// 128, so values of 128 and above are snow of `value - 160` dBZ
+pub fn decode_black_and_white(value: u8) -> (f32, PrecipKind) {
+    if value >= 128 {
+        (value as f32 - 160.0, PrecipKind::Snow)
+    } else {
+        (value as f32 - 32.0, PrecipKind::Rain)
+    }
+}
+
+/// A relationship `Z = a * R^b` between reflectivity `Z` in mm⁶/m³ and precipitation rate `R`
+/// in mm/h
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ZrRelationship {
+    pub a: f32,
+    pub b: f32,
+}
+
+impl ZrRelationship {
+    /// The Marshall-Palmer relationship `Z = 200 R^1.6` for stratiform rain, the most widely
+    /// used default
+    pub const MARSHALL_PALMER: ZrRelationship = ZrRelationship::new(200.0, 1.6);
+
+    /// `Z = 300 R^1.4`, used by the US NEXRAD network for convective rain
+    pub const NEXRAD_CONVECTIVE: ZrRelationship = ZrRelationship::new(300.0, 1.4);
+
+    /// Rosenfeld's `Z = 250 R^1.2` for tropical rain
+    pub const ROSENFELD_TROPICAL: ZrRelationship = ZrRelationship::new(250.0, 1.2);
+
+    /// Gunn and Marshall's `Z = 2000 S^2` for snow, giving the liquid water equivalent rate
+    pub const GUNN_MARSHALL_SNOW: ZrRelationship = ZrRelationship::new(2000.0, 2.0);
+
+    /// The relationship `Z = a * R^b`
+    pub const fn new(a: f32, b: f32) -> Self {
+        Self { a, b }
+    }
+
+    /// The precipitation rate in mm/h for a reflectivity of `dbz`
+    pub fn rate(&self, dbz: f32) -> f32 {
+        let z = 10f32.powf(dbz / 10.0);
+        (z / self.a).powf(1.0 / self.b)
+    }
+
+    /// The reflectivity in dBZ giving a precipitation rate of `rate` mm/h, the inverse of
+    /// [`ZrRelationship::rate`]
+    pub fn dbz(&self, rate: f32) -> f32 {
+        10.0 * (self.a * rate.powf(self.b)).log10()
+    }
+}
+
+impl Default for ZrRelationship {
+    fn default() -> Self {
+        Self::MARSHALL_PALMER
+    }
+}
+
+/// Converts reflectivity to precipitation rates, using separate relationships for rain and
+/// snow
+///
+/// Defaults to [`ZrRelationship::MARSHALL_PALMER`] for rain and
+/// [`ZrRelationship::GUNN_MARSHALL_SNOW`] for snow
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RateConverter {
+    rain: ZrRelationship,
+    snow: ZrRelationship,
+}
+
+impl Default for RateConverter {
+    fn default() -> Self {
+        Self {
+            rain: ZrRelationship::MARSHALL_PALMER,
+            snow: ZrRelationship::GUNN_MARSHALL_SNOW,
+        }
+    }
+}
+
+impl RateConverter {
+    /// A converter using the default relationships
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the relationship used for rain
+    pub fn with_rain(mut self, rain: ZrRelationship) -> Self {
+        self.rain = rain;
+        self
+    }
+
+    /// Sets the relationship used for snow
+    pub fn with_snow(mut self, snow: ZrRelationship) -> Self {
+        self.snow = snow;
+        self
+    }
+
+    /// The precipitation rate in mm/h for `dbz` of precipitation of `kind`. Snow rates are
+    /// liquid water equivalent
+    pub fn rate(&self, dbz: f32, kind: PrecipKind) -> f32 {
+        match kind {
+            PrecipKind::Rain => self.rain.rate(dbz),
+            PrecipKind::Snow => self.snow.rate(dbz),
+        }
+    }
+
+    /// The precipitation rate in mm/h shown by an RGBA pixel of a
+    /// [`ColorKind::BlackAndWhite`](crate::ColorKind::BlackAndWhite) tile, or `None` if the
+    /// pixel is transparent because nothing falls there
+    pub fn rate_for_pixel(&self, rgba: [u8; 4]) -> Option<f32> {
+        let [value, _, _, alpha] = rgba;
+        if alpha == 0 {
+            return None;
+        }
+        let (dbz, kind) = decode_black_and_white(value);
+        Some(self.rate(dbz, kind))
+    }
+}
+
+/// How strongly precipitation falls at a point
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Intensity {
+    /// Reflectivity in dBZ
+    pub dbz: f32,
+    /// Precipitation rate in mm/h, liquid water equivalent for snow
+    pub rate: f32,
+}
+
+/// The precipitation shown at a point of a radar frame
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PrecipEstimate {
+    /// Nothing falls, or too little for the radar to detect
+    None,
+    Rain(Intensity),
+    Snow(Intensity),
+}
+
+impl PrecipEstimate {
+    /// Reads an RGBA pixel of a [`ColorKind::BlackAndWhite`](crate::ColorKind::BlackAndWhite)
+    /// tile, converting its reflectivity to a rate with `converter`
+    pub fn from_pixel(rgba: [u8; 4], converter: &RateConverter) -> Self {
+        let [value, _, _, alpha] = rgba;
+        if alpha == 0 {
+            return Self::None;
+        }
+        let (dbz, kind) = decode_black_and_white(value);
+        let intensity = Intensity {
+            dbz,
+            rate: converter.rate(dbz, kind),
+        };
+        match kind {
+            PrecipKind::Rain => Self::Rain(intensity),
+            PrecipKind::Snow => Self::Snow(intensity),
+        }
+    }
+
+    /// The kind of precipitation falling, if any
+    pub fn kind(&self) -> Option<PrecipKind> {
+        match self {
+            Self::None => None,
+            Self::Rain(_) => Some(PrecipKind::Rain),
+            Self::Snow(_) => Some(PrecipKind::Snow),
+        }
+    }
+
+    /// How strongly precipitation falls, if any does
+    pub fn intensity(&self) -> Option<Intensity> {
+        match self {
+            Self::None => None,
+            Self::Rain(intensity) | Self::Snow(intensity) => Some(*intensity),
+        }
+    }
+
+    /// Returns true if rain or snow falls
+    pub fn is_precipitating(&self) -> bool {
+        !matches!(self, Self::None)
+    }
+}
+
+/// Whether rain, snow or both fall around a point, see [`PrecipPhase::from_pixels`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PrecipPhase {
+    Rain,
+    Snow,
+    /// Both rain and snow, usually sleet or wet snow near the freezing line
+    Mixed,
+}
+
+impl PrecipPhase {
+    /// The smallest share of the pixels with precipitation a kind needs for both to count as
+    /// [`PrecipPhase::Mixed`], so that a stray pixel of the other kind does not turn rain or
+    /// snow into mixed precipitation
+    const MIXED_FRACTION: f64 = 0.2;
+
+    /// Classifies RGBA pixels of a [`ColorKind::BlackAndWhite`](crate::ColorKind::BlackAndWhite)
+    /// tile requested with snow, such as the pixels around a point, or returns `None` if none
+    /// show precipitation
+    ///
+    /// Black and white tiles encode snow in its own band of values, see
+    /// [`decode_black_and_white`]. A single pixel is either rain or snow, so mixed
+    /// precipitation is told apart by sampling several pixels: it is reported when both kinds
+    /// cover at least a fifth of the pixels with precipitation.
+    ///
+    /// ```
+    /// use rain_viewer::precip::PrecipPhase;
+    ///
+    /// let rain = [72, 72, 72, 255];
+    /// let snow = [200, 200, 200, 255];
+    /// assert_eq!(PrecipPhase::from_pixels([rain, rain, [0; 4]]), Some(PrecipPhase::Rain));
+    /// assert_eq!(PrecipPhase::from_pixels([rain, snow, snow]), Some(PrecipPhase::Mixed));
+    /// assert_eq!(PrecipPhase::from_pixels([[0; 4]]), None);
+    /// ```
+    pub fn from_pixels(pixels: impl IntoIterator<Item = [u8; 4]>) -> Option<Self> {
+        let (mut rain, mut snow) = (0u32, 0u32);
+        for [value, _, _, alpha] in pixels {
+            if alpha == 0 {
+                continue;
+            }
+            match decode_black_and_white(value).1 {
+                PrecipKind::Rain => rain += 1,
+                PrecipKind::Snow => snow += 1,
+            }
+        }
+        let total = (rain + snow) as f64;
+        let share = |count: u32| count as f64 / total;
+        match (rain, snow) {
+            (0, 0) => None,
+            (rain, snow)
+                if share(rain) >= Self::MIXED_FRACTION && share(snow) >= Self::MIXED_FRACTION =>
+            {
+                Some(Self::Mixed)
+            }
+            (rain, snow) if rain > snow => Some(Self::Rain),
+            _ => Some(Self::Snow),
+        }
+    }
+
+    /// Classifies the pixels of `image` within `radius` pixels of `(x, y)` in each direction,
+    /// clipped to the image, see [`PrecipPhase::from_pixels`]
+    #[cfg(feature = "image")]
+    pub fn around(image: &image::RgbaImage, x: u32, y: u32, radius: u32) -> Option<Self> {
+        let columns = x.saturating_sub(radius)..x.saturating_add(radius + 1).min(image.width());
+        let rows = y.saturating_sub(radius)..y.saturating_add(radius + 1).min(image.height());
+        Self::from_pixels(rows.flat_map(|row| {
+            columns
+                .clone()
+                .map(move |column| image.get_pixel(column, row).0)
+        }))
+    }
+}
+
+/// Precipitation statistics over an area, such as for dashboards tracking fields or delivery
+/// zones, see [`WeatherRequester::region_stats`](crate::WeatherRequester::region_stats)
+///
+/// Pixels are counted equally, so over large areas the pixels nearer the poles, which cover
+/// less ground in web mercator, weigh more than their area.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RegionStats {
+    /// The mean reflectivity and rate of the pixels with precipitation, or `None` if the area
+    /// is dry. Multiply the rate by [`Self::covered_fraction`] for the mean over the whole area
+    pub mean: Option<Intensity>,
+    /// The pixel of the strongest reflectivity, or `None` if the area is dry
+    pub max: Option<Intensity>,
+    /// The fraction of the area with precipitation
+    pub covered_fraction: f64,
+    /// The fraction of the area with precipitation where it is snow
+    pub snow_fraction: f64,
+    /// The number of pixels read
+    pub pixels: u64,
+}
+
+impl RegionStats {
+    /// Summarizes the RGBA pixels of a [`ColorKind::BlackAndWhite`](crate::ColorKind::BlackAndWhite)
+    /// tile or mosaic, converting reflectivity to rates with `converter`
+    pub fn from_pixels(
+        pixels: impl IntoIterator<Item = [u8; 4]>,
+        converter: &RateConverter,
+    ) -> Self {
+        let (mut count, mut wet, mut snow) = (0u64, 0u64, 0u64);
+        let (mut dbz_sum, mut rate_sum) = (0.0f64, 0.0f64);
+        let mut max: Option<Intensity> = None;
+        for rgba in pixels {
+            count += 1;
+            let estimate = PrecipEstimate::from_pixel(rgba, converter);
+            let Some(intensity) = estimate.intensity() else {
+                continue;
+            };
+            wet += 1;
+            if estimate.kind() == Some(PrecipKind::Snow) {
+                snow += 1;
+            }
+            dbz_sum += intensity.dbz as f64;
+            rate_sum += intensity.rate as f64;
+            if max.is_none_or(|max| intensity.dbz > max.dbz) {
+                max = Some(intensity);
+            }
+        }
+        let fraction = |part: u64, whole: u64| match whole {
+            0 => 0.0,
+            whole => part as f64 / whole as f64,
+        };
+        Self {
+            mean: (wet > 0).then(|| Intensity {
+                dbz: (dbz_sum / wet as f64) as f32,
+                rate: (rate_sum / wet as f64) as f32,
+            }),
+            max,
+            covered_fraction: fraction(wet, count),
+            snow_fraction: fraction(snow, wet),
+            pixels: count,
+        }
+    }
+}
+
+/// Whether precipitation over an area is strengthening, see [`IntensityTrend`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TrendDirection {
+    /// Growing by more than 10% of its mean per hour
+    Strengthening,
+    Steady,
+    /// Shrinking by more than 10% of its mean per hour
+    Weakening,
+}
+
+/// How the precipitation over an area changed across frames, such as for "storm
+/// strengthening" indicators, see
+/// [`WeatherRequester::region_trend`](crate::WeatherRequester::region_trend)
+///
+/// Changes are the slopes of least squares lines through the frames, per hour. The direction
+/// follows the areal rate, the mean rate over the whole area with dry pixels as zero, so it
+/// reflects both more intense and more widespread precipitation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntensityTrend {
+    pub direction: TrendDirection,
+    /// The change of the areal rate in mm/h per hour
+    pub rate_change: f64,
+    /// The change of the areal rate per hour as a fraction of its mean, or `None` if the area
+    /// stayed dry
+    pub relative_change: Option<f64>,
+    /// The change of [`RegionStats::covered_fraction`] per hour
+    pub coverage_change: f64,
+    /// The change of the strongest reflectivity in dBZ per hour, over the frames with
+    /// precipitation, or `None` if fewer than two had any
+    pub max_dbz_change: Option<f64>,
+    /// The statistics of each frame, in chronological order
+    pub samples: Vec<(Timestamp, RegionStats)>,
+}
+
+impl IntensityTrend {
+    /// The relative change per hour beyond which precipitation is strengthening or weakening
+    const STEADY: f64 = 0.1;
+
+    /// Fits the trend of `samples`, or returns `None` if they span less than two distinct
+    /// times
+    pub fn from_samples(mut samples: Vec<(Timestamp, RegionStats)>) -> Option<Self> {
+        samples.sort_by_key(|(time, _)| *time);
+        let start = samples.first()?.0;
+        let hours = |time: Timestamp| timestamp::hours_between(start, time);
+        let areal = |stats: &RegionStats| {
+            stats.mean.map_or(0.0, |mean| mean.rate as f64) * stats.covered_fraction
+        };
+
+        let points = |value: &dyn Fn(&RegionStats) -> Option<f64>| -> Vec<(f64, f64)> {
+            samples
+                .iter()
+                .filter_map(|(time, stats)| Some((hours(*time), value(stats)?)))
+                .collect()
+        };
+        let rates = points(&|stats| Some(areal(stats)));
+        let rate_change = slope(&rates)?;
+        let coverage_change = slope(&points(&|stats| Some(stats.covered_fraction)))?;
+        let max_dbz_change = slope(&points(&|stats| Some(stats.max?.dbz as f64)));
+
+        let mean = rates.iter().map(|(_, rate)| rate).sum::<f64>() / rates.len() as f64;
+        let relative_change = (mean > 0.0).then(|| rate_change / mean);
+        let direction = match relative_change {
+            Some(change) if change > Self::STEADY => TrendDirection::Strengthening,
+            Some(change) if change < -Self::STEADY => TrendDirection::Weakening,
+            _ => TrendDirection::Steady,
+        };
+        Some(Self {
+            direction,
+            rate_change,
+            relative_change,
+            coverage_change,
+            max_dbz_change,
+            samples,
+        })
+    }
+}
+
+/// The slope of the least squares line through `points`, or `None` if they share one `x`
+fn slope(points: &[(f64, f64)]) -> Option<f64> {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let (covariance, variance) =
+        points
+            .iter()
+            .fold((0.0, 0.0), |(covariance, variance), (x, y)| {
+                let dx = x - mean_x;
+                (covariance + dx * (y - mean_y), variance + dx * dx)
+            });
+    (variance > 0.0).then(|| covariance / variance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn black_and_white() {
+        assert_eq!(decode_black_and_white(32), (0.0, PrecipKind::Rain));
+        assert_eq!(decode_black_and_white(127), (95.0, PrecipKind::Rain));
+        assert_eq!(decode_black_and_white(128), (-32.0, PrecipKind::Snow));
+        assert_eq!(decode_black_and_white(180), (20.0, PrecipKind::Snow));
+    }
+
+    #[test]
+    fn relationships() {
+        let mp = ZrRelationship::default();
+        // 23 dBZ is about 1 mm/h of stratiform rain
+        assert!((mp.rate(23.0) - 1.0).abs() < 0.02);
+        for dbz in [-10.0, 15.0, 40.0, 60.0] {
+            assert!((mp.dbz(mp.rate(dbz)) - dbz).abs() < 1e-3);
+        }
+        assert!(ZrRelationship::NEXRAD_CONVECTIVE.rate(50.0) > mp.rate(50.0));
+    }
+
+    #[test]
+    fn converter() {
+        let converter = RateConverter::new();
+        assert_eq!(converter.rate_for_pixel([72, 72, 72, 0]), None);
+        let rain = converter.rate_for_pixel([72, 72, 72, 255]).unwrap();
+        let snow = converter.rate_for_pixel([200, 200, 200, 255]).unwrap();
+        assert_eq!(rain, ZrRelationship::MARSHALL_PALMER.rate(40.0));
+        assert_eq!(snow, ZrRelationship::GUNN_MARSHALL_SNOW.rate(40.0));
+
+        let tropical = converter.with_rain(ZrRelationship::ROSENFELD_TROPICAL);
+        assert_eq!(
+            tropical.rate(40.0, PrecipKind::Rain),
+            ZrRelationship::ROSENFELD_TROPICAL.rate(40.0)
+        );
+    }
+
+    #[test]
+    fn estimates() {
+        let converter = RateConverter::new();
+        assert_eq!(
+            PrecipEstimate::from_pixel([72, 72, 72, 0], &converter),
+            PrecipEstimate::None
+        );
+        let rain = PrecipEstimate::from_pixel([72, 72, 72, 255], &converter);
+        assert_eq!(
+            rain,
+            PrecipEstimate::Rain(Intensity {
+                dbz: 40.0,
+                rate: ZrRelationship::MARSHALL_PALMER.rate(40.0),
+            })
+        );
+        assert!(rain.is_precipitating());
+        let snow = PrecipEstimate::from_pixel([180, 180, 180, 255], &converter);
+        assert_eq!(snow.kind(), Some(PrecipKind::Snow));
+        assert_eq!(snow.intensity().unwrap().dbz, 20.0);
+        assert_eq!(PrecipEstimate::None.intensity(), None);
+    }
+
+    #[test]
+    fn region_stats() {
+        let converter = RateConverter::new();
+        // Dry, 20 and 40 dBZ of rain, and 30 dBZ of snow
+        let pixels = [
+            [0, 0, 0, 0],
+            [52, 52, 52, 255],
+            [72, 72, 72, 255],
+            [190, 190, 190, 255],
+        ];
+        let stats = RegionStats::from_pixels(pixels, &converter);
+        assert_eq!(stats.pixels, 4);
+        assert_eq!(stats.covered_fraction, 0.75);
+        assert_eq!(stats.snow_fraction, 1.0 / 3.0);
+        let mean = stats.mean.unwrap();
+        assert_eq!(mean.dbz, 30.0);
+        let rates = converter.rate(20.0, PrecipKind::Rain)
+            + converter.rate(40.0, PrecipKind::Rain)
+            + converter.rate(30.0, PrecipKind::Snow);
+        assert!((mean.rate - rates / 3.0).abs() < 1e-4);
+        assert_eq!(stats.max.unwrap().dbz, 40.0);
+
+        let dry = RegionStats::from_pixels([[0, 0, 0, 0]], &converter);
+        assert_eq!((dry.mean, dry.max), (None, None));
+        assert_eq!((dry.covered_fraction, dry.snow_fraction), (0.0, 0.0));
+        assert_eq!(RegionStats::from_pixels([], &converter).pixels, 0);
+    }
+
+    #[test]
+    fn trends() {
+        let converter = RateConverter::new();
+        let at = |minutes: i64| timestamp::from_unix_seconds(1697000400 + minutes * 60).unwrap();
+        let stats = |value: u8, covered: usize| {
+            let pixels = (0..4).map(|i| {
+                if i < covered {
+                    [value, value, value, 255]
+                } else {
+                    [0; 4]
+                }
+            });
+            RegionStats::from_pixels(pixels, &converter)
+        };
+
+        // Rain spreading from one to three quarters of the area over 20 minutes, and
+        // intensifying from 20 to 40 dBZ
+        let trend = IntensityTrend::from_samples(vec![
+            (at(20), stats(72, 3)),
+            (at(0), stats(52, 1)),
+            (at(10), stats(62, 2)),
+        ])
+        .unwrap();
+        assert_eq!(trend.direction, TrendDirection::Strengthening);
+        assert_eq!(trend.samples[0].0, at(0));
+        assert!((trend.coverage_change - 1.5).abs() < 1e-9);
+        assert!((trend.max_dbz_change.unwrap() - 60.0).abs() < 1e-9);
+        assert!(trend.rate_change > 0.0 && trend.relative_change.unwrap() > 0.1);
+
+        let trend =
+            IntensityTrend::from_samples(vec![(at(0), stats(72, 4)), (at(60), stats(72, 2))])
+                .unwrap();
+        assert_eq!(trend.direction, TrendDirection::Weakening);
+        assert_eq!(trend.max_dbz_change, Some(0.0));
+
+        let trend =
+            IntensityTrend::from_samples(vec![(at(0), stats(72, 4)), (at(10), stats(0, 0))])
+                .unwrap();
+        assert_eq!(trend.max_dbz_change, None);
+        let dry = IntensityTrend::from_samples(vec![(at(0), stats(0, 0)), (at(10), stats(0, 0))])
+            .unwrap();
+        assert_eq!(dry.direction, TrendDirection::Steady);
+        assert_eq!(dry.relative_change, None);
+
+        assert!(IntensityTrend::from_samples(vec![(at(0), stats(72, 4))]).is_none());
+        assert!(IntensityTrend::from_samples(vec![]).is_none());
+    }
+
+    #[test]
+    fn phases() {
+        let (rain, snow, dry) = ([72, 72, 72, 255], [200, 200, 200, 255], [0; 4]);
+        assert_eq!(
+            PrecipPhase::from_pixels([snow, dry]),
+            Some(PrecipPhase::Snow)
+        );
+        // A stray pixel of snow among rain is not mixed
+        let mostly_rain = [rain, rain, rain, rain, rain, snow];
+        assert_eq!(
+            PrecipPhase::from_pixels(mostly_rain),
+            Some(PrecipPhase::Rain)
+        );
+        let sleet = [rain, rain, rain, rain, snow];
+        assert_eq!(PrecipPhase::from_pixels(sleet), Some(PrecipPhase::Mixed));
+        assert_eq!(PrecipPhase::from_pixels([]), None);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn phase_around() {
+        // Rain on the left half and snow on the right
+        let image = image::RgbaImage::from_fn(10, 10, |x, _| {
+            let value = if x < 5 { 72 } else { 200 };
+            image::Rgba([value, value, value, 255])
+        });
+        assert_eq!(
+            PrecipPhase::around(&image, 1, 5, 1),
+            Some(PrecipPhase::Rain)
+        );
+        assert_eq!(
+            PrecipPhase::around(&image, 9, 9, 2),
+            Some(PrecipPhase::Snow)
+        );
+        assert_eq!(
+            PrecipPhase::around(&image, 5, 5, 2),
+            Some(PrecipPhase::Mixed)
+        );
+        assert_eq!(
+            PrecipPhase::around(&image, 0, 0, 0),
+            Some(PrecipPhase::Rain)
+        );
+    }
+}
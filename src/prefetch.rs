@@ -0,0 +1,148 @@
+use std::collections::{BTreeMap, HashMap};
+
+use bytes::Bytes;
+use futures_util::stream::{FuturesUnordered, StreamExt};
+
+use crate::args::RequestArguments;
+use crate::data::{AvailableData, Frame};
+use crate::error::Error;
+use crate::player::AnimationPlayer;
+use crate::requester::WeatherRequester;
+
+/// Keeps the tiles of the frames an [`AnimationPlayer`] is about to show downloaded, so that
+/// playback and scrubbing do not stall on the network
+///
+/// Each call to [`Prefetcher::fill`] downloads the current frame, then the next `ahead` frames
+/// of the loop concurrently, and drops every other frame, so at most `ahead + 1` tiles are held.
+/// Frames are marked as loaded in the player as they arrive.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use rain_viewer::{AnimationPlayer, Prefetcher, RequestArguments, TileCoord, WeatherRequester};
+///
+/// # async fn run() -> Result<(), rain_viewer::Error> {
+/// let req = WeatherRequester::new();
+/// let maps = req.available().await?;
+/// let mut player = AnimationPlayer::from_radar(&maps);
+/// let args = RequestArguments::new_tile(TileCoord::new(4, 7, 6))?;
+/// let mut prefetcher = Prefetcher::new(req, args, 4);
+///
+/// player.play();
+/// loop {
+///     prefetcher.fill(&maps, &mut player).await;
+///     player.tick(Duration::from_millis(100));
+///     if let Some(png) = player.current().and_then(|frame| prefetcher.get(frame)) {
+///         // Display the tile
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Prefetcher {
+    requester: WeatherRequester,
+    args: RequestArguments,
+    ahead: usize,
+    tiles: HashMap<String, Bytes>,
+}
+
+impl Prefetcher {
+    /// A prefetcher downloading the tile of `args` for each frame, staying `ahead` frames ahead
+    /// of playback
+    pub fn new(requester: WeatherRequester, args: RequestArguments, ahead: usize) -> Self {
+        Self {
+            requester,
+            args,
+            ahead,
+            tiles: HashMap::new(),
+        }
+    }
+
+    /// The downloaded tile of `frame`, if it is held
+    pub fn get(&self, frame: &Frame) -> Option<&Bytes> {
+        self.tiles.get(&frame.path)
+    }
+
+    /// The number of frames held
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// Downloads the frames of `player` from its current frame to `ahead` frames after it that
+    /// are not held yet, and drops the others
+    ///
+    /// The current frame is downloaded before the upcoming ones, which are then downloaded
+    /// concurrently. Each frame is marked as loaded in `player` as soon as it is downloaded, and
+    /// dropped frames as unloaded. Dropping the returned future, such as when racing it against
+    /// the next redraw, keeps the frames downloaded so far, and the next call resumes with the
+    /// others.
+    ///
+    /// Returns the frames that failed to download by their index in the player's timeline.
+    /// They are marked as unloaded, so the player requests them again and the next call retries
+    /// them
+    pub async fn fill(
+        &mut self,
+        maps: &AvailableData,
+        player: &mut AnimationPlayer,
+    ) -> BTreeMap<usize, Error> {
+        let mut errors = BTreeMap::new();
+        if player.current().is_none() {
+            self.tiles.clear();
+            return errors;
+        }
+        let window: Vec<usize> = std::iter::once(player.current_index())
+            .chain(player.upcoming(self.ahead))
+            .collect();
+
+        let frames = player.frames();
+        self.tiles
+            .retain(|path, _| window.iter().any(|&i| frames[i].path == *path));
+        let evicted: Vec<usize> = (0..frames.len())
+            .filter(|i| !window.contains(i) && player.is_loaded(*i))
+            .collect();
+        for index in evicted {
+            player.mark_unloaded(index);
+        }
+
+        let mut missing = Vec::new();
+        for index in window {
+            let frame = &player.frames()[index];
+            if self.tiles.contains_key(&frame.path) {
+                player.mark_loaded(index);
+            } else {
+                missing.push((index, frame.clone()));
+            }
+        }
+
+        let requester = &self.requester;
+        let args = self.args;
+        let download = |(index, frame): (usize, Frame)| async move {
+            let result = requester.get_tile(maps, &frame, args).await;
+            (index, frame, result)
+        };
+        // The current frame is shown next, so it does not share the network with the others
+        let current = player.current_index();
+        let (first, rest): (Vec<_>, Vec<_>) = missing
+            .into_iter()
+            .partition(|(index, _)| *index == current);
+        for batch in [first, rest] {
+            let mut downloads: FuturesUnordered<_> = batch.into_iter().map(download).collect();
+            while let Some((index, frame, result)) = downloads.next().await {
+                match result {
+                    Ok(tile) => {
+                        self.tiles.insert(frame.path, tile);
+                        player.mark_loaded(index);
+                    }
+                    Err(err) => {
+                        player.mark_unloaded(index);
+                        errors.insert(index, err);
+                    }
+                }
+            }
+        }
+        errors
+    }
+}
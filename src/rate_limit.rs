@@ -0,0 +1,116 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The rate at which a [`WeatherRequester`](crate::WeatherRequester) may issue requests
+///
+/// Requests are limited with a token bucket: up to `requests` may be issued at once, after which
+/// further requests wait until the bucket refills at a rate of `requests` per `period`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    requests: u32,
+    period: Duration,
+}
+
+impl RateLimit {
+    /// Allows up to `requests` requests per `period`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `requests` or `period` is zero
+    pub fn new(requests: u32, period: Duration) -> Self {
+        assert!(requests > 0, "rate limit must allow at least one request");
+        assert!(!period.is_zero(), "rate limit period must not be zero");
+        Self { requests, period }
+    }
+
+    /// Allows up to `requests` requests per second
+    ///
+    /// # Panics
+    ///
+    /// Panics if `requests` is zero
+    pub fn per_second(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(1))
+    }
+
+    /// The time it takes for one token to be added back to the bucket
+    fn interval(&self) -> Duration {
+        self.period / self.requests
+    }
+}
+
+/// Token bucket shared by all clones of a requester
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    state: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Available tokens. Negative when requests are queued waiting for tokens
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            state: Mutex::new(Bucket {
+                tokens: limit.requests as f64,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token, returning how long the caller must wait before issuing its request
+    fn reserve(&self) -> Duration {
+        let mut bucket = self.state.lock().unwrap();
+        let now = Instant::now();
+        let refilled = now.duration_since(bucket.last_refill).as_secs_f64()
+            / self.limit.interval().as_secs_f64();
+        bucket.tokens = (bucket.tokens + refilled).min(self.limit.requests as f64) - 1.0;
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            self.limit.interval().mul_f64(-bucket.tokens)
+        }
+    }
+
+    /// Waits until a request may be issued
+    pub(crate) async fn acquire(&self) {
+        let wait = self.reserve();
+        if !wait.is_zero() {
+            futures_timer::Delay::new(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_bucket() {
+        let limiter = RateLimiter::new(RateLimit::new(2, Duration::from_secs(10)));
+        assert_eq!(limiter.reserve(), Duration::ZERO);
+        assert_eq!(limiter.reserve(), Duration::ZERO);
+
+        // The bucket is empty, so each request waits for one more interval than the last
+        let third = limiter.reserve();
+        let fourth = limiter.reserve();
+        assert!(third > Duration::from_millis(4900) && third <= Duration::from_secs(5));
+        assert!(fourth > Duration::from_millis(9900) && fourth <= Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn acquire_waits() {
+        let limiter = RateLimiter::new(RateLimit::new(1, Duration::from_millis(50)));
+        let start = Instant::now();
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
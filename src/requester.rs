@@ -0,0 +1,1465 @@
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures_util::future::Either;
+use futures_util::io::{AsyncWrite, AsyncWriteExt};
+use futures_util::{stream, Stream, StreamExt};
+
+use crate::args::{self, RequestArguments, RequestArgumentsInner, SatelliteArguments};
+use crate::cache::{TileCache, TileKey};
+use crate::coalesce::Coalescer;
+use crate::conditional::{Conditional, Validators};
+use crate::data::{AvailableData, Frame, RawAvailableData};
+use crate::error::{self, Error};
+use crate::geo::{tiles_in_bbox, LatLonBounds, TileCoord};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::rate_limit::{RateLimit, RateLimiter};
+use crate::transport::{HttpTransport, ReqwestTransport, ResponseSizeLimit};
+
+/// The host serving tiles which are not tied to a frame, such as the radar coverage layer
+pub(crate) const TILE_CACHE_HOST: &str = "https://tilecache.rainviewer.com";
+
+/// The number of coverage tiles [`WeatherRequester::has_coverage`] keeps in memory
+#[cfg(feature = "image")]
+const COVERAGE_CACHE_ENTRIES: usize = 64;
+
+/// The bytes of coverage tiles [`WeatherRequester::has_coverage`] keeps in memory
+#[cfg(feature = "image")]
+const COVERAGE_CACHE_BYTES: usize = 4 * 1024 * 1024;
+
+/// The endpoint listing the available frames
+pub(crate) const WEATHER_MAPS_URL: &str = "https://api.rainviewer.com/public/weather-maps.json";
+
+/// Builds the key of a radar tile, checking that `frame` holds radar imagery
+pub(crate) fn radar_tile_key(
+    maps: &AvailableData,
+    frame: &Frame,
+    args: &RequestArguments,
+) -> Result<TileKey, error::ParameterError> {
+    frame.expect_radar()?;
+    let url = match &args.inner {
+        RequestArgumentsInner::Tile(args) => args.url(&maps.host, &frame.path),
+    };
+    Ok(TileKey::new(&frame.path, Some(frame.kind), url))
+}
+
+/// The tile at [`POINT_ZOOM`](crate::POINT_ZOOM) covering the given WGS84 location, along
+/// with the arguments requesting it in black and white with snow, for reading precipitation
+#[cfg(feature = "image")]
+pub(crate) fn point_args(
+    lat: f64,
+    lon: f64,
+) -> Result<(TileCoord, RequestArguments), error::ParameterError> {
+    // Validates the location, which tile coordinates would clamp
+    RequestArguments::new_position(lat, lon, crate::POINT_ZOOM)?;
+    let tile = crate::geo::lat_lon_to_tile(lat, lon, crate::POINT_ZOOM);
+    let mut args = RequestArguments::new_tile(tile)?;
+    args.set_color(crate::ColorKind::BlackAndWhite)
+        .set_smooth(false)
+        .set_snow(true);
+    Ok((tile, args))
+}
+
+/// The pixel of `image`, the decoded `tile`, covering the given WGS84 location
+///
+/// Clamped rather than checked, as points on the southern web mercator limit fall on the
+/// bottom edge of the last row of tiles
+#[cfg(feature = "image")]
+pub(crate) fn point_pixel(
+    image: &image::RgbaImage,
+    tile: TileCoord,
+    lat: f64,
+    lon: f64,
+) -> (u32, u32) {
+    let (x, y) = crate::geo::lat_lon_to_tile_fraction(lat, lon, tile.z);
+    let pixel =
+        |offset: f64, size: u32| ((offset * size as f64) as u32).min(size.saturating_sub(1));
+    (
+        pixel(x - tile.x as f64, image.width()),
+        pixel(y - tile.y as f64, image.height()),
+    )
+}
+
+/// Builds the key of a satellite tile, checking that `frame` holds satellite imagery
+pub(crate) fn satellite_tile_key(
+    maps: &AvailableData,
+    frame: &Frame,
+    args: &SatelliteArguments,
+) -> Result<TileKey, error::ParameterError> {
+    frame.expect_satellite()?;
+    let url = args.url(&maps.host, &frame.path);
+    Ok(TileKey::new(&frame.path, Some(frame.kind), url))
+}
+
+/// Builds the key of a radar coverage tile
+pub(crate) fn coverage_tile_key(
+    x: u32,
+    y: u32,
+    zoom: u32,
+) -> Result<TileKey, error::ParameterError> {
+    let url = args::coverage_url(TILE_CACHE_HOST, x, y, zoom)?;
+    Ok(TileKey::new(args::COVERAGE_PATH, None, url))
+}
+
+/// The signature every PNG file starts with
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Checks that a tile response holds a PNG image, rather than an error page served with a
+/// success status
+pub(crate) fn check_png(url: &str, res: &http::Response<Bytes>) -> Result<(), error::Error> {
+    check_png_content_type(url, res.headers())?;
+    check_png_signature(url, res.body())
+}
+
+/// Checks the content type of a tile response, if it has one
+fn check_png_content_type(url: &str, headers: &http::HeaderMap) -> Result<(), error::Error> {
+    if let Some(content_type) = headers.get(http::header::CONTENT_TYPE) {
+        let content_type = content_type.to_str().unwrap_or_default();
+        if !content_type.starts_with("image/png") {
+            return Err(Error::InvalidImage {
+                url: url.to_owned(),
+                reason: format!("unexpected content type {content_type:?}"),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `body` starts with the PNG signature
+fn check_png_signature(url: &str, body: &[u8]) -> Result<(), error::Error> {
+    if !body.starts_with(PNG_SIGNATURE) {
+        return Err(Error::InvalidImage {
+            url: url.to_owned(),
+            reason: "missing PNG signature".to_owned(),
+        });
+    }
+    Ok(())
+}
+
+/// How long to wait before retrying a rate limited request without a `Retry-After` header
+#[cfg(not(target_arch = "wasm32"))]
+const DEFAULT_RETRY_AFTER: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// The User-Agent sent when none is configured on the [`WeatherRequesterBuilder`]
+const DEFAULT_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Issues requests to the Rain Viewer API
+///
+/// Cloning a requester is cheap and shares the underlying connection
+///
+/// Concurrent requests for the same url made through a requester or its clones are coalesced
+/// into a single HTTP request whose response is shared
+#[derive(Clone)]
+pub struct WeatherRequester {
+    transport: Arc<dyn HttpTransport>,
+    coalescer: Arc<Coalescer>,
+    #[cfg(not(target_arch = "wasm32"))]
+    rate_limiter: Option<Arc<RateLimiter>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    rate_limit_retries: u32,
+    #[cfg(not(target_arch = "wasm32"))]
+    request_timeout: Option<std::time::Duration>,
+    max_response_size: Option<usize>,
+    #[cfg(feature = "cancellation")]
+    cancellation: Option<tokio_util::sync::CancellationToken>,
+    /// Cache layers, consulted in order
+    caches: Vec<Arc<dyn TileCache>>,
+    /// Coverage tiles read by [`Self::has_coverage`], which rarely change
+    #[cfg(feature = "image")]
+    coverage: Arc<crate::cache::MemoryCache>,
+}
+
+impl Default for WeatherRequester {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Configures the HTTP connection used by a [`WeatherRequester`]
+///
+/// Created by [`WeatherRequester::builder`]
+#[derive(Debug)]
+pub struct WeatherRequesterBuilder {
+    client: reqwest::ClientBuilder,
+    #[cfg(not(target_arch = "wasm32"))]
+    rate_limit: Option<RateLimit>,
+    #[cfg(not(target_arch = "wasm32"))]
+    rate_limit_retries: u32,
+    max_response_size: Option<usize>,
+}
+
+impl WeatherRequesterBuilder {
+    fn new() -> Self {
+        Self {
+            client: reqwest::Client::builder().user_agent(DEFAULT_USER_AGENT),
+            #[cfg(not(target_arch = "wasm32"))]
+            rate_limit: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            rate_limit_retries: 0,
+            max_response_size: None,
+        }
+    }
+
+    /// Sets the timeout for establishing a connection to Rain Viewer
+    ///
+    /// Not available on wasm32, where the browser manages connections
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn connect_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.client = self.client.connect_timeout(timeout);
+        self
+    }
+
+    /// Sets the timeout for each read of the response body. The timer resets after every
+    /// successful read
+    ///
+    /// Not available on wasm32, where the browser manages connections
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.client = self.client.read_timeout(timeout);
+        self
+    }
+
+    /// Sets a timeout for each whole request, from connecting until the response body has been
+    /// read
+    ///
+    /// Not available on wasm32, where the browser manages connections
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.client = self.client.timeout(timeout);
+        self
+    }
+
+    /// Routes requests through `proxy`. May be called multiple times to add more proxies
+    ///
+    /// Not available on wasm32, where the browser manages connections
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.client = self.client.proxy(proxy);
+        self
+    }
+
+    /// Sets the maximum number of idle connections kept open to each host. Raise this for bulk
+    /// downloads issuing many concurrent tile requests
+    ///
+    /// Not available on wasm32, where the browser manages connections
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.client = self.client.pool_max_idle_per_host(max);
+        self
+    }
+
+    /// Sets how long idle connections are kept open. `None` keeps them open indefinitely
+    ///
+    /// Not available on wasm32, where the browser manages connections
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn pool_idle_timeout(mut self, timeout: Option<std::time::Duration>) -> Self {
+        self.client = self.client.pool_idle_timeout(timeout);
+        self
+    }
+
+    /// Sets the interval of TCP keepalive probes. `None` disables keepalive
+    ///
+    /// Not available on wasm32, where the browser manages connections
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn tcp_keepalive(mut self, interval: Option<std::time::Duration>) -> Self {
+        self.client = self.client.tcp_keepalive(interval);
+        self
+    }
+
+    /// Issues requests over HTTP/3 (QUIC) without first negotiating it over HTTP/1 or HTTP/2.
+    /// Rain Viewer's CDN supports HTTP/3, which copes better with lossy cellular links
+    ///
+    /// The QUIC endpoint is bound when the requester is built, so [`Self::build`] must be called
+    /// from within a Tokio runtime
+    ///
+    /// Enabled with the `http3` feature
+    #[cfg(all(feature = "http3", not(target_arch = "wasm32")))]
+    pub fn http3_prior_knowledge(mut self) -> Self {
+        self.client = self.client.http3_prior_knowledge();
+        self
+    }
+
+    /// Limits how often requests are issued, see [`WeatherRequester::with_rate_limit`]
+    ///
+    /// Not available on wasm32
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// Retries requests rejected with `429 Too Many Requests`, see
+    /// [`WeatherRequester::with_rate_limit_retries`]
+    ///
+    /// Not available on wasm32
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn rate_limit_retries(mut self, retries: u32) -> Self {
+        self.rate_limit_retries = retries;
+        self
+    }
+
+    /// Limits the size of response bodies, see [`WeatherRequester::with_max_response_size`]
+    pub fn max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_size = Some(bytes);
+        self
+    }
+
+    /// Sets headers sent with every request
+    pub fn default_headers(mut self, headers: reqwest::header::HeaderMap) -> Self {
+        self.client = self.client.default_headers(headers);
+        self
+    }
+
+    /// Sets the User-Agent header sent with every request. Defaults to `rain_viewer/<version>`
+    pub fn user_agent<V>(mut self, user_agent: V) -> Self
+    where
+        V: TryInto<reqwest::header::HeaderValue>,
+        V::Error: Into<http::Error>,
+    {
+        self.client = self.client.user_agent(user_agent);
+        self
+    }
+
+    /// Creates the [`WeatherRequester`]
+    ///
+    /// Returns Err(...) if the HTTP client cannot be initialized, such as when the TLS backend
+    /// fails to load
+    pub fn build(self) -> Result<WeatherRequester, error::Error> {
+        let mut requester = WeatherRequester::with_client(self.client.build()?);
+        requester.max_response_size = self.max_response_size;
+        #[cfg(not(target_arch = "wasm32"))]
+        let requester = match self.rate_limit {
+            Some(limit) => requester.with_rate_limit(limit),
+            None => requester,
+        }
+        .with_rate_limit_retries(self.rate_limit_retries);
+        Ok(requester)
+    }
+}
+
+impl WeatherRequester {
+    /// Creates a requester with the default connection options
+    ///
+    /// # Panics
+    ///
+    /// Panics if the HTTP client cannot be initialized. Use [`WeatherRequester::builder`] to
+    /// handle this error instead
+    pub fn new() -> Self {
+        Self::builder()
+            .build()
+            .expect("Failed to initialize the HTTP client")
+    }
+
+    /// Returns a builder for configuring timeouts, proxies, connection pooling and headers
+    pub fn builder() -> WeatherRequesterBuilder {
+        WeatherRequesterBuilder::new()
+    }
+
+    /// Creates a requester that issues requests through an existing `client`, sharing its
+    /// connection pool, TLS configuration and default headers
+    ///
+    /// The client is used as is, so the default `rain_viewer` User-Agent is not applied
+    pub fn with_client(client: reqwest::Client) -> Self {
+        Self::with_transport(ReqwestTransport::new(client))
+    }
+
+    /// Creates a requester that issues requests through a [`reqwest_middleware`] client, so its
+    /// retry, tracing and caching middlewares apply to Rain Viewer requests
+    ///
+    /// Enabled with the `middleware` feature
+    #[cfg(feature = "middleware")]
+    pub fn with_middleware_client(client: reqwest_middleware::ClientWithMiddleware) -> Self {
+        Self::with_transport(crate::transport::MiddlewareTransport::new(client))
+    }
+
+    /// Creates a requester that issues requests through a custom [`HttpTransport`], such as
+    /// another HTTP library or a test double
+    pub fn with_transport<T: HttpTransport + 'static>(transport: T) -> Self {
+        Self {
+            transport: Arc::new(transport),
+            coalescer: Arc::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            rate_limiter: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            rate_limit_retries: 0,
+            #[cfg(not(target_arch = "wasm32"))]
+            request_timeout: None,
+            max_response_size: None,
+            #[cfg(feature = "cancellation")]
+            cancellation: None,
+            caches: Vec::new(),
+            #[cfg(feature = "image")]
+            coverage: Arc::new(crate::cache::MemoryCache::new(
+                COVERAGE_CACHE_ENTRIES,
+                COVERAGE_CACHE_BYTES,
+            )),
+        }
+    }
+
+    /// Serves tiles from `cache` when possible and stores downloaded tiles in it, so that
+    /// panning back to a previously viewed area does not download the same tiles again
+    ///
+    /// May be called multiple times to layer caches, such as a [`MemoryCache`] in front of a
+    /// `DiskCache`. Layers are consulted in the order they were added, and a tile found in a later
+    /// layer is stored in the earlier ones
+    ///
+    /// Caches are shared by clones of the returned requester. Conditional requests such as
+    /// [`Self::get_tile_if_modified`] bypass them
+    ///
+    /// [`MemoryCache`]: crate::MemoryCache
+    pub fn with_cache<C: TileCache + 'static>(mut self, cache: C) -> Self {
+        self.caches.push(Arc::new(cache));
+        self
+    }
+
+    /// Removes cached tiles of frames that are not in `maps`
+    async fn retain_frames(&self, maps: &AvailableData) {
+        if self.caches.is_empty() {
+            return;
+        }
+        let frames: HashSet<String> = maps
+            .past_radar
+            .iter()
+            .chain(&maps.nowcast_radar)
+            .chain(&maps.infrared_satellite)
+            .map(|frame| frame.path.clone())
+            .collect();
+        for cache in &self.caches {
+            cache.retain_frames(&frames).await;
+        }
+    }
+
+    /// Looks `key` up in each cache layer, filling the layers in front of the one it was found in
+    async fn cached(&self, key: &TileKey) -> Option<Bytes> {
+        for (i, cache) in self.caches.iter().enumerate() {
+            if let Some(tile) = cache.get(key).await {
+                for earlier in &self.caches[..i] {
+                    earlier.put(key, tile.clone()).await;
+                }
+                return Some(tile);
+            }
+        }
+        None
+    }
+
+    /// Abandons pending and future calls once `token` is cancelled, returning
+    /// [`Error::Cancelled`]
+    ///
+    /// Apply this to a clone to cancel a group of requests together, such as the tiles of a map
+    /// view that was panned away, without affecting other requests sharing the connection:
+    ///
+    /// ```no_run
+    /// # async fn run(req: &rain_viewer::WeatherRequester) -> Result<(), rain_viewer::Error> {
+    /// # let maps = req.available().await?;
+    /// # let frame = &maps.past_radar[0];
+    /// use tokio_util::sync::CancellationToken;
+    ///
+    /// let view = CancellationToken::new();
+    /// let view_req = req.clone().with_cancellation(view.clone());
+    /// # let args = rain_viewer::RequestArguments::new_tile(rain_viewer::TileCoord::new(4, 7, 6))?;
+    /// let tile = view_req.get_tile(&maps, frame, args);
+    ///
+    /// // Panned away, so the tile is no longer needed
+    /// view.cancel();
+    /// assert!(matches!(tile.await, Err(rain_viewer::Error::Cancelled)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Enabled with the `cancellation` feature
+    #[cfg(feature = "cancellation")]
+    pub fn with_cancellation(mut self, token: tokio_util::sync::CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Fails requests whose response body is larger than `bytes` with
+    /// [`Error::ResponseTooLarge`], instead of buffering arbitrarily large responses such as
+    /// pages served by captive portals. Unlimited by default
+    ///
+    /// The limit is passed to the transport as a [`ResponseSizeLimit`] request extension, which
+    /// the built in transports use to stop reading the body early
+    pub fn with_max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_size = Some(bytes);
+        self
+    }
+
+    /// Abandons calls that take longer than `timeout`, returning [`Error::Timeout`]. The
+    /// deadline covers the whole call, including rate limit waits and retries
+    ///
+    /// Unlike [`WeatherRequesterBuilder::timeout`], this works with any [`HttpTransport`] and can
+    /// be applied to a clone for individual requests, so an interactive app can give up on a
+    /// slow tile without affecting other requests sharing the connection:
+    ///
+    /// ```no_run
+    /// # async fn run(req: &rain_viewer::WeatherRequester) -> Result<(), rain_viewer::Error> {
+    /// # let maps = req.available().await?;
+    /// # let frame = &maps.past_radar[0];
+    /// # let args = rain_viewer::RequestArguments::new_tile(rain_viewer::TileCoord::new(4, 7, 6))?;
+    /// let png = req
+    ///     .clone()
+    ///     .with_request_timeout(std::time::Duration::from_millis(500))
+    ///     .get_tile(&maps, frame, args)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Not available on wasm32
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_request_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Limits how often this requester issues requests, so bulk downloads stay within Rain
+    /// Viewer's fair use limits. Requests over the limit wait until they are allowed
+    ///
+    /// The limit is shared by clones of the returned requester
+    ///
+    /// Not available on wasm32
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::new(limit)));
+        self
+    }
+
+    /// Retries requests rejected with `429 Too Many Requests` up to `retries` times, waiting for
+    /// the delay given by the `Retry-After` header, or one second if there is none. Defaults to 0,
+    /// in which case [`Error::RateLimited`] is returned immediately
+    ///
+    /// Not available on wasm32
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_rate_limit_retries(mut self, retries: u32) -> Self {
+        self.rate_limit_retries = retries;
+        self
+    }
+
+    /// Queries the Rain Viewer API for what current and historical data is available.
+    /// This function should serve as the entry point so that the caller has the correct path and time
+    /// information to call [`get_tile`]
+    ///
+    /// Cached tiles of frames that are no longer available are removed from the caches set by
+    /// [`Self::with_cache`]
+    pub async fn available(&self) -> Result<AvailableData, error::Error> {
+        let body = self.get(WEATHER_MAPS_URL).await?;
+        let raw: RawAvailableData = serde_json::from_slice(&body)?;
+
+        let maps = raw.into();
+        self.retain_frames(&maps).await;
+        Ok(maps)
+    }
+
+    /// Like [`Self::available`], but returns [`Conditional::NotModified`] instead of downloading
+    /// the frame list again if it is unchanged since `validators` were obtained
+    ///
+    /// Pollers should keep the validators of the last [`Conditional::Modified`] result and pass
+    /// them to the next call
+    pub async fn available_if_modified(
+        &self,
+        validators: &Validators,
+    ) -> Result<Conditional<AvailableData>, error::Error> {
+        let res = self.get_conditional(WEATHER_MAPS_URL, validators).await?;
+        Ok(match res {
+            Conditional::Modified(body, validators) => {
+                let raw: RawAvailableData = serde_json::from_slice(&body)?;
+                let maps = raw.into();
+                self.retain_frames(&maps).await;
+                Conditional::Modified(maps, validators)
+            }
+            Conditional::NotModified => Conditional::NotModified,
+        })
+    }
+
+    /// Hits the Rain Viewer API to obtain a single tile of rain for the world
+    ///
+    /// `maps` is the struct returned from [`available`]
+    ///
+    /// `frame` is the data frame indicating the moment in time to pull from. It must be a past or
+    /// nowcast radar frame, else Err(...) is returned
+    ///
+    /// See <https://www.rainviewer.com/api/weather-maps-api.html> for more details
+    pub async fn get_tile(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        args: RequestArguments,
+    ) -> Result<Bytes, error::Error> {
+        self.get_png(radar_tile_key(maps, frame, &args)?).await
+    }
+
+    /// Like [`WeatherRequester::get_tile`], but decodes the tile into an image
+    ///
+    /// Enabled with the `image` feature. Returns Err(...) if the tile is not a valid PNG image
+    #[cfg(feature = "image")]
+    pub async fn get_tile_image(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        args: RequestArguments,
+    ) -> Result<image::DynamicImage, error::Error> {
+        crate::tile::decode_png(&self.get_tile(maps, frame, args).await?)
+    }
+
+    /// Downloads every radar tile at `zoom` intersecting `bounds`
+    ///
+    /// The size, color and options of `args` apply to every tile, while its location and zoom
+    /// are ignored. `zoom` must be at most [`MAX_RADAR_ZOOM`](crate::MAX_RADAR_ZOOM) and
+    /// `frame` must be a radar frame, else Err(...) is returned. Tiles are downloaded one after
+    /// another, and the first failure aborts the whole region
+    pub async fn get_region(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        bounds: LatLonBounds,
+        zoom: u32,
+        args: RequestArguments,
+    ) -> Result<BTreeMap<TileCoord, Bytes>, error::Error> {
+        frame.expect_radar()?;
+        args::validate_zoom(zoom, crate::MAX_RADAR_ZOOM)?;
+        let mut tiles = BTreeMap::new();
+        for coord in tiles_in_bbox(&bounds, zoom) {
+            let tile = self.get_tile(maps, frame, args.for_tile(coord)?).await?;
+            tiles.insert(coord, tile);
+        }
+        Ok(tiles)
+    }
+
+    /// Renders a small preview of `frame` over `bounds`, at most `max_px` pixels wide and high,
+    /// such as for a frame picker or a notification
+    ///
+    /// The lowest zoom at which `bounds` spans `max_px` pixels is picked, up to
+    /// [`MAX_RADAR_ZOOM`](crate::MAX_RADAR_ZOOM), so as few tiles as possible are downloaded.
+    /// They are assembled with [`stitch`](crate::stitch), cropped to `bounds` and scaled down
+    /// so that the longer side is `max_px` pixels, keeping the aspect ratio. Black and white
+    /// tiles are scaled with [`Resampling::Nearest`](crate::Resampling::Nearest) to keep their
+    /// values, and others with [`Resampling::Bilinear`](crate::Resampling::Bilinear). The
+    /// size, color and options of `args` apply to every tile, while its location and zoom are
+    /// ignored.
+    ///
+    /// Enabled with the `image` feature. Returns Err(...) if `max_px` is zero, `frame` is not a
+    /// radar frame, or a download fails
+    #[cfg(feature = "image")]
+    pub async fn thumbnail(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        bounds: LatLonBounds,
+        max_px: u32,
+        args: RequestArguments,
+    ) -> Result<image::DynamicImage, error::Error> {
+        if max_px == 0 {
+            return Err(error::ParameterError::InvalidSize(
+                max_px,
+                "A thumbnail must be at least one pixel wide and high".to_owned(),
+            )
+            .into());
+        }
+        let RequestArgumentsInner::Tile(tile) = args.inner;
+        let zoom =
+            crate::mosaic::thumbnail_zoom(&bounds, max_px, tile.size.into(), crate::MAX_RADAR_ZOOM);
+        let tiles = self.get_region(maps, frame, bounds, zoom, args).await?;
+        let mosaic = crate::stitch(&tiles)?;
+        let cropped = crate::crop_to_bounds(&mosaic, &tiles, &bounds)?;
+
+        let scale = (max_px as f64 / cropped.width().max(cropped.height()) as f64).min(1.0);
+        let to_size = |pixels: u32| ((pixels as f64 * scale).round() as u32).max(1);
+        let thumbnail = crate::resize(
+            &cropped,
+            to_size(cropped.width()),
+            to_size(cropped.height()),
+            crate::Resampling::for_color(tile.color),
+        )?;
+        Ok(image::DynamicImage::ImageRgba8(thumbnail))
+    }
+
+    /// Renders the radar tile at `coord` from tiles of a neighbouring zoom level, either by
+    /// upscaling a quarter of its parent or by downsampling its four children
+    ///
+    /// See [`upsample_from_ancestor`](crate::upsample_from_ancestor) and
+    /// [`downsample_children`](crate::downsample_children) to resample tiles that were already
+    /// downloaded, such as a whole region at one zoom level. Black and white tiles are
+    /// resampled with [`Resampling::Nearest`](crate::Resampling::Nearest) to keep their values,
+    /// and others with [`Resampling::Bilinear`](crate::Resampling::Bilinear). The size, color
+    /// and options of `args` apply to the downloaded tiles, while its location and zoom are
+    /// ignored. The children are downloaded concurrently.
+    ///
+    /// Enabled with the `image` feature. Returns Err(...) if `frame` is not a radar frame,
+    /// `coord` is at zoom 0 with [`PyramidSource::Parent`](crate::PyramidSource::Parent), the
+    /// tiles to download are beyond [`MAX_RADAR_ZOOM`](crate::MAX_RADAR_ZOOM), or a download
+    /// fails
+    #[cfg(feature = "image")]
+    pub async fn get_resampled_tile(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        coord: TileCoord,
+        source: crate::PyramidSource,
+        args: RequestArguments,
+    ) -> Result<image::DynamicImage, error::Error> {
+        coord.validate()?;
+        let RequestArgumentsInner::Tile(tile) = args.inner;
+        let resampling = crate::Resampling::for_color(tile.color);
+        let image = match source {
+            crate::PyramidSource::Parent => {
+                let parent = coord.parent().ok_or_else(|| {
+                    error::ParameterError::InvalidZoom(
+                        coord.z,
+                        "Tiles at zoom 0 have no parent".to_owned(),
+                    )
+                })?;
+                let png = self.get_tile(maps, frame, args.for_tile(parent)?).await?;
+                let parent_image = crate::tile::decode_png(&png)?;
+                crate::upsample_from_ancestor(&parent_image, parent, coord, resampling)?
+            }
+            crate::PyramidSource::Children => {
+                if coord.z >= crate::MAX_RADAR_ZOOM {
+                    return Err(error::ParameterError::InvalidZoom(
+                        coord.z + 1,
+                        "The children of the tile are beyond the maximum radar zoom".to_owned(),
+                    )
+                    .into());
+                }
+                let children = coord.children();
+                let requests = children
+                    .iter()
+                    .map(|&child| Ok(self.get_tile(maps, frame, args.for_tile(child)?)))
+                    .collect::<Result<Vec<_>, error::ParameterError>>()?;
+                let pngs = futures_util::future::try_join_all(requests).await?;
+                let tiles: BTreeMap<_, _> = children.into_iter().zip(pngs).collect();
+                crate::downsample_children(&tiles, coord, resampling)?
+            }
+        };
+        Ok(image::DynamicImage::ImageRgba8(image))
+    }
+
+    /// Answers whether rain or snow is falling at the given WGS84 location, from the latest past
+    /// radar frame
+    ///
+    /// The frames are listed with [`Self::available`], and the black and white tile covering
+    /// the location is downloaded at [`POINT_ZOOM`](crate::POINT_ZOOM) to read the reflectivity
+    /// of its pixel. Rates are estimated with the default [`RateConverter`].
+    ///
+    /// ```no_run
+    /// use rain_viewer::{precip::PrecipEstimate, WeatherRequester};
+    ///
+    /// # async fn run() -> Result<(), rain_viewer::Error> {
+    /// let req = WeatherRequester::new();
+    /// match req.is_raining_at(51.5074, -0.1278).await? {
+    ///     PrecipEstimate::None => println!("Dry"),
+    ///     PrecipEstimate::Rain(intensity) => println!("Rain, {:.1} mm/h", intensity.rate),
+    ///     PrecipEstimate::Snow(intensity) => println!("Snow, {:.1} mm/h", intensity.rate),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Enabled with the `image` feature. Returns Err(...) if `lat` is beyond the web mercator
+    /// limits of +/-85.0511 degrees, `lon` is beyond +/-180 degrees, no past radar frames are
+    /// available, or a download fails
+    ///
+    /// [`RateConverter`]: crate::precip::RateConverter
+    #[cfg(feature = "image")]
+    pub async fn is_raining_at(
+        &self,
+        lat: f64,
+        lon: f64,
+    ) -> Result<crate::precip::PrecipEstimate, error::Error> {
+        let maps = self.available().await?;
+        let frame = maps
+            .latest_past()
+            .ok_or(Error::NoFrames(crate::data::FrameKind::PastRadar))?;
+        self.precip_at(&maps, frame, lat, lon).await
+    }
+
+    /// Tells whether rain, snow or a mix of both is falling at the given WGS84 location, from
+    /// the latest past radar frame, or `None` if nothing is
+    ///
+    /// The black and white tile covering the location is downloaded at
+    /// [`POINT_ZOOM`](crate::POINT_ZOOM) with snow enabled, and the pixels within 2 pixels of
+    /// the location, a few kilometers across, are classified with
+    /// [`PrecipPhase::from_pixels`](crate::precip::PrecipPhase::from_pixels).
+    ///
+    /// ```no_run
+    /// use rain_viewer::{precip::PrecipPhase, WeatherRequester};
+    ///
+    /// # async fn run() -> Result<(), rain_viewer::Error> {
+    /// let req = WeatherRequester::new();
+    /// if req.precip_phase_at(46.5197, 6.6323).await? == Some(PrecipPhase::Mixed) {
+    ///     println!("Sleet");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Enabled with the `image` feature. Returns Err(...) if `lat` is beyond the web mercator
+    /// limits of +/-85.0511 degrees, `lon` is beyond +/-180 degrees, no past radar frames are
+    /// available, or a download fails
+    #[cfg(feature = "image")]
+    pub async fn precip_phase_at(
+        &self,
+        lat: f64,
+        lon: f64,
+    ) -> Result<Option<crate::precip::PrecipPhase>, error::Error> {
+        let maps = self.available().await?;
+        let frame = maps
+            .latest_past()
+            .ok_or(Error::NoFrames(crate::data::FrameKind::PastRadar))?;
+        let (image, x, y) = self.point_tile(&maps, frame, lat, lon).await?;
+        Ok(crate::precip::PrecipPhase::around(&image, x, y, 2))
+    }
+
+    /// Reads the precipitation at the given WGS84 location in every past and nowcast radar
+    /// frame, in chronological order, such as for "rain in the next hour" widgets
+    ///
+    /// Each frame is read like [`Self::is_raining_at`], and the frames are downloaded
+    /// concurrently. Entries after [`AvailableData::latest_past`] are forecasts. Pass the
+    /// entries to a chart as they are, since frames are usually 10 minutes apart but may skip
+    /// times the radar network missed.
+    ///
+    /// ```no_run
+    /// use rain_viewer::WeatherRequester;
+    ///
+    /// # async fn run() -> Result<(), rain_viewer::Error> {
+    /// let req = WeatherRequester::new();
+    /// let now = chrono::Utc::now();
+    /// let timeline = req.nowcast_at(51.5074, -0.1278).await?;
+    /// let rain = timeline
+    ///     .iter()
+    ///     .find(|(time, estimate)| *time > now && estimate.is_precipitating());
+    /// if let Some((time, _)) = rain {
+    ///     println!("Rain expected at {time}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Enabled with the `image` feature. Returns Err(...) if `lat` is beyond the web mercator
+    /// limits of +/-85.0511 degrees, `lon` is beyond +/-180 degrees, or a download fails
+    #[cfg(feature = "image")]
+    pub async fn nowcast_at(
+        &self,
+        lat: f64,
+        lon: f64,
+    ) -> Result<Vec<(crate::Timestamp, crate::precip::PrecipEstimate)>, error::Error> {
+        RequestArguments::new_position(lat, lon, crate::POINT_ZOOM)?;
+        let maps = self.available().await?;
+        let frames: Vec<&Frame> = maps.radar_timeline().map(|entry| entry.frame()).collect();
+        let estimates = futures_util::future::try_join_all(
+            frames
+                .iter()
+                .map(|frame| self.precip_at(&maps, frame, lat, lon)),
+        )
+        .await?;
+        Ok(frames
+            .iter()
+            .map(|frame| frame.time)
+            .zip(estimates)
+            .collect())
+    }
+
+    /// Summarizes the precipitation over `bounds` in `frame`, from the black and white tiles at
+    /// `zoom` intersecting it, cropped to `bounds`
+    ///
+    /// Only the tiles covering `bounds` are downloaded, one after another, so lower zoom levels
+    /// are faster and coarser. Rates are estimated with the default [`RateConverter`].
+    ///
+    /// ```no_run
+    /// use rain_viewer::{LatLonBounds, WeatherRequester};
+    ///
+    /// # async fn run() -> Result<(), rain_viewer::Error> {
+    /// let req = WeatherRequester::new();
+    /// let maps = req.available().await?;
+    /// let frame = maps.latest_past().unwrap();
+    /// let bounds = LatLonBounds::new(-0.5, 51.3, 0.3, 51.7)?;
+    /// let stats = req.region_stats(&maps, frame, bounds, 8).await?;
+    /// println!(
+    ///     "{:.0}% covered, {:.0}% of it snow",
+    ///     stats.covered_fraction * 100.0,
+    ///     stats.snow_fraction * 100.0
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Enabled with the `image` feature. Returns Err(...) if `zoom` is beyond
+    /// [`MAX_RADAR_ZOOM`](crate::MAX_RADAR_ZOOM), `frame` is not a radar frame, or a download
+    /// fails
+    ///
+    /// [`RateConverter`]: crate::precip::RateConverter
+    #[cfg(feature = "image")]
+    pub async fn region_stats(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        bounds: LatLonBounds,
+        zoom: u32,
+    ) -> Result<crate::precip::RegionStats, error::Error> {
+        let mut args = RequestArguments::new_tile(TileCoord::new(0, 0, 0))?;
+        args.set_color(crate::ColorKind::BlackAndWhite)
+            .set_smooth(false)
+            .set_snow(true);
+        let tiles = self.get_region(maps, frame, bounds, zoom, args).await?;
+        let image = crate::crop_to_bounds(&crate::stitch(&tiles)?, &tiles, &bounds)?.into_rgba8();
+        Ok(crate::precip::RegionStats::from_pixels(
+            image.pixels().map(|pixel| pixel.0),
+            &crate::precip::RateConverter::default(),
+        ))
+    }
+
+    /// Measures whether the precipitation over `bounds` is strengthening or weakening across
+    /// `frames`, such as the last few past radar frames
+    ///
+    /// Each frame is summarized like [`Self::region_stats`], and the frames are downloaded
+    /// concurrently.
+    ///
+    /// ```no_run
+    /// use rain_viewer::precip::TrendDirection;
+    /// use rain_viewer::{LatLonBounds, WeatherRequester};
+    ///
+    /// # async fn run() -> Result<(), rain_viewer::Error> {
+    /// let req = WeatherRequester::new();
+    /// let maps = req.available().await?;
+    /// let last_hour = &maps.past_radar[maps.past_radar.len().saturating_sub(6)..];
+    /// let bounds = LatLonBounds::new(-0.5, 51.3, 0.3, 51.7)?;
+    /// let trend = req.region_trend(&maps, last_hour, bounds, 8).await?;
+    /// if trend.direction == TrendDirection::Strengthening {
+    ///     println!("Precipitation is strengthening by {:.1} mm/h per hour", trend.rate_change);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Enabled with the `image` feature. Returns [`Error::TooFewFrames`] without downloading
+    /// anything if `frames` span less than two distinct times. Returns Err(...) if `zoom` is beyond [`MAX_RADAR_ZOOM`](crate::MAX_RADAR_ZOOM), a frame is
+    /// not a radar frame, or a download fails
+    #[cfg(feature = "image")]
+    pub async fn region_trend(
+        &self,
+        maps: &AvailableData,
+        frames: &[Frame],
+        bounds: LatLonBounds,
+        zoom: u32,
+    ) -> Result<crate::precip::IntensityTrend, error::Error> {
+        let times: HashSet<_> = frames.iter().map(|frame| frame.time).collect();
+        if times.len() < 2 {
+            return Err(Error::TooFewFrames(times.len()));
+        }
+        let stats = futures_util::future::try_join_all(
+            frames
+                .iter()
+                .map(|frame| self.region_stats(maps, frame, bounds, zoom)),
+        )
+        .await?;
+        let samples = frames.iter().map(|frame| frame.time).zip(stats).collect();
+        crate::precip::IntensityTrend::from_samples(samples).ok_or(Error::TooFewFrames(times.len()))
+    }
+
+    /// Reads the precipitation at the given WGS84 location from the black and white tile of
+    /// `frame` covering it at [`POINT_ZOOM`](crate::POINT_ZOOM)
+    #[cfg(feature = "image")]
+    pub(crate) async fn precip_at(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        lat: f64,
+        lon: f64,
+    ) -> Result<crate::precip::PrecipEstimate, error::Error> {
+        let (image, x, y) = self.point_tile(maps, frame, lat, lon).await?;
+        Ok(crate::precip::PrecipEstimate::from_pixel(
+            image.get_pixel(x, y).0,
+            &crate::precip::RateConverter::default(),
+        ))
+    }
+
+    /// Downloads the black and white tile of `frame` covering the given WGS84 location at
+    /// [`POINT_ZOOM`](crate::POINT_ZOOM), along with the pixel of the location in it
+    #[cfg(feature = "image")]
+    async fn point_tile(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        lat: f64,
+        lon: f64,
+    ) -> Result<(image::RgbaImage, u32, u32), error::Error> {
+        let (tile, args) = point_args(lat, lon)?;
+        let image = self.get_tile_image(maps, frame, args).await?.into_rgba8();
+        let (x, y) = point_pixel(&image, tile, lat, lon);
+        Ok((image, x, y))
+    }
+
+    /// Like [`Self::get_region`], but yields tiles as they are downloaded instead of collecting
+    /// them, so that large regions can be decoded or written out without holding every tile in
+    /// memory
+    ///
+    /// Tiles are downloaded one after another, in the order of rows then columns, as the stream
+    /// is polled. A failed tile is yielded as an error and does not end the stream. If `frame`
+    /// is not a radar frame, or `zoom` is greater than [`MAX_RADAR_ZOOM`](crate::MAX_RADAR_ZOOM),
+    /// the stream yields a single error
+    pub fn get_region_stream<'a>(
+        &'a self,
+        maps: &'a AvailableData,
+        frame: &'a Frame,
+        bounds: LatLonBounds,
+        zoom: u32,
+        args: RequestArguments,
+    ) -> impl Stream<Item = Result<(TileCoord, Bytes), error::Error>> + 'a {
+        let valid = frame
+            .expect_radar()
+            .and_then(|()| args::validate_zoom(zoom, crate::MAX_RADAR_ZOOM));
+        if let Err(err) = valid {
+            return Either::Left(stream::once(std::future::ready(Err(err.into()))));
+        }
+        Either::Right(
+            stream::iter(tiles_in_bbox(&bounds, zoom)).then(move |coord| async move {
+                let key = radar_tile_key(maps, frame, &args.for_tile(coord)?)?;
+                Ok((coord, self.get_png(key).await?))
+            }),
+        )
+    }
+
+    /// Like [`Self::get_tile`], but writes the tile to `writer` as it is received instead of
+    /// buffering it, returning the number of bytes written
+    ///
+    /// Tiles found in the caches set by [`Self::with_cache`] are written from the cache.
+    /// Otherwise the tile is streamed from the transport, and is neither stored in the caches
+    /// nor shared with concurrent requests for the same tile. Rate limited responses are not
+    /// retried. The PNG signature is checked before anything is written, but `writer` may hold
+    /// part of the tile if the download fails midway
+    pub async fn get_tile_to(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        args: RequestArguments,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> Result<u64, error::Error> {
+        let key = radar_tile_key(maps, frame, &args)?;
+        if let Some(tile) = self.cached(&key).await {
+            writer.write_all(&tile).await?;
+            writer.flush().await?;
+            return Ok(tile.len() as u64);
+        }
+        self.deadline(self.stream_png(&key.url, writer)).await
+    }
+
+    /// Like [`Self::get_tile`], but returns [`Conditional::NotModified`] instead of downloading
+    /// the tile again if it is unchanged since `validators` were obtained
+    pub async fn get_tile_if_modified(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        args: RequestArguments,
+        validators: &Validators,
+    ) -> Result<Conditional<Bytes>, error::Error> {
+        let url = radar_tile_key(maps, frame, &args)?.url;
+        let res = self
+            .deadline(self.send(&url, validators.to_headers()))
+            .await?;
+        if res.status() == http::StatusCode::NOT_MODIFIED {
+            return Ok(Conditional::NotModified);
+        }
+        check_png(&url, &res)?;
+        let validators = Validators::from_headers(res.headers());
+        Ok(Conditional::Modified(res.into_body(), validators))
+    }
+
+    /// Hits the Rain Viewer API to obtain a single tile of infrared satellite imagery
+    ///
+    /// `maps` is the struct returned from [`available`]
+    ///
+    /// `frame` must be one of the frames from [`AvailableData::infrared_satellite`], else
+    /// Err(...) is returned
+    pub async fn get_satellite_tile(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        args: SatelliteArguments,
+    ) -> Result<Bytes, error::Error> {
+        self.get_png(satellite_tile_key(maps, frame, &args)?).await
+    }
+
+    /// Downloads a radar tile together with the infrared satellite tile at the same location
+    /// closest in time to `frame`, and draws the radar over the satellite imagery
+    ///
+    /// Radar and satellite frames are published on separate schedules, so the satellite frame
+    /// is picked with [`AvailableData::nearest_satellite_frame`]. The satellite tile has the
+    /// size and location of `args`, and `opacity` applies to the radar as in
+    /// [`compose_over`](crate::compose_over).
+    ///
+    /// Enabled with the `image` feature. Returns Err(...) if `frame` is not a radar frame,
+    /// `maps` lists no satellite frames, the zoom of `args` exceeds
+    /// [`MAX_SATELLITE_ZOOM`](crate::MAX_SATELLITE_ZOOM), or either download fails
+    #[cfg(feature = "image")]
+    pub async fn get_blended_tile(
+        &self,
+        maps: &AvailableData,
+        frame: &Frame,
+        args: RequestArguments,
+        opacity: f32,
+    ) -> Result<image::DynamicImage, error::Error> {
+        frame.expect_radar()?;
+        let satellite_frame = maps
+            .nearest_satellite_frame(frame.time)
+            .ok_or(Error::NoFrames(crate::data::FrameKind::Satellite))?;
+        let satellite_args = SatelliteArguments::matching(&args)?;
+        let (satellite, radar) = futures_util::future::try_join(
+            self.get_satellite_tile(maps, satellite_frame, satellite_args),
+            self.get_tile(maps, frame, args),
+        )
+        .await?;
+        crate::compose::compose_over(&satellite, &radar, opacity)
+    }
+
+    /// Hits the Rain Viewer API to obtain a single tile of the radar coverage layer
+    ///
+    /// Opaque pixels in the returned PNG mark regions without radar coverage, which can be used
+    /// to gray out areas where the absence of rain is meaningless
+    ///
+    /// `zoom` must be at most [`MAX_COVERAGE_ZOOM`](crate::MAX_COVERAGE_ZOOM) and `x` and `y`
+    /// must be less than `2^zoom`, or Err(...) is returned
+    pub async fn get_coverage_tile(
+        &self,
+        x: u32,
+        y: u32,
+        zoom: u32,
+    ) -> Result<Bytes, error::Error> {
+        self.get_png(coverage_tile_key(x, y, zoom)?).await
+    }
+
+    /// Answers whether radar covers the given WGS84 location, so that applications can show
+    /// "no radar data here" over oceans and gaps in the radar network instead of an empty
+    /// tile that looks dry
+    ///
+    /// The coverage tile containing the location is read at [`POINT_ZOOM`](crate::POINT_ZOOM),
+    /// see [`Self::get_coverage_tile`]. Coverage tiles rarely change, so the requester and its
+    /// clones keep the last 64 read in memory, besides any caches set by [`Self::with_cache`].
+    ///
+    /// ```no_run
+    /// use rain_viewer::WeatherRequester;
+    ///
+    /// # async fn run() -> Result<(), rain_viewer::Error> {
+    /// let req = WeatherRequester::new();
+    /// if !req.has_coverage(-48.8767, -123.3933).await? {
+    ///     println!("No radar data here");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Enabled with the `image` feature. Returns Err(...) if `lat` is beyond the web mercator
+    /// limits of +/-85.0511 degrees, `lon` is beyond +/-180 degrees, or a download fails
+    #[cfg(feature = "image")]
+    pub async fn has_coverage(&self, lat: f64, lon: f64) -> Result<bool, error::Error> {
+        RequestArguments::new_position(lat, lon, crate::POINT_ZOOM)?;
+        let tile = crate::geo::lat_lon_to_tile(lat, lon, crate::POINT_ZOOM);
+        let key = coverage_tile_key(tile.x, tile.y, tile.z)?;
+        let png = match crate::cache::MemoryCache::get(&self.coverage, &key) {
+            Some(png) => png,
+            None => {
+                let png = self.get_png(key.clone()).await?;
+                crate::cache::MemoryCache::put(&self.coverage, key, png.clone());
+                png
+            }
+        };
+        let image = crate::tile::decode_png(&png)?.into_rgba8();
+        let (x, y) = point_pixel(&image, tile, lat, lon);
+        // Opaque pixels mark the areas without coverage
+        Ok(image.get_pixel(x, y).0[3] == 0)
+    }
+
+    /// Downloads a tile, checking that the response is a PNG image
+    ///
+    /// Tiles are served from and stored in the caches set by [`Self::with_cache`], if any
+    pub(crate) async fn get_png(&self, key: TileKey) -> Result<Bytes, error::Error> {
+        if let Some(tile) = self.cached(&key).await {
+            return Ok(tile);
+        }
+        let url = &key.url;
+        let get = self.coalescer.run(url, || async {
+            let res = self.send(url, http::HeaderMap::new()).await?;
+            check_png(url, &res)?;
+            Ok(res.into_body())
+        });
+        let tile = self.deadline(get).await?;
+        for cache in &self.caches {
+            cache.put(&key, tile.clone()).await;
+        }
+        Ok(tile)
+    }
+
+    /// Streams a PNG to `writer`, checking its signature before writing anything
+    async fn stream_png(
+        &self,
+        url: &str,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> Result<u64, error::Error> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
+        let request = self.request(url, http::HeaderMap::new())?;
+        let (parts, mut body) = self.transport.get_streaming(request).await?.into_parts();
+        let limit = self.max_response_size.unwrap_or(usize::MAX);
+
+        let mut received = Vec::new();
+        if parts.status != http::StatusCode::OK {
+            while let Some(chunk) = body.next().await {
+                received.extend_from_slice(&chunk?);
+                if received.len() > limit {
+                    return Err(Error::ResponseTooLarge(limit));
+                }
+            }
+            return Err(Error::from_response(
+                url,
+                parts.status,
+                &parts.headers,
+                &received,
+            ));
+        }
+        check_png_content_type(url, &parts.headers)?;
+
+        // Chunks are held back until the signature has been received and checked
+        let mut written = 0;
+        let mut checked = false;
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk?;
+            if written + received.len() + chunk.len() > limit {
+                return Err(Error::ResponseTooLarge(limit));
+            }
+            if checked {
+                writer.write_all(&chunk).await?;
+                written += chunk.len();
+                continue;
+            }
+            received.extend_from_slice(&chunk);
+            if received.len() >= PNG_SIGNATURE.len() {
+                check_png_signature(url, &received)?;
+                checked = true;
+                writer.write_all(&received).await?;
+                written += received.len();
+                received.clear();
+            }
+        }
+        if !checked {
+            check_png_signature(url, &received)?;
+        }
+        writer.flush().await?;
+        Ok(written as u64)
+    }
+
+    /// Performs a GET request through the transport, returning the body of successful responses
+    async fn get(&self, url: &str) -> Result<Bytes, error::Error> {
+        let get = self.coalescer.run(url, || async {
+            Ok(self.send(url, http::HeaderMap::new()).await?.into_body())
+        });
+        self.deadline(get).await
+    }
+
+    /// Performs a GET request with the conditional headers for `validators`
+    ///
+    /// Conditional requests are not coalesced, since a `304 Not Modified` response only applies
+    /// to the caller holding the validators
+    async fn get_conditional(
+        &self,
+        url: &str,
+        validators: &Validators,
+    ) -> Result<Conditional<Bytes>, error::Error> {
+        let res = self
+            .deadline(self.send(url, validators.to_headers()))
+            .await?;
+        Ok(match res.status() {
+            http::StatusCode::NOT_MODIFIED => Conditional::NotModified,
+            _ => {
+                let validators = Validators::from_headers(res.headers());
+                Conditional::Modified(res.into_body(), validators)
+            }
+        })
+    }
+
+    /// Applies the timeout set by [`Self::with_request_timeout`] and the token set by
+    /// `with_cancellation` to `future`
+    async fn deadline<T>(
+        &self,
+        future: impl std::future::Future<Output = Result<T, error::Error>>,
+    ) -> Result<T, error::Error> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let future = async {
+            match self.request_timeout {
+                Some(timeout) => with_timeout(timeout, future).await,
+                None => future.await,
+            }
+        };
+        #[cfg(feature = "cancellation")]
+        if let Some(token) = &self.cancellation {
+            return token
+                .run_until_cancelled(future)
+                .await
+                .unwrap_or(Err(Error::Cancelled));
+        }
+        future.await
+    }
+
+    /// Sends a GET request through the transport, returning `200 OK` and `304 Not Modified`
+    /// responses
+    ///
+    /// Rate limited requests are retried as configured by [`Self::with_rate_limit_retries`]
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn send(
+        &self,
+        url: &str,
+        headers: http::HeaderMap,
+    ) -> Result<http::Response<Bytes>, error::Error> {
+        let mut retries = 0;
+        loop {
+            match self.send_once(url, headers.clone()).await {
+                Err(Error::RateLimited { retry_after }) if retries < self.rate_limit_retries => {
+                    retries += 1;
+                    futures_timer::Delay::new(retry_after.unwrap_or(DEFAULT_RETRY_AFTER)).await;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    async fn send(
+        &self,
+        url: &str,
+        headers: http::HeaderMap,
+    ) -> Result<http::Response<Bytes>, error::Error> {
+        self.send_once(url, headers).await
+    }
+
+    /// Builds a GET request carrying the configured response size limit
+    fn request(
+        &self,
+        url: &str,
+        headers: http::HeaderMap,
+    ) -> Result<http::Request<()>, error::Error> {
+        let mut request = http::Request::get(url).body(())?;
+        *request.headers_mut() = headers;
+        if let Some(limit) = self.max_response_size {
+            request.extensions_mut().insert(ResponseSizeLimit(limit));
+        }
+        Ok(request)
+    }
+
+    async fn send_once(
+        &self,
+        url: &str,
+        headers: http::HeaderMap,
+    ) -> Result<http::Response<Bytes>, error::Error> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
+        let request = self.request(url, headers)?;
+        let res = self.transport.get(request).await?;
+        if let Some(limit) = self.max_response_size {
+            if res.body().len() > limit {
+                return Err(Error::ResponseTooLarge(limit));
+            }
+        }
+        match res.status() {
+            http::StatusCode::OK | http::StatusCode::NOT_MODIFIED => Ok(res),
+            status => Err(Error::from_response(url, status, res.headers(), res.body())),
+        }
+    }
+}
+
+/// Resolves to the output of `future`, or [`Error::Timeout`] if it does not complete within
+/// `timeout`
+#[cfg(not(target_arch = "wasm32"))]
+async fn with_timeout<T>(
+    timeout: std::time::Duration,
+    future: impl std::future::Future<Output = Result<T, error::Error>>,
+) -> Result<T, error::Error> {
+    use std::future::Future;
+    use std::task::Poll;
+
+    let mut future = std::pin::pin!(future);
+    let mut delay = futures_timer::Delay::new(timeout);
+    std::future::poll_fn(|cx| {
+        if let Poll::Ready(res) = future.as_mut().poll(cx) {
+            return Poll::Ready(res);
+        }
+        match std::pin::Pin::new(&mut delay).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(Error::Timeout(timeout))),
+            Poll::Pending => Poll::Pending,
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::{TileArguments, TileLocation, TileSize};
+    use crate::color::ColorKind;
+
+    #[test]
+    fn builder() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("X-Test", "1".parse().unwrap());
+        let req = WeatherRequester::builder()
+            .connect_timeout(std::time::Duration::from_secs(5))
+            .read_timeout(std::time::Duration::from_secs(10))
+            .timeout(std::time::Duration::from_secs(30))
+            .proxy(reqwest::Proxy::all("http://localhost:8080").unwrap())
+            .pool_max_idle_per_host(32)
+            .pool_idle_timeout(Some(std::time::Duration::from_secs(90)))
+            .tcp_keepalive(Some(std::time::Duration::from_secs(60)))
+            .default_headers(headers)
+            .user_agent("my-app/1.0")
+            .rate_limit(RateLimit::per_second(10))
+            .rate_limit_retries(3)
+            .build();
+        assert!(req.is_ok());
+    }
+
+    #[cfg(feature = "http3")]
+    #[tokio::test]
+    async fn http3_builder() {
+        WeatherRequester::builder()
+            .http3_prior_knowledge()
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    fn shares_client() {
+        let client = reqwest::Client::new();
+        let _a = WeatherRequester::with_client(client.clone());
+        let _b = WeatherRequester::with_client(client);
+    }
+
+    #[cfg(feature = "middleware")]
+    #[test]
+    fn middleware_client() {
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new()).build();
+        let _ = WeatherRequester::with_middleware_client(client);
+    }
+
+    #[tokio::test]
+    async fn test() {
+        let req = WeatherRequester::new();
+        let maps = req.available().await.unwrap();
+        let frame = &maps.past_radar[0];
+        let args = RequestArgumentsInner::Tile(TileArguments {
+            size: TileSize::Px256,
+            location: TileLocation::Coordinates { x: 26, y: 12 },
+            zoom: 6,
+            color: ColorKind::UniversalBlue,
+            smooth: true,
+            snow: true,
+        });
+        let png = req
+            .get_tile(&maps, frame, RequestArguments { inner: args })
+            .await
+            .unwrap();
+
+        //Check for PNG magic
+        assert_eq!(&png[0..4], &[0x89, 0x50, 0x4e, 0x47]);
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::BTreeMap;
+
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, Rgba, Rgba32FImage, RgbaImage};
+
+use crate::args::TileSize;
+use crate::color::ColorKind;
+use crate::error::{self, ParameterError};
+use crate::geo::TileCoord;
+use crate::tile::{decode_png, Tile};
+
+/// How pixels are interpolated when resizing tiles
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Resampling {
+    /// Each pixel takes the value of the nearest source pixel. This keeps the exact values of
+    /// [`ColorKind::BlackAndWhite`](crate::ColorKind::BlackAndWhite) tiles, which blending
+    /// would turn into different reflectivities
+    Nearest,
+    /// Each pixel blends the source pixels around it, for smooth edges on colorized tiles
+    #[default]
+    Bilinear,
+}
+
+impl Resampling {
+    /// The resampling suited to tiles rendered with `color`
+    pub(crate) fn for_color(color: ColorKind) -> Self {
+        match color {
+            ColorKind::BlackAndWhite => Resampling::Nearest,
+            _ => Resampling::Bilinear,
+        }
+    }
+}
+
+/// Where a tile is rendered from when it is resampled from another zoom level
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PyramidSource {
+    /// Upscale the quarter of the parent tile covering the tile, for one request per four
+    /// tiles at the cost of detail
+    Parent,
+    /// Downsample the tile's four children, for crisper output at the cost of four requests
+    Children,
+}
+
+/// Renders the tile at `coord` by cropping the part of `ancestor`, the decoded image of the
+/// tile at `ancestor_coord`, that covers it and scaling it up to the size of `ancestor`
+///
+/// This lets a display show tiles at a zoom level one or more levels in from those it
+/// downloaded, such as every zoom 10 tile from the zoom 9 tiles of a region.
+///
+/// Enabled with the `image` feature. Returns Err(...) if `coord` does not lie within
+/// `ancestor_coord`
+pub fn upsample_from_ancestor(
+    ancestor: &DynamicImage,
+    ancestor_coord: TileCoord,
+    coord: TileCoord,
+    resampling: Resampling,
+) -> Result<RgbaImage, error::Error> {
+    let levels = coord.z.checked_sub(ancestor_coord.z).ok_or_else(|| {
+        ParameterError::InvalidZoom(
+            coord.z,
+            "A tile can only be upsampled from a tile at the same or a lower zoom".to_owned(),
+        )
+    })?;
+    let (x, y) = (
+        coord.x.checked_shr(levels).unwrap_or(0),
+        coord.y.checked_shr(levels).unwrap_or(0),
+    );
+    if x != ancestor_coord.x {
+        return Err(ParameterError::XOutOfRange(
+            coord.x,
+            "The tile does not lie within the ancestor tile".to_owned(),
+        )
+        .into());
+    }
+    if y != ancestor_coord.y {
+        return Err(ParameterError::YOutOfRange(
+            coord.y,
+            "The tile does not lie within the ancestor tile".to_owned(),
+        )
+        .into());
+    }
+
+    let (width, height) = (ancestor.width(), ancestor.height());
+    let scale = 2f64.powi(levels as i32);
+    let column = (coord.x as f64 - x as f64 * scale) / scale;
+    let row = (coord.y as f64 - y as f64 * scale) / scale;
+    let crop_width = ((width as f64 / scale).round() as u32).max(1);
+    let crop_height = ((height as f64 / scale).round() as u32).max(1);
+    let left = ((column * width as f64) as u32).min(width - crop_width);
+    let top = ((row * height as f64) as u32).min(height - crop_height);
+    let quarter = ancestor.crop_imm(left, top, crop_width, crop_height);
+    resize(&quarter, width, height, resampling)
+}
+
+/// Renders the tile at `coord` from the PNG images of its four children, as given by
+/// [`TileCoord::children`], scaled down to the size of the largest child
+///
+/// Other tiles in `tiles` are ignored, and missing children are left transparent.
+///
+/// Enabled with the `image` feature. Returns Err(...) if none of the children are in `tiles`,
+/// or a child is not a valid PNG image
+pub fn downsample_children(
+    tiles: &BTreeMap<TileCoord, impl AsRef<[u8]>>,
+    coord: TileCoord,
+    resampling: Resampling,
+) -> Result<RgbaImage, error::Error> {
+    let mut children = Vec::with_capacity(4);
+    for (i, child) in coord.children().iter().enumerate() {
+        if let Some(png) = tiles.get(child) {
+            children.push((i as u32, decode_png(png.as_ref())?));
+        }
+    }
+    let size = children
+        .iter()
+        .map(|(_, child)| child.width().max(child.height()))
+        .max()
+        .ok_or_else(|| {
+            ParameterError::InvalidZoom(
+                coord.z + 1,
+                "None of the tile's children were given".to_owned(),
+            )
+        })?;
+
+    let mut canvas = RgbaImage::new(size * 2, size * 2);
+    for (i, child) in children {
+        let child = if child.width() == size && child.height() == size {
+            child.into_rgba8()
+        } else {
+            resize(&child, size, size, resampling)?
+        };
+        imageops::replace(
+            &mut canvas,
+            &child,
+            (i % 2 * size) as i64,
+            (i / 2 * size) as i64,
+        );
+    }
+    resize(&DynamicImage::ImageRgba8(canvas), size, size, resampling)
+}
+
+/// Resizes a decoded tile or mosaic to `width` by `height` pixels
+///
+/// Bilinear resampling weighs colors by their alpha, so transparent pixels, which hold no
+/// meaningful color, do not darken the edges of precipitation.
+///
+/// Enabled with the `image` feature. Returns Err(...) if `width` or `height` is zero
+pub fn resize(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    resampling: Resampling,
+) -> Result<RgbaImage, error::Error> {
+    if width == 0 || height == 0 {
+        return Err(ParameterError::InvalidSize(
+            width.min(height),
+            "A resized image must be at least one pixel wide and high".to_owned(),
+        )
+        .into());
+    }
+    Ok(match resampling {
+        Resampling::Nearest => {
+            imageops::resize(&image.to_rgba8(), width, height, FilterType::Nearest)
+        }
+        Resampling::Bilinear => {
+            let mut premultiplied = image.to_rgba32f();
+            for pixel in premultiplied.pixels_mut() {
+                let alpha = pixel.0[3];
+                for channel in &mut pixel.0[..3] {
+                    *channel *= alpha;
+                }
+            }
+            let resized: Rgba32FImage =
+                imageops::resize(&premultiplied, width, height, FilterType::Triangle);
+            RgbaImage::from_fn(width, height, |x, y| {
+                let [r, g, b, alpha] = resized.get_pixel(x, y).0;
+                let alpha = alpha.clamp(0.0, 1.0);
+                if alpha == 0.0 {
+                    return Rgba([0; 4]);
+                }
+                let to_u8 = |value: f32| (value * 255.0).round().clamp(0.0, 255.0) as u8;
+                Rgba([
+                    to_u8(r / alpha),
+                    to_u8(g / alpha),
+                    to_u8(b / alpha),
+                    to_u8(alpha),
+                ])
+            })
+        }
+    })
+}
+
+impl Tile {
+    /// Decodes this tile and resizes it to `size`, such as to show 256 pixel tiles next to 512
+    /// pixel ones without downloading them again
+    ///
+    /// Enabled with the `image` feature. Returns Err(...) if the tile is not a valid PNG image
+    pub fn resize(
+        &self,
+        size: TileSize,
+        resampling: Resampling,
+    ) -> Result<RgbaImage, error::Error> {
+        let size = u32::from(size);
+        resize(&self.decode()?, size, size, resampling)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::ColorKind;
+
+    #[test]
+    fn nearest() {
+        let image = RgbaImage::from_fn(2, 2, |x, y| Rgba([(x * 2 + y) as u8 * 50, 0, 0, 255]));
+        let resized = resize(&DynamicImage::ImageRgba8(image), 4, 4, Resampling::Nearest).unwrap();
+        assert_eq!(resized.dimensions(), (4, 4));
+        assert_eq!(resized.get_pixel(1, 1).0, [0, 0, 0, 255]);
+        assert_eq!(resized.get_pixel(3, 0).0, [100, 0, 0, 255]);
+        assert_eq!(resized.get_pixel(2, 3).0, [150, 0, 0, 255]);
+
+        let blank = DynamicImage::new_rgba8(1, 1);
+        assert!(resize(&blank, 0, 4, Resampling::Nearest).is_err());
+    }
+
+    #[test]
+    fn bilinear() {
+        // Opaque red beside transparent black
+        let image = RgbaImage::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                Rgba([200, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        });
+        let resized = resize(&DynamicImage::ImageRgba8(image), 4, 1, Resampling::Bilinear).unwrap();
+        // Blended pixels fade out without darkening
+        for pixel in resized.pixels().filter(|pixel| pixel.0[3] > 0) {
+            assert_eq!(&pixel.0[..3], [200, 0, 0]);
+        }
+        assert!(resized.get_pixel(1, 0).0[3] < 255);
+        assert!(resized.get_pixel(1, 0).0[3] > resized.get_pixel(2, 0).0[3]);
+    }
+
+    fn png(image: RgbaImage) -> Vec<u8> {
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(image)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn from_ancestor() {
+        // Each quarter of the parent has its own color
+        let parent = RgbaImage::from_fn(4, 4, |x, y| {
+            Rgba([(x / 2 * 100) as u8, (y / 2 * 100) as u8, 0, 255])
+        });
+        let parent = DynamicImage::ImageRgba8(parent);
+        let parent_coord = TileCoord::new(3, 5, 4);
+
+        let south_east = TileCoord::new(7, 11, 5);
+        let tile =
+            upsample_from_ancestor(&parent, parent_coord, south_east, Resampling::Nearest).unwrap();
+        assert_eq!(tile.dimensions(), (4, 4));
+        assert!(tile.pixels().all(|pixel| pixel.0 == [100, 100, 0, 255]));
+
+        // Two levels in, a single pixel covers the tile
+        let grandchild = TileCoord::new(13, 20, 6);
+        let tile = upsample_from_ancestor(&parent, parent_coord, grandchild, Resampling::Bilinear)
+            .unwrap();
+        assert!(tile.pixels().all(|pixel| pixel.0 == [0, 0, 0, 255]));
+
+        let same = upsample_from_ancestor(&parent, parent_coord, parent_coord, Resampling::Nearest)
+            .unwrap();
+        assert_eq!(same, parent.to_rgba8());
+
+        assert!(upsample_from_ancestor(
+            &parent,
+            parent_coord,
+            TileCoord::new(8, 11, 5),
+            Resampling::Nearest
+        )
+        .is_err());
+        assert!(upsample_from_ancestor(
+            &parent,
+            parent_coord,
+            TileCoord::new(1, 2, 3),
+            Resampling::Nearest
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn from_children() {
+        let coord = TileCoord::new(1, 1, 2);
+        let [north_west, north_east, south_west, _] = coord.children();
+        let mut tiles = BTreeMap::new();
+        tiles.insert(
+            north_west,
+            png(RgbaImage::from_pixel(4, 4, Rgba([200, 0, 0, 255]))),
+        );
+        tiles.insert(
+            north_east,
+            png(RgbaImage::from_pixel(4, 4, Rgba([0, 200, 0, 255]))),
+        );
+        tiles.insert(
+            south_west,
+            png(RgbaImage::from_pixel(2, 2, Rgba([0, 0, 200, 255]))),
+        );
+        // Not a child, so ignored
+        tiles.insert(TileCoord::new(0, 0, 3), png(RgbaImage::new(4, 4)));
+
+        let tile = downsample_children(&tiles, coord, Resampling::Nearest).unwrap();
+        assert_eq!(tile.dimensions(), (4, 4));
+        assert_eq!(tile.get_pixel(0, 0).0, [200, 0, 0, 255]);
+        assert_eq!(tile.get_pixel(3, 1).0, [0, 200, 0, 255]);
+        assert_eq!(tile.get_pixel(1, 3).0, [0, 0, 200, 255]);
+        assert_eq!(tile.get_pixel(3, 3).0, [0; 4]);
+
+        assert!(downsample_children(&tiles, TileCoord::new(0, 0, 0), Resampling::Nearest).is_err());
+    }
+
+    #[test]
+    fn tile_sizes() {
+        let mut png = Vec::new();
+        DynamicImage::new_rgba8(256, 256)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let tile = Tile::new(png, ColorKind::Titan);
+        let resized = tile.resize(TileSize::Px512, Resampling::Bilinear).unwrap();
+        assert_eq!(resized.dimensions(), (512, 512));
+    }
+}
@@ -0,0 +1,295 @@
+use std::io::Write;
+#[cfg(feature = "arrow")]
+use std::sync::Arc;
+
+#[cfg(feature = "arrow")]
+use arrow_array::{
+    ArrayRef, BooleanArray, Float32Array, Float64Array, RecordBatch, TimestampSecondArray,
+};
+#[cfg(feature = "arrow")]
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+
+use crate::args::RequestArguments;
+use crate::data::Frame;
+use crate::error;
+use crate::precip::{Intensity, PrecipEstimate};
+use crate::requester::WeatherRequester;
+use crate::timestamp::{self, Timestamp};
+
+/// The precipitation at a location in a sequence of frames, as returned by
+/// [`WeatherRequester::point_series`], or built from
+/// [`WeatherRequester::nowcast_at`]
+///
+/// Enabled with the `image` feature
+#[derive(Clone, Debug, PartialEq)]
+pub struct PointSeries {
+    pub lat: f64,
+    pub lon: f64,
+    /// The precipitation in each frame, in chronological order
+    pub samples: Vec<(Timestamp, PrecipEstimate)>,
+}
+
+impl PointSeries {
+    /// The schema of the record batches written by [`series_to_record_batch`], whose columns
+    /// match those of [`write_series_csv`]
+    ///
+    /// Enabled with the `arrow` feature
+    ///
+    /// | Column      | Type                     | Contents                                      |
+    /// |-------------|--------------------------|-----------------------------------------------|
+    /// | `lat`       | `Float64`                | The latitude of the location                  |
+    /// | `lon`       | `Float64`                | The longitude of the location                 |
+    /// | `time`      | `Timestamp(Second, UTC)` | The time of the frame                         |
+    /// | `dbz`       | `Float32`, nullable      | The reflectivity, or null if nothing falls    |
+    /// | `rate_mm_h` | `Float32`                | The precipitation rate in mm/h, 0 if dry      |
+    /// | `snow`      | `Boolean`                | Whether snow rather than rain falls           |
+    #[cfg(feature = "arrow")]
+    pub fn schema() -> Schema {
+        Schema::new(vec![
+            Field::new("lat", DataType::Float64, false),
+            Field::new("lon", DataType::Float64, false),
+            Field::new(
+                "time",
+                DataType::Timestamp(TimeUnit::Second, Some("UTC".into())),
+                false,
+            ),
+            Field::new("dbz", DataType::Float32, true),
+            Field::new("rate_mm_h", DataType::Float32, false),
+            Field::new("snow", DataType::Boolean, false),
+        ])
+    }
+}
+
+/// Writes the samples of every series as rows of a single Arrow record batch in the
+/// [`PointSeries::schema`], in long format with one row per location and frame
+///
+/// Polars and other data frame libraries import record batches without copying through the
+/// Arrow C data interface.
+///
+/// ```no_run
+/// use rain_viewer::{series_to_record_batch, WeatherRequester};
+///
+/// # async fn run() -> Result<(), rain_viewer::Error> {
+/// let req = WeatherRequester::new();
+/// let locations = [(51.5074, -0.1278), (48.8566, 2.3522), (52.52, 13.405)];
+/// let series = req.point_series(&locations).await?;
+/// let batch = series_to_record_batch(&series);
+/// println!("{} rows", batch.num_rows());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Enabled with the `arrow` feature
+#[cfg(feature = "arrow")]
+pub fn series_to_record_batch(series: &[PointSeries]) -> RecordBatch {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Float64Array::from_iter_values(
+            rows(series).map(|(series, _, _)| series.lat),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            rows(series).map(|(series, _, _)| series.lon),
+        )),
+        Arc::new(
+            TimestampSecondArray::from_iter_values(
+                rows(series).map(|(_, time, _)| timestamp::unix_seconds(*time)),
+            )
+            .with_timezone("UTC"),
+        ),
+        Arc::new(Float32Array::from_iter(rows(series).map(
+            |(_, _, estimate)| intensity(estimate).map(|intensity| intensity.dbz),
+        ))),
+        Arc::new(Float32Array::from_iter_values(rows(series).map(
+            |(_, _, estimate)| intensity(estimate).map_or(0.0, |intensity| intensity.rate),
+        ))),
+        Arc::new(BooleanArray::from_iter(rows(series).map(
+            |(_, _, estimate)| Some(matches!(estimate, PrecipEstimate::Snow(_))),
+        ))),
+    ];
+    RecordBatch::try_new(Arc::new(PointSeries::schema()), columns)
+        .expect("the columns match the schema")
+}
+
+/// Writes the samples of every series as CSV, with a header and one row per location and
+/// frame, for spreadsheets and scripts in other languages
+///
+/// The columns are those of [`PointSeries::schema`]: `lat`, `lon`, `time` in RFC 3339,
+/// `dbz`, which is empty if nothing falls, `rate_mm_h`, which is 0 if nothing falls, and
+/// `snow` as `true` or `false`.
+///
+/// ```no_run
+/// use rain_viewer::{write_series_csv, PointSeries, WeatherRequester};
+///
+/// # async fn run() -> Result<(), rain_viewer::Error> {
+/// let req = WeatherRequester::new();
+/// let (lat, lon) = (51.5074, -0.1278);
+/// let samples = req.nowcast_at(lat, lon).await?;
+/// let series = PointSeries { lat, lon, samples };
+/// write_series_csv(std::io::stdout().lock(), &[series])?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Enabled with the `image` feature. Returns Err(...) if writing fails
+pub fn write_series_csv(
+    mut writer: impl Write,
+    series: &[PointSeries],
+) -> Result<(), error::Error> {
+    writeln!(writer, "lat,lon,time,dbz,rate_mm_h,snow")?;
+    for (series, time, estimate) in rows(series) {
+        let (dbz, rate) = match intensity(estimate) {
+            Some(intensity) => (intensity.dbz.to_string(), intensity.rate),
+            None => (String::new(), 0.0),
+        };
+        writeln!(
+            writer,
+            "{},{},{},{},{},{}",
+            series.lat,
+            series.lon,
+            timestamp::rfc3339(*time),
+            dbz,
+            rate,
+            matches!(estimate, PrecipEstimate::Snow(_)),
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Every sample of `series` along with its series, in order
+fn rows(
+    series: &[PointSeries],
+) -> impl Iterator<Item = (&PointSeries, &Timestamp, &PrecipEstimate)> {
+    series.iter().flat_map(|series| {
+        series
+            .samples
+            .iter()
+            .map(move |(time, estimate)| (series, time, estimate))
+    })
+}
+
+/// The intensity of an estimate, or `None` if nothing falls
+fn intensity(estimate: &PrecipEstimate) -> Option<Intensity> {
+    match estimate {
+        PrecipEstimate::None => None,
+        PrecipEstimate::Rain(intensity) | PrecipEstimate::Snow(intensity) => Some(*intensity),
+    }
+}
+
+impl WeatherRequester {
+    /// Reads the precipitation at each of the given WGS84 `(lat, lon)` locations in every
+    /// past and nowcast radar frame, such as for ingesting many locations into a data frame
+    /// with [`series_to_record_batch`]
+    ///
+    /// Each location is read like [`Self::nowcast_at`], sharing a single request for the
+    /// available frames. Locations are read one after another, with their frames downloaded
+    /// concurrently. Series are returned in the order of `locations`.
+    ///
+    /// Enabled with the `image` feature. Returns Err(...) if a latitude is beyond the web
+    /// mercator limits of +/-85.0511 degrees, a longitude is beyond +/-180 degrees, or a
+    /// download fails
+    pub async fn point_series(
+        &self,
+        locations: &[(f64, f64)],
+    ) -> Result<Vec<PointSeries>, error::Error> {
+        for &(lat, lon) in locations {
+            RequestArguments::new_position(lat, lon, crate::POINT_ZOOM)?;
+        }
+        let maps = self.available().await?;
+        let frames: Vec<&Frame> = maps.radar_timeline().map(|entry| entry.frame()).collect();
+        let mut series = Vec::with_capacity(locations.len());
+        for &(lat, lon) in locations {
+            let estimates = futures_util::future::try_join_all(
+                frames
+                    .iter()
+                    .map(|frame| self.precip_at(&maps, frame, lat, lon)),
+            )
+            .await?;
+            series.push(PointSeries {
+                lat,
+                lon,
+                samples: frames
+                    .iter()
+                    .map(|frame| frame.time)
+                    .zip(estimates)
+                    .collect(),
+            });
+        }
+        Ok(series)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A dry then rainy location, and a snowy one
+    fn series() -> [PointSeries; 2] {
+        let at = |seconds: i64| timestamp::from_unix_seconds(seconds).unwrap();
+        [
+            PointSeries {
+                lat: 51.5,
+                lon: -0.1,
+                samples: vec![
+                    (at(1696999800), PrecipEstimate::None),
+                    (
+                        at(1697000400),
+                        PrecipEstimate::Rain(Intensity {
+                            dbz: 40.0,
+                            rate: 11.5,
+                        }),
+                    ),
+                ],
+            },
+            PointSeries {
+                lat: 46.5,
+                lon: 6.6,
+                samples: vec![(
+                    at(1697000400),
+                    PrecipEstimate::Snow(Intensity {
+                        dbz: 20.0,
+                        rate: 1.0,
+                    }),
+                )],
+            },
+        ]
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn writes_batch() {
+        use arrow_array::cast::AsArray;
+        use arrow_array::types::{Float32Type, Float64Type, TimestampSecondType};
+        use arrow_array::Array;
+
+        let batch = series_to_record_batch(&series());
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(batch.schema().as_ref(), &PointSeries::schema());
+
+        let lat = batch.column(0).as_primitive::<Float64Type>();
+        assert_eq!(lat.values().as_ref(), &[51.5, 51.5, 46.5]);
+        let time = batch.column(2).as_primitive::<TimestampSecondType>();
+        assert_eq!(time.value(1), 1697000400);
+        let dbz = batch.column(3).as_primitive::<Float32Type>();
+        assert!(dbz.is_null(0));
+        assert_eq!((dbz.value(1), dbz.value(2)), (40.0, 20.0));
+        let rate = batch.column(4).as_primitive::<Float32Type>();
+        assert_eq!(rate.values().as_ref(), &[0.0, 11.5, 1.0]);
+        let snow = batch.column(5).as_boolean();
+        assert_eq!((snow.value(1), snow.value(2)), (false, true));
+
+        assert_eq!(series_to_record_batch(&[]).num_rows(), 0);
+    }
+
+    #[test]
+    fn writes_csv() {
+        let mut csv = Vec::new();
+        write_series_csv(&mut csv, &series()).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "lat,lon,time,dbz,rate_mm_h,snow\n\
+             51.5,-0.1,2023-10-11T04:50:00Z,,0,false\n\
+             51.5,-0.1,2023-10-11T05:00:00Z,40,11.5,false\n\
+             46.5,6.6,2023-10-11T05:00:00Z,20,1,true\n"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! A caching radar tile proxy built on [`axum`](https://docs.rs/axum)
+//!
+//! Enabled with the `server` feature. [`router`] serves radar tiles at
+//! `/radar/{ts}/{z}/{x}/{y}.png`, where `ts` is the Unix timestamp of a past or nowcast frame,
+//! downloading them through a [`WeatherRequester`]. Running one proxy lets a team share a
+//! single tile cache and rate limit instead of every client calling Rain Viewer directly.
+//!
+//! ```no_run
+//! use rain_viewer::{MemoryCache, RequestArguments, TileCoord, WeatherRequester};
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let req = WeatherRequester::new().with_cache(MemoryCache::new(4096, 256 * 1024 * 1024));
+//! let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0))?;
+//! let app = rain_viewer::server::router(req, args);
+//!
+//! let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await?;
+//! axum::serve(listener, app).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+
+use crate::args::RequestArguments;
+use crate::data::{AvailableData, Frame, FrameKind};
+use crate::error;
+use crate::geo::TileCoord;
+use crate::requester::WeatherRequester;
+
+/// The shortest time between two downloads of the frame list, which is refreshed when a tile
+/// of an unknown frame is requested
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long clients may cache tiles of past frames, which never change
+const PAST_MAX_AGE: &str = "public, max-age=86400, immutable";
+
+/// How long clients may cache tiles of nowcast frames, which are replaced as the forecast is
+/// updated
+const NOWCAST_MAX_AGE: &str = "public, max-age=60";
+
+/// The state shared by the handlers of a [`router`]
+struct Proxy {
+    requester: WeatherRequester,
+    args: RequestArguments,
+    /// The latest frame list along with when it was downloaded
+    maps: Mutex<Option<(Instant, AvailableData)>>,
+}
+
+impl Proxy {
+    /// The radar frame at Unix time `ts`, downloading the frame list if it is not known yet or
+    /// the frame is missing from it and it was not refreshed recently
+    async fn frame(&self, ts: i64) -> Result<Option<(AvailableData, Frame)>, error::Error> {
+        let find = |maps: &AvailableData| {
+            maps.past_radar
+                .iter()
+                .chain(&maps.nowcast_radar)
+                .find(|frame| frame.unix_time() == ts)
+                .cloned()
+        };
+        let stale = {
+            let maps = self.maps.lock().unwrap();
+            match &*maps {
+                Some((fetched, maps)) => match find(maps) {
+                    Some(frame) => return Ok(Some((maps.clone(), frame))),
+                    None => fetched.elapsed() >= REFRESH_INTERVAL,
+                },
+                None => true,
+            }
+        };
+        if !stale {
+            return Ok(None);
+        }
+        let maps = self.requester.available().await?;
+        let frame = find(&maps);
+        *self.maps.lock().unwrap() = Some((Instant::now(), maps.clone()));
+        Ok(frame.map(|frame| (maps, frame)))
+    }
+}
+
+/// An [`axum::Router`] serving the radar tiles of past and nowcast frames at
+/// `/radar/{ts}/{z}/{x}/{y}.png`
+///
+/// Tiles are downloaded through `requester`, so the cache set by
+/// [`WeatherRequester::with_cache`], along with its rate limit and other options, is shared by
+/// every client of the proxy. The size, color and options of `args` apply to every tile,
+/// while its location is ignored.
+///
+/// The frame list is downloaded on the first request, and again when a tile of a frame
+/// missing from it is requested, at most once a minute. Responses are:
+///
+/// - `200 OK` with the PNG tile, cacheable for a day for past frames and a minute for nowcast
+///   frames
+/// - `400 Bad Request` if the tile coordinates are invalid
+/// - `404 Not Found` if no radar frame has time `ts`
+/// - `503 Service Unavailable` if Rain Viewer rate limited the proxy, `504 Gateway Timeout`
+///   if the download timed out, and `502 Bad Gateway` if it failed otherwise
+///
+/// Enabled with the `server` feature
+pub fn router(requester: WeatherRequester, args: RequestArguments) -> Router {
+    let proxy = Proxy {
+        requester,
+        args,
+        maps: Mutex::new(None),
+    };
+    // Routes capture whole segments, so the `.png` extension is stripped by the handler
+    Router::new()
+        .route("/radar/{ts}/{z}/{x}/{y}", get(radar_tile))
+        .with_state(Arc::new(proxy))
+}
+
+async fn radar_tile(
+    State(proxy): State<Arc<Proxy>>,
+    Path((ts, z, x, y)): Path<(i64, u32, u32, String)>,
+) -> Response {
+    let Some(y) = y.strip_suffix(".png").and_then(|y| y.parse().ok()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let args = match proxy.args.for_tile(TileCoord::new(x, y, z)) {
+        Ok(args) => args,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+    let (maps, frame) = match proxy.frame(ts).await {
+        Ok(Some(found)) => found,
+        Ok(None) => return (StatusCode::NOT_FOUND, "No radar frame at this time").into_response(),
+        Err(err) => return error_response(err),
+    };
+    match proxy.requester.get_tile(&maps, &frame, args).await {
+        Ok(png) => {
+            let cache_control = match frame.kind {
+                FrameKind::NowcastRadar => NOWCAST_MAX_AGE,
+                _ => PAST_MAX_AGE,
+            };
+            (
+                [
+                    (header::CONTENT_TYPE, HeaderValue::from_static("image/png")),
+                    (
+                        header::CACHE_CONTROL,
+                        HeaderValue::from_static(cache_control),
+                    ),
+                ],
+                png,
+            )
+                .into_response()
+        }
+        Err(err) => error_response(err),
+    }
+}
+
+/// The response to a failed download from Rain Viewer
+fn error_response(err: error::Error) -> Response {
+    let status = match err {
+        error::Error::Parameter(_) => StatusCode::BAD_REQUEST,
+        error::Error::RateLimited { .. } => StatusCode::SERVICE_UNAVAILABLE,
+        error::Error::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        _ => StatusCode::BAD_GATEWAY,
+    };
+    (status, err.to_string()).into_response()
+}
@@ -0,0 +1,114 @@
+//! [`tower`](https://docs.rs/tower) integration
+//!
+//! Enabled with the `tower` feature. [`TileService`] exposes tile downloads as a
+//! `tower::Service`, so that rate limiting, retry and load shedding layers can wrap it.
+
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+
+use crate::args::{RequestArguments, SatelliteArguments};
+use crate::cache::TileKey;
+use crate::data::{AvailableData, Frame};
+use crate::error::{self, ParameterError};
+use crate::requester::{radar_tile_key, satellite_tile_key, WeatherRequester};
+use crate::transport::BoxFuture;
+
+/// A validated request for a single tile, handled by [`TileService`]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TileRequest {
+    key: TileKey,
+}
+
+impl TileRequest {
+    /// Requests a radar tile, see [`WeatherRequester::get_tile`]
+    ///
+    /// `frame` must be a past or nowcast radar frame, else Err(...) is returned
+    pub fn radar(
+        maps: &AvailableData,
+        frame: &Frame,
+        args: RequestArguments,
+    ) -> Result<Self, ParameterError> {
+        Ok(Self {
+            key: radar_tile_key(maps, frame, &args)?,
+        })
+    }
+
+    /// Requests an infrared satellite tile, see [`WeatherRequester::get_satellite_tile`]
+    ///
+    /// `frame` must be an infrared satellite frame, else Err(...) is returned
+    pub fn satellite(
+        maps: &AvailableData,
+        frame: &Frame,
+        args: SatelliteArguments,
+    ) -> Result<Self, ParameterError> {
+        Ok(Self {
+            key: satellite_tile_key(maps, frame, &args)?,
+        })
+    }
+
+    /// The url this request downloads
+    pub fn url(&self) -> &str {
+        &self.key.url
+    }
+}
+
+/// A `tower::Service` downloading the PNG bytes of [`TileRequest`]s
+#[derive(Clone)]
+pub struct TileService {
+    requester: WeatherRequester,
+}
+
+impl TileService {
+    /// Creates a service issuing requests through `requester`
+    pub fn new(requester: WeatherRequester) -> Self {
+        Self { requester }
+    }
+}
+
+impl From<WeatherRequester> for TileService {
+    fn from(requester: WeatherRequester) -> Self {
+        Self::new(requester)
+    }
+}
+
+impl tower_service::Service<TileRequest> for TileService {
+    type Response = Bytes;
+    type Error = error::Error;
+    type Future = BoxFuture<'static, Result<Bytes, error::Error>>;
+
+    /// The service is always ready. Apply a concurrency or rate limit layer to bound requests
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: TileRequest) -> Self::Future {
+        let requester = self.requester.clone();
+        Box::pin(async move { requester.get_png(request.key).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geo::TileCoord;
+
+    #[test]
+    fn request_validation() {
+        let maps = AvailableData::fixture();
+        let args = RequestArguments::new_tile(TileCoord::new(1, 2, 3)).unwrap();
+
+        let request = TileRequest::radar(&maps, &maps.past_radar[0], args).unwrap();
+        assert_eq!(
+            request.url(),
+            "https://tilecache.rainviewer.com/v2/radar/1696998600/256/3/1/2/2/1_1.png"
+        );
+        assert!(TileRequest::radar(&maps, &maps.infrared_satellite[0], args).is_err());
+        assert!(TileRequest::satellite(
+            &maps,
+            &maps.past_radar[0],
+            SatelliteArguments::new_tile(TileCoord::new(1, 2, 3)).unwrap()
+        )
+        .is_err());
+    }
+}
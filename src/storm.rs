@@ -0,0 +1,594 @@
+//! Detection and tracking of storm cells
+//!
+//! A storm cell is a connected area of strong reflectivity, which usually marks a
+//! thunderstorm's core. [`CellDetector`] finds the cells of a single frame, and
+//! [`StormTracker`] follows them from frame to frame, matching each cell to the track whose
+//! predicted position is closest, to measure how fast and where storms move.
+//!
+//! ```no_run
+//! use rain_viewer::storm::{CellDetector, StormTracker};
+//! use rain_viewer::{ColorKind, LatLonBounds, RequestArguments, Tile, TileCoord, WeatherRequester};
+//!
+//! # async fn run() -> Result<(), rain_viewer::Error> {
+//! let req = WeatherRequester::new();
+//! let maps = req.available().await?;
+//! let bounds = LatLonBounds::new(-100.0, 30.0, -90.0, 40.0)?;
+//! let mut args = RequestArguments::new_tile(TileCoord::new(0, 0, 0))?;
+//! args.set_color(ColorKind::BlackAndWhite).set_smooth(false);
+//!
+//! let detector = CellDetector::new();
+//! let mut tracker = StormTracker::new();
+//! for frame in &maps.past_radar {
+//!     let tiles = req.get_region(&maps, frame, bounds, 6, args).await?;
+//!     let tiles = tiles
+//!         .into_iter()
+//!         .map(|(coord, png)| (coord, Tile::new(png, ColorKind::BlackAndWhite)))
+//!         .collect();
+//!     tracker.update(frame.time, detector.detect_region(&tiles)?);
+//! }
+//! for track in tracker.active() {
+//!     if let Some(velocity) = track.velocity() {
+//!         let cell = track.latest();
+//!         println!(
+//!             "Cell at {:.2}, {:.2} moving {:.0} km/h towards {:.0} degrees",
+//!             cell.lat, cell.lon, velocity.speed_kmh, velocity.heading_deg
+//!         );
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Enabled with the `image` feature
+
+use std::collections::{BTreeMap, VecDeque};
+
+use image::RgbaImage;
+
+use crate::color::ColorKind;
+use crate::error::{self, ParameterError};
+use crate::geo::{wrap_lon, GeoTransform, TileCoord};
+use crate::mosaic::stitch_georeferenced;
+use crate::tile::Tile;
+use crate::timestamp::{self, Timestamp};
+
+/// The mean radius of the earth, for distances between cells
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+/// The length of a degree of latitude, and of longitude at the equator
+const KM_PER_DEGREE: f64 = EARTH_RADIUS_KM * std::f64::consts::PI / 180.0;
+
+/// The number of latest observations a track's velocity is measured over
+const VELOCITY_OBSERVATIONS: usize = 3;
+
+/// A connected area of reflectivity at or above a threshold in one frame
+#[derive(Clone, Debug, PartialEq)]
+pub struct StormCell {
+    /// The latitude of the cell's centroid
+    pub lat: f64,
+    /// The longitude of the cell's centroid
+    pub lon: f64,
+    /// The ground area of the cell in km²
+    pub area_km2: f64,
+    /// The number of pixels of the cell
+    pub pixels: usize,
+    /// The strongest reflectivity in the cell, in dBZ
+    pub max_dbz: f32,
+    /// The `(left, top, width, height)` pixel window enclosing the cell in the image it was
+    /// found in
+    pub window: (u32, u32, u32, u32),
+}
+
+/// Finds storm cells by thresholding reflectivity and labeling connected areas
+///
+/// Pixels at or above the threshold are grouped with their eight neighbours, and groups
+/// smaller than the minimum area are discarded as noise. Defaults to 40 dBZ, heavy rain
+/// usually falling from convective storms, and a minimum area of 10 km².
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CellDetector {
+    threshold_dbz: f32,
+    min_area_km2: f64,
+}
+
+impl Default for CellDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CellDetector {
+    /// A detector with the default threshold and minimum area
+    pub fn new() -> Self {
+        Self {
+            threshold_dbz: 40.0,
+            min_area_km2: 10.0,
+        }
+    }
+
+    /// Sets the weakest reflectivity in dBZ belonging to a cell
+    pub fn with_threshold(mut self, dbz: f32) -> Self {
+        self.threshold_dbz = dbz;
+        self
+    }
+
+    /// Sets the smallest ground area in km² of a cell
+    pub fn with_min_area(mut self, km2: f64) -> Self {
+        self.min_area_km2 = km2;
+        self
+    }
+
+    /// Finds the cells of a decoded tile or mosaic rendered with `color`, whose pixels are
+    /// placed on the map by `transform`
+    ///
+    /// Pixels are decoded with [`ColorKind::dbz_for_pixel`]. Cells are ordered by their top
+    /// row, then their leftmost pixel in it. Returns Err(...) if no palette is bundled for
+    /// `color`
+    pub fn detect(
+        &self,
+        image: &RgbaImage,
+        color: ColorKind,
+        transform: &GeoTransform,
+    ) -> Result<Vec<StormCell>, error::Error> {
+        if color.palette().is_none() {
+            return Err(ParameterError::InvalidColor(
+                color.into(),
+                "No palette is bundled for this color scheme".to_owned(),
+            )
+            .into());
+        }
+        let (width, height) = image.dimensions();
+        let dbz: Vec<Option<f32>> = image
+            .pixels()
+            .map(|pixel| {
+                color
+                    .dbz_for_pixel(pixel.0)
+                    .filter(|&dbz| dbz >= self.threshold_dbz)
+            })
+            .collect();
+        // Pixels cover less ground away from the equator, so their area depends on their row
+        let pixel_area: Vec<f64> = (0..height)
+            .map(|row| {
+                let (lat, _) = transform.pixel_to_lat_lon(0.0, row as f64 + 0.5);
+                let cos = lat.to_radians().cos();
+                transform.pixel_width * cos * transform.pixel_height * cos / 1e6
+            })
+            .collect();
+
+        let mut visited = vec![false; dbz.len()];
+        let mut cells = Vec::new();
+        let mut queue = VecDeque::new();
+        for start in 0..dbz.len() {
+            if visited[start] || dbz[start].is_none() {
+                continue;
+            }
+            visited[start] = true;
+            queue.push_back(start);
+            let mut cell = Labeled::default();
+            while let Some(index) = queue.pop_front() {
+                let (x, y) = (
+                    (index % width as usize) as u32,
+                    (index / width as usize) as u32,
+                );
+                cell.add(x, y, dbz[index].unwrap_or(f32::NAN), pixel_area[y as usize]);
+                for (dx, dy) in NEIGHBOURS {
+                    let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                    if nx < 0 || ny < 0 || nx >= width as i64 || ny >= height as i64 {
+                        continue;
+                    }
+                    let neighbour = ny as usize * width as usize + nx as usize;
+                    if !visited[neighbour] && dbz[neighbour].is_some() {
+                        visited[neighbour] = true;
+                        queue.push_back(neighbour);
+                    }
+                }
+            }
+            if cell.area_km2 >= self.min_area_km2 {
+                cells.push(cell.into_cell(transform));
+            }
+        }
+        Ok(cells)
+    }
+
+    /// Finds the cells of `tile`, which lies at `coord`, see [`CellDetector::detect`]
+    ///
+    /// Returns Err(...) if no palette is bundled for the color scheme of the tile, or it is
+    /// not a valid PNG image
+    pub fn detect_tile(
+        &self,
+        tile: &Tile,
+        coord: TileCoord,
+    ) -> Result<Vec<StormCell>, error::Error> {
+        let image = tile.to_rgba8()?;
+        let transform = GeoTransform::new(coord, image.width());
+        self.detect(&image, tile.color(), &transform)
+    }
+
+    /// Finds the cells of a region of tiles of one zoom level and color scheme, such as those
+    /// returned by [`WeatherRequester::get_region`](crate::WeatherRequester::get_region)
+    ///
+    /// The tiles are assembled as by [`stitch`](crate::stitch), so cells spanning several
+    /// tiles are found whole. Returns Err(...) if `tiles` is empty, the tiles do not share a
+    /// zoom level, no palette is bundled for their color scheme, or a tile is not a valid PNG
+    /// image
+    pub fn detect_region(
+        &self,
+        tiles: &BTreeMap<TileCoord, Tile>,
+    ) -> Result<Vec<StormCell>, error::Error> {
+        let color = tiles
+            .values()
+            .next()
+            .map_or(ColorKind::BlackAndWhite, Tile::color);
+        let (mosaic, transform) = stitch_georeferenced(tiles)?;
+        self.detect(&mosaic, color, &transform)
+    }
+}
+
+/// The offsets of the eight neighbours of a pixel
+const NEIGHBOURS: [(i64, i64); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// The pixels of a cell gathered while labeling it
+struct Labeled {
+    pixels: usize,
+    sum_x: f64,
+    sum_y: f64,
+    area_km2: f64,
+    max_dbz: f32,
+    min: (u32, u32),
+    max: (u32, u32),
+}
+
+impl Default for Labeled {
+    fn default() -> Self {
+        Self {
+            pixels: 0,
+            sum_x: 0.0,
+            sum_y: 0.0,
+            area_km2: 0.0,
+            max_dbz: f32::NEG_INFINITY,
+            min: (u32::MAX, u32::MAX),
+            max: (0, 0),
+        }
+    }
+}
+
+impl Labeled {
+    fn add(&mut self, x: u32, y: u32, dbz: f32, area_km2: f64) {
+        self.pixels += 1;
+        self.sum_x += x as f64 + 0.5;
+        self.sum_y += y as f64 + 0.5;
+        self.area_km2 += area_km2;
+        self.max_dbz = self.max_dbz.max(dbz);
+        self.min = (self.min.0.min(x), self.min.1.min(y));
+        self.max = (self.max.0.max(x), self.max.1.max(y));
+    }
+
+    fn into_cell(self, transform: &GeoTransform) -> StormCell {
+        let count = self.pixels as f64;
+        let (lat, lon) = transform.pixel_to_lat_lon(self.sum_x / count, self.sum_y / count);
+        StormCell {
+            lat,
+            lon: wrap_lon(lon),
+            area_km2: self.area_km2,
+            pixels: self.pixels,
+            max_dbz: self.max_dbz,
+            window: (
+                self.min.0,
+                self.min.1,
+                self.max.0 - self.min.0 + 1,
+                self.max.1 - self.min.1 + 1,
+            ),
+        }
+    }
+}
+
+/// How fast and where a storm cell moves
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Velocity {
+    /// The ground speed in km/h
+    pub speed_kmh: f64,
+    /// The direction the cell moves towards, in degrees clockwise from north
+    pub heading_deg: f64,
+}
+
+/// A storm cell followed across frames
+#[derive(Clone, Debug, PartialEq)]
+pub struct StormTrack {
+    id: u64,
+    cells: Vec<(Timestamp, StormCell)>,
+}
+
+impl StormTrack {
+    /// An identifier unique among the tracks of a [`StormTracker`]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The cell in each frame it was found in, from the earliest
+    pub fn cells(&self) -> &[(Timestamp, StormCell)] {
+        &self.cells
+    }
+
+    /// The cell in the latest frame it was found in
+    pub fn latest(&self) -> &StormCell {
+        &self.cells[self.cells.len() - 1].1
+    }
+
+    /// The time of the latest frame the cell was found in
+    pub fn last_seen(&self) -> Timestamp {
+        self.cells[self.cells.len() - 1].0
+    }
+
+    /// The motion of the cell's centroid over its latest three observations, which smooths
+    /// the jitter of centroids as cells change shape while still following turns, or `None`
+    /// if it was only seen once
+    pub fn velocity(&self) -> Option<Velocity> {
+        let (east, north, hours) = self.motion()?;
+        Some(Velocity {
+            speed_kmh: east.hypot(north) / hours,
+            heading_deg: east.atan2(north).to_degrees().rem_euclid(360.0),
+        })
+    }
+
+    /// The distance in km the cell moved east and north over its latest observations, and the
+    /// hours it took
+    fn motion(&self) -> Option<(f64, f64, f64)> {
+        let first = self.cells.len().saturating_sub(VELOCITY_OBSERVATIONS);
+        let (start, from) = &self.cells[first];
+        let (end, to) = self.cells.last()?;
+        let hours = timestamp::hours_between(*start, *end);
+        if hours <= 0.0 {
+            return None;
+        }
+        let (east, north) = displacement_km((from.lat, from.lon), (to.lat, to.lon));
+        Some((east, north, hours))
+    }
+
+    /// Where the cell is expected at `time`, extrapolating its velocity
+    fn predict(&self, time: Timestamp) -> (f64, f64) {
+        let latest = self.latest();
+        let Some((east, north, hours)) = self.motion() else {
+            return (latest.lat, latest.lon);
+        };
+        let elapsed = timestamp::hours_between(self.last_seen(), time);
+        let scale = elapsed / hours;
+        let lat = latest.lat + north * scale / KM_PER_DEGREE;
+        let lon = latest.lon + east * scale / (KM_PER_DEGREE * latest.lat.to_radians().cos());
+        (lat, wrap_lon(lon))
+    }
+}
+
+/// Follows storm cells from frame to frame
+///
+/// Each update matches the cells of a new frame to the tracks seen in the previous frame,
+/// closest pairs first, comparing each cell to where the track's velocity predicts it. A pair
+/// is only matched if the cell is within the distance the maximum speed covers between the
+/// frames. Unmatched cells start new tracks, and tracks without a match end.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StormTracker {
+    max_speed_kmh: f64,
+    tracks: Vec<StormTrack>,
+    last_update: Option<Timestamp>,
+}
+
+impl Default for StormTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StormTracker {
+    /// A tracker matching cells moving at up to 100 km/h, faster than nearly all storms
+    pub fn new() -> Self {
+        Self {
+            max_speed_kmh: 100.0,
+            tracks: Vec::new(),
+            last_update: None,
+        }
+    }
+
+    /// Sets the fastest a cell may move, in km/h, for its positions in two frames to be
+    /// matched
+    pub fn with_max_speed(mut self, kmh: f64) -> Self {
+        self.max_speed_kmh = kmh;
+        self
+    }
+
+    /// Adds the cells found in the frame at `time`, which must be later than the previous
+    /// frame. Frames that are not are ignored
+    pub fn update(&mut self, time: Timestamp, cells: Vec<StormCell>) {
+        if self.last_update.is_some_and(|last| time <= last) {
+            return;
+        }
+        let previous = self.last_update.replace(time);
+        let active: Vec<usize> = (0..self.tracks.len())
+            .filter(|&i| Some(self.tracks[i].last_seen()) == previous)
+            .collect();
+
+        let mut pairs = Vec::new();
+        for &track in &active {
+            let reach =
+                self.max_speed_kmh * timestamp::hours_between(self.tracks[track].last_seen(), time);
+            let predicted = self.tracks[track].predict(time);
+            for (cell, candidate) in cells.iter().enumerate() {
+                let (east, north) = displacement_km(predicted, (candidate.lat, candidate.lon));
+                let distance = east.hypot(north);
+                if distance <= reach {
+                    pairs.push((distance, track, cell));
+                }
+            }
+        }
+        pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut cells: Vec<Option<StormCell>> = cells.into_iter().map(Some).collect();
+        let mut matched = vec![false; self.tracks.len()];
+        for (_, track, cell) in pairs {
+            if matched[track] || cells[cell].is_none() {
+                continue;
+            }
+            matched[track] = true;
+            let cell = cells[cell].take().expect("checked above");
+            self.tracks[track].cells.push((time, cell));
+        }
+        for cell in cells.into_iter().flatten() {
+            let id = self.tracks.len() as u64;
+            self.tracks.push(StormTrack {
+                id,
+                cells: vec![(time, cell)],
+            });
+        }
+    }
+
+    /// Every track, including those that ended, in the order they started
+    pub fn tracks(&self) -> &[StormTrack] {
+        &self.tracks
+    }
+
+    /// The tracks with a cell in the latest frame
+    pub fn active(&self) -> impl Iterator<Item = &StormTrack> + '_ {
+        self.tracks
+            .iter()
+            .filter(move |track| Some(track.last_seen()) == self.last_update)
+    }
+}
+
+/// The distance in km east and north from `from` to `to`, both `(lat, lon)`, on the shorter
+/// way around the antimeridian
+///
+/// Uses an equirectangular approximation at the mean latitude, which is accurate to well under
+/// a percent at the distances storms move between frames
+fn displacement_km(from: (f64, f64), to: (f64, f64)) -> (f64, f64) {
+    let mean_lat = ((from.0 + to.0) / 2.0).to_radians();
+    let east = wrap_lon(to.1 - from.1) * KM_PER_DEGREE * mean_lat.cos();
+    let north = (to.0 - from.0) * KM_PER_DEGREE;
+    (east, north)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    /// The tile whose north west corner is at 0 degrees latitude and longitude
+    const ORIGIN: TileCoord = TileCoord {
+        x: 128,
+        y: 128,
+        z: 8,
+    };
+
+    /// A transparent black and white tile with squares of `size` pixels of `value` at each of
+    /// `positions`
+    fn image(positions: &[(u32, u32)], size: u32, value: u8) -> RgbaImage {
+        RgbaImage::from_fn(256, 256, |x, y| {
+            let inside = positions
+                .iter()
+                .any(|&(px, py)| (px..px + size).contains(&x) && (py..py + size).contains(&y));
+            if inside {
+                Rgba([value, value, value, 255])
+            } else {
+                Rgba([0; 4])
+            }
+        })
+    }
+
+    fn at(minutes: i64) -> Timestamp {
+        timestamp::from_unix_seconds(1697000400 + minutes * 60).unwrap()
+    }
+
+    #[test]
+    fn detects_cells() {
+        let transform = GeoTransform::new(ORIGIN, 256);
+        let detector = CellDetector::new().with_min_area(1.0);
+        // 50 dBZ squares, and a 20 dBZ square below the threshold
+        let mut image = image(&[(10, 10), (100, 40)], 8, 82);
+        for y in 200..210 {
+            for x in 200..210 {
+                image.put_pixel(x, y, Rgba([52, 52, 52, 255]));
+            }
+        }
+        // A single pixel touching the first square diagonally joins it
+        image.put_pixel(18, 18, Rgba([92, 92, 92, 255]));
+
+        let cells = detector
+            .detect(&image, ColorKind::BlackAndWhite, &transform)
+            .unwrap();
+        assert_eq!(cells.len(), 2);
+        assert_eq!(cells[0].pixels, 65);
+        assert_eq!(cells[0].max_dbz, 60.0);
+        assert_eq!(cells[0].window, (10, 10, 9, 9));
+        assert_eq!(cells[1].pixels, 64);
+        assert_eq!(cells[1].max_dbz, 50.0);
+        // Pixels are about 611 m wide at zoom 8 near the equator
+        assert!((cells[1].area_km2 - 64.0 * 0.611 * 0.611).abs() < 0.1);
+        let (lat, lon) = transform.pixel_to_lat_lon(104.0, 44.0);
+        assert!((cells[1].lat - lat).abs() < 1e-9 && (cells[1].lon - lon).abs() < 1e-9);
+
+        // Small cells are noise
+        let strict = detector.with_min_area(30.0);
+        let cells = strict
+            .detect(&image, ColorKind::BlackAndWhite, &transform)
+            .unwrap();
+        assert_eq!(cells.len(), 0);
+        assert!(detector
+            .detect(&image, ColorKind::Titan, &transform)
+            .is_err());
+    }
+
+    #[test]
+    fn tracks_cells() {
+        let transform = GeoTransform::new(ORIGIN, 256);
+        let detector = CellDetector::new().with_min_area(1.0);
+        let mut tracker = StormTracker::new();
+        // One cell moving 10 pixels east every 10 minutes, and one sitting still. Another
+        // appears in the last frame too far from either to continue them
+        for step in 0..3 {
+            let mut positions = vec![(20 + step * 10, 20), (20, 150)];
+            if step == 2 {
+                positions.push((200, 220));
+            }
+            let image = image(&positions, 6, 82);
+            let cells = detector
+                .detect(&image, ColorKind::BlackAndWhite, &transform)
+                .unwrap();
+            tracker.update(at(step as i64 * 10), cells);
+        }
+
+        assert_eq!(tracker.tracks().len(), 3);
+        assert_eq!(tracker.active().count(), 3);
+        let moving = &tracker.tracks()[0];
+        assert_eq!(moving.cells().len(), 3);
+        let velocity = moving.velocity().unwrap();
+        // 10 pixels of about 611 m every 10 minutes
+        assert!((velocity.speed_kmh - 36.7).abs() < 0.1);
+        assert!((velocity.heading_deg - 90.0).abs() < 0.01);
+        let still = &tracker.tracks()[1];
+        assert_eq!(still.cells().len(), 3);
+        assert!(still.velocity().unwrap().speed_kmh < 1e-6);
+        let new = &tracker.tracks()[2];
+        assert_eq!(new.cells().len(), 1);
+        assert_eq!(new.velocity(), None);
+
+        // Frames out of order are ignored, and tracks without a match end
+        tracker.update(at(0), Vec::new());
+        assert_eq!(tracker.active().count(), 3);
+        tracker.update(at(30), Vec::new());
+        assert_eq!(tracker.active().count(), 0);
+        assert_eq!(tracker.tracks().len(), 3);
+    }
+
+    #[test]
+    fn displacement() {
+        let (east, north) = displacement_km((0.0, 179.5), (1.0, -179.5));
+        assert!((east - KM_PER_DEGREE).abs() < 0.1);
+        assert!((north - KM_PER_DEGREE).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,150 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use image::RgbaImage;
+
+use crate::args::RequestArguments;
+use crate::data::{AvailableData, Frame};
+use crate::error;
+use crate::requester::WeatherRequester;
+
+/// The progress of a [`RadarTexture`]
+pub enum RadarTextureState {
+    /// The tile is still being downloaded or decoded
+    Loading,
+    /// The tile was uploaded, and can be drawn with `ui.image(&texture)`
+    Ready(egui::TextureHandle),
+    /// The tile could not be downloaded or decoded
+    Failed(error::Error),
+}
+
+/// A radar tile downloaded, decoded and uploaded to the GPU in the background, for embedding
+/// radar in [`egui`](https://docs.rs/egui) dashboards
+///
+/// [`RadarTexture::load`] returns the texture along with the future loading it, which the
+/// caller spawns on their async runtime. The future requests a repaint once it completes, so
+/// the UI only needs to check [`RadarTexture::state`] while drawing:
+///
+/// ```no_run
+/// use rain_viewer::{RadarTexture, RadarTextureState, RequestArguments, TileCoord};
+///
+/// # fn run(
+/// #     ctx: &egui::Context,
+/// #     ui: &mut egui::Ui,
+/// #     req: rain_viewer::WeatherRequester,
+/// #     maps: &rain_viewer::AvailableData,
+/// # ) -> Result<(), rain_viewer::Error> {
+/// // Once, when the frame to show is picked
+/// let frame = maps.latest_past().unwrap();
+/// let args = RequestArguments::new_tile(TileCoord::new(4, 7, 6))?;
+/// let (mut radar, load) = RadarTexture::load(ctx, req, maps, frame, args);
+/// tokio::spawn(load);
+///
+/// // On every redraw
+/// match radar.state() {
+///     RadarTextureState::Loading => { ui.spinner(); }
+///     RadarTextureState::Ready(texture) => { ui.image(texture); }
+///     RadarTextureState::Failed(err) => { ui.label(err.to_string()); }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Dropping the texture before the tile loads discards the tile once it arrives.
+///
+/// Enabled with the `egui` feature
+pub struct RadarTexture {
+    state: RadarTextureState,
+    /// Where the loading future leaves its result for [`RadarTexture::state`] to pick up
+    loaded: Arc<Mutex<Option<Result<egui::TextureHandle, error::Error>>>>,
+}
+
+impl RadarTexture {
+    /// A texture of the radar tile of `frame` at the location of `args`, along with the future
+    /// downloading, decoding and uploading it through `ctx`
+    ///
+    /// The future does nothing until it is spawned or awaited. The texture is named
+    /// `rainviewer-{ts}` after the Unix time of the frame, and is drawn with linear filtering
+    /// when scaled.
+    pub fn load(
+        ctx: &egui::Context,
+        requester: WeatherRequester,
+        maps: &AvailableData,
+        frame: &Frame,
+        args: RequestArguments,
+    ) -> (Self, impl Future<Output = ()> + 'static) {
+        let loaded = Arc::new(Mutex::new(None));
+        let texture = Self {
+            state: RadarTextureState::Loading,
+            loaded: Arc::clone(&loaded),
+        };
+
+        let ctx = ctx.clone();
+        let maps = maps.clone();
+        let frame = frame.clone();
+        let name = format!("rainviewer-{}", frame.unix_time());
+        let load = async move {
+            let result = requester
+                .get_tile_image(&maps, &frame, args)
+                .await
+                .map(|image| {
+                    let image = color_image(&image.into_rgba8());
+                    ctx.load_texture(name, image, egui::TextureOptions::LINEAR)
+                });
+            *loaded.lock().unwrap() = Some(result);
+            ctx.request_repaint();
+        };
+        (texture, load)
+    }
+
+    /// The progress of the texture, picking up the tile if it finished loading since the last
+    /// call
+    pub fn state(&mut self) -> &RadarTextureState {
+        if let Some(result) = self.loaded.lock().unwrap().take() {
+            self.state = match result {
+                Ok(texture) => RadarTextureState::Ready(texture),
+                Err(err) => RadarTextureState::Failed(err),
+            };
+        }
+        &self.state
+    }
+
+    /// The uploaded texture, or `None` if it is still loading or failed to load
+    pub fn texture(&mut self) -> Option<&egui::TextureHandle> {
+        match self.state() {
+            RadarTextureState::Ready(texture) => Some(texture),
+            _ => None,
+        }
+    }
+
+    /// Whether the tile is still being downloaded or decoded
+    pub fn is_loading(&mut self) -> bool {
+        matches!(self.state(), RadarTextureState::Loading)
+    }
+}
+
+/// Converts decoded radar imagery, such as a tile or a stitched region, into an image egui
+/// can upload with `ctx.load_texture`
+///
+/// Enabled with the `egui` feature
+pub fn color_image(image: &RgbaImage) -> egui::ColorImage {
+    let size = [image.width() as usize, image.height() as usize];
+    egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_images() {
+        let mut image = RgbaImage::new(3, 2);
+        image.put_pixel(2, 1, image::Rgba([255, 0, 0, 255]));
+        image.put_pixel(0, 0, image::Rgba([0, 0, 0, 0]));
+
+        let color = color_image(&image);
+        assert_eq!(color.size, [3, 2]);
+        assert_eq!(color.pixels[5], egui::Color32::RED);
+        assert_eq!(color.pixels[0], egui::Color32::TRANSPARENT);
+    }
+}
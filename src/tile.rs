@@ -0,0 +1,195 @@
+use bytes::Bytes;
+use image::{DynamicImage, GrayImage, RgbaImage};
+
+use crate::color::ColorKind;
+use crate::error::{self, ParameterError};
+use crate::palette::Palette;
+
+/// A downloaded radar tile, holding its PNG image and the color scheme it was requested with
+///
+/// Tile downloads return the raw PNG bytes, which can be wrapped in a `Tile` to decode them,
+/// whether they were just downloaded or loaded from a cache:
+///
+/// ```no_run
+/// # async fn run(req: &rain_viewer::WeatherRequester) -> Result<(), rain_viewer::Error> {
+/// use rain_viewer::{ColorKind, RequestArguments, Tile, TileCoord};
+///
+/// let maps = req.available().await?;
+/// let frame = maps.latest_past().unwrap();
+/// let mut args = RequestArguments::new_tile(TileCoord::new(4, 7, 6))?;
+/// args.set_color(ColorKind::Titan);
+///
+/// let tile = Tile::new(req.get_tile(&maps, frame, args).await?, ColorKind::Titan);
+/// let image = tile.decode()?;
+/// println!("{}x{}", image.width(), image.height());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Enabled with the `image` feature
+#[derive(Clone, Debug)]
+pub struct Tile {
+    png: Bytes,
+    color: ColorKind,
+}
+
+impl Tile {
+    /// Wraps the PNG image of a tile rendered with `color`
+    pub fn new(png: impl Into<Bytes>, color: ColorKind) -> Self {
+        Self {
+            png: png.into(),
+            color,
+        }
+    }
+
+    /// The PNG image of this tile
+    pub fn png(&self) -> &Bytes {
+        &self.png
+    }
+
+    /// The color scheme this tile was rendered with
+    pub fn color(&self) -> ColorKind {
+        self.color
+    }
+
+    /// Decodes the PNG image of this tile
+    ///
+    /// Returns Err(...) if the tile is not a valid PNG image
+    pub fn decode(&self) -> Result<DynamicImage, error::Error> {
+        decode_png(&self.png)
+    }
+
+    /// Decodes this tile into 8 bit RGBA pixels, whatever the layout of its PNG image
+    ///
+    /// Use [`RgbaImage::into_raw`] for a flat buffer of rows from north to south.
+    /// Returns Err(...) if the tile is not a valid PNG image
+    pub fn to_rgba8(&self) -> Result<RgbaImage, error::Error> {
+        Ok(self.decode()?.into_rgba8())
+    }
+
+    /// The raw reflectivity value of each pixel, for tiles requested with
+    /// [`ColorKind::BlackAndWhite`]
+    ///
+    /// That scheme encodes reflectivity as the brightness of each pixel: values below 128 are
+    /// rain of `value - 32` dBZ, and values of 128 and above are snow of `value - 160` dBZ, if
+    /// snow was requested. Pixels without precipitation are transparent, and are returned as
+    /// `0`. Use [`GrayImage::into_raw`] for a flat buffer of rows from north to south.
+    ///
+    /// Returns Err(...) if the tile was rendered with a colorized scheme, or is not a valid PNG
+    /// image
+    pub fn to_intensity(&self) -> Result<GrayImage, error::Error> {
+        self.expect_black_and_white()?;
+        let rgba = self.to_rgba8()?;
+        Ok(GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+            let [value, _, _, alpha] = rgba.get_pixel(x, y).0;
+            image::Luma([if alpha == 0 { 0 } else { value }])
+        }))
+    }
+
+    /// Redraws this tile with the colors of `palette`, which saves downloading it again in
+    /// another scheme
+    ///
+    /// Returns Err(...) if the tile was not requested with [`ColorKind::BlackAndWhite`], or is
+    /// not a valid PNG image
+    pub fn recolor(&self, palette: &Palette) -> Result<RgbaImage, error::Error> {
+        self.expect_black_and_white()?;
+        let mut image = self.to_rgba8()?;
+        for pixel in image.pixels_mut() {
+            pixel.0 = palette.recolor_pixel(pixel.0);
+        }
+        Ok(image)
+    }
+
+    /// Checks that this tile encodes raw reflectivity, see [`Tile::to_intensity`]
+    pub(crate) fn expect_black_and_white(&self) -> Result<(), ParameterError> {
+        if matches!(self.color, ColorKind::BlackAndWhite) {
+            Ok(())
+        } else {
+            Err(ParameterError::InvalidColor(
+                self.color.into(),
+                "Only black and white tiles encode raw reflectivity".to_owned(),
+            ))
+        }
+    }
+}
+
+impl AsRef<[u8]> for Tile {
+    fn as_ref(&self) -> &[u8] {
+        &self.png
+    }
+}
+
+/// Decodes the PNG image of a tile
+pub(crate) fn decode_png(png: &[u8]) -> Result<DynamicImage, error::Error> {
+    Ok(image::load_from_memory_with_format(
+        png,
+        image::ImageFormat::Png,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(image: DynamicImage) -> Vec<u8> {
+        let mut png = Vec::new();
+        image
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn decodes_tiles() {
+        let image = RgbaImage::from_pixel(4, 2, image::Rgba([1, 2, 3, 4]));
+        let tile = Tile::new(png(DynamicImage::ImageRgba8(image)), ColorKind::Titan);
+        let image = tile.decode().unwrap();
+        assert_eq!((image.width(), image.height()), (4, 2));
+        assert_eq!(image.into_rgba8().get_pixel(3, 1).0, [1, 2, 3, 4]);
+
+        let garbage = Tile::new(&b"not a png"[..], ColorKind::Titan);
+        assert!(matches!(garbage.decode(), Err(error::Error::Image(_))));
+    }
+
+    #[test]
+    fn intensity() {
+        // Black and white tiles are served as gray with alpha
+        let mut image = image::GrayAlphaImage::new(3, 1);
+        image.put_pixel(0, 0, image::LumaA([52, 255]));
+        image.put_pixel(1, 0, image::LumaA([140, 255]));
+        image.put_pixel(2, 0, image::LumaA([90, 0]));
+        let png = png(DynamicImage::ImageLumaA8(image));
+
+        let tile = Tile::new(png.clone(), ColorKind::BlackAndWhite);
+        assert_eq!(
+            tile.to_rgba8().unwrap().get_pixel(1, 0).0,
+            [140, 140, 140, 255]
+        );
+        assert_eq!(tile.to_intensity().unwrap().into_raw(), [52, 140, 0]);
+
+        let colorized = Tile::new(png, ColorKind::Titan);
+        assert!(matches!(
+            colorized.to_intensity(),
+            Err(error::Error::Parameter(ParameterError::InvalidColor(3, _)))
+        ));
+    }
+
+    #[test]
+    fn recolors() {
+        let mut image = image::GrayAlphaImage::new(2, 1);
+        image.put_pixel(0, 0, image::LumaA([52, 255]));
+        image.put_pixel(1, 0, image::LumaA([52, 0]));
+        let tile = Tile::new(
+            png(DynamicImage::ImageLumaA8(image)),
+            ColorKind::BlackAndWhite,
+        );
+
+        let palette = Palette::from_stops([(20.0, [10, 20, 30, 255])]).unwrap();
+        let recolored = tile.recolor(&palette).unwrap();
+        assert_eq!(recolored.get_pixel(0, 0).0, [10, 20, 30, 255]);
+        assert_eq!(recolored.get_pixel(1, 0).0, [0; 4]);
+
+        let colorized = Tile::new(tile.png().clone(), ColorKind::Titan);
+        assert!(colorized.recolor(&palette).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+use std::ops::RangeInclusive;
+
+use serde::Serialize;
+
+use crate::args::{tile_url_template, RequestArguments, MAX_LATITUDE, MAX_RADAR_ZOOM};
+use crate::data::{AvailableData, Frame};
+use crate::error::ParameterError;
+use crate::geo::LatLonBounds;
+
+/// The attribution RainViewer requires wherever its imagery is shown, as HTML for map
+/// libraries' attribution controls
+pub(crate) const ATTRIBUTION_HTML: &str = "<a href=\"https://www.rainviewer.com\">RainViewer</a>";
+
+/// A [TileJSON 3.0](https://github.com/mapbox/tilejson-spec/tree/master/3.0.0) document
+/// describing a radar tile layer, which MapLibre GL, Mapbox GL and other map libraries load
+/// as a source without further configuration
+///
+/// Serialize it with [`TileJson::to_json`], or with serde, and serve it next to the map or pass
+/// it to the map library inline.
+///
+/// ```
+/// use rain_viewer::{RequestArguments, TileCoord, TileJson, TileSize};
+///
+/// # fn run(maps: &rain_viewer::AvailableData) -> Result<(), rain_viewer::Error> {
+/// let frame = maps.latest_past().unwrap();
+/// let mut args = RequestArguments::new_tile(TileCoord::new(0, 0, 0))?;
+/// args.set_size(TileSize::Px512);
+///
+/// // Tiles straight from Rain Viewer
+/// let direct = TileJson::for_frame(maps, frame, &args)?;
+/// // Or tiles from a proxy, such as the `server` feature's router
+/// let ts = frame.unix_time();
+/// let template = format!("https://tiles.example.com/radar/{ts}/{{z}}/{{x}}/{{y}}.png");
+/// let proxied = TileJson::new(template).with_zooms(0..=7)?;
+/// println!("{}", proxied.to_json());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TileJson {
+    /// The version of the TileJSON specification, `3.0.0`
+    pub tilejson: String,
+    /// The url templates of the tiles, with `{z}`, `{x}` and `{y}` placeholders
+    pub tiles: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// HTML crediting RainViewer, which its terms require wherever the tiles are shown
+    pub attribution: String,
+    pub minzoom: u32,
+    pub maxzoom: u32,
+    /// The area covered, as `[west, south, east, north]` in degrees
+    pub bounds: [f64; 4],
+}
+
+impl TileJson {
+    /// A layer of the tiles at `template`, with `{z}`, `{x}` and `{y}` placeholders, covering
+    /// the whole world at every zoom level up to [`MAX_RADAR_ZOOM`]
+    pub fn new(template: impl Into<String>) -> Self {
+        Self {
+            tilejson: "3.0.0".to_owned(),
+            tiles: vec![template.into()],
+            name: None,
+            attribution: ATTRIBUTION_HTML.to_owned(),
+            minzoom: 0,
+            maxzoom: MAX_RADAR_ZOOM,
+            bounds: [-180.0, -MAX_LATITUDE, 180.0, MAX_LATITUDE],
+        }
+    }
+
+    /// A layer of the radar tiles of `frame` served directly by Rain Viewer, named after the
+    /// time of the frame
+    ///
+    /// The size, color and options of `args` apply to every tile, while its location is
+    /// ignored. Map libraries draw tiles at 512 pixels only if told to, so set the source's tile
+    /// size to match [`TileSize::Px512`](crate::TileSize::Px512) tiles.
+    ///
+    /// Returns Err(...) if `frame` is not a past or nowcast radar frame
+    pub fn for_frame(
+        maps: &AvailableData,
+        frame: &Frame,
+        args: &RequestArguments,
+    ) -> Result<Self, ParameterError> {
+        frame.expect_radar()?;
+        Ok(
+            Self::new(tile_url_template(maps, frame, args)).with_name(format!(
+                "Rain Viewer radar {}",
+                crate::timestamp::rfc3339(frame.time)
+            )),
+        )
+    }
+
+    /// Sets the name of the layer
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the zoom levels tiles are available at. Map libraries upscale tiles of
+    /// the highest zoom level when zoomed in further
+    ///
+    /// `zooms` must not be empty and must not exceed [`MAX_RADAR_ZOOM`], or Err(...) is returned
+    pub fn with_zooms(mut self, zooms: RangeInclusive<u32>) -> Result<Self, ParameterError> {
+        if zooms.is_empty() {
+            return Err(ParameterError::InvalidZoom(
+                *zooms.start(),
+                format!("The zoom range ends at {}", zooms.end()),
+            ));
+        }
+        if *zooms.end() > MAX_RADAR_ZOOM {
+            return Err(ParameterError::InvalidZoom(
+                *zooms.end(),
+                format!("The max zoom for this product is {}", MAX_RADAR_ZOOM),
+            ));
+        }
+        self.minzoom = *zooms.start();
+        self.maxzoom = *zooms.end();
+        Ok(self)
+    }
+
+    /// Sets the area tiles are requested for, such as the area covered by radar. Latitudes are
+    /// clamped to the web mercator limits
+    pub fn with_bounds(mut self, bounds: LatLonBounds) -> Self {
+        self.bounds = [
+            bounds.west,
+            bounds.south.max(-MAX_LATITUDE),
+            bounds.east,
+            bounds.north.min(MAX_LATITUDE),
+        ];
+        self
+    }
+
+    /// The document as JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("TileJSON documents always serialize")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geo::TileCoord;
+    use crate::ColorKind;
+
+    #[test]
+    fn for_frame() {
+        let maps = AvailableData::fixture();
+        let mut args = RequestArguments::new_tile(TileCoord::new(1, 2, 3)).unwrap();
+        args.set_color(ColorKind::BlackAndWhite).set_snow(false);
+
+        let tilejson = TileJson::for_frame(&maps, &maps.past_radar[0], &args).unwrap();
+        assert_eq!(
+            tilejson.tiles,
+            ["https://tilecache.rainviewer.com/v2/radar/1696998600/256/{z}/{x}/{y}/0/1_0.png"]
+        );
+        assert_eq!(
+            tilejson.name.as_deref(),
+            Some("Rain Viewer radar 2023-10-11T04:30:00Z")
+        );
+        assert!(TileJson::for_frame(&maps, &maps.infrared_satellite[0], &args).is_err());
+    }
+
+    #[test]
+    fn serializes() {
+        let bounds = LatLonBounds::new(-10.0, 49.0, 2.0, 59.0).unwrap();
+        let tilejson = TileJson::new("https://example.com/{z}/{x}/{y}.png")
+            .with_zooms(2..=7)
+            .unwrap()
+            .with_bounds(bounds);
+        let json: serde_json::Value = serde_json::from_str(&tilejson.to_json()).unwrap();
+        assert_eq!(json["tilejson"], "3.0.0");
+        assert_eq!(json["tiles"][0], "https://example.com/{z}/{x}/{y}.png");
+        assert_eq!(
+            (json["minzoom"].as_u64(), json["maxzoom"].as_u64()),
+            (Some(2), Some(7))
+        );
+        assert_eq!(json["bounds"], serde_json::json!([-10.0, 49.0, 2.0, 59.0]));
+        assert!(json["attribution"].as_str().unwrap().contains("RainViewer"));
+        assert!(json.get("name").is_none());
+
+        assert!(TileJson::new("")
+            .with_zooms(std::ops::RangeInclusive::new(3, 2))
+            .is_err());
+        assert!(TileJson::new("").with_zooms(0..=13).is_err());
+    }
+}
@@ -0,0 +1,307 @@
+#[cfg(not(any(feature = "chrono", feature = "time", feature = "jiff")))]
+compile_error!("One of the `chrono`, `time` or `jiff` features must be enabled");
+
+/// A point in time in UTC, such as [`Frame::time`](crate::Frame::time)
+///
+/// This is a `chrono::DateTime<Utc>` with the default `chrono` feature. Without it, this is a
+/// `time::OffsetDateTime` with the `time` feature, or a `jiff::Timestamp` with the `jiff`
+/// feature
+pub type Timestamp = backend::Timestamp;
+
+/// A signed span of time between two [`Timestamp`]s, such as [`Frame::age`](crate::Frame::age)
+///
+/// A `chrono::Duration`, `time::Duration` or `jiff::SignedDuration`, following [`Timestamp`]
+pub type TimeDelta = backend::TimeDelta;
+
+/// A fixed offset from UTC, such as the one of
+/// [`TimeZoneLabel::Offset`](crate::TimeZoneLabel::Offset)
+///
+/// A `chrono::FixedOffset`, `time::UtcOffset` or `jiff::tz::Offset`, following [`Timestamp`]
+pub type UtcOffset = backend::UtcOffset;
+
+#[cfg(all(test, feature = "image"))]
+pub(crate) use backend::offset_from_seconds;
+pub(crate) use backend::{delta_from_millis, delta_millis, from_unix_millis, now, unix_millis};
+#[cfg(feature = "image")]
+pub(crate) use backend::{local_offset_seconds, offset_seconds};
+
+#[cfg(feature = "chrono")]
+use chrono_backend as backend;
+#[cfg(all(feature = "jiff", not(any(feature = "chrono", feature = "time"))))]
+use jiff_backend as backend;
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+use time_backend as backend;
+
+#[cfg(feature = "chrono")]
+mod chrono_backend {
+    pub type Timestamp = chrono::DateTime<chrono::Utc>;
+    pub type TimeDelta = chrono::Duration;
+    pub type UtcOffset = chrono::FixedOffset;
+
+    /// The time `millis` milliseconds after the Unix epoch, or `None` if it is out of range
+    pub fn from_unix_millis(millis: i64) -> Option<Timestamp> {
+        let nanos = millis.rem_euclid(1000) as u32 * 1_000_000;
+        chrono::DateTime::from_timestamp(millis.div_euclid(1000), nanos)
+    }
+
+    /// The number of milliseconds from the Unix epoch to `time`
+    pub fn unix_millis(time: Timestamp) -> i64 {
+        time.timestamp() * 1000 + time.timestamp_subsec_millis() as i64
+    }
+
+    /// The current time
+    pub fn now() -> Timestamp {
+        chrono::Utc::now()
+    }
+
+    /// A span of `millis` milliseconds
+    pub fn delta_from_millis(millis: i64) -> TimeDelta {
+        TimeDelta::milliseconds(millis)
+    }
+
+    /// The length of `delta` in whole milliseconds
+    pub fn delta_millis(delta: TimeDelta) -> i64 {
+        delta.num_milliseconds()
+    }
+
+    /// The number of seconds `offset` is ahead of UTC
+    #[cfg(feature = "image")]
+    pub fn offset_seconds(offset: UtcOffset) -> i32 {
+        offset.local_minus_utc()
+    }
+
+    /// The offset `seconds` ahead of UTC, or `None` if it is out of range
+    #[cfg(all(test, feature = "image"))]
+    pub fn offset_from_seconds(seconds: i32) -> Option<UtcOffset> {
+        UtcOffset::east_opt(seconds)
+    }
+
+    /// The number of seconds the system's local time zone is ahead of UTC at `time`
+    #[cfg(feature = "image")]
+    pub fn local_offset_seconds(time: Timestamp) -> i32 {
+        use chrono::{Local, Offset, TimeZone};
+
+        Local
+            .offset_from_utc_datetime(&time.naive_utc())
+            .fix()
+            .local_minus_utc()
+    }
+}
+
+#[cfg(feature = "time")]
+#[cfg_attr(feature = "chrono", allow(dead_code))]
+mod time_backend {
+    pub type Timestamp = time::OffsetDateTime;
+    pub type TimeDelta = time::Duration;
+    pub type UtcOffset = time::UtcOffset;
+
+    /// The time `millis` milliseconds after the Unix epoch, or `None` if it is out of range
+    pub fn from_unix_millis(millis: i64) -> Option<Timestamp> {
+        Timestamp::from_unix_timestamp_nanos(millis as i128 * 1_000_000).ok()
+    }
+
+    /// The number of milliseconds from the Unix epoch to `time`
+    pub fn unix_millis(time: Timestamp) -> i64 {
+        time.unix_timestamp_nanos().div_euclid(1_000_000) as i64
+    }
+
+    /// The current time
+    pub fn now() -> Timestamp {
+        Timestamp::now_utc()
+    }
+
+    /// A span of `millis` milliseconds
+    pub fn delta_from_millis(millis: i64) -> TimeDelta {
+        TimeDelta::milliseconds(millis)
+    }
+
+    /// The length of `delta` in whole milliseconds
+    pub fn delta_millis(delta: TimeDelta) -> i64 {
+        delta.whole_milliseconds() as i64
+    }
+
+    /// The number of seconds `offset` is ahead of UTC
+    #[cfg(feature = "image")]
+    pub fn offset_seconds(offset: UtcOffset) -> i32 {
+        offset.whole_seconds()
+    }
+
+    /// The offset `seconds` ahead of UTC, or `None` if it is out of range
+    #[cfg(all(test, feature = "image"))]
+    pub fn offset_from_seconds(seconds: i32) -> Option<UtcOffset> {
+        UtcOffset::from_whole_seconds(seconds).ok()
+    }
+
+    /// The number of seconds the system's local time zone is ahead of UTC at `time`, or 0
+    /// where it cannot be determined soundly, such as in multithreaded programs on Unix
+    #[cfg(feature = "image")]
+    pub fn local_offset_seconds(time: Timestamp) -> i32 {
+        UtcOffset::local_offset_at(time).map_or(0, offset_seconds)
+    }
+}
+
+#[cfg(feature = "jiff")]
+#[cfg_attr(any(feature = "chrono", feature = "time"), allow(dead_code))]
+mod jiff_backend {
+    pub type Timestamp = jiff::Timestamp;
+    pub type TimeDelta = jiff::SignedDuration;
+    pub type UtcOffset = jiff::tz::Offset;
+
+    /// The time `millis` milliseconds after the Unix epoch, or `None` if it is out of range
+    pub fn from_unix_millis(millis: i64) -> Option<Timestamp> {
+        Timestamp::from_millisecond(millis).ok()
+    }
+
+    /// The number of milliseconds from the Unix epoch to `time`
+    pub fn unix_millis(time: Timestamp) -> i64 {
+        time.as_millisecond()
+    }
+
+    /// The current time
+    pub fn now() -> Timestamp {
+        Timestamp::now()
+    }
+
+    /// A span of `millis` milliseconds
+    pub fn delta_from_millis(millis: i64) -> TimeDelta {
+        TimeDelta::from_millis(millis)
+    }
+
+    /// The length of `delta` in whole milliseconds
+    pub fn delta_millis(delta: TimeDelta) -> i64 {
+        delta.as_millis() as i64
+    }
+
+    /// The number of seconds `offset` is ahead of UTC
+    #[cfg(feature = "image")]
+    pub fn offset_seconds(offset: UtcOffset) -> i32 {
+        offset.seconds()
+    }
+
+    /// The offset `seconds` ahead of UTC, or `None` if it is out of range
+    #[cfg(all(test, feature = "image"))]
+    pub fn offset_from_seconds(seconds: i32) -> Option<UtcOffset> {
+        UtcOffset::from_seconds(seconds).ok()
+    }
+
+    /// The number of seconds the system's local time zone is ahead of UTC at `time`
+    #[cfg(feature = "image")]
+    pub fn local_offset_seconds(time: Timestamp) -> i32 {
+        jiff::tz::TimeZone::system().to_offset(time).seconds()
+    }
+}
+
+/// The time `seconds` seconds after the Unix epoch, or `None` if it is out of range
+pub(crate) fn from_unix_seconds(seconds: i64) -> Option<Timestamp> {
+    from_unix_millis(seconds.checked_mul(1000)?)
+}
+
+/// The number of whole seconds from the Unix epoch to `time`
+pub(crate) fn unix_seconds(time: Timestamp) -> i64 {
+    unix_millis(time).div_euclid(1000)
+}
+
+/// The span from `start` to `end`, negative if `end` is before `start`
+pub(crate) fn between(start: Timestamp, end: Timestamp) -> TimeDelta {
+    delta_from_millis(unix_millis(end) - unix_millis(start))
+}
+
+/// The number of hours from `start` to `end`, negative if `end` is before `start`
+pub(crate) fn hours_between(start: Timestamp, end: Timestamp) -> f64 {
+    (unix_millis(end) - unix_millis(start)) as f64 / 3_600_000.0
+}
+
+/// `time` as a `time::OffsetDateTime` in UTC, or `None` if it is out of that crate's range
+#[cfg(feature = "time")]
+pub(crate) fn to_offset_date_time(time: Timestamp) -> Option<time::OffsetDateTime> {
+    time_backend::from_unix_millis(unix_millis(time))
+}
+
+/// `time` as a `jiff::Timestamp`, or `None` if it is out of that crate's range
+#[cfg(feature = "jiff")]
+pub(crate) fn to_jiff_timestamp(time: Timestamp) -> Option<jiff::Timestamp> {
+    jiff_backend::from_unix_millis(unix_millis(time))
+}
+
+/// Formats `time` as an RFC 3339 timestamp to the second in UTC, such as
+/// `2023-10-11T05:00:00Z`
+pub(crate) fn rfc3339(time: Timestamp) -> String {
+    let (year, month, day, hour, minute, second) = civil(unix_seconds(time));
+    format!("{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}Z")
+}
+
+/// Formats `time` to the minute at `offset` seconds ahead of UTC, such as `2023-10-11 05:00`
+#[cfg(feature = "image")]
+pub(crate) fn format_minutes(time: Timestamp, offset: i32) -> String {
+    let (year, month, day, hour, minute, _) = civil(unix_seconds(time) + offset as i64);
+    format!("{year:04}-{month:02}-{day:02} {hour:02}:{minute:02}")
+}
+
+/// Formats an offset of `seconds` ahead of UTC as `+HH:MM`
+#[cfg(feature = "image")]
+pub(crate) fn format_offset(seconds: i32) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.unsigned_abs() / 60;
+    format!("{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// The proleptic Gregorian year, month, day, hour, minute and second of `seconds` after the
+/// Unix epoch
+fn civil(seconds: i64) -> (i64, u32, u32, u32, u32, u32) {
+    let (days, time) = (
+        seconds.div_euclid(86_400),
+        seconds.rem_euclid(86_400) as u32,
+    );
+    // Howard Hinnant's `civil_from_days`, counting 400 year eras from March 1st, year 0
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day, time / 3600, time / 60 % 60, time % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        let time = from_unix_millis(1_697_000_400_250).unwrap();
+        assert_eq!(unix_millis(time), 1_697_000_400_250);
+        assert_eq!(unix_seconds(time), 1_697_000_400);
+        assert_eq!(unix_seconds(from_unix_seconds(-1).unwrap()), -1);
+        assert_eq!(from_unix_seconds(i64::MAX), None);
+
+        let later = from_unix_seconds(1_697_005_800).unwrap();
+        assert_eq!(delta_millis(between(time, later)), 90 * 60_000 - 250);
+        assert_eq!(hours_between(later, time), -1.5 + 250.0 / 3_600_000.0);
+    }
+
+    #[test]
+    fn formatting() {
+        let time = from_unix_seconds(1_697_000_400).unwrap();
+        assert_eq!(rfc3339(time), "2023-10-11T05:00:00Z");
+        assert_eq!(
+            rfc3339(from_unix_seconds(951_782_400).unwrap()),
+            "2000-02-29T00:00:00Z"
+        );
+        assert_eq!(
+            rfc3339(from_unix_seconds(-1).unwrap()),
+            "1969-12-31T23:59:59Z"
+        );
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn local_formatting() {
+        let time = from_unix_seconds(1_697_000_400).unwrap();
+        assert_eq!(format_minutes(time, -4 * 3600), "2023-10-11 01:00");
+        assert_eq!(format_offset(2 * 3600), "+02:00");
+        assert_eq!(format_offset(-(9 * 3600 + 30 * 60)), "-09:30");
+    }
+}
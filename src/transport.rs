@@ -0,0 +1,259 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use bytes::Bytes;
+use futures_util::Stream;
+
+use crate::error::Error;
+
+/// A boxed future returned by [`HttpTransport`] implementations
+#[cfg(not(target_arch = "wasm32"))]
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A boxed future returned by [`HttpTransport`] implementations
+///
+/// Browser futures cannot be sent between threads, so this is not `Send` on wasm32
+#[cfg(target_arch = "wasm32")]
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+/// The body of a response returned by [`HttpTransport::get_streaming`], as a stream of chunks
+#[cfg(not(target_arch = "wasm32"))]
+pub type BodyStream = Pin<Box<dyn Stream<Item = Result<Bytes, Error>> + Send>>;
+
+/// The body of a response returned by [`HttpTransport::get_streaming`], as a stream of chunks
+///
+/// Browser streams cannot be sent between threads, so this is not `Send` on wasm32
+#[cfg(target_arch = "wasm32")]
+pub type BodyStream = Pin<Box<dyn Stream<Item = Result<Bytes, Error>>>>;
+
+/// Request extension limiting the size of the response body, set by
+/// [`WeatherRequester::with_max_response_size`](crate::WeatherRequester::with_max_response_size)
+///
+/// Transports should stop reading the body and return [`Error::ResponseTooLarge`] once it
+/// exceeds this many bytes. The requester checks the size of the returned body as well, so
+/// ignoring this extension only wastes bandwidth
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ResponseSizeLimit(pub usize);
+
+/// The HTTP client used by [`WeatherRequester`](crate::WeatherRequester) to talk to Rain Viewer
+///
+/// [`ReqwestTransport`] is used by default. Implement this trait to issue requests through
+/// another HTTP library, or to serve canned responses in tests.
+///
+/// Implementations should return the response for any status code, since the requester
+/// interprets non-success statuses itself. `Err(...)` is reserved for requests that could not be
+/// completed at all. Custom errors can be wrapped in [`Error::Transport`]
+pub trait HttpTransport: Send + Sync {
+    /// Performs a GET request
+    fn get(
+        &self,
+        request: http::Request<()>,
+    ) -> BoxFuture<'_, Result<http::Response<Bytes>, Error>>;
+
+    /// Performs a GET request, returning the response once its headers are received and the
+    /// body as a stream of chunks
+    ///
+    /// This is used by [`WeatherRequester::get_tile_to`](crate::WeatherRequester::get_tile_to).
+    /// The default implementation reads the whole body with [`Self::get`] and yields it as a
+    /// single chunk. Override it to avoid buffering large bodies
+    fn get_streaming(
+        &self,
+        request: http::Request<()>,
+    ) -> BoxFuture<'_, Result<http::Response<BodyStream>, Error>> {
+        let res = self.get(request);
+        Box::pin(async move {
+            Ok(res.await?.map(|body| {
+                Box::pin(futures_util::stream::once(std::future::ready(Ok(body)))) as BodyStream
+            }))
+        })
+    }
+}
+
+/// The default [`HttpTransport`], backed by a [`reqwest::Client`]
+#[derive(Clone, Debug, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    /// Creates a transport issuing requests through `client`
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+impl HttpTransport for ReqwestTransport {
+    fn get(
+        &self,
+        request: http::Request<()>,
+    ) -> BoxFuture<'_, Result<http::Response<Bytes>, Error>> {
+        Box::pin(async move {
+            let (parts, ()) = request.into_parts();
+            let limit = parts.extensions.get::<ResponseSizeLimit>().copied();
+            let res = self
+                .client
+                .get(parts.uri.to_string())
+                .headers(parts.headers)
+                .send()
+                .await?;
+            into_http_response(res, limit).await
+        })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn get_streaming(
+        &self,
+        request: http::Request<()>,
+    ) -> BoxFuture<'_, Result<http::Response<BodyStream>, Error>> {
+        Box::pin(async move {
+            let (parts, ()) = request.into_parts();
+            let limit = parts.extensions.get::<ResponseSizeLimit>().copied();
+            let res = self
+                .client
+                .get(parts.uri.to_string())
+                .headers(parts.headers)
+                .send()
+                .await?;
+            into_streaming_response(res, limit)
+        })
+    }
+}
+
+/// An [`HttpTransport`] issuing requests through a [`reqwest_middleware::ClientWithMiddleware`],
+/// so retry, tracing and caching middlewares apply to Rain Viewer requests
+///
+/// Enabled with the `middleware` feature
+#[cfg(feature = "middleware")]
+#[derive(Clone, Debug)]
+pub struct MiddlewareTransport {
+    client: reqwest_middleware::ClientWithMiddleware,
+}
+
+#[cfg(feature = "middleware")]
+impl MiddlewareTransport {
+    /// Creates a transport issuing requests through `client`
+    pub fn new(client: reqwest_middleware::ClientWithMiddleware) -> Self {
+        Self { client }
+    }
+}
+
+#[cfg(feature = "middleware")]
+impl HttpTransport for MiddlewareTransport {
+    fn get(
+        &self,
+        request: http::Request<()>,
+    ) -> BoxFuture<'_, Result<http::Response<Bytes>, Error>> {
+        Box::pin(async move {
+            let (parts, ()) = request.into_parts();
+            let limit = parts.extensions.get::<ResponseSizeLimit>().copied();
+            let res = self
+                .client
+                .get(parts.uri.to_string())
+                .headers(parts.headers)
+                .send()
+                .await
+                .map_err(from_middleware_error)?;
+            into_http_response(res, limit).await
+        })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn get_streaming(
+        &self,
+        request: http::Request<()>,
+    ) -> BoxFuture<'_, Result<http::Response<BodyStream>, Error>> {
+        Box::pin(async move {
+            let (parts, ()) = request.into_parts();
+            let limit = parts.extensions.get::<ResponseSizeLimit>().copied();
+            let res = self
+                .client
+                .get(parts.uri.to_string())
+                .headers(parts.headers)
+                .send()
+                .await
+                .map_err(from_middleware_error)?;
+            into_streaming_response(res, limit)
+        })
+    }
+}
+
+/// Maps errors of the middleware client onto this crate's errors, keeping reqwest errors intact
+#[cfg(feature = "middleware")]
+fn from_middleware_error(err: reqwest_middleware::Error) -> Error {
+    match err {
+        reqwest_middleware::Error::Reqwest(err) => Error::Reqwest(err),
+        reqwest_middleware::Error::Middleware(err) => Error::Transport(err.into()),
+    }
+}
+
+/// Reads a whole reqwest response into an [`http::Response`], failing once the body exceeds
+/// `limit`
+async fn into_http_response(
+    res: reqwest::Response,
+    limit: Option<ResponseSizeLimit>,
+) -> Result<http::Response<Bytes>, Error> {
+    let mut response = http::Response::builder().status(res.status());
+    if let Some(headers) = response.headers_mut() {
+        *headers = res.headers().clone();
+    }
+    let body = match limit {
+        Some(ResponseSizeLimit(limit)) => {
+            if res.content_length().is_some_and(|len| len > limit as u64) {
+                return Err(Error::ResponseTooLarge(limit));
+            }
+            read_limited(res, limit).await?
+        }
+        None => res.bytes().await?,
+    };
+    Ok(response
+        .body(body)
+        .expect("status and headers come from a valid response"))
+}
+
+/// Wraps a reqwest response into an [`http::Response`] streaming its body. Bodies announced to
+/// be larger than `limit` are rejected, while the caller enforces the limit on other bodies
+#[cfg(not(target_arch = "wasm32"))]
+fn into_streaming_response(
+    res: reqwest::Response,
+    limit: Option<ResponseSizeLimit>,
+) -> Result<http::Response<BodyStream>, Error> {
+    use futures_util::TryStreamExt;
+
+    if let Some(ResponseSizeLimit(limit)) = limit {
+        if res.content_length().is_some_and(|len| len > limit as u64) {
+            return Err(Error::ResponseTooLarge(limit));
+        }
+    }
+    let mut response = http::Response::builder().status(res.status());
+    if let Some(headers) = response.headers_mut() {
+        *headers = res.headers().clone();
+    }
+    let body: BodyStream = Box::pin(res.bytes_stream().map_err(Error::from));
+    Ok(response
+        .body(body)
+        .expect("status and headers come from a valid response"))
+}
+
+/// Reads the body chunk by chunk, so that oversized bodies without a `Content-Length` are not
+/// downloaded completely
+#[cfg(not(target_arch = "wasm32"))]
+async fn read_limited(mut res: reqwest::Response, limit: usize) -> Result<Bytes, Error> {
+    let mut body = bytes::BytesMut::new();
+    while let Some(chunk) = res.chunk().await? {
+        if body.len() + chunk.len() > limit {
+            return Err(Error::ResponseTooLarge(limit));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body.freeze())
+}
+
+/// The browser buffers the whole body, so it can only be checked once read
+#[cfg(target_arch = "wasm32")]
+async fn read_limited(res: reqwest::Response, limit: usize) -> Result<Bytes, Error> {
+    let body = res.bytes().await?;
+    if body.len() > limit {
+        return Err(Error::ResponseTooLarge(limit));
+    }
+    Ok(body)
+}
@@ -0,0 +1,88 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+#[cfg(feature = "gif")]
+#[tokio::test]
+async fn gif() {
+    use common::MockTransport;
+    use image::AnimationDecoder;
+    use rain_viewer::animation::{self, AnimationArea, AnimationOptions};
+    use rain_viewer::{RequestArguments, TileCoord, WeatherRequester};
+
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    let frames = &maps.past_radar[maps.past_radar.len() - 2..];
+    let area = AnimationArea::Tile(TileCoord::new(4, 7, 6));
+    let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0)).unwrap();
+
+    let gif = animation::gif(&req, &maps, frames, &area, args, &AnimationOptions::new())
+        .await
+        .unwrap();
+    let decoder = image::codecs::gif::GifDecoder::new(std::io::Cursor::new(gif)).unwrap();
+    let decoded = decoder.into_frames().collect_frames().unwrap();
+    assert_eq!(decoded.len(), 2);
+    assert_eq!(decoded[0].buffer().dimensions(), (256, 256));
+
+    let urls = mock.urls();
+    for (url, frame) in urls[1..].iter().zip(frames) {
+        assert!(url.contains(&format!("{}/256/6/4/7/", frame.path)));
+    }
+
+    assert!(
+        animation::gif(&req, &maps, [], &area, args, &AnimationOptions::new())
+            .await
+            .is_err()
+    );
+    let satellite = &maps.infrared_satellite[..1];
+    assert!(animation::gif(
+        &req,
+        &maps,
+        satellite,
+        &area,
+        args,
+        &AnimationOptions::new()
+    )
+    .await
+    .is_err());
+}
+
+#[cfg(feature = "apng")]
+#[tokio::test]
+async fn apng() {
+    use common::MockTransport;
+    use image::AnimationDecoder;
+    use rain_viewer::animation::{self, AnimationArea, AnimationOptions};
+    use rain_viewer::{LatLonBounds, RequestArguments, TileCoord, WeatherRequester};
+
+    let req = WeatherRequester::with_transport(MockTransport::new());
+    let maps = req.available().await.unwrap();
+    let area = AnimationArea::Region {
+        bounds: LatLonBounds::new(-180.0, 1.0, -1.0, 85.0).unwrap(),
+        zoom: 1,
+    };
+    let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0)).unwrap();
+
+    let apng = animation::apng(
+        &req,
+        &maps,
+        maps.all_radar(),
+        &area,
+        args,
+        &AnimationOptions::new(),
+    )
+    .await
+    .unwrap();
+    let decoder = image::codecs::png::PngDecoder::new(std::io::Cursor::new(apng)).unwrap();
+    let frames = decoder
+        .apng()
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .unwrap();
+    assert_eq!(frames.len(), maps.all_radar().count());
+    // The north west quarter of the world, cropped from the single tile covering it
+    let (width, height) = frames[0].buffer().dimensions();
+    assert!(width <= 256 && height <= 256 && width > 200);
+}
@@ -1,9 +1,12 @@
+#![cfg(not(target_arch = "wasm32"))]
+
 #[tokio::test]
 async fn api() {
     let req = rain_viewer::WeatherRequester::new();
     let maps = req.available().await.unwrap();
     let frame = &maps.past_radar[0];
-    let mut args = rain_viewer::RequestArguments::new_tile(4, 7, 6).unwrap();
+    let mut args =
+        rain_viewer::RequestArguments::new_tile(rain_viewer::TileCoord::new(4, 7, 6)).unwrap();
     args.set_color(rain_viewer::ColorKind::Titan);
     args.set_snow(true);
     args.set_smooth(false);
@@ -16,19 +19,63 @@ async fn api() {
 #[should_panic]
 #[tokio::test]
 async fn bad_x() {
-    let _ = rain_viewer::RequestArguments::new_tile(40, 1, 2).unwrap();
+    let _ = rain_viewer::RequestArguments::new_tile(rain_viewer::TileCoord::new(40, 1, 2)).unwrap();
 }
 
 #[should_panic]
 #[tokio::test]
 async fn bad_y() {
-    let _ = rain_viewer::RequestArguments::new_tile(0, 4, 2).unwrap();
+    let _ = rain_viewer::RequestArguments::new_tile(rain_viewer::TileCoord::new(0, 4, 2)).unwrap();
 }
 
 #[should_panic]
 #[tokio::test]
 async fn bad_size() {
-    let _ = rain_viewer::RequestArguments::new_tile(0, 4, 2)
+    let _ = rain_viewer::RequestArguments::new_tile(rain_viewer::TileCoord::new(0, 4, 2))
         .unwrap()
-        .set_size(100);
+        .set_size(rain_viewer::TileSize::try_from(100).unwrap());
+}
+
+#[should_panic]
+#[tokio::test]
+async fn bad_latitude() {
+    let _ = rain_viewer::RequestArguments::new_position(89.0, 0.0, 2).unwrap();
+}
+
+#[should_panic]
+#[tokio::test]
+async fn bad_longitude() {
+    let _ = rain_viewer::RequestArguments::new_position(0.0, 181.0, 2).unwrap();
+}
+
+#[should_panic]
+#[tokio::test]
+async fn bad_satellite_x() {
+    let _ =
+        rain_viewer::SatelliteArguments::new_tile(rain_viewer::TileCoord::new(4, 0, 2)).unwrap();
+}
+
+#[tokio::test]
+async fn bad_coverage_y() {
+    let req = rain_viewer::WeatherRequester::new();
+    let err = req.get_coverage_tile(0, 9, 3).await.unwrap_err();
+    assert!(matches!(
+        err,
+        rain_viewer::Error::Parameter(rain_viewer::ParameterError::YOutOfRange(9, _))
+    ));
+}
+
+#[test]
+fn tile_size_from_pixels() {
+    assert_eq!(
+        rain_viewer::TileSize::try_from(512).unwrap(),
+        rain_viewer::TileSize::Px512
+    );
+    assert!(rain_viewer::TileSize::try_from(100).is_err());
+}
+
+#[should_panic]
+#[tokio::test]
+async fn bad_zoom() {
+    let _ = rain_viewer::RequestArguments::new_tile(rain_viewer::TileCoord::new(0, 0, 40)).unwrap();
 }
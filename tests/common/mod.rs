@@ -0,0 +1,161 @@
+//! Offline test doubles shared by the integration tests
+
+#![allow(dead_code)]
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use rain_viewer::{BodyStream, BoxFuture, Error, HttpTransport};
+
+pub const WEATHER_MAPS_URL: &str = "https://api.rainviewer.com/public/weather-maps.json";
+pub const WEATHER_MAPS: &[u8] = include_bytes!("../fixtures/weather-maps.json");
+pub const TILE: &[u8] = include_bytes!("../fixtures/tile.png");
+
+#[derive(Clone)]
+struct MockResponse {
+    status: http::StatusCode,
+    headers: http::HeaderMap,
+    body: Bytes,
+}
+
+/// Serves canned responses by url and records every request made
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    responses: Arc<Mutex<HashMap<String, MockResponse>>>,
+    queued: Arc<Mutex<HashMap<String, VecDeque<MockResponse>>>>,
+    requests: Arc<Mutex<Vec<http::Request<()>>>>,
+}
+
+impl MockTransport {
+    /// A transport serving the sample `weather-maps.json` and the sample tile for any other url
+    pub fn new() -> Self {
+        let mock = Self::default();
+        mock.respond(WEATHER_MAPS_URL, http::StatusCode::OK, WEATHER_MAPS);
+        mock
+    }
+
+    /// Serves `body` with `status` for every request to `url`
+    pub fn respond(&self, url: &str, status: http::StatusCode, body: &'static [u8]) {
+        self.responses.lock().unwrap().insert(
+            url.to_owned(),
+            MockResponse {
+                status,
+                headers: http::HeaderMap::new(),
+                body: Bytes::from_static(body),
+            },
+        );
+    }
+
+    /// Serves a response with `status`, `headers` and `body` for the next request to `url` only.
+    /// Responses queued for the same url are served in order
+    pub fn respond_once(
+        &self,
+        url: &str,
+        status: http::StatusCode,
+        headers: &[(http::HeaderName, &'static str)],
+        body: &'static [u8],
+    ) {
+        let headers = headers
+            .iter()
+            .map(|(name, value)| (name.clone(), http::HeaderValue::from_static(value)))
+            .collect();
+        self.queued
+            .lock()
+            .unwrap()
+            .entry(url.to_owned())
+            .or_default()
+            .push_back(MockResponse {
+                status,
+                headers,
+                body: Bytes::from_static(body),
+            });
+    }
+
+    /// The headers sent with the most recent request
+    pub fn last_headers(&self) -> http::HeaderMap {
+        self.requests
+            .lock()
+            .unwrap()
+            .last()
+            .expect("no requests made")
+            .headers()
+            .clone()
+    }
+
+    /// The urls requested so far, in order
+    pub fn urls(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| r.uri().to_string())
+            .collect()
+    }
+}
+
+impl HttpTransport for MockTransport {
+    fn get(
+        &self,
+        request: http::Request<()>,
+    ) -> BoxFuture<'_, Result<http::Response<Bytes>, Error>> {
+        let url = request.uri().to_string();
+        self.requests.lock().unwrap().push(request);
+        let queued = self
+            .queued
+            .lock()
+            .unwrap()
+            .get_mut(&url)
+            .and_then(VecDeque::pop_front);
+        let response = queued
+            .or_else(|| self.responses.lock().unwrap().get(&url).cloned())
+            .unwrap_or_else(|| {
+                let mut headers = http::HeaderMap::new();
+                headers.insert(
+                    http::header::CONTENT_TYPE,
+                    http::HeaderValue::from_static("image/png"),
+                );
+                MockResponse {
+                    status: http::StatusCode::OK,
+                    headers,
+                    body: Bytes::from_static(TILE),
+                }
+            });
+        Box::pin(async move {
+            let mut res = http::Response::new(response.body);
+            *res.status_mut() = response.status;
+            *res.headers_mut() = response.headers;
+            Ok(res)
+        })
+    }
+
+    /// Streams bodies in chunks of 3 bytes, so that consumers must handle split chunks
+    fn get_streaming(
+        &self,
+        request: http::Request<()>,
+    ) -> BoxFuture<'_, Result<http::Response<BodyStream>, Error>> {
+        let res = self.get(request);
+        Box::pin(async move {
+            Ok(res.await?.map(|body| {
+                let chunks: Vec<_> = body
+                    .chunks(3)
+                    .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+                    .collect();
+                Box::pin(futures_util::stream::iter(chunks)) as BodyStream
+            }))
+        })
+    }
+}
+
+/// A transport whose requests never complete
+#[derive(Clone, Default)]
+pub struct HangingTransport;
+
+impl HttpTransport for HangingTransport {
+    fn get(
+        &self,
+        _request: http::Request<()>,
+    ) -> BoxFuture<'_, Result<http::Response<Bytes>, Error>> {
+        Box::pin(std::future::pending())
+    }
+}
@@ -0,0 +1,187 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+#[cfg(feature = "mbtiles")]
+#[tokio::test]
+async fn mbtiles() {
+    use common::{MockTransport, TILE};
+    use rain_viewer::export::{mbtiles, TileRange};
+    use rain_viewer::{LatLonBounds, RequestArguments, TileCoord, WeatherRequester};
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("radar.mbtiles");
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+
+    // The north west quarter of the world at zoom 0 and 1
+    let range = TileRange::new(LatLonBounds::new(-180.0, 1.0, -1.0, 85.0).unwrap(), 0..=1).unwrap();
+    let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0)).unwrap();
+    let count = mbtiles::export(&req, &maps, frame, args, &range, &path)
+        .await
+        .unwrap();
+    assert_eq!(count, 2);
+    assert!(mock.urls()[2].contains("/256/1/0/0/"));
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let format: String = conn
+        .query_row(
+            "SELECT value FROM metadata WHERE name = 'format'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(format, "png");
+
+    // Rows are flipped, so the north west tile at zoom 1 is stored in row 1
+    let tile: Vec<u8> = conn
+        .query_row(
+            "SELECT tile_data FROM tiles WHERE zoom_level = 1 AND tile_column = 0 AND tile_row = 1",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(tile, TILE);
+}
+
+#[tokio::test]
+async fn pmtiles() {
+    use common::MockTransport;
+    use rain_viewer::export::{pmtiles, TileRange};
+    use rain_viewer::{LatLonBounds, RequestArguments, TileCoord, WeatherRequester};
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("radar.pmtiles");
+    let req = WeatherRequester::with_transport(MockTransport::new());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+
+    let range = TileRange::new(
+        LatLonBounds::new(-180.0, -85.0, 180.0, 85.0).unwrap(),
+        0..=2,
+    )
+    .unwrap();
+    let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0)).unwrap();
+    let count = pmtiles::export(&req, &maps, frame, args, &range, &path)
+        .await
+        .unwrap();
+    assert_eq!(count, 1 + 4 + 16);
+
+    let archive = std::fs::read(&path).unwrap();
+    assert_eq!(&archive[..8], b"PMTiles\x03");
+    // Every tile is the same sample tile, so a single copy is stored
+    let contents = u64::from_le_bytes(archive[88..96].try_into().unwrap());
+    assert_eq!(contents, 1);
+}
+
+#[tokio::test]
+async fn tree() {
+    use common::{MockTransport, TILE};
+    use rain_viewer::export::{tree, TileRange};
+    use rain_viewer::{LatLonBounds, RequestArguments, TileCoord, TileScheme, WeatherRequester};
+
+    let dir = tempfile::tempdir().unwrap();
+    let req = WeatherRequester::with_transport(MockTransport::new());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+
+    // New York at zoom 4 and 5
+    let range =
+        TileRange::new(LatLonBounds::new(-74.1, 40.6, -73.9, 40.8).unwrap(), 4..=5).unwrap();
+    let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0)).unwrap();
+    let count = tree::export(&req, &maps, frame, args, &range, dir.path())
+        .await
+        .unwrap();
+    assert_eq!(count, 2);
+    assert_eq!(std::fs::read(dir.path().join("4/4/6.png")).unwrap(), TILE);
+    assert_eq!(std::fs::read(dir.path().join("5/9/12.png")).unwrap(), TILE);
+
+    let tms = tempfile::tempdir().unwrap();
+    tree::export_with_scheme(
+        &req,
+        &maps,
+        frame,
+        args,
+        &range,
+        tms.path(),
+        TileScheme::Tms,
+    )
+    .await
+    .unwrap();
+    assert!(tms.path().join("4/4/9.png").exists());
+    assert!(tms.path().join("5/9/19.png").exists());
+}
+
+#[cfg(feature = "image")]
+#[tokio::test]
+async fn geotiff() {
+    use common::MockTransport;
+    use rain_viewer::export::geotiff::{self, GeoTiffOptions, Projection};
+    use rain_viewer::{LatLonBounds, RequestArguments, TileCoord, WeatherRequester};
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("radar.tif");
+    let req = WeatherRequester::with_transport(MockTransport::new());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+
+    // New York at zoom 5, within a single tile
+    let bounds = LatLonBounds::new(-74.1, 40.6, -73.9, 40.8).unwrap();
+    let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0)).unwrap();
+    let options = GeoTiffOptions::new().with_projection(Projection::Wgs84);
+    let count = geotiff::export(&req, &maps, frame, args, bounds, 5, options, &path)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+
+    let tiff = std::fs::read(&path).unwrap();
+    assert_eq!(&tiff[..4], b"II*\0");
+    // The crop is a few pixels across rather than the whole tile
+    assert!(tiff.len() < 256 * 256 * 4);
+}
+
+#[cfg(feature = "image")]
+#[tokio::test]
+async fn kmz() {
+    use common::MockTransport;
+    use rain_viewer::export::kmz::{self, KmzOptions};
+    use rain_viewer::{LatLonBounds, RequestArguments, TileCoord, WeatherRequester};
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("radar.kmz");
+    let req = WeatherRequester::with_transport(MockTransport::new());
+    let maps = req.available().await.unwrap();
+
+    let bounds = LatLonBounds::new(-74.1, 40.6, -73.9, 40.8).unwrap();
+    let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0)).unwrap();
+    let options = KmzOptions::new().with_time_spans(true);
+    let count = kmz::export(
+        &req,
+        &maps,
+        &maps.past_radar,
+        args,
+        bounds,
+        5,
+        options,
+        &path,
+    )
+    .await
+    .unwrap();
+    assert_eq!(count, maps.past_radar.len());
+
+    // The document comes first, with an overlay per frame
+    let archive = std::fs::read(&path).unwrap();
+    assert_eq!(&archive[..4], b"PK\x03\x04");
+    assert_eq!(&archive[30..37], b"doc.kml");
+    let kml = String::from_utf8_lossy(&archive);
+    assert_eq!(
+        kml.matches("<GroundOverlay>").count(),
+        maps.past_radar.len()
+    );
+    assert!(kml.contains("<TimeSpan><begin>2023-10-11T05:00:00Z</begin><end>"));
+
+    let empty = kmz::export(&req, &maps, &[], args, bounds, 5, options, &path).await;
+    assert!(empty.is_err());
+}
@@ -0,0 +1,252 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use common::{MockTransport, TILE};
+use rain_viewer::{LatLonBounds, RequestArguments, TileCoord, WeatherRequester};
+
+#[tokio::test]
+async fn get_region() {
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+
+    // The Benelux straddles four tiles at zoom 6
+    let bounds = LatLonBounds::new(2.5, 49.5, 7.2, 53.5).unwrap();
+    let mut args = RequestArguments::new_tile(TileCoord::new(0, 0, 0)).unwrap();
+    args.set_smooth(false);
+    let tiles = req.get_region(&maps, frame, bounds, 6, args).await.unwrap();
+
+    let coords: Vec<_> = tiles.keys().map(|c| (c.x, c.y)).collect();
+    assert_eq!(coords, [(32, 20), (32, 21), (33, 20), (33, 21)]);
+    assert!(tiles.values().all(|tile| tile == TILE));
+    assert!(tiles.contains_key(&TileCoord { x: 32, y: 20, z: 6 }));
+    assert!(mock.urls()[1..].iter().all(|url| url.ends_with("/0_1.png")));
+
+    for zoom in [13, 32] {
+        let err = req
+            .get_region(&maps, frame, bounds, zoom, args)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            rain_viewer::Error::Parameter(rain_viewer::ParameterError::InvalidZoom(z, _)) if z == zoom
+        ));
+    }
+}
+
+#[tokio::test]
+async fn batch_download() {
+    use std::sync::{Arc, Mutex};
+
+    use rain_viewer::{BatchDownloader, Progress};
+
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+    let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0)).unwrap();
+
+    // The first tile fails once with a server error and is retried, the second is not found
+    let url = |x, y| {
+        format!("https://tilecache.rainviewer.com/v2/radar/1697000400/256/1/{x}/{y}/2/1_1.png")
+    };
+    mock.respond_once(&url(0, 0), http::StatusCode::BAD_GATEWAY, &[], b"");
+    mock.respond(&url(1, 0), http::StatusCode::NOT_FOUND, b"");
+
+    let progress = Arc::new(Mutex::new(Vec::new()));
+    let batch = BatchDownloader::new(req)
+        .with_concurrency(2)
+        .with_progress({
+            let progress = progress.clone();
+            move |p| progress.lock().unwrap().push(p)
+        });
+    let coords = [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(x, y)| TileCoord { x, y, z: 1 });
+    let result = batch.download(&maps, frame, args, coords).await.unwrap();
+
+    assert_eq!(result.tiles.len(), 3);
+    assert!(result.tiles.contains_key(&coords[0]));
+    assert_eq!(result.errors.len(), 1);
+    assert_eq!(
+        result.errors[&coords[1]].status(),
+        Some(http::StatusCode::NOT_FOUND)
+    );
+
+    let progress = progress.lock().unwrap();
+    assert_eq!(progress.len(), 4);
+    assert_eq!(
+        progress.last(),
+        Some(&Progress {
+            done: 4,
+            total: 4,
+            bytes: 3 * TILE.len() as u64
+        })
+    );
+}
+
+#[tokio::test]
+async fn region_stream() {
+    use futures_util::StreamExt;
+
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+    let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0)).unwrap();
+    let world = LatLonBounds::new(-180.0, -85.0, 180.0, 85.0).unwrap();
+
+    let url = "https://tilecache.rainviewer.com/v2/radar/1697000400/256/1/1/0/2/1_1.png";
+    mock.respond(url, http::StatusCode::NOT_FOUND, b"");
+    let tiles: Vec<_> = req
+        .get_region_stream(&maps, frame, world, 1, args)
+        .collect()
+        .await;
+    assert_eq!(tiles.len(), 4);
+    let (coord, tile) = tiles[0].as_ref().unwrap();
+    assert_eq!((*coord, &tile[..]), (TileCoord { x: 0, y: 0, z: 1 }, TILE));
+    // The missing tile does not end the stream
+    assert!(tiles[1].is_err());
+    assert!(tiles[3].is_ok());
+
+    let satellite = &maps.infrared_satellite[0];
+    let tiles: Vec<_> = req
+        .get_region_stream(&maps, satellite, world, 1, args)
+        .collect()
+        .await;
+    assert_eq!(tiles.len(), 1);
+    assert!(tiles[0].is_err());
+
+    for zoom in [13, 32] {
+        let tiles: Vec<_> = req
+            .get_region_stream(&maps, frame, world, zoom, args)
+            .collect()
+            .await;
+        assert_eq!(tiles.len(), 1);
+        assert!(tiles[0].is_err());
+    }
+}
+
+#[tokio::test]
+async fn resume_batch() {
+    use std::sync::{Arc, Mutex};
+
+    use rain_viewer::BatchDownloader;
+
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = dir.path().join("manifest.jsonl");
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+    let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0)).unwrap();
+    let world = LatLonBounds::new(-180.0, -85.0, 180.0, 85.0).unwrap();
+
+    // The first run fails to download one tile
+    let url = "https://tilecache.rainviewer.com/v2/radar/1697000400/256/1/1/1/2/1_1.png";
+    mock.respond_once(url, http::StatusCode::NOT_FOUND, &[], b"");
+    let saved = Arc::new(Mutex::new(Vec::new()));
+    let batch = BatchDownloader::new(req.clone())
+        .with_on_tile({
+            let saved = saved.clone();
+            move |coord, _| {
+                saved.lock().unwrap().push(coord);
+                Ok(())
+            }
+        })
+        .resume(&manifest)
+        .unwrap();
+    let result = batch
+        .download_region(&maps, frame, world, 1, args)
+        .await
+        .unwrap();
+    assert_eq!((result.tiles.len(), result.errors.len()), (3, 1));
+    assert_eq!(saved.lock().unwrap().len(), 3);
+
+    // Running the job again only downloads the missing tile
+    let requests = mock.urls().len();
+    let batch = BatchDownloader::new(req).resume(&manifest).unwrap();
+    let result = batch
+        .download_region(&maps, frame, world, 1, args)
+        .await
+        .unwrap();
+    assert_eq!(result.skipped.len(), 3);
+    assert_eq!(
+        result.tiles.keys().collect::<Vec<_>>(),
+        [&TileCoord { x: 1, y: 1, z: 1 }]
+    );
+    assert_eq!(mock.urls()[requests..], [url]);
+
+    // Zooms past the radar maximum fail up front instead of once per tile
+    let batch = BatchDownloader::new(WeatherRequester::with_transport(mock.clone()));
+    for zoom in [13, 32] {
+        assert!(batch
+            .download_region(&maps, frame, world, zoom, args)
+            .await
+            .is_err());
+    }
+    assert_eq!(mock.urls().len(), requests + 1);
+}
+
+#[cfg(feature = "image")]
+#[tokio::test]
+async fn thumbnail() {
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+    let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0)).unwrap();
+
+    // The Benelux first spans 64 pixels at zoom 4, where it fits in a single tile
+    let bounds = LatLonBounds::new(2.5, 49.5, 7.2, 53.5).unwrap();
+    let thumbnail = req.thumbnail(&maps, frame, bounds, 64, args).await.unwrap();
+    assert_eq!(thumbnail.height(), 64);
+    assert!(thumbnail.width() < 64);
+    let urls = mock.urls();
+    assert_eq!(urls.len(), 2);
+    assert!(urls[1].contains("/256/4/8/5/"));
+
+    assert!(req.thumbnail(&maps, frame, bounds, 0, args).await.is_err());
+}
+
+#[cfg(feature = "image")]
+#[tokio::test]
+async fn resampled_tile() {
+    use rain_viewer::PyramidSource;
+
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+    let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0)).unwrap();
+    let coord = TileCoord::new(33, 21, 6);
+
+    let tile = req
+        .get_resampled_tile(&maps, frame, coord, PyramidSource::Parent, args)
+        .await
+        .unwrap();
+    assert_eq!((tile.width(), tile.height()), (256, 256));
+    assert!(mock.urls()[1].contains("/256/5/16/10/"));
+
+    let tile = req
+        .get_resampled_tile(&maps, frame, coord, PyramidSource::Children, args)
+        .await
+        .unwrap();
+    assert_eq!((tile.width(), tile.height()), (256, 256));
+    let mut children: Vec<_> = mock.urls()[2..].to_vec();
+    children.sort();
+    assert_eq!(children.len(), 4);
+    assert!(children[0].contains("/256/7/66/42/"));
+    assert!(children[3].contains("/256/7/67/43/"));
+
+    let root = TileCoord::new(0, 0, 0);
+    assert!(req
+        .get_resampled_tile(&maps, frame, root, PyramidSource::Parent, args)
+        .await
+        .is_err());
+    let deepest = TileCoord::new(0, 0, 12);
+    assert!(req
+        .get_resampled_tile(&maps, frame, deepest, PyramidSource::Children, args)
+        .await
+        .is_err());
+}
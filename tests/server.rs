@@ -0,0 +1,101 @@
+#![cfg(all(feature = "server", not(target_arch = "wasm32")))]
+
+mod common;
+
+use common::{MockTransport, TILE, WEATHER_MAPS_URL};
+use rain_viewer::{MemoryCache, RequestArguments, TileCoord, WeatherRequester};
+
+/// Serves a proxy through `mock` on a free local port, returning its base url
+async fn serve(mock: MockTransport) -> String {
+    let req = WeatherRequester::with_transport(mock).with_cache(MemoryCache::new(64, 1 << 20));
+    let args = RequestArguments::new_tile(TileCoord::new(0, 0, 0)).unwrap();
+    let app = rain_viewer::server::router(req, args);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+#[tokio::test]
+async fn proxies_tiles() {
+    let mock = MockTransport::new();
+    let base = serve(mock.clone()).await;
+    let client = reqwest::Client::new();
+
+    let res = client
+        .get(format!("{base}/radar/1697000400/3/1/2.png"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    assert_eq!(res.headers()["content-type"], "image/png");
+    assert!(res.headers()["cache-control"]
+        .to_str()
+        .unwrap()
+        .contains("immutable"));
+    assert_eq!(res.bytes().await.unwrap(), TILE);
+
+    // Nowcast frames are addressed by time too, and are cached briefly
+    let res = client
+        .get(format!("{base}/radar/1697001000/3/1/2.png"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    assert_eq!(res.headers()["cache-control"], "public, max-age=60");
+
+    // The frame list and the repeated tile are downloaded once
+    let urls = mock.urls();
+    assert_eq!(
+        urls.iter().filter(|url| *url == WEATHER_MAPS_URL).count(),
+        1
+    );
+    client
+        .get(format!("{base}/radar/1697000400/3/1/2.png"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(mock.urls().len(), urls.len());
+}
+
+#[tokio::test]
+async fn rejects_bad_requests() {
+    let mock = MockTransport::new();
+    let base = serve(mock.clone()).await;
+    let client = reqwest::Client::new();
+    let status = |path: &'static str| {
+        let client = client.clone();
+        let url = format!("{base}{path}");
+        async move { client.get(url).send().await.unwrap().status() }
+    };
+
+    assert_eq!(
+        status("/radar/1697000400/3/8/2.png").await,
+        reqwest::StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        status("/radar/1697000400/3/1/2.jpg").await,
+        reqwest::StatusCode::NOT_FOUND
+    );
+    // Unknown frames refresh the frame list, but at most once a minute
+    assert_eq!(
+        status("/radar/1600000000/3/1/2.png").await,
+        reqwest::StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        status("/radar/1600000600/3/1/2.png").await,
+        reqwest::StatusCode::NOT_FOUND
+    );
+    let maps = mock.urls();
+    assert_eq!(
+        maps.iter().filter(|url| *url == WEATHER_MAPS_URL).count(),
+        1
+    );
+
+    let url = "https://tilecache.rainviewer.com/v2/radar/1696999800/256/3/1/2/2/1_1.png";
+    mock.respond(url, http::StatusCode::INTERNAL_SERVER_ERROR, b"");
+    assert_eq!(
+        status("/radar/1696999800/3/1/2.png").await,
+        reqwest::StatusCode::BAD_GATEWAY
+    );
+}
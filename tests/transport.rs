@@ -0,0 +1,805 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use common::{MockTransport, TILE, WEATHER_MAPS_URL};
+use rain_viewer::{RequestArguments, TileCoord, WeatherRequester};
+
+#[tokio::test]
+async fn custom_transport() {
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+    let png = req
+        .get_tile(
+            &maps,
+            frame,
+            RequestArguments::new_tile(TileCoord::new(4, 7, 6)).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(png, TILE);
+    assert_eq!(
+        mock.urls(),
+        [
+            WEATHER_MAPS_URL,
+            "https://tilecache.rainviewer.com/v2/radar/1697000400/256/6/4/7/2/1_1.png"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn http_error() {
+    let mock = MockTransport::new();
+    let url = "https://tilecache.rainviewer.com/v2/coverage/0/256/1/0/0/0/0_0.png";
+    mock.respond(url, http::StatusCode::NOT_FOUND, b"no such tile");
+    let req = WeatherRequester::with_transport(mock);
+
+    let err = req.get_coverage_tile(0, 0, 1).await.unwrap_err();
+    let rain_viewer::Error::Http(err) = err else {
+        panic!("expected an http error, got {err:?}");
+    };
+    assert_eq!(err.status, http::StatusCode::NOT_FOUND);
+    assert_eq!(err.url, url);
+    assert_eq!(err.body, "no such tile");
+}
+
+#[cfg(feature = "tower")]
+#[tokio::test]
+async fn tile_service() {
+    use rain_viewer::service::{TileRequest, TileService};
+    use tower_service::Service;
+
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+    let request = TileRequest::radar(
+        &maps,
+        frame,
+        RequestArguments::new_tile(TileCoord::new(4, 7, 6)).unwrap(),
+    )
+    .unwrap();
+
+    let mut service = TileService::new(req);
+    std::future::poll_fn(|cx| service.poll_ready(cx))
+        .await
+        .unwrap();
+    let png = service.call(request.clone()).await.unwrap();
+
+    assert_eq!(png, TILE);
+    assert_eq!(mock.urls(), [WEATHER_MAPS_URL, request.url()]);
+}
+
+#[tokio::test]
+async fn rate_limited() {
+    let mock = MockTransport::new();
+    let url = "https://tilecache.rainviewer.com/v2/coverage/0/256/1/0/0/0/0_0.png";
+    mock.respond_once(
+        url,
+        http::StatusCode::TOO_MANY_REQUESTS,
+        &[(http::header::RETRY_AFTER, "30")],
+        b"",
+    );
+
+    let req = WeatherRequester::with_transport(mock);
+    let err = req.get_coverage_tile(0, 0, 1).await.unwrap_err();
+    assert!(matches!(
+        err,
+        rain_viewer::Error::RateLimited { retry_after: Some(d) } if d.as_secs() == 30
+    ));
+}
+
+#[tokio::test]
+async fn rate_limit_retries() {
+    let mock = MockTransport::new();
+    let url = "https://tilecache.rainviewer.com/v2/coverage/0/256/1/0/0/0/0_0.png";
+    mock.respond_once(
+        url,
+        http::StatusCode::TOO_MANY_REQUESTS,
+        &[(http::header::RETRY_AFTER, "0")],
+        b"",
+    );
+    mock.respond_once(url, http::StatusCode::TOO_MANY_REQUESTS, &[], b"");
+
+    let req = WeatherRequester::with_transport(mock.clone()).with_rate_limit_retries(1);
+    assert!(req.get_coverage_tile(0, 0, 1).await.is_err());
+
+    let req = req.with_rate_limit_retries(2);
+    mock.respond_once(
+        url,
+        http::StatusCode::TOO_MANY_REQUESTS,
+        &[(http::header::RETRY_AFTER, "0")],
+        b"",
+    );
+    assert_eq!(req.get_coverage_tile(0, 0, 1).await.unwrap(), TILE);
+    assert_eq!(mock.urls().len(), 4);
+}
+
+#[tokio::test]
+async fn request_timeout() {
+    let timeout = std::time::Duration::from_millis(20);
+    let req = WeatherRequester::with_transport(common::HangingTransport);
+
+    let err = req
+        .clone()
+        .with_request_timeout(timeout)
+        .get_coverage_tile(0, 0, 1)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, rain_viewer::Error::Timeout(t) if t == timeout));
+
+    // The timeout only applies to the clone it was set on
+    let req = WeatherRequester::with_transport(MockTransport::new());
+    let png = req
+        .clone()
+        .with_request_timeout(std::time::Duration::from_secs(10))
+        .get_coverage_tile(0, 0, 1)
+        .await
+        .unwrap();
+    assert_eq!(png, TILE);
+}
+
+#[tokio::test]
+async fn conditional_requests() {
+    use rain_viewer::{Conditional, Validators};
+
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+    let args = RequestArguments::new_tile(TileCoord::new(4, 7, 6)).unwrap();
+    let url = "https://tilecache.rainviewer.com/v2/radar/1697000400/256/6/4/7/2/1_1.png";
+
+    mock.respond_once(
+        url,
+        http::StatusCode::OK,
+        &[(http::header::ETAG, "\"v1\"")],
+        TILE,
+    );
+    let res = req
+        .get_tile_if_modified(&maps, frame, args, &Validators::default())
+        .await
+        .unwrap();
+    assert!(!mock
+        .last_headers()
+        .contains_key(http::header::IF_NONE_MATCH));
+    let Conditional::Modified(_, validators) = res else {
+        panic!("expected a modified tile");
+    };
+    assert_eq!(validators.etag.as_deref(), Some("\"v1\""));
+
+    mock.respond_once(url, http::StatusCode::NOT_MODIFIED, &[], b"");
+    let res = req
+        .get_tile_if_modified(&maps, frame, args, &validators)
+        .await
+        .unwrap();
+    assert_eq!(mock.last_headers()[http::header::IF_NONE_MATCH], "\"v1\"");
+    assert_eq!(res, Conditional::NotModified);
+
+    let res = req.available_if_modified(&validators).await.unwrap();
+    assert_eq!(res.modified().unwrap().version, "2.0");
+}
+
+#[tokio::test]
+async fn invalid_image() {
+    let mock = MockTransport::new();
+    let url = "https://tilecache.rainviewer.com/v2/coverage/0/256/1/0/0/0/0_0.png";
+    let portal: &[u8] = b"<html><body>Sign in to continue</body></html>";
+    mock.respond_once(
+        url,
+        http::StatusCode::OK,
+        &[(http::header::CONTENT_TYPE, "text/html")],
+        portal,
+    );
+    mock.respond_once(url, http::StatusCode::OK, &[], portal);
+
+    let req = WeatherRequester::with_transport(mock);
+    for _ in 0..2 {
+        let err = req.get_coverage_tile(0, 0, 1).await.unwrap_err();
+        assert!(
+            matches!(err, rain_viewer::Error::InvalidImage { .. }),
+            "{err}"
+        );
+    }
+    assert_eq!(req.get_coverage_tile(0, 0, 1).await.unwrap(), TILE);
+}
+
+#[tokio::test]
+async fn get_tile_to() {
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+    let args = RequestArguments::new_tile(TileCoord::new(4, 7, 6)).unwrap();
+
+    let mut out = Vec::new();
+    let written = req.get_tile_to(&maps, frame, args, &mut out).await.unwrap();
+    assert_eq!(written, TILE.len() as u64);
+    assert_eq!(out, TILE);
+
+    // Nothing is written when the signature does not match
+    let url = mock.urls().pop().unwrap();
+    mock.respond(&url, http::StatusCode::OK, b"<html>login</html>");
+    let mut out = Vec::new();
+    let err = req
+        .get_tile_to(&maps, frame, args, &mut out)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, rain_viewer::Error::InvalidImage { .. }));
+    assert!(out.is_empty());
+}
+
+#[tokio::test]
+async fn max_response_size() {
+    let req = WeatherRequester::with_transport(MockTransport::new())
+        .with_max_response_size(TILE.len() - 1);
+    let err = req.get_coverage_tile(0, 0, 1).await.unwrap_err();
+    assert!(matches!(err, rain_viewer::Error::ResponseTooLarge(_)));
+
+    let req = req.with_max_response_size(TILE.len());
+    assert_eq!(req.get_coverage_tile(0, 0, 1).await.unwrap(), TILE);
+}
+
+#[cfg(feature = "cancellation")]
+#[tokio::test]
+async fn cancellation() {
+    let token = tokio_util::sync::CancellationToken::new();
+    let req =
+        WeatherRequester::with_transport(common::HangingTransport).with_cancellation(token.clone());
+
+    let tile = tokio::spawn(async move { req.get_coverage_tile(0, 0, 1).await });
+    token.cancel();
+    let err = tile.await.unwrap().unwrap_err();
+    assert!(matches!(err, rain_viewer::Error::Cancelled));
+}
+
+#[tokio::test]
+async fn memory_cache() {
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone())
+        .with_cache(rain_viewer::MemoryCache::new(16, 1 << 20));
+
+    assert_eq!(req.get_coverage_tile(0, 0, 1).await.unwrap(), TILE);
+    assert_eq!(req.get_coverage_tile(0, 0, 1).await.unwrap(), TILE);
+    assert_eq!(req.clone().get_coverage_tile(0, 0, 1).await.unwrap(), TILE);
+    assert_eq!(mock.urls().len(), 1);
+
+    req.get_coverage_tile(1, 0, 1).await.unwrap();
+    assert_eq!(mock.urls().len(), 2);
+}
+
+#[tokio::test]
+async fn disk_cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone())
+        .with_cache(rain_viewer::DiskCache::open(dir.path()).unwrap());
+    assert_eq!(req.get_coverage_tile(0, 0, 1).await.unwrap(), TILE);
+
+    // A new requester, as after a restart, is served from the files written by the first one
+    let req = WeatherRequester::with_transport(mock.clone())
+        .with_cache(rain_viewer::DiskCache::open(dir.path()).unwrap());
+    assert_eq!(req.get_coverage_tile(0, 0, 1).await.unwrap(), TILE);
+    assert_eq!(mock.urls().len(), 1);
+}
+
+#[cfg(feature = "moka")]
+#[tokio::test]
+async fn moka_cache() {
+    let mock = MockTransport::new();
+    let cache = rain_viewer::moka_tile_cache(1 << 20);
+    let req = WeatherRequester::with_transport(mock.clone()).with_cache(cache.clone());
+
+    req.get_coverage_tile(0, 0, 1).await.unwrap();
+    assert_eq!(req.get_coverage_tile(0, 0, 1).await.unwrap(), TILE);
+    assert_eq!(mock.urls().len(), 1);
+
+    cache.run_pending_tasks().await;
+    assert_eq!(cache.weighted_size(), TILE.len() as u64);
+}
+
+#[tokio::test]
+async fn layered_caches() {
+    use std::sync::Arc;
+
+    let dir = tempfile::tempdir().unwrap();
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone())
+        .with_cache(rain_viewer::DiskCache::open(dir.path()).unwrap());
+    req.get_coverage_tile(0, 0, 1).await.unwrap();
+
+    // The tile found on disk is copied into the memory layer in front of it
+    let memory = Arc::new(rain_viewer::MemoryCache::new(16, 1 << 20));
+    let req = WeatherRequester::with_transport(mock.clone())
+        .with_cache(memory.clone())
+        .with_cache(rain_viewer::DiskCache::open(dir.path()).unwrap());
+    assert_eq!(req.get_coverage_tile(0, 0, 1).await.unwrap(), TILE);
+    assert_eq!(memory.len(), 1);
+    assert_eq!(mock.urls().len(), 1);
+}
+
+#[tokio::test]
+async fn expired_frames_are_invalidated() {
+    use std::sync::Arc;
+
+    let mock = MockTransport::new();
+    let memory = Arc::new(rain_viewer::MemoryCache::new(16, 1 << 20));
+    let req = WeatherRequester::with_transport(mock.clone()).with_cache(memory.clone());
+
+    let maps = req.available().await.unwrap();
+    let args = RequestArguments::new_tile(TileCoord::new(4, 7, 6)).unwrap();
+    req.get_tile(&maps, &maps.past_radar[0], args)
+        .await
+        .unwrap();
+    req.get_coverage_tile(0, 0, 1).await.unwrap();
+    assert_eq!(memory.len(), 2);
+
+    // Still listed, so the tile is kept
+    req.available().await.unwrap();
+    assert_eq!(memory.len(), 2);
+
+    // Rolled off, as simulated by dropping it from the fixture
+    mock.respond(
+        WEATHER_MAPS_URL,
+        http::StatusCode::OK,
+        br#"{"version":"2.0","generated":1697000450,"host":"https://tilecache.rainviewer.com",
+            "radar":{"past":[],"nowcast":[]},"satellite":{"infrared":[]}}"#,
+    );
+    let maps = req.available().await.unwrap();
+    assert!(maps.past_radar.is_empty());
+    assert_eq!(memory.len(), 1);
+}
+
+#[cfg(feature = "image")]
+#[tokio::test]
+async fn tile_image() {
+    let req = WeatherRequester::with_transport(MockTransport::new());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+    let args = RequestArguments::new_tile(TileCoord::new(4, 7, 6)).unwrap();
+
+    let image = req.get_tile_image(&maps, frame, args).await.unwrap();
+    assert_eq!((image.width(), image.height()), (256, 256));
+}
+
+#[cfg(feature = "image")]
+#[tokio::test]
+async fn blended_tile() {
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+    let args = RequestArguments::new_tile(TileCoord::new(4, 7, 6)).unwrap();
+
+    let image = req.get_blended_tile(&maps, frame, args, 0.8).await.unwrap();
+    assert_eq!((image.width(), image.height()), (256, 256));
+    // The radar frame is drawn over the satellite frame closest to it in time
+    assert!(mock.urls().contains(
+        &"https://tilecache.rainviewer.com/v2/satellite/2b3c4d5e6f70/256/6/4/7/0/0_0.png"
+            .to_owned()
+    ));
+
+    let zoomed = RequestArguments::new_tile(TileCoord::new(300, 400, 10)).unwrap();
+    assert!(req
+        .get_blended_tile(&maps, frame, zoomed, 0.8)
+        .await
+        .is_err());
+    let satellite = &maps.infrared_satellite[0];
+    assert!(req
+        .get_blended_tile(&maps, satellite, args, 0.8)
+        .await
+        .is_err());
+}
+
+#[cfg(feature = "image")]
+#[tokio::test]
+async fn no_frames_available() {
+    use rain_viewer::{Error, FrameKind};
+
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap().clone();
+
+    // Rain Viewer can briefly publish an empty frame list, which is not the caller's fault
+    let empty = br#"{
+        "version": "2.0",
+        "generated": 1697000450,
+        "host": "https://tilecache.rainviewer.com",
+        "radar": { "past": [], "nowcast": [] },
+        "satellite": { "infrared": [] }
+    }"#;
+    mock.respond(common::WEATHER_MAPS_URL, http::StatusCode::OK, empty);
+    let maps = req.available().await.unwrap();
+    for result in [
+        req.is_raining_at(51.5, -0.1).await.map(|_| ()),
+        req.precip_phase_at(51.5, -0.1).await.map(|_| ()),
+    ] {
+        assert!(matches!(result, Err(Error::NoFrames(FrameKind::PastRadar))));
+    }
+    let args = RequestArguments::new_tile(TileCoord::new(4, 7, 6)).unwrap();
+    assert!(matches!(
+        req.get_blended_tile(&maps, &frame, args, 0.8).await,
+        Err(Error::NoFrames(FrameKind::Satellite))
+    ));
+}
+
+#[tokio::test]
+async fn prefetch() {
+    use std::time::Duration;
+
+    use rain_viewer::{AnimationPlayer, PlayerEvent, Prefetcher};
+
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    let args = RequestArguments::new_tile(TileCoord::new(4, 7, 6)).unwrap();
+    let mut player = AnimationPlayer::from_radar(&maps).with_frame_delay(Duration::from_secs(1));
+    let mut prefetcher = Prefetcher::new(req, args, 2);
+
+    assert!(prefetcher.fill(&maps, &mut player).await.is_empty());
+    assert_eq!(prefetcher.len(), 3);
+    assert!((0..3).all(|i| player.is_loaded(i)));
+    assert_eq!(prefetcher.get(&player.frames()[1]).unwrap(), TILE);
+    assert_eq!(mock.urls().len(), 4);
+    // The current frame is downloaded before the upcoming ones
+    assert!(mock.urls()[1].contains(&player.frames()[0].path));
+
+    // Moving on by a frame downloads only the newly upcoming frame and drops the passed one
+    player.play();
+    assert_eq!(player.tick(Duration::from_secs(1)), [PlayerEvent::Show(1)]);
+    assert!(prefetcher.fill(&maps, &mut player).await.is_empty());
+    assert_eq!(mock.urls().len(), 5);
+    assert_eq!(prefetcher.len(), 3);
+    assert!(prefetcher.get(&player.frames()[0]).is_none());
+    assert!(!player.is_loaded(0));
+    assert!(player.is_loaded(3));
+
+    // Failed frames are reported and retried on the next fill
+    let failing = format!(
+        "https://tilecache.rainviewer.com{}/256/6/4/7/2/1_1.png",
+        player.frames()[4].path
+    );
+    mock.respond_once(&failing, http::StatusCode::NOT_FOUND, &[], b"");
+    player.step_forward();
+    let errors = prefetcher.fill(&maps, &mut player).await;
+    assert_eq!(errors.keys().copied().collect::<Vec<_>>(), [4]);
+    assert!(!player.is_loaded(4));
+    assert!(prefetcher.fill(&maps, &mut player).await.is_empty());
+    assert!(player.is_loaded(4));
+}
+
+/// A black and white tile of a single value, leaked so that it can be served by the mock
+#[cfg(feature = "image")]
+fn black_and_white_png(value: u8, alpha: u8) -> &'static [u8] {
+    let tile = image::GrayAlphaImage::from_pixel(256, 256, image::LumaA([value, alpha]));
+    let mut png = std::io::Cursor::new(Vec::new());
+    tile.write_to(&mut png, image::ImageFormat::Png).unwrap();
+    png.into_inner().leak()
+}
+
+#[cfg(feature = "image")]
+#[tokio::test]
+async fn is_raining_at() {
+    use rain_viewer::precip::PrecipEstimate;
+
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    // London is in tile (63, 42) at zoom 7, read in black and white without smoothing
+    let url = "https://tilecache.rainviewer.com/v2/radar/1697000400/256/7/63/42/0/0_1.png";
+
+    mock.respond(url, http::StatusCode::OK, black_and_white_png(72, 255));
+    let estimate = req.is_raining_at(51.5074, -0.1278).await.unwrap();
+    assert_eq!(mock.urls().last(), Some(&url.to_owned()));
+    assert!(matches!(estimate, PrecipEstimate::Rain(intensity) if intensity.dbz == 40.0));
+
+    mock.respond(url, http::StatusCode::OK, black_and_white_png(0, 0));
+    assert_eq!(
+        req.is_raining_at(51.5074, -0.1278).await.unwrap(),
+        PrecipEstimate::None
+    );
+
+    assert!(req.is_raining_at(89.0, 0.0).await.is_err());
+}
+
+#[cfg(feature = "image")]
+#[tokio::test]
+async fn nowcast_at() {
+    use rain_viewer::precip::{PrecipEstimate, PrecipKind};
+
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let url = |path: &str| {
+        format!("https://tilecache.rainviewer.com/v2/radar/{path}/256/7/63/42/0/0_1.png")
+    };
+    for path in ["1696998600", "1696999200", "1696999800", "1697000400"] {
+        mock.respond(&url(path), http::StatusCode::OK, black_and_white_png(0, 0));
+    }
+    // Snow is forecast in the first nowcast frame, and rain in the second
+    let snow = black_and_white_png(180, 255);
+    mock.respond(&url("nowcast_4f2b3c1d9e0a"), http::StatusCode::OK, snow);
+    let rain = black_and_white_png(72, 255);
+    mock.respond(&url("nowcast_8a7c6e5d4b3f"), http::StatusCode::OK, rain);
+
+    let timeline = req.nowcast_at(51.5074, -0.1278).await.unwrap();
+    let maps = req.available().await.unwrap();
+    let frames: Vec<_> = maps.radar_timeline().map(|entry| entry.frame()).collect();
+    let times: Vec<_> = frames.iter().map(|frame| frame.unix_time()).collect();
+    assert_eq!(
+        times,
+        [1696998600, 1696999200, 1696999800, 1697000400, 1697001000, 1697001600]
+    );
+    assert!(timeline
+        .iter()
+        .zip(&frames)
+        .all(|((time, _), frame)| *time == frame.time));
+    assert!(timeline[..4]
+        .iter()
+        .all(|(_, estimate)| *estimate == PrecipEstimate::None));
+    assert_eq!(timeline[4].1.kind(), Some(PrecipKind::Snow));
+    assert_eq!(timeline[5].1.kind(), Some(PrecipKind::Rain));
+
+    assert!(req.nowcast_at(0.0, 181.0).await.is_err());
+}
+
+#[cfg(feature = "arrow")]
+#[tokio::test]
+async fn point_series() {
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let url = |path: &str| {
+        format!("https://tilecache.rainviewer.com/v2/radar/{path}/256/7/63/42/0/0_1.png")
+    };
+    for path in [
+        "1696998600",
+        "1696999200",
+        "1696999800",
+        "nowcast_8a7c6e5d4b3f",
+    ] {
+        mock.respond(&url(path), http::StatusCode::OK, black_and_white_png(0, 0));
+    }
+    // Rain in the latest past frame, and snow in the first nowcast frame, over the whole tile
+    let rain = black_and_white_png(72, 255);
+    mock.respond(&url("1697000400"), http::StatusCode::OK, rain);
+    let snow = black_and_white_png(180, 255);
+    mock.respond(&url("nowcast_4f2b3c1d9e0a"), http::StatusCode::OK, snow);
+
+    // Two locations in London, within the same tile
+    let locations = [(51.5074, -0.1278), (51.55, -0.3)];
+    let series = req.point_series(&locations).await.unwrap();
+    assert_eq!(series.len(), 2);
+    assert_eq!((series[1].lat, series[1].lon), (51.55, -0.3));
+    assert_eq!(series[0].samples.len(), 6);
+
+    let batch = rain_viewer::series_to_record_batch(&series);
+    assert_eq!(batch.num_rows(), 12);
+    assert_eq!(batch.column_by_name("dbz").unwrap().null_count(), 8);
+
+    assert!(req.point_series(&[(0.0, 181.0)]).await.is_err());
+}
+
+#[cfg(feature = "image")]
+#[tokio::test]
+async fn accumulate() {
+    use rain_viewer::precip::ZrRelationship;
+    use rain_viewer::{AccumulationArea, LatLonBounds};
+
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    for path in ["1696998600", "1696999200", "1696999800", "1697000400"] {
+        let url = format!("https://tilecache.rainviewer.com/v2/radar/{path}/256/7/63/42/0/0_1.png");
+        mock.respond(&url, http::StatusCode::OK, black_and_white_png(72, 255));
+    }
+
+    // A steady 40 dBZ over the 20 minutes spanned by the last three past frames
+    let area = AccumulationArea::Point {
+        lat: 51.5074,
+        lon: -0.1278,
+    };
+    let period = maps.past_radar[1].age(maps.past_radar[3].time);
+    let total = rain_viewer::accumulate(&req, &maps, &maps.past_radar, area, period)
+        .await
+        .unwrap();
+    assert_eq!(total.frames, 3);
+    assert_eq!(total.covered, period);
+    let expected = ZrRelationship::MARSHALL_PALMER.rate(40.0) as f64 / 3.0;
+    assert!((total.mean_mm - expected).abs() < 1e-4);
+    assert_eq!(total.max_mm, total.mean_mm);
+
+    let bounds = LatLonBounds::new(-0.2, 51.4, 0.0, 51.6).unwrap();
+    let area = AccumulationArea::Region { bounds, zoom: 13 };
+    assert!(
+        rain_viewer::accumulate(&req, &maps, &maps.past_radar, area, period)
+            .await
+            .is_err()
+    );
+}
+
+#[cfg(feature = "image")]
+#[tokio::test]
+async fn region_stats() {
+    use rain_viewer::LatLonBounds;
+
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+    // Around London, within tile (63, 42) at zoom 7
+    let bounds = LatLonBounds::new(-0.2, 51.4, 0.0, 51.6).unwrap();
+    let url = "https://tilecache.rainviewer.com/v2/radar/1697000400/256/7/63/42/0/0_1.png";
+
+    mock.respond(url, http::StatusCode::OK, black_and_white_png(190, 255));
+    let stats = req.region_stats(&maps, frame, bounds, 7).await.unwrap();
+    assert_eq!(mock.urls().last(), Some(&url.to_owned()));
+    assert!(stats.pixels > 0);
+    assert_eq!(stats.covered_fraction, 1.0);
+    assert_eq!(stats.snow_fraction, 1.0);
+    assert_eq!(stats.max.unwrap().dbz, 30.0);
+
+    mock.respond(url, http::StatusCode::OK, black_and_white_png(0, 0));
+    let stats = req.region_stats(&maps, frame, bounds, 7).await.unwrap();
+    assert_eq!(stats.covered_fraction, 0.0);
+    assert_eq!(stats.mean, None);
+
+    assert!(req.region_stats(&maps, frame, bounds, 13).await.is_err());
+}
+
+#[cfg(feature = "image")]
+#[tokio::test]
+async fn region_trend() {
+    use rain_viewer::precip::TrendDirection;
+    use rain_viewer::{Error, LatLonBounds};
+
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    let bounds = LatLonBounds::new(-0.2, 51.4, 0.0, 51.6).unwrap();
+    // Rain intensifying from 20 to 40 dBZ over the last three past frames
+    for (path, value) in [("1696999200", 52), ("1696999800", 62), ("1697000400", 72)] {
+        let url = format!("https://tilecache.rainviewer.com/v2/radar/{path}/256/7/63/42/0/0_1.png");
+        mock.respond(&url, http::StatusCode::OK, black_and_white_png(value, 255));
+    }
+
+    let trend = req
+        .region_trend(&maps, &maps.past_radar[1..], bounds, 7)
+        .await
+        .unwrap();
+    assert_eq!(trend.direction, TrendDirection::Strengthening);
+    assert_eq!(trend.samples.len(), 3);
+    assert_eq!(trend.coverage_change, 0.0);
+    assert!((trend.max_dbz_change.unwrap() - 60.0).abs() < 1e-9);
+
+    // One frame, or the same frame twice, has no trend and downloads nothing
+    let requests = mock.urls().len();
+    let latest = &maps.past_radar[3..];
+    assert!(matches!(
+        req.region_trend(&maps, latest, bounds, 7).await,
+        Err(Error::TooFewFrames(1))
+    ));
+    let repeated = [latest[0].clone(), latest[0].clone()];
+    assert!(matches!(
+        req.region_trend(&maps, &repeated, bounds, 7).await,
+        Err(Error::TooFewFrames(1))
+    ));
+    assert!(matches!(
+        req.region_trend(&maps, &[], bounds, 7).await,
+        Err(Error::TooFewFrames(0))
+    ));
+    assert_eq!(mock.urls().len(), requests);
+}
+
+#[cfg(feature = "image")]
+#[tokio::test]
+async fn rain_arrival() {
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let url = |path: &str| {
+        format!("https://tilecache.rainviewer.com/v2/radar/{path}/256/7/63/42/0/0_1.png")
+    };
+    let maps = req.available().await.unwrap();
+    let at = |timestamp| {
+        let mut frames = maps.all_radar();
+        frames
+            .find(|frame| frame.unix_time() == timestamp)
+            .unwrap()
+            .time
+    };
+
+    // Already raining in the latest past frame
+    let rain = black_and_white_png(72, 255);
+    mock.respond(&url("1697000400"), http::StatusCode::OK, rain);
+    let arrival = req.rain_arrival(51.5074, -0.1278).await.unwrap();
+    assert_eq!(arrival.eta, Some(at(1697000400)));
+    assert_eq!(arrival.confidence, 1.0);
+
+    // Rain in the second nowcast frame
+    let dry = black_and_white_png(0, 0);
+    mock.respond(&url("1697000400"), http::StatusCode::OK, dry);
+    mock.respond(&url("nowcast_4f2b3c1d9e0a"), http::StatusCode::OK, dry);
+    mock.respond(&url("nowcast_8a7c6e5d4b3f"), http::StatusCode::OK, rain);
+    let arrival = req.rain_arrival(51.5074, -0.1278).await.unwrap();
+    assert_eq!(arrival.nowcast, Some(at(1697001600)));
+    assert!(arrival.eta.is_some_and(|eta| eta <= at(1697001600)));
+    // The motion of the two latest past frames was read around the location at zoom 6
+    assert!(mock
+        .urls()
+        .iter()
+        .any(|url| url.contains("/1696999800/256/6/31/21/0/0_1.png")));
+
+    assert!(req.rain_arrival(89.0, 0.0).await.is_err());
+}
+
+#[cfg(feature = "image")]
+#[tokio::test]
+async fn precip_phase_at() {
+    use rain_viewer::precip::PrecipPhase;
+
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    let url = "https://tilecache.rainviewer.com/v2/radar/1697000400/256/7/63/42/0/0_1.png";
+
+    mock.respond(url, http::StatusCode::OK, black_and_white_png(180, 255));
+    let phase = req.precip_phase_at(51.5074, -0.1278).await.unwrap();
+    assert_eq!(mock.urls().last(), Some(&url.to_owned()));
+    assert_eq!(phase, Some(PrecipPhase::Snow));
+
+    mock.respond(url, http::StatusCode::OK, black_and_white_png(0, 0));
+    assert_eq!(req.precip_phase_at(51.5074, -0.1278).await.unwrap(), None);
+
+    assert!(req.precip_phase_at(0.0, 181.0).await.is_err());
+}
+
+#[cfg(feature = "image")]
+#[tokio::test]
+async fn has_coverage() {
+    let mock = MockTransport::new();
+    let req = WeatherRequester::with_transport(mock.clone());
+    // London is covered, and the middle of the Pacific is not
+    let london = "https://tilecache.rainviewer.com/v2/coverage/0/256/7/63/42/0/0_0.png";
+    mock.respond(london, http::StatusCode::OK, black_and_white_png(0, 0));
+    let pacific = "https://tilecache.rainviewer.com/v2/coverage/0/256/7/19/75/0/0_0.png";
+    mock.respond(pacific, http::StatusCode::OK, black_and_white_png(0, 255));
+
+    assert!(req.has_coverage(51.5074, -0.1278).await.unwrap());
+    assert!(!req.has_coverage(-30.0, -125.0).await.unwrap());
+    assert_eq!(mock.urls(), [london, pacific]);
+
+    // Coverage tiles are kept in memory, and shared with clones
+    assert!(req.clone().has_coverage(51.6, -0.2).await.unwrap());
+    assert_eq!(mock.urls().len(), 2);
+
+    assert!(req.has_coverage(86.0, 0.0).await.is_err());
+}
+
+#[cfg(feature = "egui")]
+#[tokio::test]
+async fn radar_texture() {
+    use rain_viewer::{RadarTexture, RadarTextureState};
+
+    let mock = MockTransport::new();
+    let url = "https://tilecache.rainviewer.com/v2/radar/1697000400/256/6/4/7/2/1_1.png";
+    mock.respond(url, http::StatusCode::OK, black_and_white_png(72, 255));
+    let req = WeatherRequester::with_transport(mock.clone());
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+    let args = RequestArguments::new_tile(TileCoord::new(4, 7, 6)).unwrap();
+
+    let ctx = egui::Context::default();
+    let (mut radar, load) = RadarTexture::load(&ctx, req.clone(), &maps, frame, args);
+    assert!(radar.is_loading());
+    tokio::spawn(load).await.unwrap();
+    let texture = radar.texture().expect("the tile should have loaded");
+    assert_eq!(texture.size(), [256, 256]);
+    assert_eq!(texture.name(), "rainviewer-1697000400");
+
+    mock.respond(url, http::StatusCode::OK, b"<html>captive portal</html>");
+    let (mut radar, load) = RadarTexture::load(&ctx, req, &maps, frame, args);
+    load.await;
+    assert!(matches!(radar.state(), RadarTextureState::Failed(_)));
+    assert!(radar.texture().is_none());
+}
@@ -0,0 +1,18 @@
+#![cfg(target_arch = "wasm32")]
+
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+#[wasm_bindgen_test]
+async fn api() {
+    let req = rain_viewer::WeatherRequester::new();
+    let maps = req.available().await.unwrap();
+    let frame = maps.latest_past().unwrap();
+    let args =
+        rain_viewer::RequestArguments::new_tile(rain_viewer::TileCoord::new(4, 7, 6)).unwrap();
+    let png = req.get_tile(&maps, frame, args).await.unwrap();
+
+    //Check for PNG magic
+    assert_eq!(&png[0..4], &[0x89, 0x50, 0x4e, 0x47]);
+}